@@ -1,3 +1,11 @@
 fn main() {
+    // Compiled with protox rather than protoc, so the build needs no protobuf install.
+    println!("cargo:rerun-if-changed=proto/vtype.proto");
+    let descriptors =
+        protox::compile(["proto/vtype.proto"], ["proto"]).expect("Invalid proto/vtype.proto");
+    tonic_prost_build::configure()
+        .build_client(false)
+        .compile_fds(descriptors)
+        .expect("Failed to generate the gRPC service");
     tauri_build::build()
 }
@@ -0,0 +1,269 @@
+//! Carries out voice commands: the action of a directive at the end of a dictation, and the
+//! steps of a macro said as a whole one. Shell commands, and the launch and HTTP steps of
+//! macros, run only as `shellActions` allows, which by default means the user confirms each
+//! in the overlay first.
+
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{Emitter, Manager};
+use tauri_plugin_opener::OpenerExt;
+use vtype_core::activity::{self, ActivityKind};
+use vtype_core::inject::{Chord, Key};
+use vtype_core::macros::{Macro, Step};
+use vtype_core::metrics::{self, ErrorCategory};
+use vtype_core::settings::{self, DirectiveAction, ShellPolicy};
+use vtype_core::{log, process, voice};
+
+use crate::announce::{self, Announcement};
+use crate::output;
+
+/// An unanswered confirmation counts as a refusal after this long.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+const HTTP_TIMEOUT_SECS: &str = "15";
+
+static NEXT_CONFIRMATION: AtomicU64 = AtomicU64::new(1);
+static CONFIRMATIONS: Mutex<Vec<(u64, mpsc::Sender<bool>)>> = Mutex::new(Vec::new());
+
+/// Sent as `shell-confirmation` for the overlay to ask the user.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ShellConfirmation {
+    id: u64,
+    commands: Vec<String>,
+}
+
+pub fn run_directive(
+    app: &tauri::AppHandle,
+    text: String,
+    action: DirectiveAction,
+) -> Result<(), String> {
+    let result = match action {
+        DirectiveAction::PressEnter => {
+            if text.is_empty() {
+                output::return_focus(app);
+            } else {
+                output::deliver_text(app, text)?;
+            }
+            output::press_chord(&Chord::key(Key::Return))
+        }
+        DirectiveAction::OpenUrl { url } => {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.hide();
+            }
+            app.opener()
+                .open_url(voice::expand_url(&url, &text), None::<&str>)
+                .map_err(|err| err.to_string())
+        }
+        DirectiveAction::Shell { command } => {
+            // Asking waits on the overlay, which must not be blocked meanwhile.
+            let app = app.clone();
+            std::thread::spawn(move || {
+                let result = confirm_shell(&app, vec![command.clone()])
+                    .and_then(|()| voice::run_shell(&command, &text));
+                if let Err(err) = result {
+                    failed(&app, &err);
+                }
+            });
+            Ok(())
+        }
+    };
+    result.inspect_err(|err| failed(app, err))
+}
+
+/// Runs the steps of `found` in order on a thread of their own, stopping at the first that
+/// fails.
+pub fn start_macro(app: &tauri::AppHandle, found: Macro) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let commands = found.shell_commands();
+        let result = if commands.is_empty() {
+            Ok(())
+        } else {
+            confirm_shell(&app, commands)
+        };
+        let result = result.and_then(|()| {
+            output::return_focus(&app);
+            found.steps.iter().try_for_each(|step| run_step(&app, step))
+        });
+        match result {
+            Ok(()) => {
+                let name = found.phrases.first().cloned().unwrap_or_default();
+                activity::record(ActivityKind::MacroRan, None, Some(name));
+            }
+            Err(err) => failed(&app, &err),
+        }
+    });
+}
+
+fn run_step(app: &tauri::AppHandle, step: &Step) -> Result<(), String> {
+    match step {
+        Step::Keys { keys } => output::press_chord(&Chord::parse(keys)?),
+        Step::Launch { app: name } => launch(name),
+        Step::Snippet { text } => output::deliver_text(app, text.clone()),
+        Step::Http {
+            url,
+            method,
+            body,
+            headers,
+        } => http(url, method, body.as_deref(), headers),
+        Step::Shell { command } => voice::run_shell(command, ""),
+        Step::Wait { ms } => {
+            std::thread::sleep(Duration::from_millis(*ms));
+            Ok(())
+        }
+    }
+}
+
+fn failed(app: &tauri::AppHandle, err: &str) {
+    let _ = log::write(format!("Voice command failed: {}", err));
+    activity::record(ActivityKind::DeliveryFailed, None, Some(err.to_string()));
+    announce::announce(app, Announcement::Failed(Some(err.to_string())));
+    metrics::count_error(ErrorCategory::Output);
+}
+
+/// Whether `commands` may run, asking the user when the settings say so.
+fn confirm_shell(app: &tauri::AppHandle, commands: Vec<String>) -> Result<(), String> {
+    match settings::current().voice_commands.shell_actions {
+        ShellPolicy::Allow => return Ok(()),
+        ShellPolicy::Deny => return Err("Shell actions are turned off".to_string()),
+        ShellPolicy::Ask => {}
+    }
+    let id = NEXT_CONFIRMATION.fetch_add(1, Ordering::Relaxed);
+    let (answer, answered) = mpsc::channel();
+    CONFIRMATIONS
+        .lock()
+        .map_err(|err| err.to_string())?
+        .push((id, answer));
+    let handle = app.clone();
+    let _ = app.run_on_main_thread(move || crate::overlay::show(&handle));
+    let _ = app.emit("shell-confirmation", ShellConfirmation { id, commands });
+    let allowed = answered.recv_timeout(CONFIRM_TIMEOUT).unwrap_or(false);
+    if let Ok(mut confirmations) = CONFIRMATIONS.lock() {
+        confirmations.retain(|(pending, _)| *pending != id);
+    }
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.hide();
+    }
+    if allowed {
+        Ok(())
+    } else {
+        Err("Shell command not confirmed".to_string())
+    }
+}
+
+/// The user's answer to a `shell-confirmation` event.
+pub fn answer(id: u64, allow: bool) -> Result<(), String> {
+    let confirmations = CONFIRMATIONS.lock().map_err(|err| err.to_string())?;
+    let (_, answer) = confirmations
+        .iter()
+        .find(|(pending, _)| *pending == id)
+        .ok_or("No such confirmation, or it timed out")?;
+    answer.send(allow).map_err(|err| err.to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn launch(name: &str) -> Result<(), String> {
+    let status = Command::new("open")
+        .args(["-a", name])
+        .status()
+        .map_err(|err| err.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("Failed to launch {}", name))
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn launch(name: &str) -> Result<(), String> {
+    use windows::core::{HSTRING, PCWSTR};
+    use windows::Win32::UI::Shell::ShellExecuteW;
+    use windows::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+    // Finds apps by name in App Paths as well as on the PATH, like `start`, but without a
+    // command line for cmd to split the name up in.
+    let instance = unsafe {
+        ShellExecuteW(
+            None,
+            &HSTRING::from("open"),
+            &HSTRING::from(name),
+            PCWSTR::null(),
+            PCWSTR::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+    // Anything above 32 is success; lower values are error codes.
+    if instance.0 as usize > 32 {
+        Ok(())
+    } else {
+        Err(format!("Failed to launch {}", name))
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn launch(name: &str) -> Result<(), String> {
+    let mut child = Command::new(name)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("Failed to launch {}: {}", name, err))?;
+    // Reaped once it exits, which may be long after VType.
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Through `curl`, which every desktop VType runs on ships and which brings its own TLS.
+fn http(
+    url: &str,
+    method: &str,
+    body: Option<&str>,
+    headers: &std::collections::BTreeMap<String, String>,
+) -> Result<(), String> {
+    use std::io::Write;
+
+    let mut command = Command::new("curl");
+    command.args([
+        "--fail",
+        "--silent",
+        "--show-error",
+        "--max-time",
+        HTTP_TIMEOUT_SECS,
+        "--request",
+        method,
+    ]);
+    for (name, value) in headers {
+        command.args(["--header", &format!("{}: {}", name, value)]);
+    }
+    if body.is_some() {
+        command.args(["--data-binary", "@-"]);
+    }
+    process::configure_background_command(&mut command);
+    let mut child = command
+        .args(["--url", url])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Failed to run curl: {}", err))?;
+    if let (Some(mut stdin), Some(body)) = (child.stdin.take(), body) {
+        stdin
+            .write_all(body.as_bytes())
+            .map_err(|err| err.to_string())?;
+    }
+    let output = child.wait_with_output().map_err(|err| err.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "HTTP {} {} failed: {}",
+            method,
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
@@ -0,0 +1,100 @@
+//! State changes worded for screen reader users, such as "Recording started" or "Pasted 42
+//! words". Each is sent as an `announcement` event, which the overlay puts in a live region,
+//! and with `announceAloud` set is also read out by the system voice.
+
+use serde::Serialize;
+use tauri::Emitter;
+use vtype_core::i18n::{self, Message};
+use vtype_core::{log, settings, speech};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Announcement {
+    RecordingStarted,
+    Transcribing,
+    /// Pasted or typed, with the number of words.
+    Delivered(usize),
+    /// Left on the clipboard only, with the number of words.
+    Copied(usize),
+    /// Carries the error, which is already in the user's language.
+    Failed(Option<String>),
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Payload {
+    kind: &'static str,
+    text: String,
+    words: Option<usize>,
+}
+
+pub fn announce(app: &tauri::AppHandle, announcement: Announcement) {
+    let payload = payload(announcement);
+    if settings::current().announce_aloud {
+        if let Err(err) = speech::speak(&payload.text) {
+            let _ = log::write(format!("Failed to read announcement aloud: {}", err));
+        }
+    }
+    let _ = app.emit("announcement", payload);
+}
+
+/// For the moment just before the microphone opens: read aloud, it is finished before this
+/// returns, so the voice does not end up in the recording.
+pub fn announce_before_recording(app: &tauri::AppHandle) {
+    let payload = payload(Announcement::RecordingStarted);
+    if settings::current().announce_aloud {
+        if let Err(err) = speech::speak_and_wait(&payload.text) {
+            let _ = log::write(format!("Failed to read announcement aloud: {}", err));
+        }
+    }
+    let _ = app.emit("announcement", payload);
+}
+
+fn payload(announcement: Announcement) -> Payload {
+    let (kind, text, words) = match announcement {
+        Announcement::RecordingStarted => (
+            "recordingStarted",
+            i18n::text(Message::RecordingStarted, &[]),
+            None,
+        ),
+        Announcement::Transcribing => {
+            ("transcribing", i18n::text(Message::Transcribing, &[]), None)
+        }
+        Announcement::Delivered(words) => (
+            "delivered",
+            counted(Message::PastedOneWord, Message::PastedWords, words),
+            Some(words),
+        ),
+        Announcement::Copied(words) => (
+            "copied",
+            counted(Message::CopiedOneWord, Message::CopiedWords, words),
+            Some(words),
+        ),
+        Announcement::Failed(err) => (
+            "failed",
+            err.map(|err| readable(&err).to_string())
+                .unwrap_or_else(|| i18n::text(Message::TranscriptionFailed, &[])),
+            None,
+        ),
+    };
+    Payload { kind, text, words }
+}
+
+/// An error without its code.
+fn readable(err: &str) -> &str {
+    match err.split_once(": ") {
+        Some((code, text))
+            if !code.is_empty() && code.chars().all(|c| c.is_ascii_uppercase() || c == '_') =>
+        {
+            text
+        }
+        _ => err,
+    }
+}
+
+fn counted(one: Message, many: Message, words: usize) -> String {
+    if words == 1 {
+        i18n::text(one, &[])
+    } else {
+        i18n::text(many, &[&words.to_string()])
+    }
+}
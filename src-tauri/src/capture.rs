@@ -0,0 +1,762 @@
+//! Microphone capture. Streams live on a dedicated thread for the length of a recording,
+//! since cpal streams cannot be moved between threads on every platform; `stop` returns
+//! the preprocessed WAV ready for the pipeline, and the parts of it already on their way to
+//! the backend when `transcribeWhileRecording` is on.
+
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{
+    ErrorKind, FromSample, InterfaceType, Sample, SampleFormat, SizedSample, SupportedStreamConfig,
+    SupportedStreamConfigRange, I24, U24,
+};
+use serde::Serialize;
+use tauri::Emitter;
+use vtype_core::i18n::{self, Message};
+use vtype_core::preprocess::{self, Captured, TARGET_SAMPLE_RATE};
+use vtype_core::settings::{self, CaptureSettings};
+use vtype_core::{audio, early, log, pipeline, recording, worker};
+
+struct Session {
+    stop: Sender<()>,
+    thread: JoinHandle<Recording>,
+    capture: CaptureSettings,
+    /// When the stream was opened by [`prepare`], until a recording takes it up.
+    prepared: Option<Instant>,
+}
+
+impl Session {
+    /// Stops the stream, dropping what it recorded.
+    fn close(self) {
+        let _ = self.stop.send(());
+        let _ = self.thread.join();
+        PAUSED.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Microphone audio in one segment per device used, and the loopback reference.
+#[derive(Default)]
+struct Recording {
+    mic: Vec<Captured>,
+    reference: Option<Captured>,
+    parts: Parts,
+}
+
+/// A finished recording.
+pub struct Stopped {
+    /// The whole recording as a preprocessed 16 kHz WAV.
+    pub wav: Vec<u8>,
+    /// Set when parts of it were sent for transcription while it went on.
+    pub early: Option<Early>,
+}
+
+pub struct Early {
+    /// What came after the parts, as a WAV.
+    pub tail: Vec<u8>,
+    pub parts: Parts,
+}
+
+/// Transcriptions of the parts of a recording, started while it went on and stopped if
+/// dropped unawaited; see [`vtype_core::early`].
+#[derive(Default)]
+pub struct Parts {
+    /// Samples at [`TARGET_SAMPLE_RATE`] the parts cover, from the start.
+    covered: usize,
+    pending: Vec<tauri::async_runtime::JoinHandle<Result<pipeline::Part, String>>>,
+}
+
+impl Parts {
+    /// The parts in order, or why one of them failed.
+    pub async fn finished(mut self) -> Result<Vec<pipeline::Part>, String> {
+        let mut parts = Vec::with_capacity(self.pending.len());
+        for part in std::mem::take(&mut self.pending) {
+            parts.push(part.await.map_err(|err| err.to_string())??);
+        }
+        Ok(parts)
+    }
+
+    /// Sends the next part of the recording so far, the finished `segments` and what the
+    /// open `mic` holds, for transcription if it has one.
+    fn send_next(
+        &mut self,
+        segments: &[Captured],
+        mic: Option<(&Input, Vec<u16>)>,
+        reference: Option<&Input>,
+        capture: &CaptureSettings,
+    ) {
+        let captured_secs = segments.iter().map(Captured::duration_secs).sum::<f32>()
+            + mic.as_ref().map_or(0.0, |(mic, _)| mic.buffered_secs());
+        let covered_secs = self.covered as f32 / TARGET_SAMPLE_RATE as f32;
+        // Cheaper than preprocessing everything to find that there cannot be a part yet.
+        if captured_secs < covered_secs + early::MIN_PART_MS as f32 / 1000.0 {
+            return;
+        }
+        let mut so_far = segments.to_vec();
+        so_far.extend(mic.map(|(mic, selection)| mic.snapshot(selection)));
+        let reference = reference.map(|reference| reference.snapshot(Vec::new()));
+        let samples = preprocess::process(so_far, reference, capture);
+        let Some(cut) = early::next_cut(&samples, self.covered, TARGET_SAMPLE_RATE) else {
+            return;
+        };
+        let mut wav = worker::take_buffer();
+        audio::write_wav_f32(&samples[self.covered..cut], TARGET_SAMPLE_RATE, &mut wav);
+        let _ = log::write(format!(
+            "Transcribing part {} of the recording, {:.2}s in",
+            self.pending.len() + 1,
+            cut as f32 / TARGET_SAMPLE_RATE as f32
+        ));
+        self.pending
+            .push(tauri::async_runtime::spawn(pipeline::transcribe_part(wav)));
+        self.covered = cut;
+    }
+}
+
+impl Drop for Parts {
+    fn drop(&mut self) {
+        for part in &self.pending {
+            part.abort();
+        }
+    }
+}
+
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+/// Set while the recording is paused: the streams stay open, but what they deliver is dropped.
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Inputs at or below this rate are running a Bluetooth hands-free profile (HFP), whose
+/// narrowband codecs cost recognition accuracy.
+const NARROWBAND_MAX_RATE: u32 = 24_000;
+/// Name fragments of Bluetooth inputs on hosts that do not report the transport.
+const BLUETOOTH_NAME_HINTS: &[&str] = &["bluetooth", "bluez", "hands-free", "handsfree", "airpods"];
+
+/// Emitted when the microphone in use is a narrowband Bluetooth headset.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BluetoothWarning {
+    pub device: String,
+    pub sample_rate: u32,
+}
+
+/// The microphone a recording opened.
+struct Opened {
+    device: String,
+    sample_rate: u32,
+    bluetooth: bool,
+}
+
+/// How often a recording checks whether its microphone went away or the configured one
+/// came back.
+const DEVICE_POLL: Duration = Duration::from_millis(500);
+
+/// Emitted when recording moves off the configured microphone, back to it, or to a new
+/// default device after the one in use disappeared.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceChanged {
+    pub device: String,
+    pub preferred: Option<String>,
+    /// True while the configured microphone is missing and the default stands in.
+    pub fallback: bool,
+}
+
+/// Whether the last recording fell back, so its return is reported once.
+static ON_FALLBACK: AtomicBool = AtomicBool::new(false);
+
+/// Opens the microphone, and the loopback reference when echo cancellation is on, and
+/// starts buffering. A stream [`prepare`] opened is taken up with what it has buffered since.
+pub fn start(app: &tauri::AppHandle) -> Result<(), String> {
+    begin(app, settings::current().transcribe_while_recording, false)
+}
+
+/// Opens the microphone ahead of [`start`], as soon as the hotkey is pressed, so the words
+/// said while the overlay comes up are kept. Nothing counts as a recording until `start`
+/// takes the stream up, and [`discard_prepared`] closes it if that never happens.
+pub fn prepare(app: &tauri::AppHandle) -> Result<(), String> {
+    begin(app, settings::current().transcribe_while_recording, true)
+}
+
+/// Closes the stream [`prepare`] opened, if no recording took it up within `grace`.
+pub fn discard_prepared(grace: Duration) {
+    let Ok(mut session) = SESSION.lock() else {
+        return;
+    };
+    let stale = session
+        .as_ref()
+        .and_then(|session| session.prepared)
+        .is_some_and(|prepared| prepared.elapsed() >= grace);
+    if let Some(session) = session.take_if(|_| stale) {
+        session.close();
+        let _ = log::write(
+            "Closed the microphone opened for a dictation that did not start".to_string(),
+        );
+    }
+}
+
+/// [`start`], sending parts for transcription while recording when `early` is set, or
+/// [`prepare`] when `prepared` is. A prepared stream keeps the `early` it was opened with.
+fn begin(app: &tauri::AppHandle, early: bool, prepared: bool) -> Result<(), String> {
+    let mut session = SESSION.lock().map_err(|err| err.to_string())?;
+    if let Some(open) = session.as_mut() {
+        if prepared || open.prepared.is_none() {
+            return Err("Already recording".to_string());
+        }
+        open.prepared = None;
+        PAUSED.store(false, Ordering::Relaxed);
+        recording::started();
+        return Ok(());
+    }
+    let capture = settings::current().capture;
+    PAUSED.store(false, Ordering::Relaxed);
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let (stop_tx, stop_rx) = mpsc::channel();
+    let thread = {
+        let capture = capture.clone();
+        let app = app.clone();
+        std::thread::spawn(move || run(&app, &capture, early, ready_tx, stop_rx))
+    };
+    let opened = ready_rx
+        .recv()
+        .map_err(|_| "Capture thread exited before the stream opened".to_string())??;
+    if opened.bluetooth && opened.sample_rate <= NARROWBAND_MAX_RATE {
+        let _ = log::write(format!(
+            "Recording from Bluetooth input {} at {}Hz; accuracy may suffer",
+            opened.device, opened.sample_rate
+        ));
+        let _ = app.emit(
+            "bluetooth-input",
+            BluetoothWarning {
+                device: opened.device,
+                sample_rate: opened.sample_rate,
+            },
+        );
+    }
+    *session = Some(Session {
+        stop: stop_tx,
+        thread,
+        capture,
+        prepared: prepared.then(Instant::now),
+    });
+    if !prepared {
+        recording::started();
+    }
+    Ok(())
+}
+
+/// Stops keeping audio until [`resume`], say for a phone call, without ending the recording.
+/// The microphone stays open, so nothing said either side of the pause is cut.
+pub fn pause() -> Result<(), String> {
+    set_paused(true)
+}
+
+pub fn resume() -> Result<(), String> {
+    set_paused(false)
+}
+
+pub fn paused() -> bool {
+    PAUSED.load(Ordering::Relaxed)
+}
+
+fn set_paused(paused: bool) -> Result<(), String> {
+    let session = SESSION.lock().map_err(|err| err.to_string())?;
+    if session.is_none() {
+        return Err("Not recording".to_string());
+    }
+    PAUSED.store(paused, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Ends the recording.
+pub fn stop() -> Result<Stopped, String> {
+    let (recording, capture) = finish()?;
+    let samples = preprocess::process(recording.mic, recording.reference, &capture);
+    let mut wav = worker::take_buffer();
+    audio::write_wav_f32(&samples, TARGET_SAMPLE_RATE, &mut wav);
+    let parts = recording.parts;
+    let early = (!parts.pending.is_empty()).then(|| {
+        let mut tail = worker::take_buffer();
+        let rest = samples.get(parts.covered..).unwrap_or_default();
+        audio::write_wav_f32(rest, TARGET_SAMPLE_RATE, &mut tail);
+        Early { tail, parts }
+    });
+    Ok(Stopped { wav, early })
+}
+
+/// What a setup check records and hears.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestClip {
+    /// The preprocessed clip, for playback.
+    pub wav_base64: String,
+    pub levels: audio::Levels,
+    pub transcript: String,
+}
+
+pub const TEST_CLIP_SECONDS: RangeInclusive<f32> = 1.0..=10.0;
+
+/// Records for `seconds` and returns the preprocessed mono samples at
+/// [`preprocess::TARGET_SAMPLE_RATE`].
+pub fn record_clip(app: &tauri::AppHandle, seconds: f32) -> Result<Vec<f32>, String> {
+    if !TEST_CLIP_SECONDS.contains(&seconds) {
+        return Err(format!(
+            "Test clip length out of range (1-10s): {}",
+            seconds
+        ));
+    }
+    // What was said before the check was asked for is not part of the clip.
+    discard_prepared(Duration::ZERO);
+    begin(app, false, false)?;
+    std::thread::sleep(Duration::from_secs_f32(seconds));
+    let (recording, capture) = finish()?;
+    Ok(preprocess::process(
+        recording.mic,
+        recording.reference,
+        &capture,
+    ))
+}
+
+fn finish() -> Result<(Recording, CaptureSettings), String> {
+    let session = SESSION
+        .lock()
+        .map_err(|err| err.to_string())?
+        .take()
+        .ok_or_else(|| "Not recording".to_string())?;
+    let _ = session.stop.send(());
+    let recording = session
+        .thread
+        .join()
+        .map_err(|_| "Capture thread panicked".to_string())?;
+    PAUSED.store(false, Ordering::Relaxed);
+    recording::stopped();
+
+    let Recording { mic, reference, .. } = &recording;
+    if mic.iter().all(|segment| segment.samples.is_empty()) {
+        return Err("No audio captured. Check microphone input.".to_string());
+    }
+    let _ = log::write(format!(
+        "Captured {:.2}s from {} device segment(s){}",
+        mic.iter().map(Captured::duration_secs).sum::<f32>(),
+        mic.len(),
+        if reference.is_some() {
+            " with loopback reference"
+        } else {
+            ""
+        }
+    ));
+    Ok((recording, session.capture))
+}
+
+fn run(
+    app: &tauri::AppHandle,
+    capture: &CaptureSettings,
+    early: bool,
+    ready: Sender<Result<Opened, String>>,
+    stop: Receiver<()>,
+) -> Recording {
+    let host = cpal::default_host();
+    let (mic, mut preferred) = match open_microphone(&host, capture) {
+        Ok(opened) => opened,
+        Err(err) => {
+            let _ = ready.send(Err(err));
+            return Recording::default();
+        }
+    };
+    let reference = if capture.echo_cancellation {
+        match open_loopback(&host, capture.loopback_device.as_deref()) {
+            Ok(reference) => Some(reference),
+            Err(err) => {
+                let _ = log::write(format!(
+                    "Echo cancellation unavailable, recording without it: {}",
+                    err
+                ));
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let _ = ready.send(Ok(Opened {
+        device: mic.device.to_string(),
+        sample_rate: mic.sample_rate,
+        bluetooth: is_bluetooth(&mic.device),
+    }));
+    let fallback = !preferred;
+    if ON_FALLBACK.swap(fallback, Ordering::Relaxed) != fallback {
+        report_device(app, capture, &mic.device, preferred);
+    }
+
+    let selection = |preferred: bool| {
+        if preferred {
+            capture.channels.clone()
+        } else {
+            Vec::new()
+        }
+    };
+    let mut mic = Some(mic);
+    let mut segments = Vec::new();
+    let mut parts = Parts::default();
+    while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(DEVICE_POLL) {
+        if early {
+            let open = mic.as_ref().map(|mic| (mic, selection(preferred)));
+            parts.send_next(&segments, open, reference.as_ref(), capture);
+        }
+        let lost = mic.as_ref().is_none_or(Input::lost);
+        let returned = !preferred
+            && capture
+                .input_device
+                .as_deref()
+                .is_some_and(|name| find_input(&host, name).is_ok());
+        if !lost && !returned {
+            continue;
+        }
+        if let Some(current) = mic.take() {
+            segments.push(current.finish(selection(preferred)));
+        }
+        match open_microphone(&host, capture) {
+            Ok((next, next_preferred)) => {
+                let _ = log::write(format!("Recording switched to {}", next.device));
+                preferred = next_preferred;
+                ON_FALLBACK.store(!preferred, Ordering::Relaxed);
+                report_device(app, capture, &next.device, preferred);
+                mic = Some(next);
+            }
+            // Retried on the next poll; audio until then is lost.
+            Err(err) => {
+                let _ = log::write(format!("No microphone to switch to: {}", err));
+            }
+        }
+    }
+    segments.extend(mic.map(|mic| mic.finish(selection(preferred))));
+    Recording {
+        mic: segments,
+        reference: reference.map(|reference| reference.finish(Vec::new())),
+        parts,
+    }
+}
+
+fn report_device(
+    app: &tauri::AppHandle,
+    capture: &CaptureSettings,
+    device: &cpal::Device,
+    preferred: bool,
+) {
+    let _ = app.emit(
+        "device-changed",
+        DeviceChanged {
+            device: device.to_string(),
+            preferred: capture.input_device.clone(),
+            fallback: !preferred,
+        },
+    );
+}
+
+/// Sandboxed, cpal reaches the sound server over the PulseAudio socket, and there is no
+/// portal to ask for it at runtime.
+fn no_microphone() -> String {
+    #[cfg(target_os = "linux")]
+    if crate::portal::sandboxed() {
+        return i18n::error(Message::NoMicrophoneSandboxed, &[]);
+    }
+    i18n::error(Message::NoMicrophone, &[])
+}
+
+/// Opens the configured microphone, or the default one while it is missing. The flag is
+/// false when the default stands in for a configured device.
+fn open_microphone(host: &cpal::Host, capture: &CaptureSettings) -> Result<(Input, bool), String> {
+    let default = || host.default_input_device().ok_or_else(no_microphone);
+    let (mut device, preferred) = match capture.input_device.as_deref() {
+        Some(name) => match find_input(host, name) {
+            Ok(device) => (device, true),
+            Err(err) => {
+                let _ = log::write(format!("{}; using the default microphone", err));
+                (default()?, false)
+            }
+        },
+        None => (default()?, true),
+    };
+    if capture.avoid_bluetooth_input && is_bluetooth(&device) {
+        // Checked before opening: opening the mic is what switches the headset to HFP.
+        if let Some(wired) = host.input_devices().ok().and_then(|mut devices| {
+            devices.find(|other| !is_bluetooth(other) && !is_monitor(other))
+        }) {
+            let _ = log::write(format!(
+                "Using {} instead of Bluetooth input {}",
+                wired, device
+            ));
+            device = wired;
+        }
+    }
+    let input = Input::open(device, false)?;
+    Ok((input, preferred))
+}
+
+fn is_bluetooth(device: &cpal::Device) -> bool {
+    if device
+        .description()
+        .is_ok_and(|description| description.interface_type() == InterfaceType::Bluetooth)
+    {
+        return true;
+    }
+    let name = device.to_string().to_lowercase();
+    BLUETOOTH_NAME_HINTS.iter().any(|hint| name.contains(hint))
+}
+
+fn is_monitor(device: &cpal::Device) -> bool {
+    device.to_string().to_lowercase().contains("monitor")
+}
+
+/// The stream echo cancellation subtracts: a named input, the system output in loopback
+/// on platforms whose audio API offers it, or a PulseAudio/PipeWire monitor source.
+fn open_loopback(host: &cpal::Host, name: Option<&str>) -> Result<Input, String> {
+    if let Some(name) = name {
+        return Input::open(find_input(host, name)?, false);
+    }
+    if cfg!(any(target_os = "windows", target_os = "macos")) {
+        // Building an input stream on an output device records what it plays.
+        let device = host
+            .default_output_device()
+            .ok_or_else(|| "No output device to record".to_string())?;
+        return Input::open(device, true);
+    }
+    let device = host
+        .input_devices()
+        .map_err(|err| err.to_string())?
+        .find(is_monitor)
+        .ok_or_else(|| "No monitor source found; set capture.loopbackDevice".to_string())?;
+    Input::open(device, false)
+}
+
+fn find_input(host: &cpal::Host, name: &str) -> Result<cpal::Device, String> {
+    host.input_devices()
+        .map_err(|err| err.to_string())?
+        .find(|device| device.to_string() == name)
+        .ok_or_else(|| format!("Audio input {} not found", name))
+}
+
+/// An input the settings UI can offer.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioInput {
+    pub name: String,
+    /// Channels in the device's default configuration, for channel selection.
+    pub channels: u16,
+    pub is_default: bool,
+}
+
+pub fn list_inputs() -> Result<Vec<AudioInput>, String> {
+    let host = cpal::default_host();
+    let default = host.default_input_device().map(|device| device.to_string());
+    Ok(host
+        .input_devices()
+        .map_err(|err| err.to_string())?
+        .filter_map(|device| {
+            let config = device.default_input_config().ok()?;
+            let name = device.to_string();
+            Some(AudioInput {
+                is_default: default.as_deref() == Some(name.as_str()),
+                channels: config.channels(),
+                name,
+            })
+        })
+        .collect())
+}
+
+/// A running input stream appending interleaved samples to a shared buffer.
+struct Input {
+    device: cpal::Device,
+    stream: cpal::Stream,
+    buffer: Arc<Mutex<Vec<f32>>>,
+    /// Set when the device disappears mid-recording.
+    lost: Arc<AtomicBool>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl Input {
+    /// Starts recording `device`; with `loopback`, what an output device plays.
+    fn open(device: cpal::Device, loopback: bool) -> Result<Self, String> {
+        let config = negotiate(&device, loopback).map_err(|err| format!("{}: {}", device, err))?;
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let lost = Arc::new(AtomicBool::new(false));
+        let sink = Sink {
+            buffer: buffer.clone(),
+            lost: lost.clone(),
+        };
+        let device_ref = &device;
+        let stream = match config.sample_format() {
+            SampleFormat::F32 => build::<f32>(device_ref, &config, sink),
+            SampleFormat::F64 => build::<f64>(device_ref, &config, sink),
+            SampleFormat::I8 => build::<i8>(device_ref, &config, sink),
+            SampleFormat::I16 => build::<i16>(device_ref, &config, sink),
+            SampleFormat::I24 => build::<I24>(device_ref, &config, sink),
+            SampleFormat::I32 => build::<i32>(device_ref, &config, sink),
+            SampleFormat::I64 => build::<i64>(device_ref, &config, sink),
+            SampleFormat::U8 => build::<u8>(device_ref, &config, sink),
+            SampleFormat::U16 => build::<u16>(device_ref, &config, sink),
+            SampleFormat::U24 => build::<U24>(device_ref, &config, sink),
+            SampleFormat::U32 => build::<u32>(device_ref, &config, sink),
+            SampleFormat::U64 => build::<u64>(device_ref, &config, sink),
+            format => Err(format!("Unsupported sample format {}", format)),
+        }
+        .map_err(|err| format!("{}: {}", device, err))?;
+        stream
+            .play()
+            .map_err(|err| format!("{}: {}", device, err))?;
+        Ok(Self {
+            device,
+            stream,
+            buffer,
+            lost,
+            channels: config.channels(),
+            sample_rate: config.sample_rate(),
+        })
+    }
+
+    fn lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    fn buffered_secs(&self) -> f32 {
+        let samples = self.buffer.lock().map_or(0, |buffer| buffer.len());
+        samples as f32 / (f32::from(self.channels.max(1)) * self.sample_rate.max(1) as f32)
+    }
+
+    /// What was recorded so far, as [`Input::finish`] would return it.
+    fn snapshot(&self, selection: Vec<u16>) -> Captured {
+        Captured {
+            samples: self
+                .buffer
+                .lock()
+                .map(|buffer| buffer.clone())
+                .unwrap_or_default(),
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            selection,
+        }
+    }
+
+    /// Stops the stream; `selection` picks the channels to keep, as in [`Captured`].
+    fn finish(self, selection: Vec<u16>) -> Captured {
+        drop(self.stream);
+        let samples = self
+            .buffer
+            .lock()
+            .map(|mut buffer| std::mem::take(&mut *buffer))
+            .unwrap_or_default();
+        Captured {
+            samples,
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            selection,
+        }
+    }
+}
+
+/// Formats converted to float on capture, most preferred first: float needs no conversion
+/// and wider integers keep more of a quiet signal.
+const FORMAT_PREFERENCE: &[SampleFormat] = &[
+    SampleFormat::F32,
+    SampleFormat::I32,
+    SampleFormat::I24,
+    SampleFormat::F64,
+    SampleFormat::I16,
+    SampleFormat::U32,
+    SampleFormat::U24,
+    SampleFormat::U16,
+    SampleFormat::I64,
+    SampleFormat::U64,
+    SampleFormat::I8,
+    SampleFormat::U8,
+];
+/// Rate asked for when the default configuration cannot be used; resampling to 16 kHz
+/// from here is cheap and the rate is supported almost everywhere.
+const PREFERRED_SAMPLE_RATE: u32 = 48_000;
+
+/// The device's default configuration when its format converts, otherwise the supported
+/// configuration with the most preferred format at the rate closest to the default. Among
+/// equal formats the widest channel layout wins, so a selected channel stays available.
+fn negotiate(device: &cpal::Device, loopback: bool) -> Result<SupportedStreamConfig, String> {
+    let default = if loopback {
+        device.default_output_config()
+    } else {
+        device.default_input_config()
+    };
+    if let Ok(config) = &default {
+        if FORMAT_PREFERENCE.contains(&config.sample_format()) {
+            return Ok(*config);
+        }
+    }
+    let ranges: Vec<SupportedStreamConfigRange> = if loopback {
+        device
+            .supported_output_configs()
+            .map_err(|err| err.to_string())?
+            .collect()
+    } else {
+        device
+            .supported_input_configs()
+            .map_err(|err| err.to_string())?
+            .collect()
+    };
+    let rate = default
+        .as_ref()
+        .map(|config| config.sample_rate())
+        .unwrap_or(PREFERRED_SAMPLE_RATE);
+    let range = ranges
+        .into_iter()
+        .filter_map(|range| {
+            let rank = FORMAT_PREFERENCE
+                .iter()
+                .position(|format| *format == range.sample_format())?;
+            Some((rank, range))
+        })
+        .min_by_key(|(rank, range)| (*rank, std::cmp::Reverse(range.channels())))
+        .map(|(_, range)| range)
+        .ok_or_else(|| match default {
+            Ok(config) => format!("Unsupported sample format {}", config.sample_format()),
+            Err(err) => err.to_string(),
+        })?;
+    let rate = rate.clamp(range.min_sample_rate(), range.max_sample_rate());
+    Ok(range.with_sample_rate(rate))
+}
+
+/// Where a stream's callbacks deliver samples and device loss.
+struct Sink {
+    buffer: Arc<Mutex<Vec<f32>>>,
+    lost: Arc<AtomicBool>,
+}
+
+fn build<T>(
+    device: &cpal::Device,
+    config: &SupportedStreamConfig,
+    sink: Sink,
+) -> Result<cpal::Stream, String>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let Sink { buffer, lost } = sink;
+    device
+        .build_input_stream::<T, _, _>(
+            config.config(),
+            move |data: &[T], _| {
+                if PAUSED.load(Ordering::Relaxed) {
+                    return;
+                }
+                if let Ok(mut buffer) = buffer.lock() {
+                    buffer.extend(data.iter().map(|sample| f32::from_sample(*sample)));
+                }
+            },
+            move |err| {
+                if err.kind() == ErrorKind::DeviceNotAvailable {
+                    lost.store(true, Ordering::Relaxed);
+                }
+                let _ = log::write(format!("Audio stream error: {}", err));
+            },
+            None,
+        )
+        .map_err(|err| err.to_string())
+}
@@ -0,0 +1,108 @@
+//! Finds a recording to transcribe on the clipboard: files copied in a file manager, a path
+//! or `file://` URL copied as text, or a `data:` URL holding the audio itself.
+
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose;
+use base64::Engine as _;
+use vtype_core::{decode, paths, safe_io};
+
+/// Where `data:` URLs are written so they can be decoded like any other file.
+const DATA_FILE_STEM: &str = "vtype_clipboard";
+
+/// The media file the clipboard refers to, written to a temporary file when the clipboard
+/// holds the data rather than a path.
+pub fn media_file() -> Result<PathBuf, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|err| err.to_string())?;
+    if let Ok(files) = clipboard.get().file_list() {
+        if let Some(path) = files.into_iter().find(|path| decode::is_media(path)) {
+            return Ok(path);
+        }
+    }
+    let text = clipboard
+        .get_text()
+        .map_err(|_| "The clipboard holds no audio file".to_string())?;
+    let text = text.trim();
+    if let Some(data) = text.strip_prefix("data:") {
+        return write_data_url(data);
+    }
+    text.lines()
+        .filter_map(path_from_text)
+        .find(|path| decode::is_media(path) && path.is_file())
+        .ok_or_else(|| "The clipboard holds no audio file".to_string())
+}
+
+fn path_from_text(line: &str) -> Option<PathBuf> {
+    let line = line.trim().trim_matches('"');
+    if line.is_empty() {
+        return None;
+    }
+    let Some(rest) = line.strip_prefix("file://") else {
+        return Some(PathBuf::from(line));
+    };
+    // Drop the host part; `file:///C:/x` names `C:/x` on Windows.
+    let path = &rest[rest.find('/')?..];
+    let path = percent_decode(path)?;
+    if cfg!(windows) {
+        let trimmed = path.trim_start_matches('/');
+        if trimmed.as_bytes().get(1) == Some(&b':') {
+            return Some(PathBuf::from(trimmed));
+        }
+    }
+    Some(PathBuf::from(path))
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(index + 1..index + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            index += 3;
+        } else {
+            out.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Writes the payload of a base64 `data:` URL (without its scheme) to a temporary file
+/// named for its media type.
+fn write_data_url(data: &str) -> Result<PathBuf, String> {
+    let (header, payload) = data
+        .split_once(',')
+        .ok_or_else(|| "Malformed data URL".to_string())?;
+    let mime = header.split(';').next().unwrap_or_default();
+    if !header.ends_with(";base64") {
+        return Err("Only base64 data URLs are supported".to_string());
+    }
+    let extension =
+        extension_for(mime).ok_or_else(|| format!("Unsupported clipboard data: {}", mime))?;
+    let bytes = general_purpose::STANDARD
+        .decode(payload.trim())
+        .map_err(|err| err.to_string())?;
+    let dir = paths::working_dir();
+    std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    let path = dir.join(Path::new(DATA_FILE_STEM).with_extension(extension));
+    safe_io::write(&path, bytes).map_err(|err| err.to_string())?;
+    Ok(path)
+}
+
+fn extension_for(mime: &str) -> Option<&'static str> {
+    Some(match mime.to_ascii_lowercase().as_str() {
+        "audio/wav" | "audio/wave" | "audio/x-wav" | "audio/vnd.wave" => "wav",
+        "audio/mpeg" | "audio/mp3" => "mp3",
+        "audio/flac" | "audio/x-flac" => "flac",
+        "audio/ogg" => "ogg",
+        "audio/mp4" | "audio/x-m4a" => "m4a",
+        "audio/aac" => "aac",
+        "audio/webm" | "video/webm" => "webm",
+        "video/mp4" => "mp4",
+        "video/quicktime" => "mov",
+        "video/x-matroska" => "mkv",
+        _ => return None,
+    })
+}
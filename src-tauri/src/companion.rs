@@ -0,0 +1,440 @@
+//! A phone on the same network as a wireless microphone. While `companion.enabled` is set,
+//! VType listens for WebSocket connections on `companion.port`:
+//!
+//! - `/pair?code=<code>&name=<name>` pairs a phone with the code [`start_pairing`] shows,
+//!   answering `{"type":"paired","token":...}`.
+//! - `/stream`, with the token in an `Authorization: Bearer` header, takes a dictation: a
+//!   `{"type":"start","sampleRate":16000}` message, the recording as binary messages of mono
+//!   16-bit little-endian PCM, and `{"type":"stop"}`. The transcript is pasted here and sent
+//!   back as `{"type":"transcript","text":...}`, or `{"type":"error","message":...}`.
+//!
+//! The traffic is plain `ws://`, so only connections from private, link-local and loopback
+//! addresses are taken; a port forwarded from the internet gets nowhere.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+use vtype_core::http::{self, Request};
+use vtype_core::options::TranscribeOptions;
+use vtype_core::pairing::{self, Pairing, PAIRING_TIMEOUT};
+use vtype_core::preprocess::TARGET_SAMPLE_RATE;
+use vtype_core::settings::{self, PairedDevice};
+use vtype_core::websocket::{self, Message};
+use vtype_core::worker::Priority;
+use vtype_core::{audio, log, pipeline};
+
+use crate::{mdns, review};
+
+/// A connection that sends nothing for this long is dropped.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+/// Connections served at once; more are turned away until one closes.
+const MAX_CONNECTIONS: usize = 8;
+pub const SAMPLE_RATES: std::ops::RangeInclusive<u32> = 8_000..=48_000;
+/// Longest recording taken, in seconds.
+pub const MAX_RECORDING_SECS: usize = 600;
+
+struct Server {
+    port: u16,
+    stopped: Arc<AtomicBool>,
+    advertisement: Option<mdns::Advertisement>,
+}
+
+static SERVER: Mutex<Option<Server>> = Mutex::new(None);
+static PAIRING: Mutex<Option<Pairing>> = Mutex::new(None);
+
+/// What a phone needs to pair, for the user to type in or show as a QR code.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairingInfo {
+    /// This machine's address on the network, with the port.
+    pub address: String,
+    pub code: String,
+    /// The address and code in one, for a QR code.
+    pub url: String,
+    pub expires_in_secs: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+enum ClientMessage {
+    Start {
+        #[serde(rename = "sampleRate")]
+        sample_rate: u32,
+    },
+    Stop,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+enum ServerMessage {
+    Paired { token: String },
+    Transcript { text: String },
+    Error { message: String },
+}
+
+/// Starts or stops listening, and advertising, to match the settings.
+pub fn apply(app: &tauri::AppHandle, companion: &settings::CompanionSettings) {
+    let Ok(mut server) = SERVER.lock() else {
+        return;
+    };
+    let wanted = companion.enabled.then_some(companion.port);
+    if server.as_ref().map(|server| server.port) != wanted {
+        if let Some(running) = server.take() {
+            running.stopped.store(true, Ordering::SeqCst);
+            // Wakes the accept loop so it sees the flag.
+            let _ = TcpStream::connect((Ipv4Addr::LOCALHOST, running.port));
+        }
+        if let Some(port) = wanted {
+            *server = listen(app, port);
+        }
+    }
+    let Some(running) = server.as_mut() else {
+        return;
+    };
+    if !companion.advertise {
+        running.advertisement = None;
+    } else if running.advertisement.is_none() {
+        running.advertisement = advertise(running.port)
+            .inspect_err(|err| {
+                let _ = log::write(format!("Failed to advertise companion devices: {}", err));
+            })
+            .ok();
+    }
+}
+
+fn listen(app: &tauri::AppHandle, port: u16) -> Option<Server> {
+    match TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)) {
+        Ok(listener) => {
+            let stopped = Arc::new(AtomicBool::new(false));
+            let flag = stopped.clone();
+            let app = app.clone();
+            std::thread::spawn(move || accept(app, listener, flag));
+            Some(Server {
+                port,
+                stopped,
+                advertisement: None,
+            })
+        }
+        Err(err) => {
+            let _ = log::write(format!(
+                "Failed to listen for companion devices on port {}: {}",
+                port, err
+            ));
+            None
+        }
+    }
+}
+
+fn advertise(port: u16) -> Result<mdns::Advertisement, String> {
+    match local_address()? {
+        IpAddr::V4(address) => mdns::advertise(address, port),
+        IpAddr::V6(_) => Err("No IPv4 address to advertise".to_string()),
+    }
+}
+
+fn accept(app: tauri::AppHandle, listener: TcpListener, stopped: Arc<AtomicBool>) {
+    let open = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        if stopped.load(Ordering::SeqCst) {
+            return;
+        }
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        let Some(slot) = Slot::take(&open) else {
+            http::reject(&mut stream, "503 Service Unavailable");
+            continue;
+        };
+        let app = app.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = serve(&app, stream) {
+                let _ = log::write(format!("Companion connection failed: {}", err));
+            }
+            drop(slot);
+        });
+    }
+}
+
+/// One of the [`MAX_CONNECTIONS`] connections served at once, given back when dropped.
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    fn take(open: &Arc<AtomicUsize>) -> Option<Slot> {
+        open.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+            (count < MAX_CONNECTIONS).then_some(count + 1)
+        })
+        .ok()
+        .map(|_| Slot(open.clone()))
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Shows a new pairing code, replacing any earlier one.
+pub fn start_pairing() -> Result<PairingInfo, String> {
+    let port = SERVER
+        .lock()
+        .map_err(|err| err.to_string())?
+        .as_ref()
+        .map(|server| server.port)
+        .ok_or("Turn on companion devices first")?;
+    let code = format!(
+        "{:06}",
+        u32::from_le_bytes(random_bytes::<4>()?) % 1_000_000
+    );
+    *PAIRING.lock().map_err(|err| err.to_string())? =
+        Some(Pairing::new(code.clone(), Instant::now()));
+    let address = SocketAddr::new(local_address()?, port).to_string();
+    Ok(PairingInfo {
+        url: format!("vtype-companion://{}/pair?code={}", address, code),
+        address,
+        code,
+        expires_in_secs: PAIRING_TIMEOUT.as_secs(),
+    })
+}
+
+/// Forgets the device called `name`, which then has to pair again.
+pub fn unpair(name: &str) -> Result<(), String> {
+    let mut current = settings::current();
+    let before = current.companion.devices.len();
+    current
+        .companion
+        .devices
+        .retain(|device| device.name != name);
+    if current.companion.devices.len() == before {
+        return Err(format!("No paired device called {}", name));
+    }
+    settings::update(current).map(|_| ())
+}
+
+fn serve(app: &tauri::AppHandle, mut stream: TcpStream) -> Result<(), String> {
+    stream
+        .set_read_timeout(Some(IDLE_TIMEOUT))
+        .map_err(|err| err.to_string())?;
+    let peer = stream.peer_addr().map_err(|err| err.to_string())?.ip();
+    if !on_local_network(peer) {
+        http::reject(&mut stream, "403 Forbidden");
+        return Err(format!(
+            "Connection from {}, outside the local network",
+            peer
+        ));
+    }
+    let request = http::read_request(&mut stream)?;
+    match request.path.as_str() {
+        "/pair" => pair(app, &mut stream, &request),
+        "/stream" => {
+            let Some(device) = authenticate(&request) else {
+                http::reject(&mut stream, "401 Unauthorized");
+                return Err("Connection with an unknown token".to_string());
+            };
+            websocket::upgrade(&mut stream, &request)?;
+            stream_dictations(app, &mut stream, &device)
+        }
+        _ => {
+            http::reject(&mut stream, "404 Not Found");
+            Ok(())
+        }
+    }
+}
+
+fn pair(app: &tauri::AppHandle, stream: &mut TcpStream, request: &Request) -> Result<(), String> {
+    // Checked first, so a request that could never be sent the token doesn't use up the code.
+    if !websocket::is_upgrade(request) {
+        http::reject(stream, "400 Bad Request");
+        return Err("Pairing request is not a WebSocket upgrade".to_string());
+    }
+    let code = request.query.get("code").map(String::as_str).unwrap_or("");
+    if !take_pairing(code)? {
+        http::reject(stream, "403 Forbidden");
+        return Err("Wrong or expired pairing code".to_string());
+    }
+    let name = request
+        .query
+        .get("name")
+        .map(|name| name.trim())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("Phone")
+        .to_string();
+    let token = random_bytes::<32>()?
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    websocket::upgrade(stream, request)?;
+    let mut current = settings::current();
+    current
+        .companion
+        .devices
+        .retain(|device| device.name != name);
+    current.companion.devices.push(PairedDevice {
+        name: name.clone(),
+        token: token.clone(),
+        paired_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as i64)
+            .unwrap_or(0),
+    });
+    if let Err(err) = settings::update(current) {
+        send_error(stream, &err)?;
+        websocket::send_close(stream);
+        return Err(err);
+    }
+    send(stream, &ServerMessage::Paired { token })?;
+    websocket::send_close(stream);
+    let _ = app.emit("companion-paired", &name);
+    Ok(())
+}
+
+/// Whether `code` is the pairing code, which it then uses up.
+fn take_pairing(code: &str) -> Result<bool, String> {
+    let mut current = PAIRING.lock().map_err(|err| err.to_string())?;
+    Ok(pairing::take(&mut current, code, Instant::now()))
+}
+
+/// Tokens are only taken from the header, since query strings end up in logs and history.
+fn authenticate(request: &Request) -> Option<PairedDevice> {
+    let token = request
+        .header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))?;
+    settings::current()
+        .companion
+        .devices
+        .into_iter()
+        .find(|device| pairing::same(device.token.as_bytes(), token.as_bytes()))
+}
+
+fn on_local_network(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => {
+            address.is_private() || address.is_link_local() || address.is_loopback()
+        }
+        IpAddr::V6(address) => match address.to_ipv4_mapped() {
+            Some(mapped) => on_local_network(IpAddr::V4(mapped)),
+            None => {
+                address.is_loopback()
+                    || address.is_unique_local()
+                    || address.is_unicast_link_local()
+            }
+        },
+    }
+}
+
+fn stream_dictations(
+    app: &tauri::AppHandle,
+    stream: &mut TcpStream,
+    device: &PairedDevice,
+) -> Result<(), String> {
+    let mut recording: Option<(u32, Vec<u8>)> = None;
+    loop {
+        match websocket::read_message(stream)? {
+            Message::Close => return Ok(()),
+            Message::Binary(pcm) => match recording.as_mut() {
+                Some((rate, recorded))
+                    if recorded.len() + pcm.len() <= *rate as usize * 2 * MAX_RECORDING_SECS =>
+                {
+                    recorded.extend_from_slice(&pcm)
+                }
+                Some(_) => {
+                    recording = None;
+                    send_error(stream, "Recording too long")?;
+                }
+                None => send_error(stream, "Audio before start")?,
+            },
+            Message::Text(text) => match serde_json::from_str::<ClientMessage>(&text) {
+                Ok(ClientMessage::Start { sample_rate }) if SAMPLE_RATES.contains(&sample_rate) => {
+                    recording = Some((sample_rate, Vec::new()));
+                }
+                Ok(ClientMessage::Start { sample_rate }) => {
+                    send_error(stream, &format!("Unsupported sample rate {}", sample_rate))?;
+                }
+                Ok(ClientMessage::Stop) => match recording.take() {
+                    Some((rate, pcm)) => {
+                        let reply = match dictate(app, device, rate, &pcm) {
+                            Ok(text) => ServerMessage::Transcript { text },
+                            Err(message) => ServerMessage::Error { message },
+                        };
+                        send(stream, &reply)?;
+                    }
+                    None => send_error(stream, "Stop before start")?,
+                },
+                Err(err) => send_error(stream, &format!("Invalid message: {}", err))?,
+            },
+        }
+    }
+}
+
+/// Transcribes a recording from `device` and pastes it into the focused app, or holds it
+/// for review as a dictation made here would be.
+fn dictate(
+    app: &tauri::AppHandle,
+    device: &PairedDevice,
+    sample_rate: u32,
+    pcm: &[u8],
+) -> Result<String, String> {
+    let wav = recording_wav(sample_rate, pcm);
+    let _ = log::write(format!("Dictation from {}", device.name));
+    let text = tauri::async_runtime::block_on(pipeline::transcribe(
+        Priority::Interactive,
+        wav,
+        TranscribeOptions::default(),
+    ))?
+    .text;
+    if !text.is_empty() {
+        review::paste_or_hold(app, text.clone())?;
+    }
+    Ok(text)
+}
+
+/// A WAV the worker takes, from mono 16-bit little-endian PCM at `sample_rate`.
+pub fn recording_wav(sample_rate: u32, pcm: &[u8]) -> Vec<u8> {
+    audio::wav_from_f32(&recording_samples(sample_rate, pcm), TARGET_SAMPLE_RATE)
+}
+
+/// Mono 16-bit little-endian PCM at `sample_rate` as samples at [`TARGET_SAMPLE_RATE`].
+pub fn recording_samples(sample_rate: u32, pcm: &[u8]) -> Vec<f32> {
+    let samples: Vec<f32> = pcm
+        .chunks_exact(2)
+        .map(|sample| f32::from(i16::from_le_bytes([sample[0], sample[1]])) / 32_768.0)
+        .collect();
+    audio::resample(&samples, sample_rate, TARGET_SAMPLE_RATE)
+}
+
+fn send(stream: &mut TcpStream, message: &ServerMessage) -> Result<(), String> {
+    let json = serde_json::to_string(message).map_err(|err| err.to_string())?;
+    websocket::send_text(stream, &json)
+}
+
+fn send_error(stream: &mut TcpStream, message: &str) -> Result<(), String> {
+    send(
+        stream,
+        &ServerMessage::Error {
+            message: message.to_string(),
+        },
+    )
+}
+
+fn random_bytes<const N: usize>() -> Result<[u8; N], String> {
+    let mut bytes = [0u8; N];
+    getrandom::fill(&mut bytes).map_err(|err| err.to_string())?;
+    Ok(bytes)
+}
+
+/// The address other machines on the network reach this one at: the one the route to the
+/// outside would leave from. Connecting a UDP socket sends nothing.
+fn local_address() -> Result<IpAddr, String> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).map_err(|err| err.to_string())?;
+    socket
+        .connect((Ipv4Addr::new(192, 0, 2, 1), 9))
+        .map_err(|err| format!("No network address: {}", err))?;
+    socket
+        .local_addr()
+        .map(|address| address.ip())
+        .map_err(|err| err.to_string())
+}
@@ -0,0 +1,172 @@
+//! Where dictation stands, so the hotkey handler can decide what a press does instead of
+//! emitting a toggle for every one, and can get the worker and microphone going for a press
+//! that starts one. Bounces are dropped, a press is dropped while the overlay
+//! is still starting the microphone, and a press during transcription does what
+//! `busyHotkey` says: wait until the transcript is delivered, drop the transcript and record
+//! again, or only beep.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use vtype_core::settings::BusyHotkey;
+
+/// Starts the error `stop_recording` returns for a transcript dropped by a restart.
+pub const CANCELLED: &str = "DICTATION_CANCELLED";
+
+/// Presses closer together than this are one press.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+/// Longer than the overlay ever takes to report back; past it a phase is stale, for
+/// instance after the overlay reloaded.
+pub const START_GRACE: Duration = Duration::from_secs(2);
+/// Past the worker's own transcription timeout.
+const TRANSCRIBE_GRACE: Duration = Duration::from_secs(660);
+
+static STATE: Mutex<State> = Mutex::new(State {
+    phase: Phase::Idle,
+    since: None,
+    last_press: None,
+    queued: false,
+    transcription: 0,
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Idle,
+    /// The overlay was told to start recording and has not said how it went.
+    Starting,
+    Recording,
+    /// From stopping the microphone until the transcript is delivered.
+    Transcribing,
+}
+
+struct State {
+    phase: Phase,
+    since: Option<Instant>,
+    last_press: Option<Instant>,
+    /// A press arrived during transcription.
+    queued: bool,
+    /// Counts transcriptions, and restarts, which drop the one under way.
+    transcription: u64,
+}
+
+impl State {
+    fn enter(&mut self, phase: Phase) {
+        self.phase = phase;
+        self.since = Some(Instant::now());
+    }
+
+    fn current(&self) -> Phase {
+        let grace = match self.phase {
+            Phase::Starting => START_GRACE,
+            Phase::Transcribing => TRANSCRIBE_GRACE,
+            Phase::Idle | Phase::Recording => return self.phase,
+        };
+        match self.since {
+            Some(since) if since.elapsed() > grace => Phase::Idle,
+            _ => self.phase,
+        }
+    }
+}
+
+/// What a dictation hotkey press should do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Press {
+    /// Tell the overlay to start recording.
+    Start,
+    /// Tell the overlay to stop recording.
+    Stop,
+    /// Start the next recording once the transcript is delivered.
+    Queued,
+    /// The transcript under way is dropped; tell the overlay to record again.
+    Restart,
+    /// Let the user hear that VType is busy.
+    Busy,
+    Ignored,
+}
+
+pub fn press(busy: BusyHotkey) -> Press {
+    let Ok(mut state) = STATE.lock() else {
+        return Press::Stop;
+    };
+    let now = Instant::now();
+    let bounced = state
+        .last_press
+        .is_some_and(|last| now.duration_since(last) < DEBOUNCE);
+    state.last_press = Some(now);
+    if bounced {
+        return Press::Ignored;
+    }
+    match state.current() {
+        Phase::Idle => {
+            state.queued = false;
+            state.enter(Phase::Starting);
+            Press::Start
+        }
+        Phase::Recording => Press::Stop,
+        Phase::Starting => Press::Ignored,
+        Phase::Transcribing => match busy {
+            BusyHotkey::Queue if state.queued => Press::Ignored,
+            BusyHotkey::Queue => {
+                state.queued = true;
+                Press::Queued
+            }
+            BusyHotkey::Restart => {
+                state.transcription += 1;
+                state.queued = false;
+                state.enter(Phase::Starting);
+                Press::Restart
+            }
+            BusyHotkey::Ignore => Press::Busy,
+        },
+    }
+}
+
+/// Whether a recording or its transcription is under way.
+pub fn active() -> bool {
+    STATE
+        .lock()
+        .is_ok_and(|state| matches!(state.current(), Phase::Recording | Phase::Transcribing))
+}
+
+/// The overlay tried to start the microphone.
+pub fn recording_started(started: bool) {
+    if let Ok(mut state) = STATE.lock() {
+        state.enter(if started {
+            Phase::Recording
+        } else {
+            Phase::Idle
+        });
+    }
+}
+
+/// The microphone stopped. Returns the transcription's number for [`cancelled`].
+pub fn transcribing() -> u64 {
+    let Ok(mut state) = STATE.lock() else {
+        return 0;
+    };
+    state.transcription += 1;
+    state.enter(Phase::Transcribing);
+    state.transcription
+}
+
+/// Whether a restart dropped `transcription` since it began.
+pub fn cancelled(transcription: u64) -> bool {
+    STATE
+        .lock()
+        .is_ok_and(|state| state.transcription != transcription)
+}
+
+/// The transcript was delivered or failed. Returns whether a press is waiting, in which case
+/// dictation is starting again. After a restart the new recording is already under way, so
+/// nothing changes.
+pub fn finished() -> bool {
+    let Ok(mut state) = STATE.lock() else {
+        return false;
+    };
+    if state.phase != Phase::Transcribing {
+        return false;
+    }
+    let queued = std::mem::take(&mut state.queued);
+    state.enter(if queued { Phase::Starting } else { Phase::Idle });
+    queued
+}
@@ -0,0 +1,140 @@
+//! Starts dictation from Mac keys an accelerator cannot name: the microphone key in the
+//! function row and a double press of Fn (Globe). Both are read from a session event tap,
+//! which macOS only allows once VType has Accessibility access.
+
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use core_foundation::base::TCFType;
+use core_foundation::runloop::{kCFRunLoopCommonModes, CFRunLoop};
+use core_graphics::event::{
+    CGEvent, CGEventFlags, CGEventTap, CGEventTapLocation, CGEventTapOptions, CGEventTapPlacement,
+    CGEventType, EventField, KeyCode,
+};
+use vtype_core::log;
+use vtype_core::settings::HardwareTrigger;
+
+use crate::HotkeyAction;
+
+/// What the microphone key on recent Apple keyboards reports as its key code.
+const DICTATION_KEY: i64 = 176;
+/// The longest gap between two Fn presses that still counts as a double press.
+const DOUBLE_PRESS: Duration = Duration::from_millis(400);
+
+static TRIGGER: Mutex<HardwareTrigger> = Mutex::new(HardwareTrigger::Off);
+static STARTED: AtomicBool = AtomicBool::new(false);
+/// The tap's mach port, for turning it back on after macOS disables a slow tap.
+static TAP: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+/// When Fn last went down with no other key since.
+static FN_PRESSED: Mutex<Option<Instant>> = Mutex::new(None);
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGEventTapEnable(tap: *mut c_void, enable: bool);
+}
+
+/// Watches for `trigger` from now on, installing the event tap the first time one is set.
+pub fn apply(app: &tauri::AppHandle, trigger: HardwareTrigger) {
+    if let Ok(mut current) = TRIGGER.lock() {
+        *current = trigger;
+    }
+    if trigger == HardwareTrigger::Off || STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || run_tap(app));
+}
+
+/// Runs the tap on a run loop of its own thread, for the life of the app.
+fn run_tap(app: tauri::AppHandle) {
+    let tap = CGEventTap::new(
+        CGEventTapLocation::Session,
+        CGEventTapPlacement::HeadInsertEventTap,
+        CGEventTapOptions::Default,
+        vec![
+            CGEventType::KeyDown,
+            CGEventType::KeyUp,
+            CGEventType::FlagsChanged,
+        ],
+        move |_proxy, event_type, event| {
+            handle(&app, event_type, event);
+            None
+        },
+    );
+    let Ok(tap) = tap else {
+        STARTED.store(false, Ordering::SeqCst);
+        let _ = log::write(
+            "Failed to watch the dictation key: grant VType Accessibility access in System \
+             Settings > Privacy & Security"
+                .to_string(),
+        );
+        return;
+    };
+    let Ok(source) = tap.mach_port.create_runloop_source(0) else {
+        STARTED.store(false, Ordering::SeqCst);
+        let _ = log::write("Failed to watch the dictation key: no run loop source".to_string());
+        return;
+    };
+    TAP.store(
+        tap.mach_port.as_concrete_TypeRef() as *mut c_void,
+        Ordering::SeqCst,
+    );
+    // SAFETY: `kCFRunLoopCommonModes` is a constant string owned by CoreFoundation.
+    unsafe {
+        CFRunLoop::get_current().add_source(&source, kCFRunLoopCommonModes);
+    }
+    tap.enable();
+    CFRunLoop::run_current();
+}
+
+/// Swallows the microphone key when it is the trigger by turning its events into null
+/// events; Fn passes through, since holding it back would leave its modifier stuck.
+fn handle(app: &tauri::AppHandle, event_type: CGEventType, event: &CGEvent) {
+    let trigger = TRIGGER
+        .lock()
+        .map_or(HardwareTrigger::Off, |trigger| *trigger);
+    let key = event.get_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE);
+    match event_type {
+        CGEventType::TapDisabledByTimeout | CGEventType::TapDisabledByUserInput => {
+            let tap = TAP.load(Ordering::SeqCst);
+            if !tap.is_null() {
+                // SAFETY: the port stays alive for as long as the run loop that calls this.
+                unsafe { CGEventTapEnable(tap, true) };
+            }
+        }
+        CGEventType::KeyDown | CGEventType::KeyUp
+            if key == DICTATION_KEY && trigger == HardwareTrigger::DictationKey =>
+        {
+            if matches!(event_type, CGEventType::KeyDown) {
+                crate::dispatch_hotkey_action(app, HotkeyAction::Dictate);
+            }
+            event.set_type(CGEventType::Null);
+        }
+        CGEventType::KeyDown => {
+            if let Ok(mut pressed) = FN_PRESSED.lock() {
+                *pressed = None;
+            }
+        }
+        CGEventType::FlagsChanged if key == i64::from(KeyCode::FUNCTION) => {
+            let down = event
+                .get_flags()
+                .contains(CGEventFlags::CGEventFlagSecondaryFn);
+            if !down || trigger != HardwareTrigger::FnDoublePress {
+                return;
+            }
+            let Ok(mut pressed) = FN_PRESSED.lock() else {
+                return;
+            };
+            let now = Instant::now();
+            match pressed.take() {
+                Some(at) if now.duration_since(at) <= DOUBLE_PRESS => {
+                    crate::dispatch_hotkey_action(app, HotkeyAction::Dictate);
+                }
+                _ => *pressed = Some(now),
+            }
+        }
+        _ => {}
+    }
+}
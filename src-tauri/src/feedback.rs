@@ -0,0 +1,82 @@
+//! Short sounds that acknowledge a hotkey press which does nothing visible.
+
+use std::time::Duration;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use vtype_core::log;
+
+const BUSY_FREQUENCY: f32 = 440.0;
+/// Two beeps of this length with as long a gap between them.
+const BEEP: Duration = Duration::from_millis(70);
+const VOLUME: f32 = 0.2;
+
+/// Two low beeps: the press was heard but VType is busy.
+pub fn busy() {
+    std::thread::spawn(|| {
+        if let Err(err) = play_busy() {
+            let _ = log::write(format!("Failed to play feedback sound: {}", err));
+        }
+    });
+}
+
+fn play_busy() -> Result<(), String> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or("No output device")?;
+    let config = device
+        .default_output_config()
+        .map_err(|err| err.to_string())?;
+    let stream = match config.sample_format() {
+        SampleFormat::F32 => build::<f32>(&device, &config),
+        SampleFormat::I16 => build::<i16>(&device, &config),
+        SampleFormat::I32 => build::<i32>(&device, &config),
+        SampleFormat::U16 => build::<u16>(&device, &config),
+        format => Err(format!("Unsupported sample format {}", format)),
+    }?;
+    stream.play().map_err(|err| err.to_string())?;
+    // A little longer than the sound, so the end is not cut off.
+    std::thread::sleep(BEEP * 4);
+    Ok(())
+}
+
+fn build<T>(
+    device: &cpal::Device,
+    config: &cpal::SupportedStreamConfig,
+) -> Result<cpal::Stream, String>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = usize::from(config.channels());
+    let rate = config.sample_rate() as f32;
+    let beep = ((BEEP.as_secs_f32() * rate) as usize).max(1);
+    let mut frame = 0usize;
+    device
+        .build_output_stream::<T, _, _>(
+            config.config(),
+            move |data: &mut [T], _| {
+                for samples in data.chunks_mut(channels) {
+                    let value = tone(frame, beep, rate);
+                    samples.fill(T::from_sample(value));
+                    frame += 1;
+                }
+            },
+            |err| {
+                let _ = log::write(format!("Audio output error: {}", err));
+            },
+            None,
+        )
+        .map_err(|err| err.to_string())
+}
+
+/// The sample at `frame`: beep, gap, beep, then silence. Each beep fades in and out so it
+/// does not click.
+fn tone(frame: usize, beep: usize, rate: f32) -> f32 {
+    let (slot, offset) = (frame / beep, frame % beep);
+    if slot != 0 && slot != 2 {
+        return 0.0;
+    }
+    let fade = (offset.min(beep - offset) as f32 / (beep as f32 * 0.15)).min(1.0);
+    let phase = frame as f32 * BUSY_FREQUENCY / rate;
+    (phase * std::f32::consts::TAU).sin() * VOLUME * fade
+}
@@ -0,0 +1,379 @@
+//! The pipeline as a gRPC service, for IDE plugins and other desktop apps that would rather
+//! have typed streaming calls than the MCP server. While `grpc.enabled` is set it answers
+//! the `vtype.v1.VType` service from `proto/vtype.proto` on `127.0.0.1:<grpc.port>`, never
+//! on another interface, in plain-text HTTP/2.
+
+use std::net::{Ipv4Addr, TcpListener};
+use std::sync::Mutex;
+
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::StreamExt;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Request, Response, Status, Streaming};
+use vtype_core::options::TranscribeOptions;
+use vtype_core::preprocess::TARGET_SAMPLE_RATE;
+use vtype_core::settings::GrpcSettings;
+use vtype_core::worker::Priority;
+use vtype_core::{audio, early, history, log, pipeline};
+
+use crate::companion::{self, MAX_RECORDING_SECS, SAMPLE_RATES};
+use crate::{output, review};
+
+mod proto {
+    tonic::include_proto!("vtype.v1");
+}
+
+use proto::audio_chunk::Chunk;
+use proto::transcript_event::Event;
+use proto::v_type_server::{VType, VTypeServer};
+
+/// Events a `StreamTranscribe` call may have waiting for the client.
+const EVENT_BUFFER: usize = 16;
+/// How much more of an upload must arrive before looking for a part in it again.
+const SEARCH_EVERY_MS: usize = 1_000;
+
+struct Server {
+    port: u16,
+    shutdown: oneshot::Sender<()>,
+}
+
+static SERVER: Mutex<Option<Server>> = Mutex::new(None);
+
+/// Starts or stops the server to match the settings.
+pub fn apply(app: &tauri::AppHandle, grpc: &GrpcSettings) {
+    let Ok(mut server) = SERVER.lock() else {
+        return;
+    };
+    let wanted = grpc.enabled.then_some(grpc.port);
+    if server.as_ref().map(|server| server.port) == wanted {
+        return;
+    }
+    if let Some(running) = server.take() {
+        let _ = running.shutdown.send(());
+    }
+    let Some(port) = wanted else {
+        return;
+    };
+    match listen(app, port) {
+        Ok(running) => *server = Some(running),
+        Err(err) => {
+            let _ = log::write(format!(
+                "Failed to start the gRPC server on port {}: {}",
+                port, err
+            ));
+        }
+    }
+}
+
+/// Binds straight away, so a port in use is reported here rather than from the server task.
+fn listen(app: &tauri::AppHandle, port: u16) -> Result<Server, String> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).map_err(|err| err.to_string())?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|err| err.to_string())?;
+    let (shutdown, stopped) = oneshot::channel::<()>();
+    let service = VTypeServer::new(Service { app: app.clone() });
+    tauri::async_runtime::spawn(async move {
+        let served = match tokio::net::TcpListener::from_std(listener) {
+            Ok(listener) => tonic::transport::Server::builder()
+                .add_service(service)
+                .serve_with_incoming_shutdown(TcpIncoming::from(listener), async {
+                    let _ = stopped.await;
+                })
+                .await
+                .map_err(|err| err.to_string()),
+            Err(err) => Err(err.to_string()),
+        };
+        if let Err(err) = served {
+            let _ = log::write(format!("gRPC server stopped: {}", err));
+        }
+    });
+    Ok(Server { port, shutdown })
+}
+
+struct Service {
+    app: tauri::AppHandle,
+}
+
+#[tonic::async_trait]
+impl VType for Service {
+    async fn transcribe(
+        &self,
+        request: Request<proto::TranscribeRequest>,
+    ) -> Result<Response<proto::TranscribeResponse>, Status> {
+        let request = request.into_inner();
+        let wav = match audio::decode_wav(&request.wav) {
+            Some((samples, rate)) if rate != TARGET_SAMPLE_RATE => audio::wav_from_f32(
+                &audio::resample(&samples, rate, TARGET_SAMPLE_RATE),
+                TARGET_SAMPLE_RATE,
+            ),
+            Some(_) => request.wav,
+            None => return Err(Status::invalid_argument("Not a PCM WAV file")),
+        };
+        let options = TranscribeOptions {
+            language: request.language,
+            initial_prompt: request.initial_prompt,
+            ..TranscribeOptions::default()
+        };
+        let text = dictate(&self.app, wav, options, request.paste).await?;
+        let history_id = pipeline::last_transcript()
+            .filter(|last| last.text == text)
+            .and_then(|last| last.entry_id)
+            .unwrap_or(0);
+        Ok(Response::new(proto::TranscribeResponse {
+            text,
+            history_id,
+        }))
+    }
+
+    type StreamTranscribeStream = ReceiverStream<Result<proto::TranscriptEvent, Status>>;
+
+    async fn stream_transcribe(
+        &self,
+        request: Request<Streaming<proto::AudioChunk>>,
+    ) -> Result<Response<Self::StreamTranscribeStream>, Status> {
+        let mut chunks = request.into_inner();
+        let Some(Chunk::Format(format)) = chunks
+            .next()
+            .await
+            .transpose()?
+            .and_then(|chunk| chunk.chunk)
+        else {
+            return Err(Status::invalid_argument(
+                "The first message must carry the format",
+            ));
+        };
+        if !SAMPLE_RATES.contains(&format.sample_rate) {
+            return Err(Status::invalid_argument(format!(
+                "Unsupported sample rate {}",
+                format.sample_rate
+            )));
+        }
+        let (events, sent) = mpsc::channel(EVENT_BUFFER);
+        tauri::async_runtime::spawn(stream(self.app.clone(), chunks, format, events));
+        Ok(Response::new(ReceiverStream::new(sent)))
+    }
+
+    async fn paste(
+        &self,
+        request: Request<proto::PasteRequest>,
+    ) -> Result<Response<proto::PasteResponse>, Status> {
+        let request = request.into_inner();
+        let app = self.app.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            if request.as_dictation {
+                output::deliver(&app, request.text)
+            } else {
+                output::deliver_text(&app, request.text)
+            }
+        })
+        .await
+        .map_err(|err| Status::internal(err.to_string()))?
+        .map_err(Status::internal)?;
+        Ok(Response::new(proto::PasteResponse {}))
+    }
+
+    async fn get_history(
+        &self,
+        request: Request<proto::GetHistoryRequest>,
+    ) -> Result<Response<proto::GetHistoryResponse>, Status> {
+        let limit = Some(request.into_inner().limit).filter(|limit| *limit > 0);
+        let entries = history::recent(limit, None)
+            .map_err(Status::internal)?
+            .into_iter()
+            .map(|entry| proto::HistoryEntry {
+                id: entry.id,
+                created_at: entry.created_at,
+                text: entry.text,
+                backend: entry.backend,
+                language: entry.language,
+                profile: entry.profile,
+                pinned: entry.pinned,
+            })
+            .collect();
+        Ok(Response::new(proto::GetHistoryResponse { entries }))
+    }
+}
+
+/// Transcribes a recording as a dictation, saved to history, and with `paste` delivers it
+/// into the focused app or holds it for review as a dictation made here would be.
+async fn dictate(
+    app: &tauri::AppHandle,
+    wav: Vec<u8>,
+    options: TranscribeOptions,
+    paste: bool,
+) -> Result<String, Status> {
+    options.validate().map_err(Status::invalid_argument)?;
+    let text = pipeline::transcribe(Priority::Interactive, wav, options)
+        .await
+        .map_err(Status::internal)?
+        .text;
+    if paste {
+        paste_or_hold(app, &text).await.map_err(Status::internal)?;
+    }
+    Ok(text)
+}
+
+async fn paste_or_hold(app: &tauri::AppHandle, text: &str) -> Result<(), String> {
+    if text.is_empty() {
+        return Ok(());
+    }
+    let app = app.clone();
+    let text = text.to_string();
+    tauri::async_runtime::spawn_blocking(move || review::paste_or_hold(&app, text))
+        .await
+        .map_err(|err| err.to_string())?
+}
+
+/// The parts of an upload, once all are transcribed.
+type Transcriber = tauri::async_runtime::JoinHandle<Result<Vec<pipeline::Part>, String>>;
+
+/// A `StreamTranscribe` upload so far.
+#[derive(Default)]
+struct Upload {
+    /// Mono 16-bit PCM at the client's sample rate.
+    pcm: Vec<u8>,
+    /// Samples at [`TARGET_SAMPLE_RATE`] the parts sent for transcription cover.
+    covered: usize,
+    /// How long `pcm` was when a part was last looked for.
+    searched: usize,
+}
+
+impl Upload {
+    /// The next part of the upload as a WAV, once one ends in a pause; see
+    /// [`early::next_cut`].
+    fn next_part(&mut self, sample_rate: u32) -> Option<Vec<u8>> {
+        let bytes_per_ms = sample_rate as usize * 2 / 1000;
+        let covered_ms = self.covered * 1000 / TARGET_SAMPLE_RATE as usize;
+        // Cheaper than resampling everything to find that there cannot be a part yet.
+        if self.pcm.len() < (covered_ms + early::MIN_PART_MS) * bytes_per_ms
+            || self.pcm.len() < self.searched + SEARCH_EVERY_MS * bytes_per_ms
+        {
+            return None;
+        }
+        self.searched = self.pcm.len();
+        let samples = companion::recording_samples(sample_rate, &self.pcm);
+        let cut = early::next_cut(&samples, self.covered, TARGET_SAMPLE_RATE)?;
+        let wav = audio::wav_from_f32(&samples[self.covered..cut], TARGET_SAMPLE_RATE);
+        self.covered = cut;
+        Some(wav)
+    }
+
+    /// What came after the parts, as a WAV.
+    fn tail(&self, sample_rate: u32) -> Vec<u8> {
+        let samples = companion::recording_samples(sample_rate, &self.pcm);
+        let from = self.covered.min(samples.len());
+        audio::wav_from_f32(&samples[from..], TARGET_SAMPLE_RATE)
+    }
+}
+
+/// Reads the rest of a `StreamTranscribe` upload, transcribing each part of it as it
+/// arrives and sending its text as a `partial` event, then sends the whole transcript once
+/// the client closes its side.
+async fn stream(
+    app: tauri::AppHandle,
+    mut chunks: Streaming<proto::AudioChunk>,
+    format: proto::AudioFormat,
+    events: mpsc::Sender<Result<proto::TranscriptEvent, Status>>,
+) {
+    let (parts, transcriber) = transcribe_parts(events.clone());
+    let upload = match receive(&mut chunks, format.sample_rate, &parts).await {
+        Ok(upload) => upload,
+        Err(status) => {
+            let _ = events.send(Err(status)).await;
+            return;
+        }
+    };
+    drop(parts);
+    let event = match finish(&app, &format, &upload, transcriber).await {
+        Ok(text) => Event::Transcript(text),
+        Err(err) => Event::Error(err),
+    };
+    let _ = events.send(Ok(transcript_event(event))).await;
+}
+
+async fn receive(
+    chunks: &mut Streaming<proto::AudioChunk>,
+    sample_rate: u32,
+    parts: &mpsc::UnboundedSender<Vec<u8>>,
+) -> Result<Upload, Status> {
+    let limit = sample_rate as usize * 2 * MAX_RECORDING_SECS;
+    let mut upload = Upload::default();
+    while let Some(chunk) = chunks.next().await.transpose()? {
+        match chunk.chunk {
+            Some(Chunk::Pcm(samples)) if upload.pcm.len() + samples.len() <= limit => {
+                upload.pcm.extend_from_slice(&samples)
+            }
+            Some(Chunk::Pcm(_)) => return Err(Status::invalid_argument("Recording too long")),
+            Some(Chunk::Format(_)) => return Err(Status::invalid_argument("Format sent twice")),
+            None => {}
+        }
+        if let Some(part) = upload.next_part(sample_rate) {
+            let _ = parts.send(part);
+        }
+    }
+    Ok(upload)
+}
+
+/// Transcribes the parts sent to it one after another, in order, sending each as a
+/// `partial` event, until the sender is dropped; returns them all.
+fn transcribe_parts(
+    events: mpsc::Sender<Result<proto::TranscriptEvent, Status>>,
+) -> (mpsc::UnboundedSender<Vec<u8>>, Transcriber) {
+    let (parts, mut sent) = mpsc::unbounded_channel::<Vec<u8>>();
+    let transcriber = tauri::async_runtime::spawn(async move {
+        let mut earlier = Vec::new();
+        while let Some(wav) = sent.recv().await {
+            let part = pipeline::transcribe_part(wav).await?;
+            let partial = transcript_event(Event::Partial(part.text.clone()));
+            let _ = events.send(Ok(partial)).await;
+            earlier.push(part);
+        }
+        Ok(earlier)
+    });
+    (parts, transcriber)
+}
+
+/// Joins the parts up with the rest of the upload into a dictation, as one made here while
+/// recording would be, or transcribes it whole when one of the parts failed.
+async fn finish(
+    app: &tauri::AppHandle,
+    format: &proto::AudioFormat,
+    upload: &Upload,
+    transcriber: Transcriber,
+) -> Result<String, String> {
+    let wav = companion::recording_wav(format.sample_rate, &upload.pcm);
+    let earlier = transcriber
+        .await
+        .map_err(|err| err.to_string())
+        .and_then(|earlier| earlier);
+    let text = match earlier {
+        Ok(earlier) if !earlier.is_empty() => {
+            let tail = upload.tail(format.sample_rate);
+            pipeline::transcribe_rest(wav, earlier, tail).await?.text
+        }
+        Ok(_) => {
+            pipeline::transcribe(Priority::Interactive, wav, TranscribeOptions::default())
+                .await?
+                .text
+        }
+        Err(err) => {
+            let _ = log::write(format!(
+                "A part of a streamed recording failed; transcribing all of it: {}",
+                err
+            ));
+            pipeline::transcribe(Priority::Interactive, wav, TranscribeOptions::default())
+                .await?
+                .text
+        }
+    };
+    if format.paste {
+        paste_or_hold(app, &text).await?;
+    }
+    Ok(text)
+}
+
+fn transcript_event(event: Event) -> proto::TranscriptEvent {
+    proto::TranscriptEvent { event: Some(event) }
+}
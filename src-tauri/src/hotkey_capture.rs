@@ -0,0 +1,128 @@
+//! Setting a hotkey by pressing it. The overlay takes the keyboard and reports the next
+//! chord pressed in it, which comes back as an accelerator such as `Ctrl+Alt+R`. VType's
+//! own hotkeys are unregistered meanwhile, so pressing the current one is caught too.
+
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Deserialize;
+use tauri::Emitter;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
+use vtype_core::settings;
+
+use crate::overlay;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where the overlay sends the chord, or `None` when Escape cancels, while a capture runs.
+static WAITING: Mutex<Option<Sender<Option<Chord>>>> = Mutex::new(None);
+
+/// A key pressed in the overlay, as the browser's `KeyboardEvent` describes it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Chord {
+    /// Such as `KeyR`, `Digit1` or `F5`.
+    pub code: String,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub meta: bool,
+}
+
+/// Waits for the next chord pressed in the overlay and returns its accelerator, checked by
+/// the shortcut plugin. `None` when the user cancels with Escape.
+pub fn capture(app: &tauri::AppHandle) -> Result<Option<String>, String> {
+    let (sender, chords) = mpsc::channel();
+    {
+        let mut waiting = WAITING.lock().map_err(|err| err.to_string())?;
+        if waiting.is_some() {
+            return Err("Already waiting for a hotkey".to_string());
+        }
+        *waiting = Some(sender);
+    }
+    // The desktop holds hotkeys bound through the portal, so those stay as they are.
+    #[cfg(target_os = "linux")]
+    let release = !crate::portal::binds_shortcuts();
+    #[cfg(not(target_os = "linux"))]
+    let release = true;
+    if release {
+        let _ = app.global_shortcut().unregister_all();
+    }
+    overlay::show_picker(app);
+    let _ = app.emit("hotkey-capture-requested", ());
+    let chord = chords.recv_timeout(TIMEOUT);
+    if let Ok(mut waiting) = WAITING.lock() {
+        *waiting = None;
+    }
+    let _ = app.emit("hotkey-capture-ended", ());
+    overlay::end_review(app);
+    overlay::hide_after(app, Some(0));
+    if release {
+        crate::register_hotkeys(app, &settings::current())?;
+    }
+    match chord {
+        Ok(Some(chord)) => accelerator(&chord).map(Some),
+        Ok(None) => Ok(None),
+        Err(RecvTimeoutError::Timeout) => Err(format!(
+            "No key was pressed within {} seconds",
+            TIMEOUT.as_secs()
+        )),
+        Err(RecvTimeoutError::Disconnected) => Ok(None),
+    }
+}
+
+/// Whether a capture is waiting for a chord, during which hotkeys that still fire do nothing.
+pub fn active() -> bool {
+    WAITING.lock().is_ok_and(|waiting| waiting.is_some())
+}
+
+/// Called by the overlay with the chord pressed, or `None` to cancel.
+pub fn pressed(chord: Option<Chord>) {
+    if let Some(sender) = WAITING.lock().ok().and_then(|sender| sender.clone()) {
+        let _ = sender.send(chord);
+    }
+}
+
+/// Modifiers come first, in the order the default hotkey uses. A function key may stand
+/// alone; any other key needs a modifier, or typing it elsewhere would start VType.
+fn accelerator(chord: &Chord) -> Result<String, String> {
+    let key = chord
+        .code
+        .strip_prefix("Key")
+        .or_else(|| chord.code.strip_prefix("Digit"))
+        .unwrap_or(&chord.code);
+    #[cfg(target_os = "macos")]
+    let modifiers = [
+        (chord.meta, "Command"),
+        (chord.ctrl, "Ctrl"),
+        (chord.alt, "Option"),
+        (chord.shift, "Shift"),
+    ];
+    #[cfg(not(target_os = "macos"))]
+    let modifiers = [
+        (chord.ctrl, "Ctrl"),
+        (chord.meta, "Super"),
+        (chord.alt, "Alt"),
+        (chord.shift, "Shift"),
+    ];
+    let mut parts: Vec<&str> = modifiers
+        .into_iter()
+        .filter_map(|(held, name)| held.then_some(name))
+        .collect();
+    let function_key = key
+        .strip_prefix('F')
+        .is_some_and(|number| number.parse::<u8>().is_ok());
+    if parts.is_empty() && !function_key {
+        return Err(format!(
+            "{} needs a modifier such as Ctrl or Alt to be a hotkey",
+            key
+        ));
+    }
+    parts.push(key);
+    let accelerator = parts.join("+");
+    accelerator
+        .parse::<Shortcut>()
+        .map_err(|err| format!("{} can't be a hotkey: {}", accelerator, err))?;
+    Ok(accelerator)
+}
@@ -0,0 +1,117 @@
+//! The taskbar jump list: right-clicking VType's taskbar button offers quick actions. Each
+//! task launches VType again with a `vtype://` link, which the single-instance plugin hands
+//! to the running instance. Also the file picker behind the `transcribe-file` task.
+
+use std::path::PathBuf;
+
+use vtype_core::decode::{AUDIO_EXTENSIONS, VIDEO_EXTENSIONS};
+use vtype_core::paths;
+use windows::core::{Interface, HSTRING, PCWSTR, PWSTR};
+use windows::Win32::Foundation::ERROR_CANCELLED;
+use windows::Win32::Storage::EnhancedStorage::PKEY_Title;
+use windows::Win32::System::Com::StructuredStorage::PROPVARIANT;
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CoTaskMemFree, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED,
+};
+use windows::Win32::UI::Shell::Common::{IObjectArray, IObjectCollection, COMDLG_FILTERSPEC};
+use windows::Win32::UI::Shell::PropertiesSystem::IPropertyStore;
+use windows::Win32::UI::Shell::{
+    DestinationList, EnumerableObjectCollection, FileOpenDialog, ICustomDestinationList,
+    IFileOpenDialog, IShellLinkW, ShellLink, SIGDN_FILESYSPATH,
+};
+
+use crate::{HotkeyAction, LINK_HISTORY, LINK_SCHEME, LINK_TRANSCRIBE_FILE};
+
+/// Replaces the jump list's tasks with VType's quick actions.
+pub fn register() -> Result<(), String> {
+    let executable = std::env::current_exe().map_err(|err| err.to_string())?;
+    let executable = HSTRING::from(executable.as_os_str());
+    let tasks = [
+        ("Start dictation", HotkeyAction::Dictate.id()),
+        ("Transcribe file", LINK_TRANSCRIBE_FILE),
+        ("Open history", LINK_HISTORY),
+    ];
+    // SAFETY: COM is initialised on this thread before any object is created, and every
+    // string outlives the call it is passed to.
+    unsafe {
+        // Already initialised, possibly in another mode, is fine for these objects.
+        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+        let list: ICustomDestinationList =
+            CoCreateInstance(&DestinationList, None, CLSCTX_INPROC_SERVER).map_err(message)?;
+        let mut slots = 0;
+        let _removed: IObjectArray = list.BeginList(&mut slots).map_err(message)?;
+        let collection: IObjectCollection =
+            CoCreateInstance(&EnumerableObjectCollection, None, CLSCTX_INPROC_SERVER)
+                .map_err(message)?;
+        for (title, id) in tasks {
+            collection
+                .AddObject(&task(&executable, title, id)?)
+                .map_err(message)?;
+        }
+        list.AddUserTasks(&collection.cast::<IObjectArray>().map_err(message)?)
+            .map_err(message)?;
+        list.CommitList().map_err(message)
+    }
+}
+
+/// A shortcut that starts VType with the link for `id`, keeping portable installs portable
+/// when VType is not already running.
+unsafe fn task(executable: &HSTRING, title: &str, id: &str) -> Result<IShellLinkW, String> {
+    let mut arguments = format!("{}{}", LINK_SCHEME, id);
+    if paths::is_portable() {
+        arguments = format!("{} {}", paths::PORTABLE_ARG, arguments);
+    }
+    let link: IShellLinkW =
+        CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER).map_err(message)?;
+    link.SetPath(executable).map_err(message)?;
+    link.SetArguments(&HSTRING::from(arguments))
+        .map_err(message)?;
+    link.SetIconLocation(executable, 0).map_err(message)?;
+    // The jump list shows the title property rather than the description.
+    let properties: IPropertyStore = link.cast().map_err(message)?;
+    properties
+        .SetValue(&PKEY_Title, &PROPVARIANT::from(title))
+        .map_err(message)?;
+    properties.Commit().map_err(message)?;
+    Ok(link)
+}
+
+/// Asks for an audio or video file. `None` when the user cancels.
+pub fn pick_media_file() -> Result<Option<PathBuf>, String> {
+    let patterns = AUDIO_EXTENSIONS
+        .iter()
+        .chain(VIDEO_EXTENSIONS)
+        .map(|extension| format!("*.{}", extension))
+        .collect::<Vec<_>>()
+        .join(";");
+    let (name, spec) = (HSTRING::from("Audio and video"), HSTRING::from(patterns));
+    let filters = [COMDLG_FILTERSPEC {
+        pszName: PCWSTR(name.as_ptr()),
+        pszSpec: PCWSTR(spec.as_ptr()),
+    }];
+    // SAFETY: as in `register`; the path the dialog returns is freed once copied.
+    unsafe {
+        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+        let dialog: IFileOpenDialog =
+            CoCreateInstance(&FileOpenDialog, None, CLSCTX_INPROC_SERVER).map_err(message)?;
+        dialog.SetFileTypes(&filters).map_err(message)?;
+        dialog
+            .SetTitle(&HSTRING::from("Transcribe file"))
+            .map_err(message)?;
+        match dialog.Show(None) {
+            Err(err) if err.code() == ERROR_CANCELLED.to_hresult() => return Ok(None),
+            result => result.map_err(message)?,
+        }
+        let path: PWSTR = dialog
+            .GetResult()
+            .and_then(|item| item.GetDisplayName(SIGDN_FILESYSPATH))
+            .map_err(message)?;
+        let picked = path.to_string().map_err(|err| err.to_string());
+        CoTaskMemFree(Some(path.0 as *const _));
+        picked.map(|path| Some(PathBuf::from(path)))
+    }
+}
+
+fn message(err: windows::core::Error) -> String {
+    err.message()
+}
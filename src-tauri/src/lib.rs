@@ -1,141 +1,407 @@
-use std::fs;
-use std::fs::OpenOptions;
-use std::io::{BufRead, BufReader, Read, Write};
+mod actions;
+mod announce;
+mod capture;
+mod clipboard;
+mod companion;
+mod dictation;
+#[cfg(target_os = "macos")]
+mod dictation_key;
+mod feedback;
+mod grpc;
+mod hotkey_capture;
 #[cfg(target_os = "windows")]
-use std::os::windows::process::CommandExt;
-use std::path::PathBuf;
-use std::process::{Child, ChildStdin, Command, Stdio};
-use std::sync::{Mutex, OnceLock};
-use std::time::{SystemTime, UNIX_EPOCH};
+mod jump_list;
+mod mcp;
+mod mdns;
+mod output;
+mod overlay;
+mod paste_test;
+#[cfg(target_os = "linux")]
+mod portal;
+mod review;
+mod updater;
 
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use announce::Announcement;
 use base64::engine::general_purpose;
 use base64::Engine as _;
-use tauri::menu::MenuBuilder;
+use tauri::menu::{CheckMenuItemBuilder, Menu, MenuBuilder, SubmenuBuilder};
 use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
-use tauri::{Emitter, Manager, Position};
+use tauri::{Emitter, Listener, Manager};
 use tauri_plugin_clipboard_manager::ClipboardExt;
-use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+use tauri_plugin_opener::OpenerExt;
+use vtype_core::activity::ActivityKind;
+use vtype_core::i18n::{self, Message};
+use vtype_core::options::TranscribeOptions;
+use vtype_core::settings::{Profile, Settings};
+use vtype_core::{
+    activity, audio, audit, backend, batch, benchmark, crash, dnd, download, frontmost, history,
+    macros, metrics, models, monitor, paste_stats, paths, pipeline, plugins, power, preprocess,
+    registration, retention, safe_io, settings, speech, transfer, venv, vocabulary, watch, worker,
+};
 
-#[cfg(target_os = "macos")]
-const HOTKEY: &str = "Command+Option+R";
-#[cfg(not(target_os = "macos"))]
-const HOTKEY: &str = "Ctrl+Alt+R";
 const TRAY_MENU_SHOW: &str = "tray_show";
 const TRAY_MENU_QUIT: &str = "tray_quit";
+const TRAY_MENU_DEFAULT_PROFILE: &str = "tray_profile_default";
+const TRAY_MENU_PROFILE_PREFIX: &str = "tray_profile:";
+const TRAY_MENU_PINNED_PREFIX: &str = "tray_pinned:";
+const TRAY_ID: &str = "main-tray";
+const LINK_SCHEME: &str = "vtype://";
+/// Links beside the hotkey actions' own, for the Windows jump list.
+const LINK_TRANSCRIBE_FILE: &str = "transcribe-file";
+const LINK_HISTORY: &str = "history";
+/// Pinned entries beyond this stay reachable from the history UI only.
+const TRAY_PINNED_LIMIT: usize = 10;
+const TRAY_LABEL_CHARS: usize = 48;
+
+/// A transcript, or with `withMetadata` the transcript together with how it came about.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(untagged)]
+enum Transcribed {
+    Text(String),
+    Utterance(pipeline::Utterance),
+}
+
+impl Transcribed {
+    fn new(utterance: pipeline::Utterance, with_metadata: Option<bool>) -> Transcribed {
+        if with_metadata.unwrap_or(false) {
+            Transcribed::Utterance(utterance)
+        } else {
+            Transcribed::Text(utterance.text)
+        }
+    }
+}
 
 #[tauri::command]
-async fn transcribe_wav(app: tauri::AppHandle, wav_base64: String) -> Result<String, String> {
-    let _ = log_message(format!("Transcribe request received, bytes(base64)={}", wav_base64.len()));
-    tauri::async_runtime::spawn_blocking(move || {
-        init_script_path(&app)?;
-        let wav_bytes = general_purpose::STANDARD
-            .decode(wav_base64)
-            .map_err(|err| err.to_string())?;
+async fn transcribe_wav(
+    wav_base64: String,
+    options: Option<TranscribeOptions>,
+    with_metadata: Option<bool>,
+) -> Result<Transcribed, String> {
+    let _ = log_message(format!(
+        "Transcribe request received, bytes(base64)={}",
+        wav_base64.len()
+    ));
+    let mut wav_bytes = worker::take_buffer();
+    general_purpose::STANDARD
+        .decode_vec(wav_base64, &mut wav_bytes)
+        .map_err(|err| err.to_string())?;
+    let utterance = transcribe_dictation(wav_bytes, options).await?;
+    Ok(Transcribed::new(utterance, with_metadata))
+}
 
-        let response = with_worker(|worker| send_wav(worker, &wav_bytes))?;
-        let text = response.trim().to_string();
-        let _ = log_message(format!("Transcribe success, chars={}", text.len()));
-    Ok(text)
+#[tauri::command]
+async fn start_recording(app: tauri::AppHandle) -> Result<(), String> {
+    let handle = app.clone();
+    let started = tauri::async_runtime::spawn_blocking(move || {
+        announce::announce_before_recording(&handle);
+        capture::start(&handle)
     })
     .await
-    .map_err(|err| err.to_string())?
+    .map_err(|err| err.to_string())?;
+    dictation::recording_started(started.is_ok());
+    match &started {
+        Ok(()) => {
+            overlay::cancel_hide();
+            activity::record(ActivityKind::RecordingStarted, None, None);
+        }
+        Err(err) => {
+            activity::record(ActivityKind::RecordingFailed, None, Some(err.clone()));
+            announce::announce(&app, Announcement::Failed(Some(err.clone())));
+        }
+    }
+    started
 }
 
+/// Holds the recording without ending it; what is said until `resume_recording` is left out.
 #[tauri::command]
-fn paste_transcription(app: tauri::AppHandle, text: String) -> Result<(), String> {
-    app.clipboard()
-        .write_text(text)
-        .map_err(|err| err.to_string())?;
+fn pause_recording(app: tauri::AppHandle) -> Result<(), String> {
+    capture::pause()?;
+    let _ = app.emit("recording-paused", ());
+    Ok(())
+}
 
-    if let Some(window) = app.get_webview_window("main") {
-        let _ = window.hide();
-    }
-    std::thread::sleep(std::time::Duration::from_millis(200));
+#[tauri::command]
+fn resume_recording(app: tauri::AppHandle) -> Result<(), String> {
+    capture::resume()?;
+    let _ = app.emit("recording-resumed", ());
+    Ok(())
+}
 
-    #[cfg(target_os = "macos")]
-    {
-        use enigo::{Key, KeyboardControllable};
-        paste_with_retry(|| {
-            let mut enigo = enigo::Enigo::new();
-            enigo.key_down(Key::Meta);
-            enigo.key_click(Key::Layout('v'));
-            enigo.key_up(Key::Meta);
-            Ok(())
+#[tauri::command]
+fn list_audio_inputs() -> Result<Vec<capture::AudioInput>, String> {
+    capture::list_inputs()
+}
+
+/// Records a short clip for the setup check: playback audio, levels and what the backend
+/// makes of it. Nothing is saved to history.
+#[tauri::command]
+async fn record_test_clip(
+    app: tauri::AppHandle,
+    seconds: f32,
+) -> Result<capture::TestClip, String> {
+    let samples = tauri::async_runtime::spawn_blocking(move || capture::record_clip(&app, seconds))
+        .await
+        .map_err(|err| err.to_string())??;
+    let wav_bytes = audio::wav_from_f32(&samples, preprocess::TARGET_SAMPLE_RATE);
+    let transcript = pipeline::transcribe(
+        worker::Priority::Background,
+        wav_bytes.clone(),
+        TranscribeOptions::default(),
+    )
+    .await?
+    .text;
+    Ok(capture::TestClip {
+        wav_base64: general_purpose::STANDARD.encode(wav_bytes),
+        levels: audio::levels(&samples, preprocess::TARGET_SAMPLE_RATE),
+        transcript,
+    })
+}
+
+/// Transcribes one audio or video file without touching history.
+#[tauri::command]
+async fn transcribe_file(
+    path: String,
+    options: Option<TranscribeOptions>,
+) -> Result<batch::FileTranscript, String> {
+    batch::transcribe_file(Path::new(&path), options.unwrap_or_default()).await
+}
+
+/// Transcribes the audio file copied to the clipboard, or audio data pasted as a `data:`
+/// URL, without touching history.
+#[tauri::command]
+async fn transcribe_clipboard(
+    options: Option<TranscribeOptions>,
+) -> Result<batch::FileTranscript, String> {
+    let path = clipboard::media_file()?;
+    batch::transcribe_file(&path, options.unwrap_or_default()).await
+}
+
+/// Downloads the audio behind a link with yt-dlp and transcribes it without touching
+/// history. Progress is reported as `url-transcription-progress` events.
+#[tauri::command]
+async fn transcribe_url(
+    app: tauri::AppHandle,
+    url: String,
+    options: Option<TranscribeOptions>,
+) -> Result<batch::FileTranscript, String> {
+    download::transcribe_url(&url, options.unwrap_or_default(), |progress| {
+        let _ = app.emit("url-transcription-progress", progress);
+    })
+    .await
+}
+
+/// Transcribes every audio and video file in a folder, writing transcripts next to the sources.
+/// Each file's status is reported as a `folder-transcription-progress` event.
+#[tauri::command]
+async fn transcribe_folder(
+    app: tauri::AppHandle,
+    path: String,
+    options: Option<batch::FolderOptions>,
+) -> Result<batch::FolderSummary, String> {
+    batch::transcribe_folder(Path::new(&path), options.unwrap_or_default(), |progress| {
+        let _ = app.emit("folder-transcription-progress", progress);
+    })
+    .await
+}
+
+/// Stops the microphone and transcribes what was recorded.
+#[tauri::command]
+async fn stop_recording(
+    app: tauri::AppHandle,
+    options: Option<TranscribeOptions>,
+    with_metadata: Option<bool>,
+) -> Result<Transcribed, String> {
+    let transcription = dictation::transcribing();
+    let stopped = tauri::async_runtime::spawn_blocking(capture::stop)
+        .await
+        .map_err(|err| err.to_string())?
+        .inspect_err(|err| {
+            activity::record(ActivityKind::RecordingFailed, None, Some(err.clone()))
         })?;
+    activity::record(
+        ActivityKind::RecordingStopped,
+        audio::wav_duration_ms(&stopped.wav).map(Duration::from_millis),
+        None,
+    );
+    announce::announce(&app, Announcement::Transcribing);
+    // Parts were transcribed with the settings' options; others need the whole recording.
+    let utterance = match stopped.early.filter(|_| options.is_none()) {
+        Some(early) => transcribe_rest(stopped.wav, early).await,
+        None => transcribe_dictation(stopped.wav, options).await,
+    };
+    if dictation::cancelled(transcription) {
+        return Err(format!(
+            "{}: the hotkey started a new recording",
+            dictation::CANCELLED
+        ));
     }
-    #[cfg(target_os = "windows")]
-    {
-        use enigo::{Key, KeyboardControllable};
-        paste_with_retry(|| {
-            let mut enigo = enigo::Enigo::new();
-            enigo.key_down(Key::Control);
-            enigo.key_click(Key::Layout('v'));
-            enigo.key_up(Key::Control);
-            Ok(())
-        })?;
+    if utterance.is_err() {
+        announce::announce(&app, Announcement::Failed(None));
     }
-    #[cfg(target_os = "linux")]
-    {
-        paste_with_retry(|| linux_paste().map_err(|err| err.to_string()))?;
+    utterance.map(|utterance| Transcribed::new(utterance, with_metadata))
+}
+
+async fn transcribe_dictation(
+    wav_bytes: Vec<u8>,
+    options: Option<TranscribeOptions>,
+) -> Result<pipeline::Utterance, String> {
+    let utterance = pipeline::transcribe(
+        worker::Priority::Interactive,
+        wav_bytes,
+        options.unwrap_or_default(),
+    )
+    .await?;
+    let _ = log_message(format!(
+        "Transcribe success, chars={}",
+        utterance.text.len()
+    ));
+    Ok(utterance)
+}
+
+/// Transcribes what is left of a dictation whose opening parts were transcribed while it
+/// was recorded, or all of it again when one of those failed.
+async fn transcribe_rest(
+    wav_bytes: Vec<u8>,
+    early: capture::Early,
+) -> Result<pipeline::Utterance, String> {
+    let capture::Early { tail, parts } = early;
+    let earlier = match parts.finished().await {
+        Ok(earlier) => earlier,
+        Err(err) => {
+            let _ = log_message(format!(
+                "A part transcribed while recording failed; transcribing the whole dictation: {}",
+                err
+            ));
+            return transcribe_dictation(wav_bytes, None).await;
+        }
+    };
+    let utterance = pipeline::transcribe_rest(wav_bytes, earlier, tail).await?;
+    let _ = log_message(format!(
+        "Transcribe success, chars={}",
+        utterance.text.len()
+    ));
+    Ok(utterance)
+}
+
+/// Delivers a transcript to the focused app, or holds it in the overlay first when review
+/// is enabled, `redaction.warnBeforePaste` finds something in it or the model was less sure
+/// of it than `review.minConfidence`. With a `source`, `text` is an excerpt the user picked
+/// from that history entry: it is pasted straight away and noted on the entry, which keeps
+/// its full text.
+#[tauri::command]
+fn paste_transcription(
+    app: tauri::AppHandle,
+    text: String,
+    source: Option<review::PasteSource>,
+) -> Result<(), String> {
+    match source {
+        Some(source) => {
+            review::settle(&app, source.entry_id);
+            review::note_paste(source.entry_id, &text);
+            output::deliver(&app, text)
+        }
+        None => review::paste_or_hold(&app, text),
     }
+}
 
-    Ok(())
+/// Delivers `text` to the first window `matcher` picks, bringing it to the front, for
+/// automations that route dictations to one app whatever is focused. Review is skipped.
+#[tauri::command]
+fn paste_to_window(
+    app: tauri::AppHandle,
+    matcher: frontmost::WindowMatcher,
+    text: String,
+) -> Result<(), String> {
+    output::deliver_to_window(&app, &matcher, text)
 }
 
-#[cfg(target_os = "linux")]
-fn linux_paste() -> Result<(), String> {
-    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
-        // Wayland: use wtype if available.
-        let status = Command::new("wtype")
-            .args(["-M", "ctrl", "-k", "v", "-m", "ctrl"])
-            .status()
-            .map_err(|err| err.to_string())?;
-        if status.success() {
-            return Ok(());
-        }
-        return Err("wtype failed to paste on Wayland".to_string());
-    }
-
-    // X11: use xdotool if available.
-    let status = Command::new("xdotool")
-        .args(["key", "--clearmodifiers", "ctrl+v"])
-        .status()
-        .map_err(|err| err.to_string())?;
-    if status.success() {
-        Ok(())
-    } else {
-        Err("xdotool failed to paste on X11".to_string())
+/// The WebAssembly plugins in the plugins directory and whether each runs.
+#[tauri::command]
+fn list_plugins() -> Result<Vec<plugins::Plugin>, String> {
+    plugins::list(&settings::current())
+}
+
+/// Turns a plugin on, to run after those already on, or off.
+#[tauri::command]
+fn set_plugin_enabled(name: String, enabled: bool) -> Result<Settings, String> {
+    let mut current = settings::current();
+    current.plugins.enabled.retain(|other| *other != name);
+    if enabled {
+        current.plugins.enabled.push(name);
     }
+    settings::update(current)
 }
 
-fn paste_with_retry<F>(mut paste_fn: F) -> Result<(), String>
-where
-    F: FnMut() -> Result<(), String>,
-{
-    if let Err(first_err) = paste_fn() {
-        std::thread::sleep(std::time::Duration::from_millis(220));
-        if let Err(second_err) = paste_fn() {
-            return Err(format!("paste failed: {} | {}", first_err, second_err));
-        }
+/// The macros in `macros.toml`, or why the file cannot be used.
+#[tauri::command]
+fn get_macros() -> Result<Vec<macros::Macro>, String> {
+    macros::load()
+}
+
+/// The user's answer to a `shell-confirmation` event.
+#[tauri::command]
+fn answer_shell_confirmation(id: u64, allow: bool) -> Result<(), String> {
+    actions::answer(id, allow)
+}
+
+#[tauri::command]
+fn get_pending_result() -> Option<review::PendingResult> {
+    review::current()
+}
+
+/// Pastes the transcript held for review, replaced by `text` when the user edited it.
+#[tauri::command]
+fn confirm_paste(app: tauri::AppHandle, text: Option<String>) -> Result<(), String> {
+    review::confirm(&app, text)
+}
+
+/// Cancels the auto-confirm timer, for when the user starts editing.
+#[tauri::command]
+fn keep_reviewing() {
+    review::hold_open();
+}
+
+/// The overlay is done with a dictation, delivered or not; a hotkey press that came in
+/// meanwhile starts the next one now.
+#[tauri::command]
+fn finish_dictation(app: tauri::AppHandle) {
+    if dictation::finished() {
+        warm_start(&app);
+        toggle_dictation(&app);
+    } else {
+        overlay::hide_after(&app, settings::current().overlay.hide_after_ms);
     }
-    Ok(())
+}
+
+#[tauri::command]
+fn discard_result(app: tauri::AppHandle) -> Result<(), String> {
+    review::discard(&app)
+}
+
+/// Reads the last dictation aloud so it can be checked without looking at the screen; a
+/// transcript held for review is read in preference.
+#[tauri::command]
+fn speak_last_transcription() -> Result<(), String> {
+    let text = review::current()
+        .map(|pending| pending.text)
+        .or_else(|| pipeline::last_transcript().map(|last| last.text))
+        .ok_or_else(|| i18n::error(Message::NothingTranscribed, &[]))?;
+    speech::speak(&text)
+}
+
+#[tauri::command]
+fn stop_speaking() {
+    speech::stop();
 }
 
 #[tauri::command]
 fn log_message(message: String) -> Result<(), String> {
-    let mut path = std::env::temp_dir();
-    path.push("vtype.log");
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|d| d.as_secs())
-        .unwrap_or(0);
-    let line = format!("[{}] {}\n", timestamp, message);
-    OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(path)
-        .and_then(|mut file| file.write_all(line.as_bytes()))
-        .map_err(|err| err.to_string())
+    vtype_core::log::write(message)
 }
 
 #[tauri::command]
@@ -143,205 +409,975 @@ fn save_wav_temp(wav_base64: String) -> Result<String, String> {
     let wav_bytes = general_purpose::STANDARD
         .decode(wav_base64)
         .map_err(|err| err.to_string())?;
-    let path = std::env::temp_dir().join("vtype_last.wav");
-    fs::write(&path, wav_bytes).map_err(|err| err.to_string())?;
+    let dir = paths::working_dir();
+    fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    let path = dir.join("vtype_last.wav");
+    safe_io::write(&path, wav_bytes).map_err(|err| err.to_string())?;
     Ok(path.to_string_lossy().to_string())
 }
 
 #[tauri::command]
-fn warm_asr(app: tauri::AppHandle) -> Result<(), String> {
-    std::thread::spawn(move || {
-        let _ = init_script_path(&app);
-        let _ = ensure_worker();
-    });
-    Ok(())
+fn get_settings() -> Settings {
+    settings::current()
 }
 
-struct AsrWorker {
-    child: Child,
-    stdin: ChildStdin,
-    stdout: BufReader<std::process::ChildStdout>,
+#[tauri::command]
+fn update_settings(settings: Settings) -> Result<Settings, String> {
+    validate_hotkeys(&settings)?;
+    settings::update(settings)
 }
 
-static ASR_WORKER: OnceLock<Mutex<Option<AsrWorker>>> = OnceLock::new();
-static SCRIPT_PATH: OnceLock<PathBuf> = OnceLock::new();
+#[tauri::command]
+fn export_settings(path: String) -> Result<(), String> {
+    transfer::export(Path::new(&path))
+}
 
-fn init_script_path(_app: &tauri::AppHandle) -> Result<(), String> {
-    let path = ensure_embedded_script()?;
-    let _ = SCRIPT_PATH.set(path);
+#[tauri::command]
+fn import_settings(path: String) -> Result<Settings, String> {
+    let settings = transfer::read(Path::new(&path), &settings::current())?;
+    validate_hotkeys(&settings)?;
+    settings::update(settings)
+}
+
+/// Accelerator syntax is only known to the shortcut plugin, so it is checked here
+/// rather than in [`Settings::validate`].
+fn validate_hotkeys(settings: &Settings) -> Result<(), String> {
+    for (action, accelerator) in HotkeyAction::bindings(settings) {
+        accelerator
+            .parse::<Shortcut>()
+            .map_err(|err| format!("Invalid {} {}: {}", action.label(), accelerator, err))?;
+    }
     Ok(())
 }
 
-fn script_path() -> Result<PathBuf, String> {
-    SCRIPT_PATH
-        .get()
-        .cloned()
-        .ok_or_else(|| "transcribe_wav.py not initialized".to_string())
+#[tauri::command]
+fn suggest_model_quantization() -> models::QuantizationSuggestion {
+    models::suggest(&settings::current().model.quantization)
+}
+
+#[tauri::command]
+async fn benchmark_asr(
+    seconds: f32,
+    runs: Option<u32>,
+) -> Result<benchmark::BenchmarkReport, String> {
+    benchmark::run(seconds, runs).await
 }
 
-fn worker_state() -> &'static Mutex<Option<AsrWorker>> {
-    ASR_WORKER.get_or_init(|| Mutex::new(None))
+#[tauri::command]
+fn list_history(
+    limit: Option<u32>,
+    before: Option<i64>,
+) -> Result<Vec<history::HistoryEntry>, String> {
+    history::recent(limit, before)
 }
 
-fn ensure_worker() -> Result<(), String> {
-    let mut guard = worker_state().lock().map_err(|_| "Worker lock poisoned".to_string())?;
-    let needs_start = match guard.as_mut() {
-        Some(worker) => worker.child.try_wait().map_err(|err| err.to_string())?.is_some(),
-        None => true,
-    };
-    if needs_start {
-        *guard = Some(start_worker()?);
+#[tauri::command]
+fn search_history(query: String, limit: Option<u32>) -> Result<Vec<history::SearchHit>, String> {
+    history::search(&query, limit)
+}
+
+/// Deletes history in `range` (everything when omitted) and returns the count removed.
+#[tauri::command]
+fn clear_history(
+    app: tauri::AppHandle,
+    range: Option<history::TimeRange>,
+) -> Result<usize, String> {
+    let deleted = history::clear(range.unwrap_or_default())?;
+    refresh_tray_menu(&app, &settings::current());
+    Ok(deleted)
+}
+
+#[tauri::command]
+async fn retranscribe(
+    entry_id: i64,
+    options: Option<TranscribeOptions>,
+) -> Result<history::Revision, String> {
+    pipeline::retranscribe(entry_id, options.unwrap_or_default()).await
+}
+
+#[tauri::command]
+fn update_entry_text(
+    app: tauri::AppHandle,
+    id: i64,
+    text: String,
+) -> Result<history::HistoryEntry, String> {
+    let entry = history::update_text(id, &text)?;
+    if entry.pinned {
+        refresh_tray_menu(&app, &settings::current());
     }
+    Ok(entry)
+}
+
+#[tauri::command]
+fn get_dictionary_suggestions() -> Result<Vec<vocabulary::DictionarySuggestion>, String> {
+    vocabulary::suggestions()
+}
+
+/// The retained recording of a history entry, Ogg Opus or WAV, as raw bytes that reach the
+/// webview as an `ArrayBuffer` rather than a base64 string a third larger. An `<audio>`
+/// element plays it from a `Blob` URL and tells the format from the data.
+#[tauri::command]
+fn get_entry_audio(id: i64) -> Result<tauri::ipc::Response, String> {
+    history::entry_audio(id).map(tauri::ipc::Response::new)
+}
+
+#[tauri::command]
+fn list_revisions(entry_id: i64) -> Result<Vec<history::Revision>, String> {
+    history::revisions(entry_id)
+}
+
+#[tauri::command]
+fn get_usage_stats(range: Option<history::TimeRange>) -> Result<history::UsageStats, String> {
+    history::usage_stats(range.unwrap_or_default())
+}
+
+#[tauri::command]
+fn pin_entry(app: tauri::AppHandle, id: i64, pinned: bool) -> Result<(), String> {
+    history::set_pinned(id, pinned)?;
+    refresh_tray_menu(&app, &settings::current());
     Ok(())
 }
 
-fn with_worker<F>(mut f: F) -> Result<String, String>
-where
-    F: FnMut(&mut AsrWorker) -> Result<String, String>,
-{
-    ensure_worker()?;
-    let mut guard = worker_state().lock().map_err(|_| "Worker lock poisoned".to_string())?;
-    if guard.is_none() {
-        return Err("ASR worker not available".to_string());
-    }
-    let worker = guard.as_mut().unwrap();
-    f(worker)
-}
-
-fn start_worker() -> Result<AsrWorker, String> {
-    let script_path = script_path()?;
-    let python = resolve_python().ok_or("Python interpreter not found (tried python3, python)")?;
-
-    let mut worker_cmd = Command::new(python);
-    worker_cmd
-        .arg(script_path)
-        .arg("--worker")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
-    configure_background_command(&mut worker_cmd);
-    let mut child = worker_cmd.spawn().map_err(|err| err.to_string())?;
-
-    let stdin = child.stdin.take().ok_or("Failed to open ASR stdin")?;
-    let stdout = child.stdout.take().ok_or("Failed to open ASR stdout")?;
-    let mut stderr = child.stderr.take();
-    let mut reader = BufReader::new(stdout);
-    let mut ready = String::new();
-    reader
-        .read_line(&mut ready)
-        .map_err(|err| err.to_string())?;
-    if ready.trim() != "ready" {
-        let mut err_buf = String::new();
-        if let Some(ref mut err) = stderr {
-            let _ = err.read_to_string(&mut err_buf);
+#[tauri::command]
+fn list_pinned() -> Result<Vec<history::HistoryEntry>, String> {
+    history::pinned()
+}
+
+/// Shows a code for pairing a phone as a wireless microphone.
+#[tauri::command]
+fn start_companion_pairing(app: tauri::AppHandle) -> Result<companion::PairingInfo, String> {
+    let pairing = companion::start_pairing()?;
+    let _ = app.emit("companion-pairing", &pairing);
+    Ok(pairing)
+}
+
+#[tauri::command]
+fn unpair_companion(name: String) -> Result<(), String> {
+    companion::unpair(&name)
+}
+
+/// Syncs with the folder in `sync.folder` straight away instead of at the next interval.
+#[tauri::command]
+fn sync_settings() -> Result<settings::SyncReport, String> {
+    settings::sync()
+}
+
+/// The newest distinct transcripts for the quick picker.
+#[tauri::command]
+fn quick_pick_history(limit: Option<u32>) -> Result<Vec<history::QuickPick>, String> {
+    history::quick_pick(limit)
+}
+
+/// Pastes a history entry picked in the quick picker into the app that had focus before.
+#[tauri::command]
+fn paste_history_entry(app: tauri::AppHandle, id: i64) -> Result<(), String> {
+    let entry = history::get(id)?;
+    overlay::end_review(&app);
+    output::deliver_text(&app, entry.text)
+}
+
+#[tauri::command]
+fn close_quick_pick(app: tauri::AppHandle) {
+    overlay::end_review(&app);
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.hide();
+    }
+}
+
+/// Lists the newest transcripts in the overlay for the user to pick one.
+fn open_quick_pick(app: &tauri::AppHandle) {
+    if dictation::active() || review::current().is_some() {
+        return;
+    }
+    match history::quick_pick(None) {
+        Ok(picks) if picks.is_empty() => {}
+        Ok(picks) => {
+            overlay::show_picker(app);
+            let _ = app.emit("quick-pick-requested", picks);
+        }
+        Err(err) => {
+            let _ = log_message(format!(
+                "Failed to list history for the quick picker: {}",
+                err
+            ));
         }
-        return Err(format!(
-            "ASR worker not ready: {} {}",
-            ready.trim(),
-            err_buf.trim()
-        ));
     }
+}
 
-    Ok(AsrWorker {
-        child,
-        stdin,
-        stdout: reader,
+#[tauri::command]
+fn list_profiles() -> Vec<Profile> {
+    settings::current().profiles
+}
+
+#[tauri::command]
+fn set_active_profile(name: Option<String>) -> Result<Settings, String> {
+    settings::set_active_profile(name)
+}
+
+#[tauri::command]
+fn get_registrations() -> registration::Registrations {
+    registration::query()
+}
+
+#[tauri::command]
+fn repair_registrations() -> Result<registration::Registrations, String> {
+    registration::repair()
+}
+
+#[tauri::command]
+fn set_autostart(enabled: bool) -> Result<registration::Registrations, String> {
+    registration::set_autostart(enabled)?;
+    Ok(registration::query())
+}
+
+/// Starts a dictation that do-not-disturb blocked; with `minutes`, also suspends the rules
+/// for that long.
+#[tauri::command]
+fn override_do_not_disturb(app: tauri::AppHandle, minutes: Option<u32>) {
+    if let Some(minutes) = minutes {
+        dnd::override_for(minutes);
+    }
+    start_dictation(&app);
+}
+
+/// Creates VType's own Python environment with the worker's pinned requirements and moves
+/// the worker onto it. Progress is reported as `python-env-progress` events.
+#[tauri::command]
+async fn setup_python_env(app: tauri::AppHandle) -> Result<String, String> {
+    let python = venv::setup(|progress| {
+        let _ = app.emit("python-env-progress", progress);
     })
+    .await?;
+    if backend::active(None)? == backend::Backend::Onnx {
+        worker::restart()?;
+    }
+    Ok(python.display().to_string())
 }
 
-fn send_wav(worker: &mut AsrWorker, wav_bytes: &[u8]) -> Result<String, String> {
-    let len = u32::try_from(wav_bytes.len()).map_err(|_| "WAV too large".to_string())?;
-    worker
-        .stdin
-        .write_all(&len.to_le_bytes())
-        .map_err(|err| err.to_string())?;
-    worker
-        .stdin
-        .write_all(wav_bytes)
-        .map_err(|err| err.to_string())?;
-    worker.stdin.flush().map_err(|err| err.to_string())?;
+/// The newest release on `updates.channel`, or `None` when VType is up to date.
+#[tauri::command]
+async fn check_for_updates(app: tauri::AppHandle) -> Result<Option<updater::UpdateInfo>, String> {
+    updater::check(&app).await
+}
+
+/// Installs the newest release and restarts into it. Progress is reported as
+/// `update-progress` events.
+#[tauri::command]
+async fn install_update(app: tauri::AppHandle) -> Result<(), String> {
+    updater::install(&app).await
+}
+
+/// The last pipeline events, newest last, at most `limit` of them.
+#[tauri::command]
+fn get_recent_activity(limit: Option<usize>) -> Vec<activity::ActivityEvent> {
+    activity::recent(limit)
+}
+
+/// Memory and CPU use of the ASR worker, sampled every few seconds; `None` while none runs.
+#[tauri::command]
+fn get_worker_stats() -> Option<monitor::WorkerStats> {
+    monitor::latest()
+}
+
+/// The crash report written since the last launch, offered once and then forgotten.
+#[tauri::command]
+fn take_crash_report() -> Option<String> {
+    crash::take_pending().map(|path| path.display().to_string())
+}
+
+#[tauri::command]
+fn open_crash_report(app: tauri::AppHandle, path: String) -> Result<(), String> {
+    let path = PathBuf::from(path);
+    if !crash::is_report(&path) {
+        return Err(format!("Not a crash report: {}", path.display()));
+    }
+    app.opener()
+        .open_path(path.display().to_string(), None::<&str>)
+        .map_err(|err| err.to_string())
+}
+
+/// The usage counters recorded with `usageMetrics` on, for the user to look over and share.
+#[tauri::command]
+fn export_metrics() -> Result<metrics::MetricsReport, String> {
+    metrics::export(env!("CARGO_PKG_VERSION"))
+}
+
+#[tauri::command]
+fn clear_metrics() -> Result<(), String> {
+    metrics::clear()
+}
 
-    let mut header = [0u8; 4];
-    worker
-        .stdout
-        .read_exact(&mut header)
+/// Pastes and types a sample into a window of VType's own and reports which worked.
+#[tauri::command]
+async fn test_paste(app: tauri::AppHandle) -> Result<paste_test::PasteTestReport, String> {
+    tauri::async_runtime::spawn_blocking(move || paste_test::run(&app))
+        .await
+        .map_err(|err| err.to_string())?
+}
+
+/// Sent by the paste test window with what its text field holds.
+#[tauri::command]
+fn paste_test_text(text: String) {
+    paste_test::received(text);
+}
+
+/// Waits for the next key chord pressed in the overlay and saves it as the hotkey for
+/// `action`, an id such as `quick-pick`, or for dictation when none is given. Returns the
+/// accelerator saved, or `None` when the user cancels with Escape.
+#[tauri::command]
+async fn capture_next_hotkey(
+    app: tauri::AppHandle,
+    action: Option<String>,
+) -> Result<Option<String>, String> {
+    let action = match action.as_deref() {
+        Some(id) => HotkeyAction::from_id(id).ok_or_else(|| format!("Unknown hotkey {}", id))?,
+        None => HotkeyAction::Dictate,
+    };
+    if dictation::active() || review::current().is_some() {
+        return Err("Finish the current dictation before changing a hotkey".to_string());
+    }
+    let handle = app.clone();
+    let Some(accelerator) =
+        tauri::async_runtime::spawn_blocking(move || hotkey_capture::capture(&handle))
+            .await
+            .map_err(|err| err.to_string())??
+    else {
+        return Ok(None);
+    };
+    let mut settings = settings::current();
+    let shortcut = accelerator
+        .parse::<Shortcut>()
         .map_err(|err| err.to_string())?;
-    let resp_len = u32::from_le_bytes(header) as usize;
-    let mut buf = vec![0u8; resp_len];
-    if resp_len > 0 {
-        worker
-            .stdout
-            .read_exact(&mut buf)
-            .map_err(|err| err.to_string())?;
+    for (other, assigned) in HotkeyAction::bindings(&settings) {
+        if other != action && assigned.parse::<Shortcut>().ok() == Some(shortcut) {
+            return Err(format!("{} is already the {}", accelerator, other.label()));
+        }
     }
-    let text = String::from_utf8_lossy(&buf).to_string();
-    if text.starts_with("ERROR:") {
-        return Err(text);
+    action.assign(&mut settings, accelerator.clone());
+    validate_hotkeys(&settings)?;
+    settings::update(settings)?;
+    Ok(Some(accelerator))
+}
+
+/// Sent by the overlay with the chord pressed while a hotkey is captured, or `None` when
+/// Escape cancels.
+#[tauri::command]
+fn hotkey_captured(chord: Option<hotkey_capture::Chord>) {
+    hotkey_capture::pressed(chord);
+}
+
+/// How pasting went in each app, to find those that need a profile typing into them.
+#[tauri::command]
+fn get_paste_stats() -> Result<Vec<paste_stats::AppPasteStats>, String> {
+    paste_stats::export()
+}
+
+#[tauri::command]
+fn export_audit_log() -> Result<Vec<audit::AuditRecord>, String> {
+    audit::export()
+}
+
+#[tauri::command]
+fn warm_asr() -> Result<(), String> {
+    backend::warm_up(backend::active(None)?)
+}
+
+/// What each configurable hotkey does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HotkeyAction {
+    Dictate,
+    CycleProfile,
+    TranscribeClipboard,
+    ConfirmReview,
+    PauseRecording,
+    ToggleCodeMode,
+    QuickPick,
+}
+
+impl HotkeyAction {
+    /// Every action, dictation first.
+    const ALL: [HotkeyAction; 7] = [
+        HotkeyAction::Dictate,
+        HotkeyAction::CycleProfile,
+        HotkeyAction::TranscribeClipboard,
+        HotkeyAction::ConfirmReview,
+        HotkeyAction::PauseRecording,
+        HotkeyAction::ToggleCodeMode,
+        HotkeyAction::QuickPick,
+    ];
+
+    /// The hotkeys `settings` assigns, dictation first.
+    fn bindings(settings: &Settings) -> Vec<(HotkeyAction, String)> {
+        HotkeyAction::ALL
+            .into_iter()
+            .filter_map(|action| {
+                action
+                    .accelerator(settings)
+                    .map(|accelerator| (action, accelerator.to_string()))
+            })
+            .collect()
     }
-    Ok(text)
+
+    /// The hotkey for this action in `settings`, if it has one.
+    fn accelerator(self, settings: &Settings) -> Option<&str> {
+        match self {
+            HotkeyAction::Dictate => Some(&settings.hotkey),
+            HotkeyAction::CycleProfile => settings.profile_hotkey.as_deref(),
+            HotkeyAction::TranscribeClipboard => settings.clipboard_hotkey.as_deref(),
+            HotkeyAction::ConfirmReview => settings.confirm_hotkey.as_deref(),
+            HotkeyAction::PauseRecording => settings.pause_hotkey.as_deref(),
+            HotkeyAction::ToggleCodeMode => settings.code_mode_hotkey.as_deref(),
+            HotkeyAction::QuickPick => settings.quick_pick_hotkey.as_deref(),
+        }
+    }
+
+    /// Makes `accelerator` the hotkey for this action in `settings`.
+    fn assign(self, settings: &mut Settings, accelerator: String) {
+        match self {
+            HotkeyAction::Dictate => settings.hotkey = accelerator,
+            HotkeyAction::CycleProfile => settings.profile_hotkey = Some(accelerator),
+            HotkeyAction::TranscribeClipboard => settings.clipboard_hotkey = Some(accelerator),
+            HotkeyAction::ConfirmReview => settings.confirm_hotkey = Some(accelerator),
+            HotkeyAction::PauseRecording => settings.pause_hotkey = Some(accelerator),
+            HotkeyAction::ToggleCodeMode => settings.code_mode_hotkey = Some(accelerator),
+            HotkeyAction::QuickPick => settings.quick_pick_hotkey = Some(accelerator),
+        }
+    }
+
+    /// Names the action in `vtype://` links and to the GlobalShortcuts portal.
+    fn id(self) -> &'static str {
+        match self {
+            HotkeyAction::Dictate => "dictate",
+            HotkeyAction::CycleProfile => "cycle-profile",
+            HotkeyAction::TranscribeClipboard => "transcribe-clipboard",
+            HotkeyAction::ConfirmReview => "confirm-review",
+            HotkeyAction::PauseRecording => "pause-recording",
+            HotkeyAction::ToggleCodeMode => "toggle-code-mode",
+            HotkeyAction::QuickPick => "quick-pick",
+        }
+    }
+
+    /// What the desktop shows for the hotkey when it lists shortcuts.
+    #[cfg(target_os = "linux")]
+    fn description(self) -> &'static str {
+        match self {
+            HotkeyAction::Dictate => "Start or stop dictation",
+            HotkeyAction::CycleProfile => "Switch to the next profile",
+            HotkeyAction::TranscribeClipboard => "Transcribe the copied audio file",
+            HotkeyAction::ConfirmReview => "Paste the reviewed transcript",
+            HotkeyAction::PauseRecording => "Pause or resume dictation",
+            HotkeyAction::ToggleCodeMode => "Turn code mode on or off",
+            HotkeyAction::QuickPick => "Pick a past transcript to paste",
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn link(self) -> String {
+        format!("{}{}", LINK_SCHEME, self.id())
+    }
+
+    /// The action a link such as `vtype://dictate` names.
+    fn from_id(id: &str) -> Option<HotkeyAction> {
+        HotkeyAction::ALL
+            .into_iter()
+            .find(|action| action.id() == id)
+    }
+
+    /// How logs name the hotkey.
+    fn label(self) -> &'static str {
+        match self {
+            HotkeyAction::Dictate => "hotkey",
+            HotkeyAction::CycleProfile => "profile hotkey",
+            HotkeyAction::TranscribeClipboard => "clipboard hotkey",
+            HotkeyAction::ConfirmReview => "confirm hotkey",
+            HotkeyAction::PauseRecording => "pause hotkey",
+            HotkeyAction::ToggleCodeMode => "code mode hotkey",
+            HotkeyAction::QuickPick => "quick pick hotkey",
+        }
+    }
+}
+
+fn register_shortcut(
+    app: &tauri::AppHandle,
+    accelerator: &str,
+    action: HotkeyAction,
+) -> Result<(), String> {
+    app.global_shortcut()
+        .on_shortcut(accelerator, move |app, _shortcut, event| {
+            if event.state == ShortcutState::Pressed {
+                run_hotkey_action(app, action);
+            }
+        })
+        .map_err(|err| err.to_string())
 }
 
-fn ensure_embedded_script() -> Result<PathBuf, String> {
-    let path = std::env::temp_dir().join("vtype_transcribe_wav.py");
-    let script = include_str!("../transcribe_wav.py");
-    fs::write(&path, script).map_err(|err| err.to_string())?;
-    Ok(path)
+/// Runs `action` on the main thread, for triggers that arrive elsewhere.
+fn dispatch_hotkey_action(app: &tauri::AppHandle, action: HotkeyAction) {
+    let handle = app.clone();
+    let _ = app.run_on_main_thread(move || run_hotkey_action(&handle, action));
 }
 
-fn resolve_python() -> Option<&'static str> {
-    let candidates = ["python3", "python"];
-    for candidate in candidates {
-        let mut version_cmd = Command::new(candidate);
-        version_cmd
-            .arg("--version")
-            .stdout(Stdio::null())
-            .stderr(Stdio::null());
-        configure_background_command(&mut version_cmd);
-        if version_cmd.status().is_ok() {
-            return Some(candidate);
+/// Runs the actions of the `vtype://` links among `args`, which another launch of VType
+/// forwards, so desktop shortcuts and the link protocol reach this instance.
+fn handle_links(app: &tauri::AppHandle, args: impl IntoIterator<Item = String>) {
+    for arg in args {
+        let Some(id) = arg.strip_prefix(LINK_SCHEME) else {
+            continue;
+        };
+        match id.trim_matches('/') {
+            LINK_TRANSCRIBE_FILE => transcribe_picked_file(app),
+            LINK_HISTORY => {
+                let handle = app.clone();
+                let _ = app.run_on_main_thread(move || {
+                    overlay::show(&handle);
+                    let _ = handle.emit("history-requested", ());
+                });
+            }
+            id => match HotkeyAction::from_id(id) {
+                Some(action) => dispatch_hotkey_action(app, action),
+                None => {
+                    let _ = log_message(format!("Ignoring unknown link {}", arg));
+                }
+            },
         }
     }
-    None
 }
 
+fn run_hotkey_action(app: &tauri::AppHandle, action: HotkeyAction) {
+    if hotkey_capture::active() {
+        return;
+    }
+    match action {
+        HotkeyAction::Dictate => {
+            if let Some(block) = dnd::check() {
+                let _ = log_message(format!("Dictation blocked by do-not-disturb: {:?}", block));
+                let _ = app.emit("do-not-disturb", block);
+                return;
+            }
+            start_dictation(app);
+        }
+        HotkeyAction::CycleProfile => {
+            if let Err(err) = settings::cycle_profile() {
+                let _ = log_message(format!("Failed to switch profile: {}", err));
+            }
+        }
+        HotkeyAction::TranscribeClipboard => transcribe_clipboard_to_clipboard(app),
+        HotkeyAction::ConfirmReview => {
+            if review::current().is_none() {
+                return;
+            }
+            if let Err(err) = review::confirm(app, None) {
+                let _ = log_message(format!("Failed to paste reviewed transcript: {}", err));
+            }
+        }
+        HotkeyAction::PauseRecording => {
+            let result = if capture::paused() {
+                resume_recording(app.clone())
+            } else {
+                pause_recording(app.clone())
+            };
+            if let Err(err) = result {
+                let _ = log_message(format!("Failed to pause or resume recording: {}", err));
+            }
+        }
+        HotkeyAction::ToggleCodeMode => {
+            if let Err(err) = settings::toggle_code_mode() {
+                let _ = log_message(format!("Failed to toggle code mode: {}", err));
+            }
+        }
+        HotkeyAction::QuickPick => open_quick_pick(app),
+    }
+}
+
+fn start_dictation(app: &tauri::AppHandle) {
+    match dictation::press(settings::current().busy_hotkey) {
+        dictation::Press::Start | dictation::Press::Restart => {
+            warm_start(app);
+            toggle_dictation(app);
+        }
+        dictation::Press::Stop => toggle_dictation(app),
+        dictation::Press::Queued => {
+            let _ = log_message("Dictation queued until the transcript is delivered".to_string());
+        }
+        dictation::Press::Busy => feedback::busy(),
+        dictation::Press::Ignored => {}
+    }
+}
+
+/// Gets the worker and the microphone going as soon as a dictation is asked for, while the
+/// overlay is still coming up, so the first words are not lost to the stream opening and the
+/// first transcript does not wait for the model to load. A microphone the overlay has not
+/// taken up by the end of [`dictation::START_GRACE`] is closed again.
+fn warm_start(app: &tauri::AppHandle) {
+    if let Err(err) = backend::active(None).and_then(backend::warm_up) {
+        let _ = log_message(format!("Failed to warm up the ASR worker: {}", err));
+    }
+    // The announcement is read out before the microphone opens, to keep it out of the
+    // recording.
+    if settings::current().announce_aloud {
+        return;
+    }
+    let app = app.clone();
+    std::thread::spawn(move || {
+        if let Err(err) = capture::prepare(&app) {
+            let _ = log_message(format!("Failed to open the microphone early: {}", err));
+            return;
+        }
+        std::thread::sleep(dictation::START_GRACE);
+        capture::discard_prepared(dictation::START_GRACE);
+    });
+}
+
+fn toggle_dictation(app: &tauri::AppHandle) {
+    let app_handle = app.clone();
+    let _ = app.run_on_main_thread(move || {
+        overlay::show(&app_handle);
+        overlay::hide_after(&app_handle, settings::current().overlay.start_timeout_ms);
+        let _ = app_handle.emit("hotkey-pressed", ());
+    });
+}
+
+/// The transcript replaces the copied file on the clipboard, since the file manager it was
+/// copied from has nowhere to paste it.
+fn transcribe_clipboard_to_clipboard(app: &tauri::AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let result = transcribe_clipboard(None).await.and_then(|transcript| {
+            app.clipboard()
+                .write_text(transcript.text.clone())
+                .map_err(|err| err.to_string())?;
+            Ok(transcript)
+        });
+        match result {
+            Ok(transcript) => {
+                let _ = app.emit("clipboard-transcribed", transcript);
+            }
+            Err(err) => {
+                let _ = log_message(format!("Clipboard transcription failed: {}", err));
+                let _ = app.emit("clipboard-transcription-failed", err);
+            }
+        }
+    });
+}
+
+/// Asks for a file with the system picker and leaves its transcript on the clipboard, like
+/// the clipboard hotkey.
 #[cfg(target_os = "windows")]
-fn configure_background_command(command: &mut Command) {
-    // CREATE_NO_WINDOW
-    command.creation_flags(0x08000000);
+fn transcribe_picked_file(app: &tauri::AppHandle) {
+    let app = app.clone();
+    // The picker is modal and needs a COM apartment, so it gets a thread of its own.
+    std::thread::spawn(move || {
+        let path = match jump_list::pick_media_file() {
+            Ok(Some(path)) => path,
+            Ok(None) => return,
+            Err(err) => {
+                let _ = log_message(format!("Failed to pick a file: {}", err));
+                return;
+            }
+        };
+        let result = tauri::async_runtime::block_on(batch::transcribe_file(
+            &path,
+            TranscribeOptions::default(),
+        ))
+        .and_then(|transcript| {
+            app.clipboard()
+                .write_text(transcript.text.clone())
+                .map_err(|err| err.to_string())?;
+            Ok(transcript)
+        });
+        match result {
+            Ok(transcript) => {
+                let _ = app.emit("file-transcribed", transcript);
+            }
+            Err(err) => {
+                let _ = log_message(format!("File transcription failed: {}", err));
+                let _ = app.emit("file-transcription-failed", err);
+            }
+        }
+    });
 }
 
+/// Elsewhere VType has no picker of its own, so the UI is asked to pick the file.
 #[cfg(not(target_os = "windows"))]
-fn configure_background_command(_command: &mut Command) {}
-
-fn show_main_window(app_handle: &tauri::AppHandle) {
-    if let Some(window) = app_handle.get_webview_window("main") {
-        #[cfg(target_os = "windows")]
-        {
-            let _ = window.set_shadow(false);
-        }
-        if let Ok(monitor) = window.current_monitor() {
-            if let Some(monitor) = monitor {
-                if let Ok(size) = window.outer_size() {
-                    let monitor_size = monitor.size();
-                    let x = (monitor_size.width.saturating_sub(size.width) / 2) as i32;
-                    let y = monitor_size.height.saturating_sub(size.height + 24) as i32;
-                    let _ = window.set_position(Position::Physical((x, y).into()));
+fn transcribe_picked_file(app: &tauri::AppHandle) {
+    let handle = app.clone();
+    let _ = app.run_on_main_thread(move || {
+        overlay::show(&handle);
+        let _ = handle.emit("transcribe-file-requested", ());
+    });
+}
+
+/// Registers the hotkeys at startup, falling back to the default when the dictation hotkey
+/// is taken. Sandboxed apps cannot grab keys and Wayland only lets them see X11 apps, so
+/// there the GlobalShortcuts portal binds them where the desktop has it.
+fn register_hotkeys(app: &tauri::AppHandle, settings: &Settings) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    if portal::binds_shortcuts() {
+        portal::bind_shortcuts(app, HotkeyAction::bindings(settings));
+        return Ok(());
+    }
+    for (action, accelerator) in HotkeyAction::bindings(settings) {
+        if let Err(err) = register_shortcut(app, &accelerator, action) {
+            let _ = log_message(format!(
+                "Failed to register {} {}: {}",
+                action.label(),
+                accelerator,
+                err
+            ));
+            if action == HotkeyAction::Dictate {
+                register_shortcut(app, settings::DEFAULT_HOTKEY, action)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn build_tray_menu(app: &tauri::AppHandle, settings: &Settings) -> tauri::Result<Menu<tauri::Wry>> {
+    let mut builder = MenuBuilder::new(app)
+        .text(TRAY_MENU_SHOW, "Show VType")
+        .separator();
+    if !settings.profiles.is_empty() {
+        let default = CheckMenuItemBuilder::with_id(TRAY_MENU_DEFAULT_PROFILE, "Default")
+            .checked(settings.active_profile.is_none())
+            .build(app)?;
+        builder = builder.item(&default);
+        for profile in &settings.profiles {
+            let item = CheckMenuItemBuilder::with_id(
+                format!("{}{}", TRAY_MENU_PROFILE_PREFIX, profile.name),
+                &profile.name,
+            )
+            .checked(settings.active_profile.as_deref() == Some(profile.name.as_str()))
+            .build(app)?;
+            builder = builder.item(&item);
+        }
+        builder = builder.separator();
+    }
+    let pinned = history::pinned().unwrap_or_default();
+    if !pinned.is_empty() {
+        let mut submenu = SubmenuBuilder::new(app, "Pinned");
+        for entry in pinned.iter().take(TRAY_PINNED_LIMIT) {
+            submenu = submenu.text(
+                format!("{}{}", TRAY_MENU_PINNED_PREFIX, entry.id),
+                tray_label(&entry.text),
+            );
+        }
+        builder = builder.item(&submenu.build()?).separator();
+    }
+    builder.text(TRAY_MENU_QUIT, "Quit").build()
+}
+
+fn tray_label(text: &str) -> String {
+    history::label(text, TRAY_LABEL_CHARS)
+}
+
+fn refresh_tray_menu(app: &tauri::AppHandle, settings: &Settings) {
+    if let (Some(tray), Ok(menu)) = (app.tray_by_id(TRAY_ID), build_tray_menu(app, settings)) {
+        let _ = tray.set_menu(Some(menu));
+    }
+}
+
+fn paste_pinned(app: &tauri::AppHandle, id: &str) -> Result<(), String> {
+    let id = id
+        .parse::<i64>()
+        .map_err(|_| format!("Invalid history id {}", id))?;
+    let entry = history::get(id)?;
+    output::deliver(app, entry.text)
+}
+
+fn handle_tray_menu(app: &tauri::AppHandle, id: &str) {
+    let profile = match id {
+        TRAY_MENU_SHOW => return overlay::show(app),
+        TRAY_MENU_QUIT => return app.exit(0),
+        TRAY_MENU_DEFAULT_PROFILE => None,
+        _ => {
+            if let Some(entry_id) = id.strip_prefix(TRAY_MENU_PINNED_PREFIX) {
+                if let Err(err) = paste_pinned(app, entry_id) {
+                    let _ = log_message(format!("Failed to paste pinned entry: {}", err));
+                }
+                return;
+            }
+            match id.strip_prefix(TRAY_MENU_PROFILE_PREFIX) {
+                Some(name) => Some(name.to_string()),
+                None => return,
+            }
+        }
+    };
+    if let Err(err) = settings::set_active_profile(profile) {
+        let _ = log_message(format!("Failed to switch profile: {}", err));
+    }
+}
+
+/// Keeps a menu-bar-only VType out of the Dock and ⌘Tab, leaving the tray item as the way
+/// to reach it.
+#[cfg(target_os = "macos")]
+fn apply_activation_policy(app: &tauri::AppHandle, settings: &Settings) {
+    let policy = if settings.menu_bar_only {
+        tauri::ActivationPolicy::Accessory
+    } else {
+        tauri::ActivationPolicy::Regular
+    };
+    if let Err(err) = app.set_activation_policy(policy) {
+        let _ = log_message(format!("Failed to change the activation policy: {}", err));
+    }
+}
+
+/// Re-registers the hotkeys that changed, keeping the old dictation hotkey when the new one
+/// is taken.
+fn apply_hotkey_changes(app: &tauri::AppHandle, previous: &Settings, current: &Settings) {
+    for action in HotkeyAction::ALL {
+        let (old, new) = (action.accelerator(previous), action.accelerator(current));
+        if old == new {
+            continue;
+        }
+        if let Some(accelerator) = old {
+            let _ = app.global_shortcut().unregister(accelerator);
+        }
+        let Some(accelerator) = new else {
+            continue;
+        };
+        if let Err(err) = register_shortcut(app, accelerator, action) {
+            match old.filter(|_| action == HotkeyAction::Dictate) {
+                Some(old) => {
+                    let _ = log_message(format!(
+                        "Failed to register {} {}, keeping {}: {}",
+                        action.label(),
+                        accelerator,
+                        old,
+                        err
+                    ));
+                    let _ = register_shortcut(app, old, action);
+                }
+                None => {
+                    let _ = log_message(format!(
+                        "Failed to register {} {}: {}",
+                        action.label(),
+                        accelerator,
+                        err
+                    ));
                 }
             }
         }
-        let _ = window.show();
-        let _ = window.set_focusable(false);
     }
 }
 
+/// Applies a settings change from the UI or an external edit of the settings file.
+fn apply_settings_change(app: &tauri::AppHandle, previous: &Settings, current: &Settings) {
+    #[cfg(target_os = "linux")]
+    if portal::binds_shortcuts() {
+        portal::bind_shortcuts(app, HotkeyAction::bindings(current));
+    } else {
+        apply_hotkey_changes(app, previous, current);
+    }
+    #[cfg(not(target_os = "linux"))]
+    apply_hotkey_changes(app, previous, current);
+    if previous.overlay != current.overlay {
+        overlay::apply_settings(app, &current.overlay);
+    }
+    if previous.companion != current.companion {
+        companion::apply(app, &current.companion);
+    }
+    if previous.mcp != current.mcp {
+        mcp::apply(&current.mcp);
+    }
+    if previous.grpc != current.grpc {
+        grpc::apply(app, &current.grpc);
+    }
+    if previous.working_dir != current.working_dir {
+        paths::set_working_dir(current.working_dir.clone().map(PathBuf::from));
+    }
+    #[cfg(target_os = "macos")]
+    if previous.menu_bar_only != current.menu_bar_only {
+        apply_activation_policy(app, current);
+    }
+    #[cfg(target_os = "macos")]
+    if previous.hardware_trigger != current.hardware_trigger {
+        dictation_key::apply(app, current.hardware_trigger);
+    }
+    if let Err(err) = backend::apply_settings_change(previous, current) {
+        let _ = log_message(format!("Failed to apply backend settings: {}", err));
+    }
+    if previous.profiles != current.profiles || previous.active_profile != current.active_profile {
+        refresh_tray_menu(app, current);
+    }
+    if previous.active_profile != current.active_profile {
+        let _ = app.emit(
+            "active-profile-changed",
+            serde_json::json!({ "name": current.active_profile }),
+        );
+    }
+    let _ = app.emit("settings-changed", current);
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    crash::install(env!("CARGO_PKG_VERSION"));
+    if let Some(result) = registration::run_installer_args() {
+        if let Err(err) = result {
+            let _ = log_message(format!("Failed to update registrations: {}", err));
+            std::process::exit(1);
+        }
+        return;
+    }
     tauri::Builder::default()
+        .plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
+            handle_links(app, args)
+        }))
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .plugin(tauri_plugin_updater::Builder::new().build())
         .setup(|app| {
-            let _ = init_script_path(&app.handle());
+            if let Ok(dir) = app.path().app_cache_dir() {
+                paths::set_default_working_dir(dir);
+            }
+            let status_handle = app.handle().clone();
+            worker::set_status_listener(move |status| {
+                let _ = status_handle.emit("asr-status", status);
+            });
+            app.listen("overlay-keep-open", |_| overlay::cancel_hide());
+            if let Some(dir) = paths::portable_dir() {
+                let _ = log_message(format!("Portable mode, data in {}", dir.display()));
+            }
+            match app.path().app_config_dir().map(paths::config_dir) {
+                Ok(dir) => {
+                    if let Err(err) = settings::init(&dir) {
+                        let _ = log_message(format!("Failed to load settings: {}", err));
+                    }
+                    match history::init(&dir) {
+                        Ok(()) => retention::start(),
+                        Err(err) => {
+                            let _ = log_message(format!("Failed to open history: {}", err));
+                        }
+                    }
+                }
+                Err(err) => {
+                    let _ = log_message(format!("No config directory: {}", err));
+                }
+            }
+            paths::set_working_dir(settings::current().working_dir.map(PathBuf::from));
+            // Once the working directory is known, since the script is copied into it.
+            let _ = worker::init();
+            power::watch();
+            let settings_handle = app.handle().clone();
+            settings::on_change(move |previous, current| {
+                let app_handle = settings_handle.clone();
+                let (previous, current) = (previous.clone(), current.clone());
+                let _ = settings_handle.run_on_main_thread(move || {
+                    apply_settings_change(&app_handle, &previous, &current);
+                });
+            });
+            if let Err(err) = watch::start() {
+                let _ = log_message(format!("Settings hot-reload unavailable: {}", err));
+            }
+            let sync_handle = app.handle().clone();
+            settings::on_sync(move |report| {
+                let _ = sync_handle.emit("settings-synced", report);
+            });
+            settings::start_sync();
+            companion::apply(app.handle(), &settings::current().companion);
+            mcp::apply(&settings::current().mcp);
+            grpc::apply(app.handle(), &settings::current().grpc);
+            updater::start(app.handle());
             #[cfg(target_os = "linux")]
             {
                 use webkit2gtk::{PermissionRequestExt, SettingsExt, WebViewExt};
@@ -351,10 +1387,13 @@ pub fn run() {
                         let inner = webview.inner();
 
                         // Allow mic access without portal prompts (temporary workaround).
-                        inner.connect_permission_request(|_, request| {
-                            request.allow();
-                            true
-                        });
+                        // Sandboxed, the portals and the Flatpak's permissions decide.
+                        if !portal::sandboxed() {
+                            inner.connect_permission_request(|_, request| {
+                                request.allow();
+                                true
+                            });
+                        }
 
                         if let Some(settings) = inner.settings() {
                             settings.set_enable_media_stream(true);
@@ -362,6 +1401,12 @@ pub fn run() {
                     });
                     let _ = window.set_focusable(false);
                 }
+                overlay::init_layer_shell(app.handle());
+            }
+            #[cfg(target_os = "macos")]
+            {
+                apply_activation_policy(app.handle(), &settings::current());
+                dictation_key::apply(app.handle(), settings::current().hardware_trigger);
             }
             #[cfg(target_os = "windows")]
             {
@@ -369,22 +1414,18 @@ pub fn run() {
                     // Prevent the DWM frame/shadow artifact around transparent windows.
                     let _ = window.set_shadow(false);
                 }
+                if let Err(err) = jump_list::register() {
+                    let _ = log_message(format!("Failed to set up the jump list: {}", err));
+                }
             }
-            let tray_menu = MenuBuilder::new(app)
-                .text(TRAY_MENU_SHOW, "Show VType")
-                .separator()
-                .text(TRAY_MENU_QUIT, "Quit")
-                .build()?;
+            overlay::apply_settings(app.handle(), &settings::current().overlay);
+            let tray_menu = build_tray_menu(app.handle(), &settings::current())?;
 
-            let mut tray_builder = TrayIconBuilder::with_id("main-tray")
+            let mut tray_builder = TrayIconBuilder::with_id(TRAY_ID)
                 .menu(&tray_menu)
                 .tooltip("VType")
                 .show_menu_on_left_click(false)
-                .on_menu_event(|app, event| match event.id().as_ref() {
-                    TRAY_MENU_SHOW => show_main_window(app),
-                    TRAY_MENU_QUIT => app.exit(0),
-                    _ => {}
-                })
+                .on_menu_event(|app, event| handle_tray_menu(app, event.id().as_ref()))
                 .on_tray_icon_event(|tray, event| {
                     if let TrayIconEvent::Click {
                         button: MouseButton::Left,
@@ -392,7 +1433,7 @@ pub fn run() {
                         ..
                     } = event
                     {
-                        show_main_window(tray.app_handle());
+                        overlay::show(tray.app_handle());
                     }
                 });
             if let Some(icon) = app.default_window_icon().cloned() {
@@ -400,27 +1441,82 @@ pub fn run() {
             }
             let _ = tray_builder.build(app)?;
 
-            let handle = app.handle();
-            handle
-                .global_shortcut()
-                .on_shortcut(HOTKEY, move |app, _shortcut, event| {
-                    if event.state != ShortcutState::Pressed {
-                        return;
-                    }
-                    let app_handle = app.clone();
-                    let _ = app.run_on_main_thread(move || {
-                        show_main_window(&app_handle);
-                        let _ = app_handle.emit("hotkey-pressed", ());
-                    });
-                })?;
+            register_hotkeys(app.handle(), &settings::current())?;
+            handle_links(app.handle(), std::env::args().skip(1));
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             transcribe_wav,
+            transcribe_file,
+            transcribe_folder,
+            transcribe_clipboard,
+            transcribe_url,
             paste_transcription,
+            paste_to_window,
+            get_macros,
+            list_plugins,
+            set_plugin_enabled,
+            answer_shell_confirmation,
+            get_pending_result,
+            confirm_paste,
+            finish_dictation,
+            discard_result,
+            keep_reviewing,
+            speak_last_transcription,
+            stop_speaking,
             log_message,
             save_wav_temp,
-            warm_asr
+            get_settings,
+            update_settings,
+            export_settings,
+            import_settings,
+            suggest_model_quantization,
+            benchmark_asr,
+            list_history,
+            search_history,
+            clear_history,
+            get_usage_stats,
+            retranscribe,
+            update_entry_text,
+            list_revisions,
+            get_entry_audio,
+            get_dictionary_suggestions,
+            pin_entry,
+            list_pinned,
+            start_companion_pairing,
+            unpair_companion,
+            sync_settings,
+            quick_pick_history,
+            paste_history_entry,
+            close_quick_pick,
+            list_profiles,
+            set_active_profile,
+            override_do_not_disturb,
+            get_registrations,
+            repair_registrations,
+            set_autostart,
+            warm_asr,
+            setup_python_env,
+            get_worker_stats,
+            get_recent_activity,
+            take_crash_report,
+            check_for_updates,
+            install_update,
+            open_crash_report,
+            export_metrics,
+            get_paste_stats,
+            test_paste,
+            paste_test_text,
+            capture_next_hotkey,
+            hotkey_captured,
+            clear_metrics,
+            export_audit_log,
+            start_recording,
+            pause_recording,
+            resume_recording,
+            stop_recording,
+            list_audio_inputs,
+            record_test_clip
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,267 @@
+//! A Model Context Protocol server, so AI agents on this machine can use VType's local
+//! transcription as a tool. While `mcp.enabled` is set it answers JSON-RPC at
+//! `http://127.0.0.1:<mcp.port>/mcp` in the Streamable HTTP transport, replying to each
+//! request with a plain JSON response; no event streams are offered.
+//!
+//! The tools are `transcribe_file`, which transcribes an audio or video file without
+//! touching history, and `get_history`, which lists or searches past dictations.
+
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use vtype_core::http::{self, Request};
+use vtype_core::options::TranscribeOptions;
+use vtype_core::settings::McpSettings;
+use vtype_core::{batch, history, log};
+
+const ENDPOINT: &str = "/mcp";
+/// Newest first; the first is answered to clients asking for one not listed.
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+const HISTORY_LIMIT: u64 = 20;
+/// A connection that sends nothing for this long is dropped.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+const PARSE_ERROR: i64 = -32_700;
+const INVALID_REQUEST: i64 = -32_600;
+const METHOD_NOT_FOUND: i64 = -32_601;
+const INVALID_PARAMS: i64 = -32_602;
+
+struct Server {
+    port: u16,
+    stopped: Arc<AtomicBool>,
+}
+
+static SERVER: Mutex<Option<Server>> = Mutex::new(None);
+
+/// Starts or stops the server to match the settings.
+pub fn apply(mcp: &McpSettings) {
+    let Ok(mut server) = SERVER.lock() else {
+        return;
+    };
+    let wanted = mcp.enabled.then_some(mcp.port);
+    if server.as_ref().map(|server| server.port) == wanted {
+        return;
+    }
+    if let Some(running) = server.take() {
+        running.stopped.store(true, Ordering::SeqCst);
+        // Wakes the accept loop so it sees the flag.
+        let _ = TcpStream::connect((Ipv4Addr::LOCALHOST, running.port));
+    }
+    let Some(port) = wanted else {
+        return;
+    };
+    match TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+        Ok(listener) => {
+            let stopped = Arc::new(AtomicBool::new(false));
+            let flag = stopped.clone();
+            std::thread::spawn(move || accept(listener, flag));
+            *server = Some(Server { port, stopped });
+        }
+        Err(err) => {
+            let _ = log::write(format!(
+                "Failed to start the MCP server on port {}: {}",
+                port, err
+            ));
+        }
+    }
+}
+
+fn accept(listener: TcpListener, stopped: Arc<AtomicBool>) {
+    for stream in listener.incoming() {
+        if stopped.load(Ordering::SeqCst) {
+            return;
+        }
+        let Ok(stream) = stream else {
+            continue;
+        };
+        std::thread::spawn(move || {
+            if let Err(err) = serve(stream) {
+                let _ = log::write(format!("MCP request failed: {}", err));
+            }
+        });
+    }
+}
+
+fn serve(mut stream: TcpStream) -> Result<(), String> {
+    stream
+        .set_read_timeout(Some(IDLE_TIMEOUT))
+        .map_err(|err| err.to_string())?;
+    let request = http::read_request(&mut stream)?;
+    if request.path != ENDPOINT {
+        http::reject(&mut stream, "404 Not Found");
+        return Ok(());
+    }
+    if !local_origin(&request) {
+        // A web page the user visits must not reach the server through their browser.
+        http::reject(&mut stream, "403 Forbidden");
+        return Err("Request from a web page".to_string());
+    }
+    if request.method != "POST" {
+        http::reject(&mut stream, "405 Method Not Allowed");
+        return Ok(());
+    }
+    let reply = match serde_json::from_slice::<Value>(&request.body) {
+        Ok(Value::Array(messages)) => {
+            let replies: Vec<Value> = messages.into_iter().filter_map(handle).collect();
+            (!replies.is_empty()).then_some(Value::Array(replies))
+        }
+        Ok(message) => handle(message),
+        Err(err) => Some(error(Value::Null, PARSE_ERROR, &err.to_string())),
+    };
+    match reply {
+        Some(reply) => http::respond(
+            &mut stream,
+            "200 OK",
+            "application/json",
+            reply.to_string().as_bytes(),
+        ),
+        // Notifications and responses get no answer.
+        None => http::respond(&mut stream, "202 Accepted", "application/json", b""),
+    }
+}
+
+/// Whether the request comes from a local program rather than a page in a browser, which
+/// is the only kind to send an `Origin` naming somewhere else.
+fn local_origin(request: &Request) -> bool {
+    let Some(origin) = request.header("origin") else {
+        return true;
+    };
+    let host = origin.split_once("://").map_or(origin, |(_, rest)| rest);
+    let host = match host.find(']') {
+        Some(end) if host.starts_with('[') => &host[..=end],
+        _ => host.split(':').next().unwrap_or(host),
+    };
+    matches!(host, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+/// The answer to one JSON-RPC message, none for a notification.
+fn handle(message: Value) -> Option<Value> {
+    let id = message.get("id").cloned();
+    let Some(method) = message.get("method").and_then(Value::as_str) else {
+        // A response to something the server asked, which it never does, or not JSON-RPC.
+        return id.map(|id| error(id, INVALID_REQUEST, "Not a request"));
+    };
+    let params = message.get("params").cloned().unwrap_or(Value::Null);
+    let result = match method {
+        "initialize" => Ok(initialize(&params)),
+        "ping" => Ok(json!({})),
+        "tools/list" => Ok(json!({ "tools": tools() })),
+        "tools/call" => call_tool(&params),
+        _ if method.starts_with("notifications/") => return None,
+        _ => Err((METHOD_NOT_FOUND, format!("Unknown method: {}", method))),
+    };
+    // Notifications are not answered, even when they fail.
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => error(id, code, &message),
+    })
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn initialize(params: &Value) -> Value {
+    let asked = params.get("protocolVersion").and_then(Value::as_str);
+    let version = PROTOCOL_VERSIONS
+        .iter()
+        .find(|version| Some(**version) == asked)
+        .unwrap_or(&PROTOCOL_VERSIONS[0]);
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": {} },
+        "serverInfo": { "name": "vtype", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+fn tools() -> Value {
+    json!([
+        {
+            "name": "transcribe_file",
+            "description": "Transcribes an audio or video file on this machine with VType's local speech recognition. The file is not kept in VType's history.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": { "type": "string", "description": "Absolute path of the file." },
+                    "language": { "type": "string", "description": "Language spoken, such as \"en\"; detected when left out." },
+                },
+                "required": ["path"],
+            },
+        },
+        {
+            "name": "get_history",
+            "description": "Past dictations from VType's history, newest first, or the best matches for a search.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "Words that must all appear." },
+                    "limit": { "type": "integer", "minimum": 1, "maximum": history::MAX_LIMIT, "description": "Most entries to return; 20 when left out." },
+                },
+            },
+        },
+    ])
+}
+
+/// Runs a tool. Failures of the tool itself are results with `isError` set, as the
+/// protocol asks, so the agent sees them.
+fn call_tool(params: &Value) -> Result<Value, (i64, String)> {
+    let name = params
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or((INVALID_PARAMS, "Missing tool name".to_string()))?;
+    let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+    let text = match name {
+        "transcribe_file" => transcribe_file(&arguments),
+        "get_history" => get_history(&arguments),
+        _ => return Err((INVALID_PARAMS, format!("Unknown tool: {}", name))),
+    };
+    Ok(match text {
+        Ok(text) => json!({ "content": [{ "type": "text", "text": text }], "isError": false }),
+        Err(err) => json!({ "content": [{ "type": "text", "text": err }], "isError": true }),
+    })
+}
+
+fn transcribe_file(arguments: &Value) -> Result<String, String> {
+    let path = arguments
+        .get("path")
+        .and_then(Value::as_str)
+        .ok_or("Missing path")?;
+    let path = Path::new(path);
+    if !path.is_absolute() {
+        return Err(format!("Path is not absolute: {}", path.display()));
+    }
+    let options = TranscribeOptions {
+        language: arguments
+            .get("language")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        ..TranscribeOptions::default()
+    };
+    let transcript = tauri::async_runtime::block_on(batch::transcribe_file(path, options))?;
+    Ok(transcript.text)
+}
+
+fn get_history(arguments: &Value) -> Result<String, String> {
+    let limit = arguments
+        .get("limit")
+        .and_then(Value::as_u64)
+        .unwrap_or(HISTORY_LIMIT)
+        .min(u32::MAX as u64) as u32;
+    let entries = match arguments.get("query").and_then(Value::as_str) {
+        Some(query) => history::search(query, Some(limit))?
+            .into_iter()
+            .map(|hit| hit.entry)
+            .collect(),
+        None => history::recent(Some(limit), None)?,
+    };
+    serde_json::to_string_pretty(&entries).map_err(|err| err.to_string())
+}
@@ -0,0 +1,364 @@
+//! Advertises the companion server on the local network as `_vtype._tcp` (RFC 6762 and
+//! RFC 6763), so companion apps and scripts find it without the user typing an address.
+//! This is a small responder of its own: it answers questions about the one service and
+//! the host name it runs on, announces them when it starts and says goodbye when it stops.
+//! It shares port 5353 with the system's responder, whose own records it leaves alone.
+
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, Socket, Type};
+use vtype_core::log;
+
+const SERVICE_TYPE: &str = "_vtype._tcp.local";
+const SERVICES: &str = "_services._dns-sd._udp.local";
+const GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const PORT: u16 = 5353;
+/// How often the responder looks at whether it should stop.
+const POLL: Duration = Duration::from_secs(1);
+/// Record lifetimes RFC 6762 suggests: short for those naming the host, long for the rest.
+const HOST_TTL: u32 = 120;
+const SERVICE_TTL: u32 = 4_500;
+const MAX_LABEL_BYTES: usize = 63;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+/// In a question, asks for a unicast answer; in a record, marks it the only one of its name.
+const CLASS_TOP_BIT: u16 = 0x8000;
+
+/// A running advertisement, withdrawn when stopped or dropped.
+pub struct Advertisement {
+    stopped: Arc<AtomicBool>,
+}
+
+impl Drop for Advertisement {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Record {
+    name: String,
+    kind: u16,
+    unique: bool,
+    ttl: u32,
+    data: Vec<u8>,
+}
+
+struct Question {
+    name: String,
+    kind: u16,
+    /// Asks for the answer to be sent straight back rather than to the group.
+    unicast: bool,
+}
+
+/// What this machine advertises.
+struct Service {
+    instance: String,
+    host: String,
+    address: Ipv4Addr,
+    port: u16,
+}
+
+/// Starts advertising the companion server listening on `port` at `address`.
+pub fn advertise(address: Ipv4Addr, port: u16) -> Result<Advertisement, String> {
+    let socket = bind(address)?;
+    let host_name = sysinfo::System::host_name().unwrap_or_else(|| "computer".to_string());
+    let service = Service {
+        instance: truncate(&format!("VType on {}", host_name)),
+        host: format!("{}.local", host_label(&host_name)),
+        address,
+        port,
+    };
+    let stopped = Arc::new(AtomicBool::new(false));
+    let flag = stopped.clone();
+    std::thread::spawn(move || respond(socket, service, flag));
+    Ok(Advertisement { stopped })
+}
+
+fn bind(address: Ipv4Addr) -> Result<UdpSocket, String> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))
+        .map_err(|err| err.to_string())?;
+    socket
+        .set_reuse_address(true)
+        .map_err(|err| err.to_string())?;
+    share_port(&socket)?;
+    socket
+        .bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, PORT)).into())
+        .map_err(|err| format!("Failed to listen for mDNS questions: {}", err))?;
+    socket
+        .join_multicast_v4(&GROUP, &address)
+        .map_err(|err| format!("Failed to join the mDNS group: {}", err))?;
+    socket
+        .set_multicast_if_v4(&address)
+        .map_err(|err| err.to_string())?;
+    socket
+        .set_multicast_ttl_v4(255)
+        .map_err(|err| err.to_string())?;
+    socket
+        .set_read_timeout(Some(POLL))
+        .map_err(|err| err.to_string())?;
+    Ok(socket.into())
+}
+
+/// Lets the system's responder keep port 5353 as well, which on macOS and Linux needs
+/// `SO_REUSEPORT`; on Windows `SO_REUSEADDR` is enough.
+#[cfg(unix)]
+fn share_port(socket: &Socket) -> Result<(), String> {
+    socket.set_reuse_port(true).map_err(|err| err.to_string())
+}
+
+#[cfg(not(unix))]
+fn share_port(_socket: &Socket) -> Result<(), String> {
+    Ok(())
+}
+
+fn respond(socket: UdpSocket, service: Service, stopped: Arc<AtomicBool>) {
+    let records = service.records(false);
+    let group = SocketAddr::V4(SocketAddrV4::new(GROUP, PORT));
+    // Announced twice, a second apart, as RFC 6762 asks.
+    for _ in 0..2 {
+        let _ = socket.send_to(&response(0, &[], &records, &[]), group);
+        std::thread::sleep(POLL);
+    }
+    let mut buffer = [0u8; 9_000];
+    while !stopped.load(Ordering::SeqCst) {
+        let Ok((len, from)) = socket.recv_from(&mut buffer) else {
+            continue;
+        };
+        let Some((id, questions)) = parse_query(&buffer[..len]) else {
+            continue;
+        };
+        let (answers, additional) = answer(&records, &questions);
+        if answers.is_empty() {
+            continue;
+        }
+        // A resolver that is not a full mDNS responder asks from another port and only
+        // hears a unicast reply echoing its id and questions.
+        let legacy = from.port() != PORT;
+        let unicast = legacy || questions.iter().all(|question| question.unicast);
+        let packet = if legacy {
+            response(id, &questions, &answers, &additional)
+        } else {
+            response(0, &[], &answers, &additional)
+        };
+        let target = if unicast { from } else { group };
+        if let Err(err) = socket.send_to(&packet, target) {
+            let _ = log::write(format!("Failed to answer an mDNS question: {}", err));
+        }
+    }
+    let _ = socket.send_to(&response(0, &[], &service.records(true), &[]), group);
+}
+
+impl Service {
+    fn service_name(&self) -> String {
+        format!("{}.{}", self.instance, SERVICE_TYPE)
+    }
+
+    /// Every record advertised, with a lifetime of zero to withdraw them.
+    fn records(&self, goodbye: bool) -> Vec<Record> {
+        let ttl = |ttl| if goodbye { 0 } else { ttl };
+        let mut srv = vec![0, 0, 0, 0];
+        srv.extend_from_slice(&self.port.to_be_bytes());
+        srv.extend(encode_name(&self.host));
+        let txt = ["pair=/pair", "stream=/stream"]
+            .iter()
+            .flat_map(|entry| std::iter::once(entry.len() as u8).chain(entry.bytes()))
+            .collect();
+        vec![
+            Record {
+                name: SERVICE_TYPE.to_string(),
+                kind: TYPE_PTR,
+                unique: false,
+                ttl: ttl(SERVICE_TTL),
+                data: encode_name(&self.service_name()),
+            },
+            Record {
+                name: self.service_name(),
+                kind: TYPE_SRV,
+                unique: true,
+                ttl: ttl(HOST_TTL),
+                data: srv,
+            },
+            Record {
+                name: self.service_name(),
+                kind: TYPE_TXT,
+                unique: true,
+                ttl: ttl(SERVICE_TTL),
+                data: txt,
+            },
+            Record {
+                name: self.host.clone(),
+                kind: TYPE_A,
+                unique: true,
+                ttl: ttl(HOST_TTL),
+                data: self.address.octets().to_vec(),
+            },
+            Record {
+                name: SERVICES.to_string(),
+                kind: TYPE_PTR,
+                unique: false,
+                ttl: ttl(SERVICE_TTL),
+                data: encode_name(SERVICE_TYPE),
+            },
+        ]
+    }
+}
+
+/// The records `questions` ask for, and the rest of those about the service to go with
+/// them so the asker needs no second round.
+fn answer(records: &[Record], questions: &[Question]) -> (Vec<Record>, Vec<Record>) {
+    let answers: Vec<Record> = records
+        .iter()
+        .filter(|record| {
+            questions.iter().any(|question| {
+                question.name.eq_ignore_ascii_case(&record.name)
+                    && (question.kind == record.kind || question.kind == TYPE_ANY)
+            })
+        })
+        .cloned()
+        .collect();
+    let additional = records
+        .iter()
+        .filter(|record| record.name != SERVICES && !answers.contains(record))
+        .cloned()
+        .collect();
+    (answers, additional)
+}
+
+/// The id and questions of a query.
+fn parse_query(packet: &[u8]) -> Option<(u16, Vec<Question>)> {
+    let header = packet.get(..12)?;
+    let id = u16::from_be_bytes([header[0], header[1]]);
+    if header[2] & 0x80 != 0 {
+        return None;
+    }
+    let count = u16::from_be_bytes([header[4], header[5]]);
+    let mut at = 12;
+    let mut questions = Vec::new();
+    for _ in 0..count {
+        let (name, next) = decode_name(packet, at)?;
+        let fields = packet.get(next..next + 4)?;
+        let kind = u16::from_be_bytes([fields[0], fields[1]]);
+        let class = u16::from_be_bytes([fields[2], fields[3]]);
+        questions.push(Question {
+            name,
+            kind,
+            unicast: class & CLASS_TOP_BIT != 0,
+        });
+        at = next + 4;
+    }
+    Some((id, questions))
+}
+
+/// The name at `at` and where what follows it starts, following compression pointers.
+fn decode_name(packet: &[u8], mut at: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Each pointer must go back, which also rules out loops.
+    let mut limit = at;
+    loop {
+        let len = *packet.get(at)? as usize;
+        match len {
+            0 => break,
+            len if len & 0xC0 == 0xC0 => {
+                let target = (len & 0x3F) << 8 | *packet.get(at + 1)? as usize;
+                if target >= limit {
+                    return None;
+                }
+                end.get_or_insert(at + 2);
+                limit = target;
+                at = target;
+            }
+            len if len <= MAX_LABEL_BYTES => {
+                let label = packet.get(at + 1..at + 1 + len)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                at += 1 + len;
+            }
+            _ => return None,
+        }
+    }
+    Some((labels.join("."), end.unwrap_or(at + 1)))
+}
+
+/// `name` as DNS labels, uncompressed. No label has a dot of its own: [`truncate`] takes
+/// them out.
+fn encode_name(name: &str) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(name.len() + 2);
+    for label in name.split('.') {
+        encoded.push(label.len() as u8);
+        encoded.extend_from_slice(label.as_bytes());
+    }
+    encoded.push(0);
+    encoded
+}
+
+fn response(id: u16, questions: &[Question], answers: &[Record], additional: &[Record]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(512);
+    packet.extend_from_slice(&id.to_be_bytes());
+    // A response, and the authoritative one.
+    packet.extend_from_slice(&0x8400u16.to_be_bytes());
+    for count in [questions.len(), answers.len(), 0, additional.len()] {
+        packet.extend_from_slice(&(count as u16).to_be_bytes());
+    }
+    for question in questions {
+        packet.extend(encode_name(&question.name));
+        packet.extend_from_slice(&question.kind.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+    }
+    for record in answers.iter().chain(additional) {
+        let class = if record.unique {
+            CLASS_IN | CLASS_TOP_BIT
+        } else {
+            CLASS_IN
+        };
+        packet.extend(encode_name(&record.name));
+        packet.extend_from_slice(&record.kind.to_be_bytes());
+        packet.extend_from_slice(&class.to_be_bytes());
+        packet.extend_from_slice(&record.ttl.to_be_bytes());
+        packet.extend_from_slice(&(record.data.len() as u16).to_be_bytes());
+        packet.extend_from_slice(&record.data);
+    }
+    packet
+}
+
+/// A label of letters, digits and hyphens for this host, apart from the name the system's
+/// responder may already hold.
+fn host_label(host_name: &str) -> String {
+    let label = host_name
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() {
+                ch.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>();
+    match label.trim_matches('-') {
+        "" => "vtype".to_string(),
+        label => truncate(&format!("vtype-{}", label)),
+    }
+}
+
+/// At most one label's worth of `label`, cut at a character boundary, without dots.
+fn truncate(label: &str) -> String {
+    let mut truncated = String::new();
+    for ch in label.chars().map(|ch| if ch == '.' { '-' } else { ch }) {
+        if truncated.len() + ch.len_utf8() > MAX_LABEL_BYTES {
+            break;
+        }
+        truncated.push(ch);
+    }
+    truncated
+}
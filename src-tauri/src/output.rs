@@ -0,0 +1,850 @@
+use std::time::{Duration, Instant};
+
+#[cfg(target_os = "linux")]
+use std::process::Command;
+
+use tauri::Manager;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use vtype_core::activity::{self, ActivityKind};
+use vtype_core::frontmost::{self, FrontmostApp, WindowMatcher};
+use vtype_core::hooks::{self, Hook};
+use vtype_core::i18n::{self, Message};
+use vtype_core::inject::{self, Chord, Key, Modifier, TextKind};
+use vtype_core::metrics::{self, ErrorCategory};
+use vtype_core::paste_stats::{self, Outcome};
+use vtype_core::settings::{
+    self, ElevatedTargets, ImeInjection, OutputMode, Settings, TerminalSafety,
+};
+use vtype_core::{audit, bidi, ime, log, macros, postprocess, spacing, voice};
+
+use crate::actions;
+use crate::announce::{self, Announcement};
+
+/// How long the target app gets to read a pasted transcript before the clipboard is
+/// restored.
+const CLIPBOARD_RESTORE_DELAY: Duration = Duration::from_millis(300);
+
+/// Hands a finished transcript to the focused application, using the output options of the
+/// profile that applies to it, or does what a voice command asks for instead.
+pub fn deliver(app: &tauri::AppHandle, text: String) -> Result<(), String> {
+    let settings = settings::current();
+    let voice_commands = &settings.voice_commands;
+    if voice_commands.enabled {
+        if let Some(found) = macros::find(&text) {
+            actions::start_macro(app, found);
+            return Ok(());
+        }
+        if let Some((text, action)) = voice::interpret(&text, &voice_commands.directives) {
+            return actions::run_directive(app, text, action);
+        }
+    }
+    deliver_text(app, postprocess::apply_template(&text, &settings.templates))
+}
+
+/// [`deliver`] without looking for voice commands or templates. The pre-paste hook may
+/// still change the text or keep it back.
+pub fn deliver_text(app: &tauri::AppHandle, text: String) -> Result<(), String> {
+    let Some(text) = hooks::pre_paste(&text) else {
+        let _ = log::write("The pre-paste hook kept a dictation from being pasted".to_string());
+        return_focus(app);
+        return Ok(());
+    };
+    let started = Instant::now();
+    let words = text.split_whitespace().count();
+    let result = send(app, text.clone());
+    match &result {
+        Ok(mode) => {
+            hooks::notify(Hook::PostPaste, &text);
+            activity::record(ActivityKind::Delivered, Some(started.elapsed()), None);
+            announce::announce(
+                app,
+                if *mode == OutputMode::Clipboard {
+                    Announcement::Copied(words)
+                } else {
+                    Announcement::Delivered(words)
+                },
+            );
+        }
+        Err(err) => {
+            activity::record(ActivityKind::DeliveryFailed, None, Some(err.clone()));
+            announce::announce(app, Announcement::Failed(Some(err.clone())));
+            metrics::count_error(if err.starts_with(Message::ElevatedTarget.code()) {
+                ErrorCategory::ElevatedTarget
+            } else {
+                ErrorCategory::Output
+            });
+        }
+    }
+    result.map(|_| ())
+}
+
+/// Brings the window `matcher` picks to the front first, so the transcript reaches it
+/// whatever had focus.
+pub fn deliver_to_window(
+    app: &tauri::AppHandle,
+    matcher: &WindowMatcher,
+    text: String,
+) -> Result<(), String> {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.hide();
+    }
+    if let Err(err) = frontmost::activate(matcher) {
+        activity::record(ActivityKind::DeliveryFailed, None, Some(err.clone()));
+        return Err(err);
+    }
+    deliver(app, text)
+}
+
+/// Returns the output mode that took effect.
+fn send(app: &tauri::AppHandle, text: String) -> Result<OutputMode, String> {
+    let settings = settings::current();
+    return_focus(app);
+    let target = frontmost::detect();
+    let terminal = settings.effective_terminal(target.as_ref());
+    let text = match terminal {
+        Some(safety) => vtype_core::terminal::sanitize(&text, safety),
+        None => text,
+    };
+    let spaced = spacing::apply(
+        &text,
+        target.as_ref().map(|target| target.name.as_str()),
+        &settings.spacing,
+    );
+    let text = bidi::mark(&spaced.text, settings.directional_marks);
+    let injected = inject_text(
+        app,
+        &settings,
+        target.as_ref(),
+        terminal,
+        spaced.retract_space,
+        text.clone(),
+    );
+    if let Some(target) = target.as_ref().filter(|target| !target.elevated) {
+        count_delivery(&settings, target, &injected);
+    }
+    let (mode, _) = injected?;
+    audit::record(target.as_ref(), &text, mode);
+    Ok(mode)
+}
+
+/// Records how a paste or typed delivery into `target` went, per app, so the ones that need
+/// typing can be found. Text left on the clipboard only is not counted.
+fn count_delivery(
+    settings: &Settings,
+    target: &FrontmostApp,
+    injected: &Result<(OutputMode, bool), String>,
+) {
+    let (mode, outcome) = match injected {
+        Ok((mode, true)) => (*mode, Outcome::Retried),
+        Ok((mode, false)) => (*mode, Outcome::Delivered),
+        Err(err) => (
+            settings.effective_output_mode(Some(target)),
+            Outcome::Failed(err.clone()),
+        ),
+    };
+    if mode != OutputMode::Clipboard {
+        paste_stats::record(&target.name, mode, outcome);
+    }
+}
+
+/// Puts `text`, ready as it is, into `target` the way its profile asks. Returns the output
+/// mode that took effect and whether a paste only went through on a second try.
+fn inject_text(
+    app: &tauri::AppHandle,
+    settings: &Settings,
+    target: Option<&FrontmostApp>,
+    terminal: Option<TerminalSafety>,
+    retract_space: bool,
+    text: String,
+) -> Result<(OutputMode, bool), String> {
+    let mode = settings.effective_output_mode(target);
+    if let Some(target) = target.filter(|target| target.elevated) {
+        // SendInput reports success even when UIPI drops every key, so nothing is sent.
+        app.clipboard()
+            .write_text(text)
+            .map_err(|err| err.to_string())?;
+        if mode == OutputMode::Clipboard || settings.elevated_targets == ElevatedTargets::Clipboard
+        {
+            return Ok((OutputMode::Clipboard, false));
+        }
+        return Err(i18n::error(Message::ElevatedTarget, &[&target.name]));
+    }
+    if mode != OutputMode::Type {
+        app.clipboard()
+            .write_text(text.clone())
+            .map_err(|err| err.to_string())?;
+    }
+    if mode == OutputMode::Clipboard {
+        return Ok((mode, false));
+    }
+
+    let injection = settings.effective_ime_injection(target);
+    let ime_on = injection != ImeInjection::Off && ime::active();
+    // Switched back on when this goes out of scope, after the keys are sent.
+    let _suspended = ime_on.then(ime::suspend).flatten();
+    if retract_space {
+        press_chord(&Chord::key(Key::Backspace))?;
+    }
+    let chunks = settings.chunking.split(&text);
+    let chunked = chunks.len() > 1;
+    let mut retried = false;
+    let injected = chunks.iter().enumerate().try_for_each(|(index, chunk)| {
+        if index > 0 {
+            std::thread::sleep(Duration::from_millis(settings.chunking.delay_ms));
+        }
+        if chunked && mode == OutputMode::Paste {
+            app.clipboard()
+                .write_text(chunk.to_string())
+                .map_err(|err| err.to_string())?;
+        }
+        retried |= match mode {
+            // A typed line break is Enter, so terminals always get a paste.
+            OutputMode::Type if terminal.is_some() => paste_restoring_clipboard(app, chunk, true)?,
+            OutputMode::Type => type_text(app, chunk)?,
+            _ if terminal.is_some() => send_paste_chord(true)?,
+            _ if ime_on && injection == ImeInjection::Type => type_text(app, chunk)?,
+            _ => send_paste_chord(false)?,
+        };
+        Ok(())
+    });
+    if chunked && mode == OutputMode::Paste {
+        // Leave the whole transcript on the clipboard, as an unchunked paste does.
+        std::thread::sleep(CLIPBOARD_RESTORE_DELAY);
+        let _ = app.clipboard().write_text(text);
+    }
+    injected.map(|()| (mode, retried))
+}
+
+/// Puts `text` into whatever has focus, `mode` taking the place of the output mode the
+/// settings ask for, for [`crate::paste_test`].
+pub fn inject_as(app: &tauri::AppHandle, mode: OutputMode, text: &str) -> Result<(), String> {
+    let mut settings = settings::current();
+    settings.output_mode = mode;
+    settings.active_profile = None;
+    inject_text(app, &settings, None, None, false, text.to_string()).map(|_| ())
+}
+
+/// What sends the keys for `mode` on this system.
+#[cfg(target_os = "macos")]
+pub fn injection_method(_mode: OutputMode) -> &'static str {
+    "CGEvent (enigo)"
+}
+
+#[cfg(target_os = "windows")]
+pub fn injection_method(mode: OutputMode) -> &'static str {
+    match mode {
+        OutputMode::Type => "SendInput (Unicode)",
+        _ => "SendInput (Ctrl+V)",
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn injection_method(_mode: OutputMode) -> &'static str {
+    if sandboxed() {
+        "RemoteDesktop portal"
+    } else if is_wayland() {
+        "wtype"
+    } else {
+        "xdotool"
+    }
+}
+
+/// Hides the overlay and gives focus time to return to the target app before anything looks
+/// at it or injects keys.
+pub fn return_focus(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.hide();
+    }
+    std::thread::sleep(Duration::from_millis(200));
+}
+
+/// `terminal` picks the chord terminal emulators paste with, where it differs. Returns
+/// whether the paste took a second try.
+fn send_paste_chord(terminal: bool) -> Result<bool, String> {
+    // Terminals on macOS and Windows paste on the usual chord.
+    #[cfg(not(target_os = "linux"))]
+    let _ = terminal;
+    #[cfg(target_os = "macos")]
+    let retried = {
+        use enigo::{Key, KeyboardControllable};
+        paste_with_retry(|| {
+            let mut enigo = enigo::Enigo::new();
+            enigo.key_down(Key::Meta);
+            enigo.key_click(Key::Raw(v_keycode()));
+            enigo.key_up(Key::Meta);
+            Ok(())
+        })?
+    };
+    #[cfg(target_os = "windows")]
+    let retried = paste_with_retry(send_ctrl_v)?;
+    #[cfg(target_os = "linux")]
+    let retried = paste_with_retry(|| linux_paste(terminal))?;
+
+    Ok(retried)
+}
+
+/// Presses `chord` in the focused app.
+pub fn press_chord(chord: &Chord) -> Result<(), String> {
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    {
+        use enigo::KeyboardControllable;
+        let mut enigo = enigo::Enigo::new();
+        let modifiers: Vec<enigo::Key> =
+            chord.modifiers.iter().map(|&m| enigo_modifier(m)).collect();
+        for &modifier in &modifiers {
+            enigo.key_down(modifier);
+        }
+        enigo.key_click(enigo_key(chord.key));
+        for &modifier in modifiers.iter().rev() {
+            enigo.key_up(modifier);
+        }
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if sandboxed() {
+            let modifiers: Vec<i32> = chord
+                .modifiers
+                .iter()
+                .map(|&modifier| crate::portal::modifier_keysym(modifier))
+                .collect();
+            return crate::portal::chord(&modifiers, crate::portal::key_keysym(chord.key));
+        }
+        let key = keysym_name(chord.key);
+        let status = if is_wayland() {
+            let mut command = Command::new("wtype");
+            for &modifier in &chord.modifiers {
+                command.args(["-M", wtype_modifier(modifier)]);
+            }
+            command.args(["-k", &key]);
+            for &modifier in chord.modifiers.iter().rev() {
+                command.args(["-m", wtype_modifier(modifier)]);
+            }
+            command.status()
+        } else {
+            let combo: Vec<&str> = chord
+                .modifiers
+                .iter()
+                .map(|&modifier| xdotool_modifier(modifier))
+                .chain([key.as_str()])
+                .collect();
+            Command::new("xdotool")
+                .args(["key", "--clearmodifiers", &combo.join("+")])
+                .status()
+        };
+        if !status.map_err(|err| err.to_string())?.success() {
+            return Err(format!("Failed to press {}", key));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn enigo_modifier(modifier: Modifier) -> enigo::Key {
+    match modifier {
+        Modifier::Control => enigo::Key::Control,
+        Modifier::Shift => enigo::Key::Shift,
+        Modifier::Alt => enigo::Key::Alt,
+        Modifier::Super => enigo::Key::Meta,
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn enigo_key(key: Key) -> enigo::Key {
+    use enigo::Key as K;
+
+    match key {
+        Key::Char(ch) => K::Layout(ch),
+        Key::Return => K::Return,
+        Key::Tab => K::Tab,
+        Key::Escape => K::Escape,
+        Key::Backspace => K::Backspace,
+        Key::Delete => K::Delete,
+        Key::Space => K::Space,
+        Key::Up => K::UpArrow,
+        Key::Down => K::DownArrow,
+        Key::Left => K::LeftArrow,
+        Key::Right => K::RightArrow,
+        Key::Home => K::Home,
+        Key::End => K::End,
+        Key::PageUp => K::PageUp,
+        Key::PageDown => K::PageDown,
+        Key::Function(number) => [
+            K::F1,
+            K::F2,
+            K::F3,
+            K::F4,
+            K::F5,
+            K::F6,
+            K::F7,
+            K::F8,
+            K::F9,
+            K::F10,
+            K::F11,
+            K::F12,
+        ][usize::from(number.clamp(1, 12)) - 1],
+    }
+}
+
+/// The keysym name xdotool and wtype know `key` by.
+#[cfg(target_os = "linux")]
+fn keysym_name(key: Key) -> String {
+    match key {
+        Key::Char(ch) => ch.to_string(),
+        Key::Function(number) => format!("F{}", number),
+        Key::Return => "Return".to_string(),
+        Key::Tab => "Tab".to_string(),
+        Key::Escape => "Escape".to_string(),
+        Key::Backspace => "BackSpace".to_string(),
+        Key::Delete => "Delete".to_string(),
+        Key::Space => "space".to_string(),
+        Key::Up => "Up".to_string(),
+        Key::Down => "Down".to_string(),
+        Key::Left => "Left".to_string(),
+        Key::Right => "Right".to_string(),
+        Key::Home => "Home".to_string(),
+        Key::End => "End".to_string(),
+        Key::PageUp => "Page_Up".to_string(),
+        Key::PageDown => "Page_Down".to_string(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn xdotool_modifier(modifier: Modifier) -> &'static str {
+    match modifier {
+        Modifier::Control => "ctrl",
+        Modifier::Shift => "shift",
+        Modifier::Alt => "alt",
+        Modifier::Super => "super",
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn wtype_modifier(modifier: Modifier) -> &'static str {
+    match modifier {
+        Modifier::Control => "ctrl",
+        Modifier::Shift => "shift",
+        Modifier::Alt => "alt",
+        Modifier::Super => "logo",
+    }
+}
+
+/// Types `text`, pasting it instead when this platform's key simulation would garble it.
+/// Returns whether such a paste took a second try.
+fn type_text(app: &tauri::AppHandle, text: &str) -> Result<bool, String> {
+    if needs_clipboard(inject::classify(text)) {
+        return paste_restoring_clipboard(app, text, false);
+    }
+    let typing = settings::current().typing;
+    if !typing.paced() {
+        return type_keys(text, None).map(|()| false);
+    }
+    let key_delay = typing
+        .chars_per_second
+        .map(|rate| Duration::from_secs(1) / rate);
+    for (index, word) in text.split_inclusive(char::is_whitespace).enumerate() {
+        if index > 0 {
+            let jitter = Duration::from_millis(random_below(typing.word_jitter_ms) as u64);
+            std::thread::sleep(key_delay.unwrap_or_default() + jitter);
+        }
+        type_keys(word, key_delay)?;
+    }
+    Ok(false)
+}
+
+/// Types `text`, waiting `key_delay` between keys when given.
+fn type_keys(text: &str, key_delay: Option<Duration>) -> Result<(), String> {
+    #[cfg(target_os = "macos")]
+    {
+        use enigo::KeyboardControllable;
+        let mut enigo = enigo::Enigo::new();
+        match key_delay {
+            Some(delay) => each_char(text, delay, |ch| {
+                enigo.key_sequence(ch);
+                Ok(())
+            })?,
+            None => enigo.key_sequence(text),
+        }
+    }
+    #[cfg(target_os = "windows")]
+    {
+        match key_delay {
+            Some(delay) => each_char(text, delay, send_unicode)?,
+            None => send_unicode(text)?,
+        }
+    }
+    #[cfg(target_os = "linux")]
+    {
+        linux_type(text, key_delay)?;
+    }
+
+    Ok(())
+}
+
+/// Types `text` a character at a time with `type_fn`.
+fn each_char<F>(text: &str, delay: Duration, mut type_fn: F) -> Result<(), String>
+where
+    F: FnMut(&str) -> Result<(), String>,
+{
+    let mut buffer = [0u8; 4];
+    for (index, ch) in text.chars().enumerate() {
+        if index > 0 {
+            std::thread::sleep(delay);
+        }
+        type_fn(ch.encode_utf8(&mut buffer))?;
+    }
+    Ok(())
+}
+
+/// Good enough for jitter: std seeds every `RandomState` differently.
+fn random_below(limit: u32) -> u32 {
+    use std::hash::{BuildHasher, Hasher};
+
+    if limit == 0 {
+        return 0;
+    }
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    (random % (u64::from(limit) + 1)) as u32
+}
+
+fn needs_clipboard(kind: TextKind) -> bool {
+    match kind {
+        TextKind::Ascii => false,
+        // xdotool types non-ASCII by remapping a spare keycode per character, which many X11
+        // apps read back as the wrong symbol, and the RemoteDesktop portal only has the keys
+        // of the active keymap. wtype, SendInput and Quartz take Unicode.
+        TextKind::Unicode => cfg!(target_os = "linux") && (!is_wayland() || sandboxed()),
+        TextKind::Composed => true,
+    }
+}
+
+/// Pastes `text` and then puts back what the clipboard held, since type mode promises to
+/// leave the clipboard alone.
+fn paste_restoring_clipboard(
+    app: &tauri::AppHandle,
+    text: &str,
+    terminal: bool,
+) -> Result<bool, String> {
+    let previous = app.clipboard().read_text().ok();
+    app.clipboard()
+        .write_text(text.to_string())
+        .map_err(|err| err.to_string())?;
+    let pasted = send_paste_chord(terminal);
+    std::thread::sleep(CLIPBOARD_RESTORE_DELAY);
+    if let Some(previous) = previous {
+        let _ = app.clipboard().write_text(previous);
+    }
+    pasted
+}
+
+/// The key code that types `v` in the current input source, so ⌘V lands on Dvorak or AZERTY
+/// too. enigo answers the `a` key when a layout has no `v`; those, such as Russian, get the
+/// ANSI V key, which macOS reads through a Latin layout for shortcuts.
+#[cfg(target_os = "macos")]
+fn v_keycode() -> u16 {
+    use std::ffi::c_void;
+
+    type CFTypeRef = *const c_void;
+
+    #[link(name = "Carbon", kind = "framework")]
+    extern "C" {
+        static kTISPropertyUnicodeKeyLayoutData: CFTypeRef;
+        fn TISCopyCurrentKeyboardLayoutInputSource() -> CFTypeRef;
+        fn TISGetInputSourceProperty(source: CFTypeRef, key: CFTypeRef) -> CFTypeRef;
+        fn LMGetKbdType() -> u8;
+        fn UCKeyTranslate(
+            layout: *const c_void,
+            key_code: u16,
+            key_action: u16,
+            modifier_state: u32,
+            keyboard_type: u32,
+            options: u32,
+            dead_key_state: *mut u32,
+            max_length: usize,
+            actual_length: *mut usize,
+            chars: *mut u16,
+        ) -> i32;
+    }
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFDataGetBytePtr(data: CFTypeRef) -> *const u8;
+        fn CFRelease(object: CFTypeRef);
+    }
+
+    const ANSI_V: u16 = 9;
+    const KEY_ACTION_DOWN: u16 = 0;
+    const NO_DEAD_KEYS: u32 = 1;
+
+    // SAFETY: the layout data belongs to `source`, which is released only after the last
+    // lookup; every out-pointer refers to a local that outlives its call.
+    unsafe {
+        let source = TISCopyCurrentKeyboardLayoutInputSource();
+        if source.is_null() {
+            return ANSI_V;
+        }
+        let data = TISGetInputSourceProperty(source, kTISPropertyUnicodeKeyLayoutData);
+        let found = (!data.is_null())
+            .then(|| {
+                let layout = CFDataGetBytePtr(data) as *const c_void;
+                let keyboard_type = u32::from(LMGetKbdType());
+                (0..128).find(|&key_code| {
+                    let mut dead_keys = 0;
+                    let mut length = 0;
+                    let mut chars = [0u16; 4];
+                    let status = UCKeyTranslate(
+                        layout,
+                        key_code,
+                        KEY_ACTION_DOWN,
+                        0,
+                        keyboard_type,
+                        NO_DEAD_KEYS,
+                        &mut dead_keys,
+                        chars.len(),
+                        &mut length,
+                        chars.as_mut_ptr(),
+                    );
+                    status == 0 && length == 1 && chars[0] == u16::from(b'v')
+                })
+            })
+            .flatten();
+        CFRelease(source);
+        found.unwrap_or(ANSI_V)
+    }
+}
+
+/// Presses Ctrl+V by virtual key, which is what apps match shortcuts on. enigo looks `v` up
+/// in VType's own layout rather than the target's, and finds nothing on layouts without a
+/// Latin `v`. Scan codes come from the target window's layout.
+#[cfg(target_os = "windows")]
+fn send_ctrl_v() -> Result<(), String> {
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+        GetKeyboardLayout, MapVirtualKeyExW, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT,
+        KEYEVENTF_KEYUP, MAPVK_VK_TO_VSC, VK_CONTROL, VK_V,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetForegroundWindow, GetWindowThreadProcessId,
+    };
+
+    // SAFETY: plain queries; a null pid pointer is allowed, and thread 0 means our own.
+    let layout = unsafe {
+        GetKeyboardLayout(GetWindowThreadProcessId(
+            GetForegroundWindow(),
+            std::ptr::null_mut(),
+        ))
+    };
+    let key = |vk: u16, flags| INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: vk,
+                // SAFETY: maps a key code to a scan code without touching memory.
+                wScan: unsafe { MapVirtualKeyExW(u32::from(vk), MAPVK_VK_TO_VSC, layout) } as u16,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    };
+    send_inputs(&[
+        key(VK_CONTROL, 0),
+        key(VK_V, 0),
+        key(VK_V, KEYEVENTF_KEYUP),
+        key(VK_CONTROL, KEYEVENTF_KEYUP),
+    ])
+}
+
+/// Sends each UTF-16 unit as a `KEYEVENTF_UNICODE` key press, which needs no key on the
+/// active layout. enigo truncates characters beyond the BMP to 16 bits.
+#[cfg(target_os = "windows")]
+fn send_unicode(text: &str) -> Result<(), String> {
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{
+        INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYEVENTF_KEYUP, KEYEVENTF_UNICODE,
+    };
+
+    let inputs: Vec<INPUT> = text
+        .encode_utf16()
+        .flat_map(|unit| {
+            [0, KEYEVENTF_KEYUP].map(|flags| INPUT {
+                r#type: INPUT_KEYBOARD,
+                Anonymous: INPUT_0 {
+                    ki: KEYBDINPUT {
+                        wVk: 0,
+                        wScan: unit,
+                        dwFlags: KEYEVENTF_UNICODE | flags,
+                        time: 0,
+                        dwExtraInfo: 0,
+                    },
+                },
+            })
+        })
+        .collect();
+    send_inputs(&inputs)
+}
+
+#[cfg(target_os = "windows")]
+fn send_inputs(
+    inputs: &[windows_sys::Win32::UI::Input::KeyboardAndMouse::INPUT],
+) -> Result<(), String> {
+    use windows_sys::Win32::UI::Input::KeyboardAndMouse::{SendInput, INPUT};
+
+    if inputs.is_empty() {
+        return Ok(());
+    }
+    // SAFETY: `inputs` is a live, correctly sized array of INPUT structs.
+    let sent = unsafe {
+        SendInput(
+            inputs.len() as u32,
+            inputs.as_ptr(),
+            std::mem::size_of::<INPUT>() as i32,
+        )
+    };
+    if sent as usize == inputs.len() {
+        Ok(())
+    } else {
+        Err(format!(
+            "SendInput injected {} of {} key events",
+            sent,
+            inputs.len()
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn is_wayland() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_wayland() -> bool {
+    false
+}
+
+/// Flatpak and Snap builds have neither wtype nor xdotool, and go through the portals.
+#[cfg(target_os = "linux")]
+fn sandboxed() -> bool {
+    crate::portal::sandboxed()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sandboxed() -> bool {
+    false
+}
+
+#[cfg(target_os = "linux")]
+fn linux_paste(terminal: bool) -> Result<(), String> {
+    // Terminals read Ctrl+V as "insert the next key literally" and paste on Ctrl+Shift+V.
+    // Both tools and the portal find the key for `v` in the active keymap themselves, so any
+    // layout works.
+    if sandboxed() {
+        use crate::portal::{CONTROL, SHIFT, V};
+        let modifiers: &[i32] = if terminal {
+            &[CONTROL, SHIFT]
+        } else {
+            &[CONTROL]
+        };
+        return crate::portal::chord(modifiers, V);
+    }
+    if is_wayland() {
+        // Wayland: use wtype if available.
+        let args: &[&str] = if terminal {
+            &[
+                "-M", "ctrl", "-M", "shift", "-k", "v", "-m", "shift", "-m", "ctrl",
+            ]
+        } else {
+            &["-M", "ctrl", "-k", "v", "-m", "ctrl"]
+        };
+        let status = Command::new("wtype")
+            .args(args)
+            .status()
+            .map_err(|err| err.to_string())?;
+        if status.success() {
+            return Ok(());
+        }
+        return Err("wtype failed to paste on Wayland".to_string());
+    }
+
+    // X11: use xdotool if available.
+    let status = Command::new("xdotool")
+        .args([
+            "key",
+            "--clearmodifiers",
+            if terminal { "ctrl+shift+v" } else { "ctrl+v" },
+        ])
+        .status()
+        .map_err(|err| err.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err("xdotool failed to paste on X11".to_string())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn linux_type(text: &str, key_delay: Option<Duration>) -> Result<(), String> {
+    if sandboxed() {
+        return match key_delay {
+            Some(delay) => each_char(text, delay, crate::portal::type_text),
+            None => crate::portal::type_text(text),
+        };
+    }
+    // Both tools wait between keys themselves; xdotool's default is 12 ms.
+    let delay_ms = key_delay.map(|delay| delay.as_millis().to_string());
+    if is_wayland() {
+        // `wtype -` reads the text from stdin, which keeps multi-line text and anything
+        // that looks like an option intact.
+        use std::io::Write;
+        use std::process::Stdio;
+
+        let mut command = Command::new("wtype");
+        if let Some(delay_ms) = &delay_ms {
+            command.args(["-d", delay_ms]);
+        }
+        let mut child = command
+            .arg("-")
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|err| err.to_string())?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(text.as_bytes())
+                .map_err(|err| err.to_string())?;
+        }
+        let status = child.wait().map_err(|err| err.to_string())?;
+        if status.success() {
+            return Ok(());
+        }
+        return Err("wtype failed to type on Wayland".to_string());
+    }
+
+    let mut command = Command::new("xdotool");
+    command.args(["type", "--clearmodifiers"]);
+    if let Some(delay_ms) = &delay_ms {
+        command.args(["--delay", delay_ms]);
+    }
+    let status = command
+        .args(["--", text])
+        .status()
+        .map_err(|err| err.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err("xdotool failed to type on X11".to_string())
+    }
+}
+
+/// Whether the paste needed a second try.
+fn paste_with_retry<F>(mut paste_fn: F) -> Result<bool, String>
+where
+    F: FnMut() -> Result<(), String>,
+{
+    let Err(first_err) = paste_fn() else {
+        return Ok(false);
+    };
+    std::thread::sleep(Duration::from_millis(220));
+    match paste_fn() {
+        Ok(()) => Ok(true),
+        Err(second_err) => Err(format!("paste failed: {} | {}", first_err, second_err)),
+    }
+}
@@ -0,0 +1,235 @@
+#[cfg(target_os = "linux")]
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use tauri::{Emitter, LogicalSize, Manager, Monitor, PhysicalPosition, Position, WebviewWindow};
+use vtype_core::settings::{self, OverlaySettings};
+
+/// Gap between the overlay and the bottom of the work area, in logical pixels.
+const BOTTOM_MARGIN: f64 = 24.0;
+/// The pill's window size, as configured in `tauri.conf.json`.
+const PILL_SIZE: (f64, f64) = (200.0, 80.0);
+/// Room for the transcript editor while a result is held for review.
+const REVIEW_SIZE: (f64, f64) = (420.0, 180.0);
+/// Room for the list of recent transcripts.
+const PICKER_SIZE: (f64, f64) = (420.0, 320.0);
+
+/// Bumped by every scheduled or cancelled hide, so only the latest timer hides the overlay.
+static HIDE_TIMER: AtomicU64 = AtomicU64::new(0);
+
+/// Set once the overlay is a layer-shell surface, which the compositor keeps in place.
+#[cfg(target_os = "linux")]
+static LAYER_SHELL: AtomicBool = AtomicBool::new(false);
+
+/// Makes the overlay a layer-shell surface on Wayland compositors that offer the protocol,
+/// such as KDE Plasma, Sway and Hyprland, so it sits at the bottom centre above panels
+/// without being moved by hand. Elsewhere, GNOME included, it stays a normal window.
+#[cfg(target_os = "linux")]
+pub fn init_layer_shell(app_handle: &tauri::AppHandle) {
+    use gtk::prelude::*;
+    use gtk_layer_shell::{Edge, KeyboardMode, LayerShell};
+
+    if !gtk_layer_shell::is_supported() {
+        return;
+    }
+    let Some(Ok(window)) = app_handle
+        .get_webview_window("main")
+        .map(|window| window.gtk_window())
+    else {
+        return;
+    };
+    // GTK only turns a window into a layer surface before it is realized.
+    let visible = window.is_visible();
+    window.hide();
+    window.unrealize();
+    window.init_layer_shell();
+    window.set_namespace("vtype");
+    window.set_anchor(Edge::Bottom, true);
+    window.set_layer_shell_margin(Edge::Bottom, BOTTOM_MARGIN as i32);
+    window.set_keyboard_mode(KeyboardMode::None);
+    LAYER_SHELL.store(true, Ordering::Relaxed);
+    set_layer(&window, settings::current().overlay.always_on_top);
+    if visible {
+        window.show();
+    }
+}
+
+/// The overlay layer stays above panels and fullscreen windows; the top layer only above
+/// normal windows.
+#[cfg(target_os = "linux")]
+fn set_layer(window: &gtk::ApplicationWindow, always_on_top: bool) {
+    use gtk_layer_shell::{Layer, LayerShell};
+
+    let layer = if always_on_top {
+        Layer::Overlay
+    } else {
+        Layer::Top
+    };
+    window.set_layer(layer);
+}
+
+/// Layer surfaces only take the keyboard when asked to, as the review editor does.
+#[cfg(target_os = "linux")]
+fn set_keyboard_focus(window: &WebviewWindow, focusable: bool) {
+    use gtk_layer_shell::{KeyboardMode, LayerShell};
+
+    if let (true, Ok(window)) = (layer_shell(), window.gtk_window()) {
+        let mode = if focusable {
+            KeyboardMode::OnDemand
+        } else {
+            KeyboardMode::None
+        };
+        window.set_keyboard_mode(mode);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_keyboard_focus(_window: &WebviewWindow, _focusable: bool) {}
+
+#[cfg(target_os = "linux")]
+fn layer_shell() -> bool {
+    LAYER_SHELL.load(Ordering::Relaxed)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn layer_shell() -> bool {
+    false
+}
+
+/// Shows the overlay at the bottom centre of the monitor the cursor is on.
+pub fn show(app_handle: &tauri::AppHandle) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        #[cfg(target_os = "windows")]
+        {
+            let _ = window.set_shadow(false);
+        }
+        apply_flags(&window, &settings::current().overlay);
+        if let Some(position) = placement(app_handle, &window) {
+            let _ = window.set_position(Position::Physical(position));
+        }
+        let _ = window.show();
+        let _ = window.set_focusable(false);
+    }
+}
+
+/// Hides the overlay after `delay_ms`, replacing any earlier timer, unless it is cancelled
+/// first or a recording or review is under way by then. `None` only cancels.
+pub fn hide_after(app_handle: &tauri::AppHandle, delay_ms: Option<u64>) {
+    let timer = HIDE_TIMER.fetch_add(1, Ordering::SeqCst) + 1;
+    let Some(delay_ms) = delay_ms else {
+        return;
+    };
+    let app_handle = app_handle.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(delay_ms));
+        if HIDE_TIMER.load(Ordering::SeqCst) != timer
+            || crate::dictation::active()
+            || crate::review::current().is_some()
+        {
+            return;
+        }
+        let handle = app_handle.clone();
+        let _ = app_handle.run_on_main_thread(move || {
+            if let Some(window) = handle.get_webview_window("main") {
+                let _ = window.hide();
+            }
+            let _ = handle.emit("overlay-auto-hidden", ());
+        });
+    });
+}
+
+/// Keeps the overlay up, for instance while the pointer is over it.
+pub fn cancel_hide() {
+    HIDE_TIMER.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Sets the window flags from the overlay settings. Called on every show because some
+/// window managers drop them while the window is hidden.
+pub fn apply_flags(window: &WebviewWindow, overlay: &OverlaySettings) {
+    let _ = window.set_always_on_top(overlay.always_on_top);
+    #[cfg(target_os = "linux")]
+    if let (true, Ok(window)) = (layer_shell(), window.gtk_window()) {
+        set_layer(&window, overlay.always_on_top);
+    }
+    let _ = window.set_ignore_cursor_events(overlay.click_through);
+}
+
+/// Enlarges the overlay around the review editor and lets it take keyboard focus.
+pub fn show_review(app_handle: &tauri::AppHandle) {
+    show_focusable(app_handle, REVIEW_SIZE);
+}
+
+/// Like [`show_review`], for the quick picker's list.
+pub fn show_picker(app_handle: &tauri::AppHandle) {
+    show_focusable(app_handle, PICKER_SIZE);
+}
+
+fn show_focusable(app_handle: &tauri::AppHandle, size: (f64, f64)) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.set_size(LogicalSize::new(size.0, size.1));
+        show(app_handle);
+        let _ = window.set_ignore_cursor_events(false);
+        let _ = window.set_focusable(true);
+        set_keyboard_focus(&window, true);
+        let _ = window.set_focus();
+    }
+}
+
+/// Shrinks the overlay back to the pill once a review is resolved or the quick picker closed.
+pub fn end_review(app_handle: &tauri::AppHandle) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        let _ = window.set_focusable(false);
+        set_keyboard_focus(&window, false);
+        let _ = window.set_size(LogicalSize::new(PILL_SIZE.0, PILL_SIZE.1));
+        apply_flags(&window, &settings::current().overlay);
+        if let Some(position) = placement(app_handle, &window) {
+            let _ = window.set_position(Position::Physical(position));
+        }
+    }
+}
+
+pub fn apply_settings(app_handle: &tauri::AppHandle, overlay: &OverlaySettings) {
+    if let Some(window) = app_handle.get_webview_window("main") {
+        apply_flags(&window, overlay);
+    }
+}
+
+/// Works in logical pixels relative to the target monitor's work area (which excludes the
+/// taskbar or dock), then converts with that monitor's scale factor. The window's own scale
+/// may differ when it last sat on another monitor, so its size is normalized first. `None`
+/// for a layer-shell surface, which the compositor places.
+fn placement(
+    app_handle: &tauri::AppHandle,
+    window: &WebviewWindow,
+) -> Option<PhysicalPosition<i32>> {
+    if layer_shell() {
+        return None;
+    }
+    let monitor = target_monitor(app_handle, window)?;
+    let scale = monitor.scale_factor();
+    let size = window
+        .outer_size()
+        .ok()?
+        .to_logical::<f64>(window.scale_factor().ok()?);
+    let area = monitor.work_area();
+    let area_position = area.position.to_logical::<f64>(scale);
+    let area_size = area.size.to_logical::<f64>(scale);
+
+    let x = area_position.x + ((area_size.width - size.width) / 2.0).max(0.0);
+    let y = area_position.y + (area_size.height - size.height - BOTTOM_MARGIN).max(0.0);
+    Some(PhysicalPosition::new(
+        (x * scale).round() as i32,
+        (y * scale).round() as i32,
+    ))
+}
+
+fn target_monitor(app_handle: &tauri::AppHandle, window: &WebviewWindow) -> Option<Monitor> {
+    app_handle
+        .cursor_position()
+        .ok()
+        .and_then(|cursor| app_handle.monitor_from_point(cursor.x, cursor.y).ok())
+        .flatten()
+        .or_else(|| window.current_monitor().ok().flatten())
+        .or_else(|| window.primary_monitor().ok().flatten())
+}
@@ -0,0 +1,137 @@
+//! A check that transcripts can reach other apps on this system. A small window of VType's
+//! own holds a text field; a sample is pasted and then typed into it the way a transcript
+//! would be, and the field is read back to see whether it arrived. Where a tool such as
+//! wtype or xdotool is missing, or the compositor drops simulated keys, this says so.
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{WebviewUrl, WebviewWindow, WebviewWindowBuilder};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use vtype_core::settings::OutputMode;
+
+use crate::output;
+
+const WINDOW: &str = "paste-test";
+/// Plain ASCII, which every method can type.
+const SAMPLE: &str = "VType paste test 123";
+const LOAD_TIMEOUT: Duration = Duration::from_secs(5);
+/// For the window to take focus before keys are sent to it.
+const FOCUS_DELAY: Duration = Duration::from_millis(300);
+/// For the field to take in a paste, which arrives after the chord is sent.
+const SETTLE_DELAY: Duration = Duration::from_millis(500);
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Where the test window sends what its field holds, while a test runs.
+static REPLIES: Mutex<Option<Sender<String>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasteTestReport {
+    /// Pasting first, then typing.
+    pub results: Vec<MethodResult>,
+    /// The output mode to use on this system; `None` when neither worked.
+    pub recommended: Option<OutputMode>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MethodResult {
+    pub mode: OutputMode,
+    /// What sent the keys, such as `wtype`.
+    pub method: &'static str,
+    /// Whether the field held exactly the sample afterwards.
+    pub arrived: bool,
+    /// What the field held.
+    pub received: String,
+    pub error: Option<String>,
+}
+
+/// Runs the test, returning once the window is closed again. The clipboard is put back as
+/// it was.
+pub fn run(app: &tauri::AppHandle) -> Result<PasteTestReport, String> {
+    let (sender, replies) = mpsc::channel();
+    {
+        let mut current = REPLIES.lock().map_err(|err| err.to_string())?;
+        if current.is_some() {
+            return Err("A paste test is already running".to_string());
+        }
+        *current = Some(sender);
+    }
+    let results = test_window(app, &replies);
+    if let Ok(mut current) = REPLIES.lock() {
+        *current = None;
+    }
+    let results = results?;
+    let recommended = results
+        .iter()
+        .find(|result| result.arrived)
+        .map(|result| result.mode);
+    Ok(PasteTestReport {
+        results,
+        recommended,
+    })
+}
+
+/// Called by the test window with what its field holds.
+pub fn received(text: String) {
+    if let Some(sender) = REPLIES.lock().ok().and_then(|sender| sender.clone()) {
+        let _ = sender.send(text);
+    }
+}
+
+fn test_window(
+    app: &tauri::AppHandle,
+    replies: &Receiver<String>,
+) -> Result<Vec<MethodResult>, String> {
+    let window = WebviewWindowBuilder::new(app, WINDOW, WebviewUrl::App("paste-test.html".into()))
+        .title("VType paste test")
+        .inner_size(360.0, 160.0)
+        .resizable(false)
+        .always_on_top(true)
+        .focused(true)
+        .build()
+        .map_err(|err| err.to_string())?;
+    // The page reports once when it has loaded.
+    let loaded = replies.recv_timeout(LOAD_TIMEOUT).is_ok();
+    let results = if loaded {
+        let saved = app.clipboard().read_text().ok();
+        let results = [OutputMode::Paste, OutputMode::Type]
+            .into_iter()
+            .map(|mode| attempt(app, &window, replies, mode))
+            .collect();
+        if let Some(saved) = saved {
+            let _ = app.clipboard().write_text(saved);
+        }
+        Ok(results)
+    } else {
+        Err("The paste test window did not load".to_string())
+    };
+    let _ = window.destroy();
+    results
+}
+
+fn attempt(
+    app: &tauri::AppHandle,
+    window: &WebviewWindow,
+    replies: &Receiver<String>,
+    mode: OutputMode,
+) -> MethodResult {
+    let _ = window.eval("reset()");
+    let _ = window.set_focus();
+    std::thread::sleep(FOCUS_DELAY);
+    while replies.try_recv().is_ok() {}
+    let injected = output::inject_as(app, mode, SAMPLE);
+    std::thread::sleep(SETTLE_DELAY);
+    let _ = window.eval("report()");
+    let received = replies.recv_timeout(REPLY_TIMEOUT).unwrap_or_default();
+    MethodResult {
+        mode,
+        method: output::injection_method(mode),
+        arrived: injected.is_ok() && received.trim() == SAMPLE,
+        received,
+        error: injected.err(),
+    }
+}
@@ -0,0 +1,351 @@
+//! XDG desktop portals, which stand in for xdotool, wtype and key grabs when VType runs
+//! sandboxed as a Flatpak or Snap. Keys are sent through the RemoteDesktop portal and the
+//! hotkeys come from the GlobalShortcuts portal, which Wayland sessions use too; the desktop
+//! asks the user once for each. There is no portal for audio input: the microphone is
+//! reached over the PulseAudio socket the sandbox grants.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex, OnceLock};
+
+use ashpd::desktop::global_shortcuts::{GlobalShortcuts, NewShortcut};
+use ashpd::desktop::remote_desktop::{DeviceType, KeyState, RemoteDesktop, SelectDevicesOptions};
+use ashpd::desktop::{PersistMode, Session};
+use ashpd::enumflags2::BitFlags;
+use futures_util::future::{self, Either};
+use futures_util::StreamExt;
+use tauri::async_runtime::{Receiver, Sender};
+use tauri::Emitter;
+use vtype_core::inject::{Key, Modifier};
+use vtype_core::{log, safe_io, settings};
+
+use crate::HotkeyAction;
+
+/// X keysyms of the keys VType presses itself.
+pub const CONTROL: i32 = 0xffe3;
+pub const SHIFT: i32 = 0xffe1;
+const BACKSPACE: i32 = 0xff08;
+pub const V: i32 = 0x76;
+const RETURN: i32 = 0xff0d;
+const TAB: i32 = 0xff09;
+
+/// Where the RemoteDesktop portal's restore token is kept, beside the settings file, so a
+/// restart does not ask for keyboard access again.
+const RESTORE_TOKEN_FILE: &str = "remote-desktop-token";
+
+type KeyEvent = (i32, KeyState);
+type Bindings = Vec<(HotkeyAction, String)>;
+type Job = (Vec<KeyEvent>, mpsc::Sender<Result<(), String>>);
+
+static KEYBOARD: OnceLock<Mutex<mpsc::Sender<Job>>> = OnceLock::new();
+static SHORTCUTS: OnceLock<Sender<Bindings>> = OnceLock::new();
+static SHORTCUTS_UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+
+pub fn sandboxed() -> bool {
+    ashpd::is_sandboxed()
+}
+
+/// Holds `modifiers` while clicking `key`.
+pub fn chord(modifiers: &[i32], key: i32) -> Result<(), String> {
+    let events = modifiers
+        .iter()
+        .map(|&modifier| (modifier, KeyState::Pressed))
+        .chain([(key, KeyState::Pressed), (key, KeyState::Released)])
+        .chain(
+            modifiers
+                .iter()
+                .rev()
+                .map(|&modifier| (modifier, KeyState::Released)),
+        )
+        .collect();
+    send(events)
+}
+
+/// Types `text` key by key. The compositor looks each keysym up in the active keymap, so
+/// characters without a key of their own may be dropped; those are pasted instead.
+pub fn type_text(text: &str) -> Result<(), String> {
+    let events = text
+        .chars()
+        .map(keysym)
+        .flat_map(|keysym| [(keysym, KeyState::Pressed), (keysym, KeyState::Released)])
+        .collect();
+    send(events)
+}
+
+pub fn key_keysym(key: Key) -> i32 {
+    match key {
+        Key::Char(ch) => keysym(ch),
+        Key::Return => RETURN,
+        Key::Tab => TAB,
+        Key::Escape => 0xff1b,
+        Key::Backspace => BACKSPACE,
+        Key::Delete => 0xffff,
+        Key::Space => 0x20,
+        Key::Home => 0xff50,
+        Key::Left => 0xff51,
+        Key::Up => 0xff52,
+        Key::Right => 0xff53,
+        Key::Down => 0xff54,
+        Key::PageUp => 0xff55,
+        Key::PageDown => 0xff56,
+        Key::End => 0xff57,
+        // F1 is 0xffbe.
+        Key::Function(number) => 0xffbd + i32::from(number),
+    }
+}
+
+pub fn modifier_keysym(modifier: Modifier) -> i32 {
+    match modifier {
+        Modifier::Control => CONTROL,
+        Modifier::Shift => SHIFT,
+        Modifier::Alt => 0xffe9,
+        Modifier::Super => 0xffeb,
+    }
+}
+
+/// Latin-1 characters are their own keysyms; the rest of Unicode is offset by 0x1000000.
+fn keysym(ch: char) -> i32 {
+    match ch {
+        '\n' => RETURN,
+        '\t' => TAB,
+        ' '..='~' | '\u{a0}'..='\u{ff}' => ch as i32,
+        _ => 0x0100_0000 + ch as i32,
+    }
+}
+
+/// Hands key events to the keyboard thread and waits for them to be sent, which the first
+/// time includes the user granting keyboard access.
+fn send(events: Vec<KeyEvent>) -> Result<(), String> {
+    let keyboard = KEYBOARD.get_or_init(|| {
+        let (jobs, receiver) = mpsc::channel();
+        std::thread::spawn(move || serve_keyboard(receiver));
+        Mutex::new(jobs)
+    });
+    let (reply, result) = mpsc::channel();
+    keyboard
+        .lock()
+        .map_err(|err| err.to_string())?
+        .send((events, reply))
+        .map_err(|err| err.to_string())?;
+    result.recv().map_err(|err| err.to_string())?
+}
+
+/// Runs the RemoteDesktop session on a thread of its own, so callers on the main thread or
+/// inside the async runtime can all wait on it. A failed session is started afresh with the
+/// next job, which covers the user revoking access in the meantime.
+fn serve_keyboard(jobs: mpsc::Receiver<Job>) {
+    let mut keyboard: Option<Keyboard> = None;
+    for (events, reply) in jobs {
+        let result = tauri::async_runtime::block_on(async {
+            let current = match keyboard.take() {
+                Some(current) => current,
+                None => Keyboard::start().await?,
+            };
+            for &(keysym, state) in &events {
+                current
+                    .portal
+                    .notify_keyboard_keysym(&current.session, keysym, state, Default::default())
+                    .await?;
+            }
+            keyboard = Some(current);
+            Ok::<_, ashpd::Error>(())
+        });
+        let _ = reply.send(result.map_err(|err| format!("Remote desktop portal: {}", err)));
+    }
+}
+
+struct Keyboard {
+    portal: RemoteDesktop,
+    session: Session<RemoteDesktop>,
+}
+
+impl Keyboard {
+    async fn start() -> Result<Keyboard, ashpd::Error> {
+        let portal = RemoteDesktop::new().await?;
+        let session = portal.create_session(Default::default()).await?;
+        let token = restore_token_path().and_then(|path| std::fs::read_to_string(path).ok());
+        portal
+            .select_devices(
+                &session,
+                SelectDevicesOptions::default()
+                    .set_devices(BitFlags::from(DeviceType::Keyboard))
+                    .set_persist_mode(PersistMode::ExplicitlyRevoked)
+                    .set_restore_token(token.as_deref().map(str::trim)),
+            )
+            .await?
+            .response()?;
+        let selected = portal
+            .start(&session, None, Default::default())
+            .await?
+            .response()?;
+        if let (Some(token), Some(path)) = (selected.restore_token(), restore_token_path()) {
+            if let Err(err) = safe_io::write(&path, token) {
+                let _ = log::write(format!("Failed to save {}: {}", path.display(), err));
+            }
+        }
+        Ok(Keyboard { portal, session })
+    }
+}
+
+fn restore_token_path() -> Option<PathBuf> {
+    settings::path()?
+        .parent()
+        .map(|dir| dir.join(RESTORE_TOKEN_FILE))
+}
+
+/// Whether the hotkeys go through the GlobalShortcuts portal: when sandboxed, and on Wayland,
+/// where key grabs only see X11 apps, unless the desktop turned out not to offer it.
+pub fn binds_shortcuts() -> bool {
+    !SHORTCUTS_UNAVAILABLE.load(Ordering::Relaxed)
+        && (sandboxed() || std::env::var_os("WAYLAND_DISPLAY").is_some())
+}
+
+/// Binds `bindings` through the GlobalShortcuts portal, replacing whatever was bound before.
+/// The desktop may let the user pick other keys; the accelerators are only suggestions.
+pub fn bind_shortcuts(app: &tauri::AppHandle, bindings: Bindings) {
+    let requests = SHORTCUTS.get_or_init(|| {
+        let (requests, receiver) = tauri::async_runtime::channel(4);
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = serve_shortcuts(&app, receiver).await {
+                shortcuts_unavailable(&app, err);
+            }
+        });
+        requests
+    });
+    if let Err(err) = requests.try_send(bindings) {
+        let _ = log::write(format!("Failed to rebind global shortcuts: {}", err));
+    }
+}
+
+/// Fails when the portal cannot be reached or the first binding is refused.
+async fn serve_shortcuts(
+    app: &tauri::AppHandle,
+    mut requests: Receiver<Bindings>,
+) -> Result<(), ashpd::Error> {
+    let portal = GlobalShortcuts::new().await?;
+    let activated = portal.receive_activated().await?;
+    let mut activated = std::pin::pin!(activated);
+    let mut bound: Option<(Bindings, Session<GlobalShortcuts>)> = None;
+    let mut first = true;
+    loop {
+        let request = std::pin::pin!(requests.recv());
+        match future::select(request, activated.next()).await {
+            Either::Left((Some(bindings), _)) => {
+                if bound
+                    .as_ref()
+                    .is_some_and(|(current, _)| *current == bindings)
+                {
+                    continue;
+                }
+                // A session's shortcuts are bound once, so a change means a new session.
+                if let Some((_, session)) = bound.take() {
+                    let _ = session.close().await;
+                }
+                match bind(&portal, &bindings).await {
+                    Ok(session) => bound = Some((bindings, session)),
+                    Err(err) if first => return Err(err),
+                    Err(err) => {
+                        let _ = log::write(format!("Failed to bind global shortcuts: {}", err));
+                    }
+                }
+                first = false;
+            }
+            Either::Right((Some(event), _)) => {
+                let action = bound.as_ref().and_then(|(bindings, _)| {
+                    bindings
+                        .iter()
+                        .map(|(action, _)| *action)
+                        .find(|action| action.id() == event.shortcut_id())
+                });
+                if let Some(action) = action {
+                    crate::dispatch_hotkey_action(app, action);
+                }
+            }
+            Either::Left((None, _)) | Either::Right((None, _)) => return Ok(()),
+        }
+    }
+}
+
+async fn bind(
+    portal: &GlobalShortcuts,
+    bindings: &Bindings,
+) -> Result<Session<GlobalShortcuts>, ashpd::Error> {
+    let session = portal.create_session(Default::default()).await?;
+    let shortcuts: Vec<NewShortcut> = bindings
+        .iter()
+        .map(|(action, accelerator)| {
+            NewShortcut::new(action.id(), action.description())
+                .preferred_trigger(trigger(accelerator).as_str())
+        })
+        .collect();
+    portal
+        .bind_shortcuts(&session, &shortcuts, None, Default::default())
+        .await?
+        .response()?;
+    Ok(session)
+}
+
+/// Falls back to key grabs, which on Wayland only fire while an X11 app is focused, and
+/// tells the user how to bind a system shortcut to the dictation link instead.
+fn shortcuts_unavailable(app: &tauri::AppHandle, err: ashpd::Error) {
+    SHORTCUTS_UNAVAILABLE.store(true, Ordering::Relaxed);
+    let command = link_command(HotkeyAction::Dictate);
+    let _ = log::write(format!(
+        "Global shortcuts portal unavailable ({}). For a hotkey that works in every app, \
+         bind a system keyboard shortcut to the command: {}",
+        err, command
+    ));
+    let handle = app.clone();
+    let _ = app.run_on_main_thread(move || {
+        if let Err(err) = crate::register_hotkeys(&handle, &settings::current()) {
+            let _ = log::write(format!("Failed to register hotkeys: {}", err));
+        }
+        let _ = handle.emit("global-shortcuts-unavailable", command);
+    });
+}
+
+/// The command that runs `action` in the running VType, for a desktop shortcut.
+fn link_command(action: HotkeyAction) -> String {
+    let program = match std::env::var("FLATPAK_ID") {
+        Ok(id) => format!("flatpak run {}", id),
+        Err(_) => std::env::current_exe()
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|_| "vtype".to_string()),
+    };
+    format!("{} {}", program, action.link())
+}
+
+/// Turns a Tauri accelerator such as `CommandOrControl+Alt+R` into the portal's trigger
+/// format, `CTRL+ALT+r`: modifiers in capitals and the key as an XKB keysym name.
+fn trigger(accelerator: &str) -> String {
+    accelerator
+        .split('+')
+        .map(str::trim)
+        .map(|part| match part.to_ascii_lowercase().as_str() {
+            "commandorcontrol" | "cmdorctrl" | "control" | "ctrl" => "CTRL".to_string(),
+            "alt" | "option" => "ALT".to_string(),
+            "shift" => "SHIFT".to_string(),
+            "super" | "command" | "cmd" | "meta" => "LOGO".to_string(),
+            "space" => "space".to_string(),
+            "enter" | "return" => "Return".to_string(),
+            "esc" | "escape" => "Escape".to_string(),
+            "backspace" => "BackSpace".to_string(),
+            "tab" => "Tab".to_string(),
+            "delete" => "Delete".to_string(),
+            _ => {
+                let key = part
+                    .strip_prefix("Key")
+                    .or_else(|| part.strip_prefix("Digit"))
+                    .filter(|key| key.len() == 1)
+                    .unwrap_or(part);
+                if key.len() == 1 {
+                    key.to_ascii_lowercase()
+                } else {
+                    key.to_string()
+                }
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("+")
+}
@@ -0,0 +1,175 @@
+//! Confirm-before-paste: with review enabled, a finished transcript is held and shown in the
+//! overlay for editing, and only reaches the focused app once confirmed.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::Emitter;
+use vtype_core::{history, pipeline, redact, settings, speech};
+
+use crate::{output, overlay};
+
+static PENDING: Mutex<Option<PendingResult>> = Mutex::new(None);
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A transcript waiting for the user's decision.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingResult {
+    pub id: u64,
+    pub text: String,
+    /// The history entry it was saved as.
+    pub entry_id: Option<i64>,
+    /// When set, the transcript is pasted as is after this long without a decision.
+    pub auto_confirm_ms: Option<u64>,
+    /// Why the transcript is held although review is off, such as sensitive text in it.
+    pub warning: Option<String>,
+}
+
+/// How a held transcript was resolved.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReviewClosed {
+    pub id: u64,
+    pub pasted: bool,
+}
+
+/// Links pasted text back to the dictation it was taken from.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasteSource {
+    pub entry_id: i64,
+}
+
+/// Delivers a new transcript, or holds it for review when review is enabled,
+/// `redaction.warnBeforePaste` finds something in it or the model was less sure of it than
+/// `review.minConfidence`.
+pub fn paste_or_hold(app: &tauri::AppHandle, text: String) -> Result<(), String> {
+    let settings = settings::current();
+    let last = pipeline::last_transcript().filter(|last| last.text == text);
+    let warning = redact::warning(&text, &settings.redaction).or_else(|| {
+        let confidence = last.as_ref()?.confidence?;
+        (confidence < settings.review.min_confidence?)
+            .then(|| format!("Low confidence ({:.0}%)", confidence * 100.0))
+    });
+    if settings.review.enabled || warning.is_some() {
+        hold(app, text, last.and_then(|last| last.entry_id), warning);
+        return Ok(());
+    }
+    output::deliver(app, text)
+}
+
+/// Holds `text` for review, replacing any transcript still waiting, and tells the overlay
+/// to show it. A transcript held with a `warning` waits for the user however long it takes.
+pub fn hold(app: &tauri::AppHandle, text: String, entry_id: Option<i64>, warning: Option<String>) {
+    let review = settings::current().review;
+    let pending = PendingResult {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        text,
+        entry_id,
+        auto_confirm_ms: review
+            .auto_confirm_secs
+            .filter(|_| warning.is_none())
+            .map(|secs| u64::from(secs) * 1000),
+        warning,
+    };
+    if let Ok(mut slot) = PENDING.lock() {
+        *slot = Some(pending.clone());
+    }
+    overlay::show_review(app);
+    let _ = app.emit("review-pending", &pending);
+    if review.read_aloud {
+        let _ = speech::speak(&pending.text);
+    }
+    if let Some(delay) = pending.auto_confirm_ms {
+        let app = app.clone();
+        let id = pending.id;
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(delay));
+            // Taken under the lock, so a newer transcript or a pause for editing is not
+            // pasted out from under the user.
+            let expired = PENDING.lock().ok().and_then(|mut slot| {
+                slot.take_if(|pending| pending.id == id && pending.auto_confirm_ms.is_some())
+            });
+            if let Some(pending) = expired {
+                if let Err(err) = paste(&app, pending, None) {
+                    let _ = vtype_core::log::write(format!("Auto-confirm failed: {}", err));
+                }
+            }
+        });
+    }
+}
+
+pub fn current() -> Option<PendingResult> {
+    PENDING.lock().ok().and_then(|pending| pending.clone())
+}
+
+/// Stops the held transcript from being pasted automatically, once the user starts
+/// editing it.
+pub fn hold_open() {
+    if let Ok(mut pending) = PENDING.lock() {
+        if let Some(pending) = pending.as_mut() {
+            pending.auto_confirm_ms = None;
+        }
+    }
+}
+
+/// Pastes the held transcript, or `edited` in its place when the user changed it.
+pub fn confirm(app: &tauri::AppHandle, edited: Option<String>) -> Result<(), String> {
+    let pending = take().ok_or_else(|| "No transcript is waiting for review".to_string())?;
+    paste(app, pending, edited)
+}
+
+fn paste(
+    app: &tauri::AppHandle,
+    pending: PendingResult,
+    edited: Option<String>,
+) -> Result<(), String> {
+    close(app, pending.id, true);
+    let text = edited.unwrap_or(pending.text);
+    if text.trim().is_empty() {
+        return Ok(());
+    }
+    if let Some(entry_id) = pending.entry_id {
+        note_paste(entry_id, &text);
+    }
+    output::deliver(app, text)
+}
+
+/// Drops the held transcript without pasting it.
+pub fn discard(app: &tauri::AppHandle) -> Result<(), String> {
+    let pending = take().ok_or_else(|| "No transcript is waiting for review".to_string())?;
+    close(app, pending.id, false);
+    Ok(())
+}
+
+/// Closes the review of `entry_id`, if it is the one held, because an excerpt of it is
+/// being pasted instead.
+pub fn settle(app: &tauri::AppHandle, entry_id: i64) {
+    let settled = PENDING
+        .lock()
+        .ok()
+        .and_then(|mut slot| slot.take_if(|pending| pending.entry_id == Some(entry_id)));
+    if let Some(pending) = settled {
+        close(app, pending.id, true);
+    }
+}
+
+/// Records in history what was pasted, so an excerpt can be told from the full entry.
+pub fn note_paste(entry_id: i64, text: &str) {
+    if let Err(err) = history::record_paste(entry_id, text) {
+        let _ = vtype_core::log::write(format!("Failed to note pasted text: {}", err));
+    }
+}
+
+fn take() -> Option<PendingResult> {
+    PENDING.lock().ok().and_then(|mut pending| pending.take())
+}
+
+fn close(app: &tauri::AppHandle, id: u64, pasted: bool) {
+    speech::stop();
+    overlay::end_review(app);
+    let _ = app.emit("review-closed", ReviewClosed { id, pasted });
+}
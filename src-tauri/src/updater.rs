@@ -0,0 +1,111 @@
+//! Updates from VType's GitHub releases through the Tauri updater, which reads the channel's
+//! `latest.json` and installs only artifacts signed with the release key. A build made
+//! without `VTYPE_UPDATER_PUBKEY` set has no key to check them with and can't update itself.
+
+use serde::Serialize;
+use tauri::{Emitter, Url};
+use tauri_plugin_updater::{Update, UpdaterExt};
+use vtype_core::log;
+use vtype_core::settings::{self, UpdateChannel};
+
+const STABLE_ENDPOINT: &str =
+    "https://github.com/theminji/VType/releases/latest/download/latest.json";
+/// A pre-release kept on the `beta` tag, moved along with each beta and stable release.
+const BETA_ENDPOINT: &str = "https://github.com/theminji/VType/releases/download/beta/latest.json";
+/// Set at compile time; the `plugins.updater.pubkey` in `tauri.conf.json` stays empty and is
+/// replaced with this on every check.
+const PUBKEY: Option<&str> = option_env!("VTYPE_UPDATER_PUBKEY");
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    pub notes: Option<String>,
+    pub date: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadProgress {
+    pub downloaded: u64,
+    /// `None` when the server doesn't say how large the update is.
+    pub total: Option<u64>,
+}
+
+/// The newest release on the configured channel, or `None` when this is it.
+pub async fn check(app: &tauri::AppHandle) -> Result<Option<UpdateInfo>, String> {
+    Ok(find(app).await?.map(|update| info(&update)))
+}
+
+/// Downloads and installs the newest release, then restarts into it. Progress is reported
+/// as `update-progress` events.
+pub async fn install(app: &tauri::AppHandle) -> Result<(), String> {
+    let update = find(app)
+        .await?
+        .ok_or_else(|| "VType is up to date".to_string())?;
+    let _ = log::write(format!(
+        "Installing update {} over {}",
+        update.version, update.current_version
+    ));
+    let mut downloaded = 0u64;
+    update
+        .download_and_install(
+            |chunk, total| {
+                downloaded += chunk as u64;
+                let _ = app.emit("update-progress", DownloadProgress { downloaded, total });
+            },
+            || {},
+        )
+        .await
+        .map_err(|err| format!("Failed to install the update: {}", err))?;
+    app.restart()
+}
+
+/// Checks once at startup when `updates.autoCheck` is set and offers what it finds as an
+/// `update-available` event.
+pub fn start(app: &tauri::AppHandle) {
+    if !settings::current().updates.auto_check || PUBKEY.is_none() {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        match check(&app).await {
+            Ok(Some(update)) => {
+                let _ = log::write(format!("Update {} available", update.version));
+                let _ = app.emit("update-available", update);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                let _ = log::write(format!("Update check failed: {}", err));
+            }
+        }
+    });
+}
+
+async fn find(app: &tauri::AppHandle) -> Result<Option<Update>, String> {
+    let pubkey =
+        PUBKEY.ok_or_else(|| "This build of VType was made without updates".to_string())?;
+    let endpoint = match settings::current().updates.channel {
+        UpdateChannel::Stable => STABLE_ENDPOINT,
+        UpdateChannel::Beta => BETA_ENDPOINT,
+    };
+    let endpoint = Url::parse(endpoint).map_err(|err| err.to_string())?;
+    app.updater_builder()
+        .pubkey(pubkey)
+        .endpoints(vec![endpoint])
+        .and_then(|builder| builder.build())
+        .map_err(|err| err.to_string())?
+        .check()
+        .await
+        .map_err(|err| format!("Failed to check for updates: {}", err))
+}
+
+fn info(update: &Update) -> UpdateInfo {
+    UpdateInfo {
+        version: update.version.clone(),
+        current_version: update.current_version.clone(),
+        notes: update.body.clone(),
+        date: update.date.map(|date| date.to_string()),
+    }
+}
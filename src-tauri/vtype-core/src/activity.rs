@@ -0,0 +1,69 @@
+//! The last pipeline events with their timings, for an activity feed in the UI and for
+//! support to see what happened without reading the log. Transcripts are never kept here,
+//! only their length.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+const CAPACITY: usize = 100;
+
+static EVENTS: Mutex<VecDeque<ActivityEvent>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ActivityKind {
+    RecordingStarted,
+    RecordingFailed,
+    /// Its duration is the length of the recording.
+    RecordingStopped,
+    WorkerStarted,
+    WorkerFailed,
+    Transcribed,
+    TranscriptionFailed,
+    Delivered,
+    DeliveryFailed,
+    /// Its detail is the macro's first phrase.
+    MacroRan,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityEvent {
+    /// Unix time in milliseconds.
+    pub at: i64,
+    pub kind: ActivityKind,
+    pub duration_ms: Option<u64>,
+    /// The error, or a short note such as the backend used.
+    pub detail: Option<String>,
+}
+
+pub fn record(kind: ActivityKind, duration: Option<Duration>, detail: Option<String>) {
+    let at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as i64)
+        .unwrap_or(0);
+    let event = ActivityEvent {
+        at,
+        kind,
+        duration_ms: duration.map(|duration| duration.as_millis() as u64),
+        detail,
+    };
+    if let Ok(mut events) = EVENTS.lock() {
+        if events.len() == CAPACITY {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+}
+
+/// Newest last.
+pub fn recent(limit: Option<usize>) -> Vec<ActivityEvent> {
+    let Ok(events) = EVENTS.lock() else {
+        return Vec::new();
+    };
+    let skip = limit.map_or(0, |limit| events.len().saturating_sub(limit));
+    events.iter().skip(skip).cloned().collect()
+}
@@ -0,0 +1,250 @@
+//! WAV and sample helpers shared by capture, the pipeline and tools that synthesize audio.
+
+use serde::Serialize;
+
+/// Wraps mono 16-bit little-endian PCM in a minimal WAV container.
+pub fn wav_from_pcm16(pcm: &[u8], sample_rate: u32) -> Vec<u8> {
+    let mut wav = Vec::with_capacity(44 + pcm.len());
+    push_wav_header(&mut wav, pcm.len() as u32, sample_rate);
+    wav.extend_from_slice(pcm);
+    wav
+}
+
+fn push_wav_header(wav: &mut Vec<u8>, data_len: u32, sample_rate: u32) {
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+}
+
+/// Length of the audio in a PCM WAV, read from its `fmt ` and `data` chunks. `None` for
+/// anything that is not a RIFF/WAVE file.
+pub fn wav_duration_ms(wav: &[u8]) -> Option<u64> {
+    if wav.len() < 12 || &wav[0..4] != b"RIFF" || &wav[8..12] != b"WAVE" {
+        return None;
+    }
+    let mut byte_rate = None;
+    let mut offset = 12;
+    while offset + 8 <= wav.len() {
+        let id = &wav[offset..offset + 4];
+        let len = u32::from_le_bytes(wav[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let body = offset + 8;
+        match id {
+            b"fmt " if len >= 12 && body + 12 <= wav.len() => {
+                byte_rate = Some(u32::from_le_bytes(
+                    wav[body + 8..body + 12].try_into().ok()?,
+                ));
+            }
+            b"data" => {
+                // Streamed recordings may leave the length unset; use what is present.
+                let data_len = len.min(wav.len() - body) as u64;
+                return match byte_rate {
+                    Some(rate) if rate > 0 => Some(data_len * 1000 / u64::from(rate)),
+                    _ => None,
+                };
+            }
+            _ => {}
+        }
+        // Chunks are padded to an even length.
+        offset = body.checked_add(len + (len & 1))?;
+    }
+    None
+}
+
+/// Encodes mono float samples in `[-1, 1]` as a 16-bit WAV, clipping anything louder.
+pub fn wav_from_f32(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let mut wav = Vec::new();
+    write_wav_f32(samples, sample_rate, &mut wav);
+    wav
+}
+
+/// [`wav_from_f32`] into `wav`, replacing what it held and reusing its capacity.
+pub fn write_wav_f32(samples: &[f32], sample_rate: u32, wav: &mut Vec<u8>) {
+    wav.clear();
+    wav.reserve(44 + samples.len() * 2);
+    push_wav_header(wav, (samples.len() * 2) as u32, sample_rate);
+    wav.extend(
+        samples
+            .iter()
+            .flat_map(|sample| ((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes()),
+    );
+}
+
+/// Converts mono samples between rates by linear interpolation. When downsampling, a box
+/// filter as wide as the ratio runs first so content above the new Nyquist rate is
+/// attenuated rather than folded back into the speech band.
+pub fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || samples.is_empty() || from == 0 || to == 0 {
+        return samples.to_vec();
+    }
+    let width = (from as usize).div_ceil(to as usize);
+    let filtered = if width > 1 {
+        let mut sum = 0.0;
+        let mut out = Vec::with_capacity(samples.len());
+        for (i, sample) in samples.iter().enumerate() {
+            sum += sample;
+            if i >= width {
+                sum -= samples[i - width];
+            }
+            out.push(sum / (i + 1).min(width) as f32);
+        }
+        out
+    } else {
+        samples.to_vec()
+    };
+
+    let step = f64::from(from) / f64::from(to);
+    let len = (samples.len() as f64 / step) as usize;
+    (0..len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let index = pos as usize;
+            let frac = (pos - index as f64) as f32;
+            let a = filtered[index];
+            let b = filtered.get(index + 1).copied().unwrap_or(a);
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+/// Channels quieter than the loudest by more than this factor (20 dB) are treated as
+/// unconnected inputs and left out of an automatic downmix.
+const SILENT_CHANNEL_RATIO: f32 = 0.1;
+
+/// Mixes interleaved frames down to mono. `selection` lists the channels to keep, numbered
+/// from 1, and is averaged as given. Without a selection, channels that carry signal are
+/// averaged, so a mic on one input of a multi-channel interface is not diluted by the
+/// silent inputs beside it.
+pub fn downmix(interleaved: &[f32], channels: u16, selection: &[u16]) -> Vec<f32> {
+    let channels = usize::from(channels.max(1));
+    if channels == 1 {
+        return interleaved.to_vec();
+    }
+    let mut picked: Vec<usize> = selection
+        .iter()
+        .filter_map(|&channel| usize::from(channel).checked_sub(1))
+        .filter(|&index| index < channels)
+        .collect();
+    if picked.is_empty() {
+        let mut energy = vec![0.0f32; channels];
+        for frame in interleaved.chunks_exact(channels) {
+            for (sum, sample) in energy.iter_mut().zip(frame) {
+                *sum += sample * sample;
+            }
+        }
+        let loudest = energy.iter().copied().fold(0.0f32, f32::max);
+        // Compared as energies, hence the squared ratio.
+        let threshold = loudest * SILENT_CHANNEL_RATIO * SILENT_CHANNEL_RATIO;
+        picked = (0..channels)
+            .filter(|&index| loudest == 0.0 || energy[index] >= threshold)
+            .collect();
+    }
+    interleaved
+        .chunks_exact(channels)
+        .map(|frame| picked.iter().map(|&index| frame[index]).sum::<f32>() / picked.len() as f32)
+        .collect()
+}
+
+/// Mono samples and rate of a 16-bit PCM or 32-bit float WAV, with channels averaged.
+/// `None` for other encodings or malformed files.
+pub fn decode_wav(wav: &[u8]) -> Option<(Vec<f32>, u32)> {
+    if wav.len() < 12 || &wav[0..4] != b"RIFF" || &wav[8..12] != b"WAVE" {
+        return None;
+    }
+    let mut format = None;
+    let mut offset = 12;
+    while offset + 8 <= wav.len() {
+        let id = &wav[offset..offset + 4];
+        let len = u32::from_le_bytes(wav[offset + 4..offset + 8].try_into().ok()?) as usize;
+        let body = offset + 8;
+        match id {
+            b"fmt " if len >= 16 && body + 16 <= wav.len() => {
+                let le16 = |at: usize| u16::from_le_bytes([wav[body + at], wav[body + at + 1]]);
+                let rate = u32::from_le_bytes(wav[body + 4..body + 8].try_into().ok()?);
+                format = Some((le16(0), le16(2).max(1), rate, le16(14)));
+            }
+            b"data" => {
+                let (tag, channels, rate, bits) = format?;
+                let data = &wav[body..body + len.min(wav.len() - body)];
+                let samples: Vec<f32> = match (tag, bits) {
+                    (1, 16) => data
+                        .chunks_exact(2)
+                        .map(|b| f32::from(i16::from_le_bytes([b[0], b[1]])) / 32768.0)
+                        .collect(),
+                    (3, 32) => data
+                        .chunks_exact(4)
+                        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                        .collect(),
+                    _ => return None,
+                };
+                return Some((downmix(&samples, channels, &[]), rate));
+            }
+            _ => {}
+        }
+        offset = body.checked_add(len + (len & 1))?;
+    }
+    None
+}
+
+/// Reported for digital silence, where the level in dBFS is unbounded.
+const SILENCE_DBFS: f32 = -100.0;
+const LEVEL_FRAME_MS: usize = 20;
+
+/// Loudness of a recording, for checking a microphone setup.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Levels {
+    pub peak_dbfs: f32,
+    pub rms_dbfs: f32,
+    /// Level of the quietest tenth of the recording, where nobody speaks.
+    pub noise_floor_dbfs: f32,
+    /// Loudest tenth against the noise floor.
+    pub snr_db: f32,
+    /// Share of samples at or near full scale.
+    pub clipping: f32,
+}
+
+/// Measures mono samples in 20 ms frames.
+pub fn levels(samples: &[f32], sample_rate: u32) -> Levels {
+    let frame = (sample_rate as usize * LEVEL_FRAME_MS / 1000).max(1);
+    let mut frames: Vec<f32> = samples.chunks(frame).map(rms).collect();
+    frames.sort_by(f32::total_cmp);
+    let percentile = |p: usize| {
+        frames
+            .get((frames.len().saturating_sub(1)) * p / 100)
+            .copied()
+            .unwrap_or(0.0)
+    };
+    let peak = samples.iter().fold(0.0f32, |max, x| max.max(x.abs()));
+    let clipped = samples.iter().filter(|x| x.abs() >= 0.999).count();
+    let noise_floor = dbfs(percentile(10));
+    Levels {
+        peak_dbfs: dbfs(peak),
+        rms_dbfs: dbfs(rms(samples)),
+        noise_floor_dbfs: noise_floor,
+        snr_db: dbfs(percentile(90)) - noise_floor,
+        clipping: clipped as f32 / samples.len().max(1) as f32,
+    }
+}
+
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+fn dbfs(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        return SILENCE_DBFS;
+    }
+    (20.0 * amplitude.log10()).max(SILENCE_DBFS)
+}
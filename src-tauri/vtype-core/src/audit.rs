@@ -0,0 +1,90 @@
+//! Opt-in audit log of injections, for organisations that must account for what software
+//! types on their machines: when text was typed or pasted, into which app, how and how many
+//! characters. It is kept in `audit.jsonl` beside the settings, one record per line, apart
+//! from the debug log and only ever appended to. The text itself is left out unless
+//! `audit.includeText` is set.
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::frontmost::FrontmostApp;
+use crate::settings::{self, OutputMode};
+
+const AUDIT_FILE: &str = "audit.jsonl";
+
+/// Keeps records from two injections from interleaving.
+static APPENDING: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    /// Unix time in milliseconds.
+    pub at: i64,
+    /// The focused app, when it could be told.
+    pub app: Option<String>,
+    pub chars: usize,
+    /// Whether the text was typed or pasted, or only copied.
+    pub mode: OutputMode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// Appends a record of `text` reaching `target`, when the user's organisation opted in.
+pub fn record(target: Option<&FrontmostApp>, text: &str, mode: OutputMode) {
+    let audit = settings::current().audit;
+    if !audit.enabled {
+        return;
+    }
+    let record = AuditRecord {
+        at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as i64)
+            .unwrap_or(0),
+        app: target.map(|target| target.name.clone()),
+        chars: text.chars().count(),
+        mode,
+        text: audit.include_text.then(|| crate::redact::stored(text)),
+    };
+    if let Err(err) = append(&record) {
+        let _ = crate::log::write(format!("Failed to write the audit log: {}", err));
+    }
+}
+
+/// Every record, oldest first. Lines that cannot be read are skipped.
+pub fn export() -> Result<Vec<AuditRecord>, String> {
+    let _appending = APPENDING.lock().map_err(|err| err.to_string())?;
+    let Some(path) = path().filter(|path| path.exists()) else {
+        return Ok(Vec::new());
+    };
+    let file = std::fs::File::open(path).map_err(|err| err.to_string())?;
+    BufReader::new(file)
+        .lines()
+        .map(|line| line.map_err(|err| err.to_string()))
+        .filter_map(|line| match line {
+            Ok(line) => serde_json::from_str(&line).ok().map(Ok),
+            Err(err) => Some(Err(err)),
+        })
+        .collect()
+}
+
+fn append(record: &AuditRecord) -> Result<(), String> {
+    let path = path().ok_or("No settings directory")?;
+    let mut line = serde_json::to_string(record).map_err(|err| err.to_string())?;
+    line.push('\n');
+    let _appending = APPENDING.lock().map_err(|err| err.to_string())?;
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|err| err.to_string())
+}
+
+/// Beside the settings file.
+fn path() -> Option<PathBuf> {
+    settings::path()?.parent().map(|dir| dir.join(AUDIT_FILE))
+}
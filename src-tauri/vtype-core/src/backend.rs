@@ -0,0 +1,88 @@
+use crate::mock;
+use crate::settings::{self, Settings};
+use crate::worker::{self, Priority, Transcription};
+
+const BACKEND_ENV: &str = "VTYPE_ASR_BACKEND";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// onnx-asr model hosted by the Python worker.
+    Onnx,
+    /// Canned transcripts with a configurable delay; needs neither Python nor a model.
+    Mock,
+}
+
+impl Backend {
+    pub fn parse(name: &str) -> Result<Backend, String> {
+        match name {
+            "onnx" => Ok(Backend::Onnx),
+            "mock" => Ok(Backend::Mock),
+            other => Err(format!("Unknown ASR backend: {}", other)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Onnx => "onnx",
+            Backend::Mock => "mock",
+        }
+    }
+}
+
+/// Resolves the backend for a request: per-request override, then `VTYPE_ASR_BACKEND`,
+/// then the saved setting.
+pub fn active(request_override: Option<&str>) -> Result<Backend, String> {
+    if let Some(name) = request_override {
+        return Backend::parse(name);
+    }
+    if let Ok(name) = std::env::var(BACKEND_ENV) {
+        if !name.trim().is_empty() {
+            return Backend::parse(name.trim());
+        }
+    }
+    Backend::parse(settings::current().effective_backend())
+}
+
+pub fn warm_up(backend: Backend) -> Result<(), String> {
+    match backend {
+        Backend::Onnx => {
+            worker::init()?;
+            worker::warm_up()
+        }
+        Backend::Mock => Ok(()),
+    }
+}
+
+pub async fn transcribe(
+    backend: Backend,
+    priority: Priority,
+    header: serde_json::Value,
+    wav_bytes: Vec<u8>,
+) -> Result<Transcription, String> {
+    match backend {
+        Backend::Onnx => {
+            worker::init()?;
+            worker::transcribe(priority, header, wav_bytes).await
+        }
+        Backend::Mock => Ok(Transcription {
+            text: mock::transcribe(&wav_bytes).await?,
+            confidence: None,
+            model: "mock".to_string(),
+            fallback: None,
+        }),
+    }
+}
+
+/// Brings the worker in line with edited settings: stop it when switching away from onnx,
+/// restart it when its launch configuration changed.
+pub fn apply_settings_change(previous: &Settings, current: &Settings) -> Result<(), String> {
+    let (was, now) = (
+        Backend::parse(previous.effective_backend())?,
+        Backend::parse(current.effective_backend())?,
+    );
+    match now {
+        Backend::Mock if was == Backend::Onnx => worker::shutdown(),
+        Backend::Onnx if was != now || previous.model != current.model => worker::restart(),
+        _ => Ok(()),
+    }
+}
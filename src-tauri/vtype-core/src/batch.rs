@@ -0,0 +1,256 @@
+//! Transcribes audio and video files from disk, one at a time or a folder at once, at
+//! background priority so dictation stays responsive while a batch runs.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use crate::options::TranscribeOptions;
+use crate::preprocess::TARGET_SAMPLE_RATE;
+use crate::subtitle::{self, Cue};
+use crate::worker::Priority;
+use crate::{audio, decode, pipeline};
+
+static FOLDER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// A file's transcript, whole and as timed cues.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileTranscript {
+    pub text: String,
+    pub cues: Vec<Cue>,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TranscriptFormat {
+    Txt,
+    Srt,
+}
+
+impl TranscriptFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            TranscriptFormat::Txt => "txt",
+            TranscriptFormat::Srt => "srt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FolderOptions {
+    #[serde(flatten)]
+    pub transcribe: TranscribeOptions,
+    /// Transcripts written next to each source, named after it.
+    pub formats: Vec<TranscriptFormat>,
+    pub recursive: bool,
+    /// Transcribe files that already have every requested transcript.
+    pub overwrite: bool,
+}
+
+impl Default for FolderOptions {
+    fn default() -> Self {
+        Self {
+            transcribe: TranscribeOptions::default(),
+            formats: vec![TranscriptFormat::Txt, TranscriptFormat::Srt],
+            recursive: false,
+            overwrite: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FileStatus {
+    Queued,
+    Transcribing,
+    Done,
+    Skipped,
+    Failed,
+}
+
+/// Reported each time a file in a folder run changes status.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileProgress {
+    pub path: String,
+    /// Position in the run, from 0.
+    pub index: usize,
+    pub total: usize,
+    pub status: FileStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderSummary {
+    pub total: usize,
+    pub done: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+/// Decodes an audio file, or a video's soundtrack, and transcribes it a cue at a time.
+pub async fn transcribe_file(
+    path: &Path,
+    options: TranscribeOptions,
+) -> Result<FileTranscript, String> {
+    let samples = decode_off_thread(path.to_path_buf()).await?;
+    let rate = TARGET_SAMPLE_RATE as u64;
+    let mut cues = Vec::new();
+    for range in subtitle::split(&samples, TARGET_SAMPLE_RATE) {
+        let wav = audio::wav_from_f32(&samples[range.clone()], TARGET_SAMPLE_RATE);
+        let text = pipeline::transcribe(Priority::Background, wav, options.clone())
+            .await?
+            .text;
+        if text.is_empty() {
+            continue;
+        }
+        cues.push(Cue {
+            start_ms: range.start as u64 * 1000 / rate,
+            end_ms: range.end as u64 * 1000 / rate,
+            text,
+        });
+    }
+    let text = cues
+        .iter()
+        .map(|cue| cue.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    Ok(FileTranscript {
+        text,
+        cues,
+        duration_ms: samples.len() as u64 * 1000 / rate,
+    })
+}
+
+/// Transcribes every audio and video file in `folder`, writing the requested transcripts next to each
+/// source. `progress` is called as each file is queued, started and finished; one failing
+/// file does not stop the run.
+pub async fn transcribe_folder(
+    folder: &Path,
+    options: FolderOptions,
+    mut progress: impl FnMut(FileProgress),
+) -> Result<FolderSummary, String> {
+    if options.formats.is_empty() {
+        return Err("No transcript format selected".to_string());
+    }
+    options.transcribe.validate()?;
+    let files = audio_files(folder, options.recursive)?;
+    if FOLDER_RUNNING.swap(true, Ordering::SeqCst) {
+        return Err("A folder transcription is already running".to_string());
+    }
+    let summary = run_folder(&files, &options, &mut progress).await;
+    FOLDER_RUNNING.store(false, Ordering::SeqCst);
+    Ok(summary)
+}
+
+async fn run_folder(
+    files: &[PathBuf],
+    options: &FolderOptions,
+    progress: &mut impl FnMut(FileProgress),
+) -> FolderSummary {
+    let total = files.len();
+    let mut report = |index: usize, status: FileStatus, error: Option<String>| {
+        progress(FileProgress {
+            path: files[index].to_string_lossy().to_string(),
+            index,
+            total,
+            status,
+            error,
+        })
+    };
+    for index in 0..total {
+        report(index, FileStatus::Queued, None);
+    }
+
+    let mut summary = FolderSummary {
+        total,
+        ..FolderSummary::default()
+    };
+    for (index, path) in files.iter().enumerate() {
+        let outputs: Vec<(TranscriptFormat, PathBuf)> = options
+            .formats
+            .iter()
+            .map(|&format| (format, path.with_extension(format.extension())))
+            .collect();
+        if !options.overwrite && outputs.iter().all(|(_, output)| output.exists()) {
+            summary.skipped += 1;
+            report(index, FileStatus::Skipped, None);
+            continue;
+        }
+        report(index, FileStatus::Transcribing, None);
+        let result = transcribe_file(path, options.transcribe.clone())
+            .await
+            .and_then(|transcript| write_outputs(&transcript, &outputs));
+        match result {
+            Ok(()) => {
+                summary.done += 1;
+                report(index, FileStatus::Done, None);
+            }
+            Err(err) => {
+                let _ =
+                    crate::log::write(format!("Failed to transcribe {}: {}", path.display(), err));
+                summary.failed += 1;
+                report(index, FileStatus::Failed, Some(err));
+            }
+        }
+    }
+    summary
+}
+
+fn write_outputs(
+    transcript: &FileTranscript,
+    outputs: &[(TranscriptFormat, PathBuf)],
+) -> Result<(), String> {
+    for (format, path) in outputs {
+        let contents = match format {
+            TranscriptFormat::Txt => format!("{}\n", transcript.text),
+            TranscriptFormat::Srt => subtitle::to_srt(&transcript.cues),
+        };
+        crate::safe_io::write(path, contents).map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+/// Audio and video files under `folder` in name order. Hidden entries are skipped.
+fn audio_files(folder: &Path, recursive: bool) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let mut pending = vec![folder.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|err| format!("Cannot read {}: {}", dir.display(), err))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let Ok(kind) = entry.file_type() else {
+                continue;
+            };
+            if kind.is_dir() {
+                if recursive {
+                    pending.push(path);
+                }
+            } else if decode::is_media(&path) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Decoding is CPU-bound; it runs on its own thread so the async runtime is not held up.
+async fn decode_off_thread(path: PathBuf) -> Result<Vec<f32>, String> {
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(decode::file(&path));
+    });
+    receiver
+        .await
+        .map_err(|_| "Decoder thread stopped".to_string())?
+}
@@ -0,0 +1,98 @@
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::backend::{self, Backend};
+use crate::options::TranscribeOptions;
+use crate::worker::Priority;
+use crate::{audio, models, settings};
+
+const SAMPLE_RATE: u32 = 16000;
+const DEFAULT_RUNS: u32 = 5;
+const MAX_RUNS: u32 = 50;
+const MAX_CLIP_SECONDS: f32 = 60.0;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BenchmarkReport {
+    pub backend: String,
+    pub model: String,
+    pub clip_seconds: f32,
+    pub runs: u32,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub max_ms: f64,
+    /// Real-time factor: processing time divided by audio duration, averaged over runs.
+    pub mean_rtf: f64,
+}
+
+/// Runs a synthetic clip through the active backend with the user's decoding settings.
+pub async fn run(seconds: f32, runs: Option<u32>) -> Result<BenchmarkReport, String> {
+    if !(0.5..=MAX_CLIP_SECONDS).contains(&seconds) {
+        return Err(format!("Clip length out of range (0.5-60s): {}", seconds));
+    }
+    let runs = runs.unwrap_or(DEFAULT_RUNS).clamp(1, MAX_RUNS);
+    let clip = sample_clip(seconds);
+    let settings = settings::current();
+    let header = TranscribeOptions::default()
+        .with_decoding_defaults(&settings.decoding)
+        .to_worker_json();
+    let backend = backend::active(None)?;
+    let model = match backend {
+        Backend::Onnx => format!(
+            "{} ({})",
+            settings::DEFAULT_MODEL,
+            models::suggest(&settings.model.quantization).effective
+        ),
+        Backend::Mock => "mock".to_string(),
+    };
+
+    // Untimed pass so model load and first-call allocation don't skew the numbers.
+    backend::transcribe(backend, Priority::Background, header.clone(), clip.clone()).await?;
+
+    let mut latencies = Vec::with_capacity(runs as usize);
+    for _ in 0..runs {
+        let started = Instant::now();
+        backend::transcribe(backend, Priority::Background, header.clone(), clip.clone()).await?;
+        latencies.push(started.elapsed().as_secs_f64() * 1000.0);
+    }
+    latencies.sort_by(|a, b| a.total_cmp(b));
+
+    let mean_ms = latencies.iter().sum::<f64>() / latencies.len() as f64;
+    Ok(BenchmarkReport {
+        backend: backend.name().to_string(),
+        model,
+        clip_seconds: seconds,
+        runs,
+        p50_ms: percentile(&latencies, 0.5),
+        p90_ms: percentile(&latencies, 0.9),
+        max_ms: latencies.last().copied().unwrap_or(0.0),
+        mean_rtf: mean_ms / 1000.0 / f64::from(seconds),
+    })
+}
+
+fn percentile(sorted: &[f64], quantile: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (quantile * (sorted.len() - 1) as f64).round() as usize;
+    sorted[rank.min(sorted.len() - 1)]
+}
+
+/// Mono 16 kHz PCM WAV with syllable-rate amplitude modulation over a few formant-like
+/// tones, so the encoder does real work instead of short-circuiting on silence.
+fn sample_clip(seconds: f32) -> Vec<u8> {
+    let frames = (seconds * SAMPLE_RATE as f32) as usize;
+    let mut pcm = Vec::with_capacity(frames * 2);
+    for n in 0..frames {
+        let t = n as f32 / SAMPLE_RATE as f32;
+        let envelope = (0.5 + 0.5 * (std::f32::consts::TAU * 4.0 * t).sin()).powi(2);
+        let voiced = [(220.0, 0.5), (700.0, 0.3), (1220.0, 0.2)]
+            .iter()
+            .map(|(freq, gain)| gain * (std::f32::consts::TAU * freq * t).sin())
+            .sum::<f32>();
+        let sample = (voiced * envelope * 0.3 * i16::MAX as f32) as i16;
+        pcm.extend_from_slice(&sample.to_le_bytes());
+    }
+    audio::wav_from_pcm16(&pcm, SAMPLE_RATE)
+}
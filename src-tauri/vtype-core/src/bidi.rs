@@ -0,0 +1,72 @@
+//! Direction marks for right-to-left transcripts. A Hebrew or Arabic sentence pasted into
+//! a left-to-right paragraph takes its direction from its surroundings, so trailing
+//! punctuation and embedded Latin words or numbers end up on the wrong side. Marking the
+//! transcript's own direction at both ends keeps it in order wherever it lands.
+//!
+//! The marks travel in logical order like every other character, so pasting and typing
+//! deliver them the same way.
+
+use crate::settings::DirectionalMarks;
+
+const LRM: char = '\u{200E}';
+const RLM: char = '\u{200F}';
+const LRI: char = '\u{2066}';
+const RLI: char = '\u{2067}';
+const PDI: char = '\u{2069}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    LeftToRight,
+    RightToLeft,
+}
+
+/// The direction of the first strongly directional character, as the Unicode bidi
+/// algorithm would pick for a paragraph of `text`.
+pub fn base_direction(text: &str) -> Option<Direction> {
+    text.chars().find_map(strong_direction)
+}
+
+/// Wraps `text` in marks of its base direction when it contains right-to-left script.
+/// Text with no right-to-left characters is returned as is.
+pub fn mark(text: &str, marks: DirectionalMarks) -> String {
+    if marks == DirectionalMarks::Off
+        || !text
+            .chars()
+            .any(|ch| strong_direction(ch) == Some(Direction::RightToLeft))
+    {
+        return text.to_string();
+    }
+    let direction = base_direction(text).unwrap_or(Direction::RightToLeft);
+    let (open, close) = match (marks, direction) {
+        (DirectionalMarks::Isolates, Direction::RightToLeft) => (RLI, PDI),
+        (DirectionalMarks::Isolates, Direction::LeftToRight) => (LRI, PDI),
+        (_, Direction::RightToLeft) => (RLM, RLM),
+        (_, Direction::LeftToRight) => (LRM, LRM),
+    };
+    format!("{}{}{}", open, text, close)
+}
+
+fn strong_direction(ch: char) -> Option<Direction> {
+    if is_rtl(ch) {
+        Some(Direction::RightToLeft)
+    } else if ch.is_alphabetic() {
+        Some(Direction::LeftToRight)
+    } else {
+        None
+    }
+}
+
+/// Letters of the Hebrew, Arabic, Syriac, Thaana, N'Ko and related blocks. Arabic-Indic
+/// digits are weak like other digits and left out.
+fn is_rtl(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{0590}'..='\u{065F}'
+            | '\u{066A}'..='\u{06EF}'
+            | '\u{06FA}'..='\u{08FF}'
+            | '\u{FB1D}'..='\u{FDFF}'
+            | '\u{FE70}'..='\u{FEFF}'
+            | '\u{10800}'..='\u{10FFF}'
+            | '\u{1E800}'..='\u{1EFFF}'
+    ) && !matches!(ch, '\u{0591}'..='\u{05C7}' | '\u{064B}'..='\u{065F}')
+}
@@ -0,0 +1,300 @@
+//! Code mode: spoken forms become symbols and identifiers, so "print open paren snake case
+//! user name close paren" gives `print(user_name)`. Punctuation comes only from the spoken
+//! words; the commas and full stops the backend adds are dropped, and so is the capital it
+//! puts at the start of a sentence.
+
+use crate::settings::CodeLanguage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Spacing {
+    /// Attached to what comes before and after, like `(` or `.`.
+    Tight,
+    /// Attached before and spaced after, like `,`.
+    Trailing,
+    /// Spaced on both sides, like `=`.
+    Spaced,
+    /// A line break, with nothing around it.
+    Line,
+}
+
+type Symbol = (&'static str, &'static str, Spacing);
+
+const COMMON: &[Symbol] = &[
+    ("open paren", "(", Spacing::Tight),
+    ("close paren", ")", Spacing::Tight),
+    ("open bracket", "[", Spacing::Tight),
+    ("close bracket", "]", Spacing::Tight),
+    ("open brace", "{", Spacing::Spaced),
+    ("close brace", "}", Spacing::Spaced),
+    ("open angle", "<", Spacing::Tight),
+    ("close angle", ">", Spacing::Tight),
+    ("less than", "<", Spacing::Spaced),
+    ("greater than", ">", Spacing::Spaced),
+    ("less or equal", "<=", Spacing::Spaced),
+    ("greater or equal", ">=", Spacing::Spaced),
+    ("dot", ".", Spacing::Tight),
+    ("comma", ",", Spacing::Trailing),
+    ("colon", ":", Spacing::Trailing),
+    ("semicolon", ";", Spacing::Trailing),
+    ("equals", "=", Spacing::Spaced),
+    ("double equals", "==", Spacing::Spaced),
+    ("not equals", "!=", Spacing::Spaced),
+    ("plus", "+", Spacing::Spaced),
+    ("minus", "-", Spacing::Spaced),
+    ("times", "*", Spacing::Spaced),
+    ("divided by", "/", Spacing::Spaced),
+    ("modulo", "%", Spacing::Spaced),
+    ("plus equals", "+=", Spacing::Spaced),
+    ("minus equals", "-=", Spacing::Spaced),
+    ("and and", "&&", Spacing::Spaced),
+    ("or or", "||", Spacing::Spaced),
+    ("arrow", "->", Spacing::Spaced),
+    ("pipe", "|", Spacing::Spaced),
+    ("star", "*", Spacing::Tight),
+    ("slash", "/", Spacing::Tight),
+    ("backslash", "\\", Spacing::Tight),
+    ("ampersand", "&", Spacing::Tight),
+    ("bang", "!", Spacing::Tight),
+    ("question mark", "?", Spacing::Tight),
+    ("quote", "\"", Spacing::Tight),
+    ("single quote", "'", Spacing::Tight),
+    ("backtick", "`", Spacing::Tight),
+    ("hash", "#", Spacing::Tight),
+    ("at sign", "@", Spacing::Tight),
+    ("dollar", "$", Spacing::Tight),
+    ("underscore", "_", Spacing::Tight),
+    ("new line", "\n", Spacing::Line),
+];
+
+const RUST: &[Symbol] = &[
+    ("fat arrow", "=>", Spacing::Spaced),
+    ("double colon", "::", Spacing::Tight),
+    ("turbofish", "::<", Spacing::Tight),
+    ("range", "..", Spacing::Tight),
+    ("lifetime", "'", Spacing::Tight),
+];
+
+const PYTHON: &[Symbol] = &[
+    ("walrus", ":=", Spacing::Spaced),
+    ("dunder", "__", Spacing::Tight),
+    ("double star", "**", Spacing::Tight),
+    ("floor divide", "//", Spacing::Spaced),
+];
+
+const JAVASCRIPT: &[Symbol] = &[
+    ("arrow", "=>", Spacing::Spaced),
+    ("fat arrow", "=>", Spacing::Spaced),
+    ("triple equals", "===", Spacing::Spaced),
+    ("not double equals", "!==", Spacing::Spaced),
+    ("optional chain", "?.", Spacing::Tight),
+    ("nullish", "??", Spacing::Spaced),
+    ("spread", "...", Spacing::Tight),
+    ("dollar brace", "${", Spacing::Tight),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Case {
+    Snake,
+    Camel,
+    Pascal,
+    Kebab,
+    Constant,
+}
+
+const CASES: &[(&str, Case)] = &[
+    ("snake case", Case::Snake),
+    ("camel case", Case::Camel),
+    ("pascal case", Case::Pascal),
+    ("kebab case", Case::Kebab),
+    ("constant case", Case::Constant),
+];
+
+/// A word as transcribed.
+struct Word {
+    text: String,
+    /// The backend put punctuation after it, which a spoken identifier ends at.
+    pause_after: bool,
+}
+
+enum Piece {
+    Word(String),
+    Symbol(&'static str, Spacing),
+}
+
+/// `text` with the spoken forms of `language`, and those every language shares, replaced.
+/// A casing command such as "snake case" joins the words after it, up to the next symbol or
+/// pause.
+pub fn apply(text: &str, language: CodeLanguage) -> String {
+    let words = words(text);
+    let symbols: Vec<(Vec<&str>, &'static str, Spacing)> = preset(language)
+        .iter()
+        .chain(COMMON)
+        .map(|(phrase, symbol, spacing)| (phrase.split(' ').collect(), *symbol, *spacing))
+        .collect();
+    let cases: Vec<(Vec<&str>, Case)> = CASES
+        .iter()
+        .map(|(phrase, case)| (phrase.split(' ').collect(), *case))
+        .collect();
+    let symbol_at = |at: usize| {
+        symbols
+            .iter()
+            .filter(|(phrase, _, _)| starts_with(&words[at..], phrase))
+            // The longest phrase, and of equally long ones the language's own.
+            .min_by_key(|(phrase, _, _)| std::cmp::Reverse(phrase.len()))
+    };
+    let case_at = |at: usize| {
+        cases
+            .iter()
+            .find(|(phrase, _)| starts_with(&words[at..], phrase))
+    };
+
+    let mut pieces = Vec::new();
+    let mut at = 0;
+    while at < words.len() {
+        if let Some((phrase, symbol, spacing)) = symbol_at(at) {
+            pieces.push(Piece::Symbol(symbol, *spacing));
+            at += phrase.len();
+            continue;
+        }
+        if let Some((phrase, case)) = case_at(at) {
+            let start = at + phrase.len();
+            let mut end = start;
+            while end < words.len() && symbol_at(end).is_none() && case_at(end).is_none() {
+                end += 1;
+                if words[end - 1].pause_after {
+                    break;
+                }
+            }
+            if end > start && !words[at + phrase.len() - 1].pause_after {
+                pieces.push(Piece::Word(identifier(&words[start..end], *case)));
+                at = end;
+                continue;
+            }
+        }
+        pieces.push(Piece::Word(words[at].text.clone()));
+        at += 1;
+    }
+    join(&pieces)
+}
+
+fn preset(language: CodeLanguage) -> &'static [Symbol] {
+    match language {
+        CodeLanguage::Generic => &[],
+        CodeLanguage::Rust => RUST,
+        CodeLanguage::Python => PYTHON,
+        CodeLanguage::JavaScript => JAVASCRIPT,
+    }
+}
+
+fn starts_with(words: &[Word], phrase: &[&str]) -> bool {
+    words.len() >= phrase.len()
+        && words
+            .iter()
+            .zip(phrase)
+            .all(|(word, expected)| word.text.eq_ignore_ascii_case(expected))
+}
+
+/// The words of `text` without the punctuation the backend ended them with; a full stop
+/// inside a word, as in `3.14`, stays.
+fn words(text: &str) -> Vec<Word> {
+    let mut words: Vec<Word> = Vec::new();
+    for token in text.split_whitespace() {
+        let bare = token.trim_end_matches(['.', ',', '?', '!', ';', ':']);
+        let pause_after = bare.len() < token.len();
+        if bare.is_empty() {
+            if let Some(last) = words.last_mut() {
+                last.pause_after = true;
+            }
+            continue;
+        }
+        let starts_sentence = words.last().is_none_or(|last| last.pause_after);
+        let text = if starts_sentence {
+            uncapitalize(bare)
+        } else {
+            bare.to_string()
+        };
+        words.push(Word { text, pause_after });
+    }
+    words
+}
+
+/// Undoes a sentence's capital, leaving words such as `HTTP` alone.
+fn uncapitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) if first.is_uppercase() && !chars.as_str().chars().any(char::is_uppercase) => {
+            first.to_lowercase().chain(chars).collect()
+        }
+        _ => word.to_string(),
+    }
+}
+
+fn identifier(words: &[Word], case: Case) -> String {
+    let parts = words.iter().map(|word| {
+        word.text
+            .chars()
+            .filter(|ch| ch.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase()
+    });
+    match case {
+        Case::Snake => parts.collect::<Vec<_>>().join("_"),
+        Case::Kebab => parts.collect::<Vec<_>>().join("-"),
+        Case::Constant => parts.collect::<Vec<_>>().join("_").to_uppercase(),
+        Case::Camel => parts
+            .enumerate()
+            .map(|(index, part)| if index == 0 { part } else { capitalize(&part) })
+            .collect(),
+        Case::Pascal => parts.map(|part| capitalize(&part)).collect(),
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// What a piece leaves for the next one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Last {
+    /// The start of the text or of a line.
+    Nothing,
+    Word,
+    /// A symbol the next piece attaches to.
+    Attached,
+    /// A symbol followed by a space, unless something attaching comes next.
+    Separated,
+}
+
+fn join(pieces: &[Piece]) -> String {
+    let mut text = String::new();
+    let mut last = Last::Nothing;
+    for piece in pieces {
+        let (space, next) = match piece {
+            Piece::Word(_) => (matches!(last, Last::Word | Last::Separated), Last::Word),
+            Piece::Symbol(_, Spacing::Tight) => (last == Last::Separated, Last::Attached),
+            Piece::Symbol(_, Spacing::Trailing) => (false, Last::Separated),
+            Piece::Symbol(_, Spacing::Spaced) => (
+                match last {
+                    Last::Nothing => false,
+                    Last::Attached => !text.ends_with(['(', '[', '<']),
+                    Last::Word | Last::Separated => true,
+                },
+                Last::Separated,
+            ),
+            Piece::Symbol(_, Spacing::Line) => (false, Last::Nothing),
+        };
+        if space {
+            text.push(' ');
+        }
+        match piece {
+            Piece::Word(word) => text.push_str(word),
+            Piece::Symbol(symbol, _) => text.push_str(symbol),
+        }
+        last = next;
+    }
+    text
+}
@@ -0,0 +1,165 @@
+//! Crash reports. A panic writes a JSON report beside the log with the backtrace, the
+//! supervisor's recent states and the tail of the worker's stderr, and leaves a note so
+//! the next launch can offer to open it. Otherwise a crash would look no different from a
+//! hotkey that never registered.
+
+use std::collections::VecDeque;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::worker::SupervisorStatus;
+
+const REPORT_PREFIX: &str = "crash-";
+/// Holds the path of the newest report that has not been offered yet.
+const PENDING_FILE: &str = "crash-pending";
+const STATUS_HISTORY: usize = 20;
+const STDERR_TAIL: usize = 50;
+
+static STATUSES: Mutex<VecDeque<StatusRecord>> = Mutex::new(VecDeque::new());
+static WORKER_STDERR: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusRecord {
+    /// Unix time in milliseconds.
+    pub at: i64,
+    pub state: &'static str,
+    pub queue_length: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrashReport {
+    pub created_at: i64,
+    pub version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub thread: Option<String>,
+    pub message: String,
+    pub location: Option<String>,
+    pub backtrace: String,
+    /// Oldest first.
+    pub statuses: Vec<StatusRecord>,
+    pub worker_stderr: Vec<String>,
+}
+
+/// Writes a report for every panic before the default hook runs. `version` is the app's.
+pub fn install(version: &'static str) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let report = report(version, info);
+        match write(&report) {
+            Ok(path) => {
+                let _ = crate::log::write(format!(
+                    "Panicked: {}; crash report at {}",
+                    report.message,
+                    path.display()
+                ));
+            }
+            Err(err) => {
+                let _ = crate::log::write(format!(
+                    "Panicked: {}; failed to write crash report: {}",
+                    report.message, err
+                ));
+            }
+        }
+        previous(info);
+    }));
+}
+
+pub fn record_status(status: &SupervisorStatus) {
+    push(
+        &STATUSES,
+        StatusRecord {
+            at: now_ms(),
+            state: status.state,
+            queue_length: status.queue_length,
+        },
+        STATUS_HISTORY,
+    );
+}
+
+pub fn record_worker_stderr(line: String) {
+    push(&WORKER_STDERR, line, STDERR_TAIL);
+}
+
+/// The report of a crash since it was last asked, which is forgotten once returned.
+pub fn take_pending() -> Option<PathBuf> {
+    let pending = log_dir()?.join(PENDING_FILE);
+    let path = PathBuf::from(std::fs::read_to_string(&pending).ok()?.trim());
+    let _ = std::fs::remove_file(&pending);
+    path.is_file().then_some(path)
+}
+
+/// Whether `path` is a report this module wrote, so only those are opened on request.
+pub fn is_report(path: &Path) -> bool {
+    let named = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with(REPORT_PREFIX) && name.ends_with(".json"));
+    named && path.parent() == log_dir().as_deref()
+}
+
+fn report(version: &'static str, info: &PanicHookInfo) -> CrashReport {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string());
+    CrashReport {
+        created_at: now_ms(),
+        version,
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        thread: std::thread::current().name().map(str::to_string),
+        message,
+        location: info.location().map(|location| location.to_string()),
+        backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+        statuses: snapshot(&STATUSES),
+        worker_stderr: snapshot(&WORKER_STDERR),
+    }
+}
+
+fn write(report: &CrashReport) -> Result<PathBuf, String> {
+    let dir = log_dir().ok_or("No log directory")?;
+    std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    let path = dir.join(format!("{}{}.json", REPORT_PREFIX, report.created_at));
+    let json = serde_json::to_vec_pretty(report).map_err(|err| err.to_string())?;
+    crate::safe_io::write(&path, json).map_err(|err| err.to_string())?;
+    crate::safe_io::write(&dir.join(PENDING_FILE), path.display().to_string())
+        .map_err(|err| err.to_string())?;
+    Ok(path)
+}
+
+fn log_dir() -> Option<PathBuf> {
+    crate::paths::log_file().parent().map(Path::to_path_buf)
+}
+
+fn push<T>(buffer: &Mutex<VecDeque<T>>, item: T, capacity: usize) {
+    if let Ok(mut buffer) = buffer.lock() {
+        if buffer.len() == capacity {
+            buffer.pop_front();
+        }
+        buffer.push_back(item);
+    }
+}
+
+/// `try_lock`, since the panicking thread may be the one holding the lock.
+fn snapshot<T: Clone>(buffer: &Mutex<VecDeque<T>>) -> Vec<T> {
+    buffer
+        .try_lock()
+        .map(|buffer| buffer.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as i64)
+        .unwrap_or(0)
+}
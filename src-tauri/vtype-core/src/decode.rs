@@ -0,0 +1,180 @@
+//! Decodes audio and video files from disk into the mono clip the backends expect.
+//!
+//! Containers and codecs Symphonia handles are decoded in process. Anything else, such as
+//! Opus or AC-3 soundtracks, is passed to `ffmpeg` when it is installed.
+
+use std::fs::File;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::audio;
+use crate::preprocess::TARGET_SAMPLE_RATE;
+
+/// Extensions handed to [`file`]; anything else is not looked at when walking a folder.
+pub const AUDIO_EXTENSIONS: &[&str] = &["wav", "mp3", "flac", "ogg", "oga", "m4a", "aac"];
+/// Containers whose soundtrack is transcribed, for subtitling recordings.
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "m4v", "mov", "mkv", "webm"];
+
+pub fn is_media(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            AUDIO_EXTENSIONS
+                .iter()
+                .chain(VIDEO_EXTENSIONS)
+                .any(|known| known.eq_ignore_ascii_case(extension))
+        })
+}
+
+enum Failure {
+    /// The container or codec is not one Symphonia knows.
+    Unsupported(String),
+    Other(String),
+}
+
+/// Decodes the first audio track of a file to mono samples at [`TARGET_SAMPLE_RATE`].
+pub fn file(path: &Path) -> Result<Vec<f32>, String> {
+    match native(path) {
+        Ok(samples) => Ok(samples),
+        Err(Failure::Other(err)) => Err(err),
+        Err(Failure::Unsupported(reason)) => match ffmpeg(path)? {
+            Some(samples) => Ok(samples),
+            None => Err(format!("{}; installing ffmpeg adds support for it", reason)),
+        },
+    }
+}
+
+/// Packets are mixed down as they arrive and resampled a second at a time, so an hour-long
+/// recording never sits in memory at its original rate and channel count.
+fn native(path: &Path) -> Result<Vec<f32>, Failure> {
+    let source = File::open(path).map_err(|err| Failure::Other(err.to_string()))?;
+    let stream = MediaSourceStream::new(Box::new(source), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        hint.with_extension(extension);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|err| Failure::Unsupported(format!("Unsupported file: {}", err)))?;
+    let mut format = probed.format;
+    // Video tracks carry no audio codec; the first audio track that can be decoded is used.
+    let mut unsupported = None;
+    let (track_id, mut decoder) = format
+        .tracks()
+        .iter()
+        .filter(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .find_map(|track| {
+            symphonia::default::get_codecs()
+                .make(&track.codec_params, &DecoderOptions::default())
+                .map_err(|err| unsupported = Some(err))
+                .ok()
+                .map(|decoder| (track.id, decoder))
+        })
+        .ok_or_else(|| match unsupported {
+            Some(err) => Failure::Unsupported(format!("Unsupported codec: {}", err)),
+            None => Failure::Other("No audio track".to_string()),
+        })?;
+
+    let mut resampler = Resampler::default();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(Error::IoError(err)) if err.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(Error::ResetRequired) => break,
+            Err(err) => return Err(Failure::Other(err.to_string())),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupt packet costs a few milliseconds of audio, not the whole file.
+            Err(Error::DecodeError(_)) => continue,
+            Err(err) => return Err(Failure::Other(err.to_string())),
+        };
+        let spec = *decoded.spec();
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        let mono = audio::downmix(buffer.samples(), spec.channels.count() as u16, &[]);
+        resampler.push(&mono, spec.rate);
+    }
+    Ok(resampler.finish())
+}
+
+/// Has `ffmpeg` decode the file to raw samples. `None` when it is not installed.
+fn ffmpeg(path: &Path) -> Result<Option<Vec<f32>>, String> {
+    let rate = TARGET_SAMPLE_RATE.to_string();
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-nostdin", "-v", "error", "-i"])
+        .arg(path)
+        .args(["-vn", "-ac", "1", "-ar", &rate, "-f", "f32le", "-"])
+        .stdin(Stdio::null());
+    crate::process::configure_background_command(&mut command);
+    let output = match command.output() {
+        Ok(output) => output,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.to_string()),
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffmpeg failed: {}", stderr.trim()));
+    }
+    Ok(Some(
+        output
+            .stdout
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect(),
+    ))
+}
+
+/// Collects mono audio at [`TARGET_SAMPLE_RATE`], resampling whole seconds of input at a
+/// time so block boundaries fall on exact output samples.
+#[derive(Default)]
+struct Resampler {
+    rate: u32,
+    pending: Vec<f32>,
+    out: Vec<f32>,
+}
+
+impl Resampler {
+    fn push(&mut self, samples: &[f32], rate: u32) {
+        if rate != self.rate {
+            self.flush();
+            self.rate = rate;
+        }
+        self.pending.extend_from_slice(samples);
+        let block = rate.max(1) as usize;
+        if self.pending.len() >= block {
+            let whole = self.pending.len() / block * block;
+            let rest = self.pending.split_off(whole);
+            self.flush();
+            self.pending = rest;
+        }
+    }
+
+    fn flush(&mut self) {
+        let pending = std::mem::take(&mut self.pending);
+        self.out
+            .extend(audio::resample(&pending, self.rate, TARGET_SAMPLE_RATE));
+    }
+
+    fn finish(mut self) -> Vec<f32> {
+        self.flush();
+        self.out
+    }
+}
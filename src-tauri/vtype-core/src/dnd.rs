@@ -0,0 +1,79 @@
+//! Do-not-disturb: whether a dictation hotkey press should be ignored right now.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use chrono::{Datelike, Local, NaiveDateTime, Timelike};
+use serde::Serialize;
+
+use crate::frontmost;
+use crate::settings::{self, QuietHours};
+
+/// Why dictation is currently blocked, sent to the UI so it can offer an override.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "reason")]
+pub enum Block {
+    Schedule { start: String, end: String },
+    App { name: String },
+}
+
+static OVERRIDE_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Returns the rule blocking dictation, if any, unless an override is in effect.
+pub fn check() -> Option<Block> {
+    let dnd = settings::current().do_not_disturb;
+    if !dnd.enabled || overridden() {
+        return None;
+    }
+    if let Some(hours) = dnd
+        .schedule
+        .iter()
+        .find(|hours| in_quiet_hours(hours, Local::now().naive_local()))
+    {
+        return Some(Block::Schedule {
+            start: hours.start.clone(),
+            end: hours.end.clone(),
+        });
+    }
+    if dnd.apps.is_empty() {
+        return None;
+    }
+    let app = frontmost::detect()?;
+    dnd.apps
+        .iter()
+        .any(|pattern| app.matches(pattern))
+        .then_some(Block::App { name: app.name })
+}
+
+/// Lets dictation through for the next `minutes` regardless of the rules.
+pub fn override_for(minutes: u32) {
+    if let Ok(mut until) = OVERRIDE_UNTIL.lock() {
+        *until = Some(Instant::now() + Duration::from_secs(u64::from(minutes) * 60));
+    }
+}
+
+fn overridden() -> bool {
+    OVERRIDE_UNTIL
+        .lock()
+        .map(|until| until.is_some_and(|until| Instant::now() < until))
+        .unwrap_or(false)
+}
+
+fn in_quiet_hours(hours: &QuietHours, now: NaiveDateTime) -> bool {
+    let (Ok(start), Ok(end)) = (
+        settings::parse_clock(&hours.start),
+        settings::parse_clock(&hours.end),
+    ) else {
+        return false;
+    };
+    let minute = now.hour() * 60 + now.minute();
+    let today = now.weekday().number_from_monday() as u8;
+    let yesterday = (today + 5) % 7 + 1;
+    let starts_on = |day: u8| hours.days.is_empty() || hours.days.contains(&day);
+    if start <= end {
+        starts_on(today) && (start..end).contains(&minute)
+    } else {
+        // Spans midnight: the evening part belongs to today, the early part to yesterday.
+        (minute >= start && starts_on(today)) || (minute < end && starts_on(yesterday))
+    }
+}
@@ -0,0 +1,154 @@
+//! Fetches the audio behind a web link with `yt-dlp`, when it is installed, and runs it
+//! through the file pipeline.
+
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::batch::{self, FileTranscript};
+use crate::options::TranscribeOptions;
+
+/// Marks yt-dlp's progress lines apart from its other output.
+const PROGRESS_PREFIX: &str = "PROGRESS ";
+/// AAC in MP4 decodes without ffmpeg; other audio is taken when a site has none.
+const FORMAT: &str = "ba[ext=m4a]/ba/b";
+
+/// Reported while a link is downloaded and transcribed.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "stage", rename_all = "camelCase")]
+pub enum UrlProgress {
+    #[serde(rename_all = "camelCase")]
+    Downloading {
+        downloaded_bytes: u64,
+        /// Exact or estimated size, when the site reports one.
+        total_bytes: Option<u64>,
+    },
+    Transcribing,
+}
+
+enum Event {
+    Progress(UrlProgress),
+    Finished(Result<PathBuf, String>),
+}
+
+/// Downloads the audio of `url` to a temporary folder, transcribes it and removes it again.
+pub async fn transcribe_url(
+    url: &str,
+    options: TranscribeOptions,
+    mut progress: impl FnMut(UrlProgress),
+) -> Result<FileTranscript, String> {
+    let url = url.trim().to_string();
+    if !url.starts_with("https://") && !url.starts_with("http://") {
+        return Err("Only http and https links can be transcribed".to_string());
+    }
+    options.validate()?;
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or_default();
+    let dir = crate::paths::working_dir().join(format!("vtype_download_{}", stamp));
+    std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let thread_dir = dir.clone();
+    std::thread::spawn(move || {
+        let result = download(&url, &thread_dir, &sender);
+        let _ = sender.send(Event::Finished(result));
+    });
+    let downloaded = loop {
+        match receiver.recv().await {
+            Some(Event::Progress(update)) => progress(update),
+            Some(Event::Finished(result)) => break result,
+            None => break Err("Download thread stopped".to_string()),
+        }
+    };
+    let result = match downloaded {
+        Ok(path) => {
+            progress(UrlProgress::Transcribing);
+            batch::transcribe_file(&path, options).await
+        }
+        Err(err) => Err(err),
+    };
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+fn download(url: &str, dir: &Path, events: &UnboundedSender<Event>) -> Result<PathBuf, String> {
+    let mut command = Command::new("yt-dlp");
+    command
+        .args(["--no-playlist", "--newline", "-f", FORMAT])
+        .arg("--progress-template")
+        .arg(format!(
+            "download:{}%(progress.downloaded_bytes)s %(progress.total_bytes)s \
+             %(progress.total_bytes_estimate)s",
+            PROGRESS_PREFIX
+        ))
+        .arg("-o")
+        .arg(dir.join("audio.%(ext)s"))
+        .arg("--")
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    crate::process::configure_background_command(&mut command);
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Err("Transcribing links needs yt-dlp installed on the PATH".to_string());
+        }
+        Err(err) => return Err(err.to_string()),
+    };
+
+    let mut stderr = child.stderr.take();
+    let errors = std::thread::spawn(move || {
+        let mut text = String::new();
+        if let Some(stderr) = stderr.as_mut() {
+            let _ = stderr.read_to_string(&mut text);
+        }
+        text
+    });
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if let Some(update) = line.strip_prefix(PROGRESS_PREFIX).and_then(parse_progress) {
+                let _ = events.send(Event::Progress(update));
+            }
+        }
+    }
+    let status = child.wait().map_err(|err| err.to_string())?;
+    let errors = errors.join().unwrap_or_default();
+    if !status.success() {
+        let reason = errors
+            .lines()
+            .rev()
+            .find(|line| line.starts_with("ERROR"))
+            .unwrap_or("yt-dlp failed");
+        return Err(reason.trim().to_string());
+    }
+    std::fs::read_dir(dir)
+        .map_err(|err| err.to_string())?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.extension()
+                .is_some_and(|extension| extension != "part")
+        })
+        .ok_or_else(|| "yt-dlp did not produce a file".to_string())
+}
+
+/// Parses `downloaded total estimate`, where yt-dlp writes `NA` for unknown values.
+fn parse_progress(line: &str) -> Option<UrlProgress> {
+    let mut fields = line
+        .split_whitespace()
+        .map(|field| field.parse::<f64>().ok().map(|value| value as u64));
+    let downloaded_bytes = fields.next()??;
+    let total = fields.next().flatten();
+    let estimate = fields.next().flatten();
+    Some(UrlProgress::Downloading {
+        downloaded_bytes,
+        total_bytes: total.or(estimate),
+    })
+}
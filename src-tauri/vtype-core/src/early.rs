@@ -0,0 +1,42 @@
+//! Transcribing a dictation while it is still being recorded, as `transcribeWhileRecording`
+//! asks. Once a part of at least [`MIN_PART_MS`] is followed by a pause and more speech,
+//! the audio up to the middle of that pause goes to the backend on its own. When recording
+//! stops only what came after the last part is left to transcribe, so the wait is for that
+//! alone rather than for the whole recording.
+
+use crate::audio;
+use crate::subtitle::{self, FRAME_MS};
+
+/// Shorter parts would cost the backend context it needs for accuracy.
+pub const MIN_PART_MS: usize = 8_000;
+/// A pause this long, or longer, can end a part.
+const PAUSE_MS: usize = 500;
+
+/// Where the part of `samples`, mono at `sample_rate`, that starts at `from` can end: the
+/// middle of its last pause past [`MIN_PART_MS`] that followed speech and that speech
+/// followed again. `None` until there is such a pause.
+pub fn next_cut(samples: &[f32], from: usize, sample_rate: u32) -> Option<usize> {
+    let samples = samples.get(from..)?;
+    let frame = (sample_rate as usize * FRAME_MS / 1000).max(1);
+    let energy: Vec<f32> = samples.chunks(frame).map(audio::rms).collect();
+    let threshold = subtitle::pause_threshold(&energy);
+    let mut spoken = false;
+    let mut quiet_since = None;
+    let mut cut = None;
+    for (index, &rms) in energy.iter().enumerate() {
+        if rms < threshold {
+            if spoken {
+                quiet_since.get_or_insert(index);
+            }
+            continue;
+        }
+        spoken = true;
+        if let Some(start) = quiet_since.take() {
+            let middle = (start + index) / 2;
+            if index - start >= PAUSE_MS / FRAME_MS && middle >= MIN_PART_MS / FRAME_MS {
+                cut = Some(middle);
+            }
+        }
+    }
+    cut.map(|middle| from + middle * frame)
+}
@@ -0,0 +1,144 @@
+//! Acoustic echo cancellation against a loopback recording of what the speakers played.
+//!
+//! The loopback and microphone streams start at slightly different moments and the sound
+//! takes a few milliseconds to reach the mic, so the reference is first aligned by
+//! cross-correlation. A normalized LMS filter then learns the room's echo path from the
+//! aligned reference and subtracts its estimate from the microphone signal. Adaptation is
+//! frozen while the user talks over the playback, so their voice is not learned as echo.
+
+use crate::audio;
+
+/// Echo tail the filter models once the streams are aligned.
+const FILTER_MS: u32 = 32;
+/// Filter taps placed before the estimated delay, for alignment error.
+const LEAD_MS: u32 = 4;
+/// Search range for the reference-to-mic offset. The reference can trail slightly when the
+/// loopback stream opened after the microphone.
+const MAX_LAG_MS: i64 = 400;
+const MAX_LEAD_MS: i64 = 100;
+/// Alignment runs on a decimated copy of the first few seconds.
+const ALIGN_RATE: u32 = 4_000;
+const ALIGN_SECONDS: usize = 8;
+/// Correlation below this means the speakers are not audible in the mic.
+const MIN_CORRELATION: f32 = 0.1;
+/// Reference quieter than this (RMS) is treated as silence.
+const SILENT_RMS: f32 = 1e-4;
+const STEP_SIZE: f32 = 0.3;
+/// Geigel double-talk detector: the mic is louder than the loudest recent reference
+/// sample times this only when someone is speaking into it.
+const DOUBLE_TALK_RATIO: f32 = 0.6;
+const DOUBLE_TALK_HOLD_MS: u32 = 60;
+
+/// Removes the part of `mic` that is `reference` played back through the speakers. Both
+/// are mono at `sample_rate`; `mic` is returned as is when no echo of the reference is
+/// found in it.
+pub fn cancel(mic: &[f32], reference: &[f32], sample_rate: u32) -> Vec<f32> {
+    if mic.is_empty() || audio::rms(reference) < SILENT_RMS {
+        return mic.to_vec();
+    }
+    let Some(delay) = estimate_delay(mic, reference, sample_rate) else {
+        return mic.to_vec();
+    };
+    let taps = ms_to_samples(FILTER_MS, sample_rate).max(1);
+    let lead = ms_to_samples(LEAD_MS, sample_rate) as i64;
+    // aligned[n] is the reference sample heard in the mic `lead` samples after n.
+    let offset = delay - lead;
+    let aligned: Vec<f32> = (0..mic.len() as i64)
+        .map(|n| {
+            let index = n - offset;
+            if index >= 0 {
+                reference.get(index as usize).copied().unwrap_or(0.0)
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    nlms(
+        mic,
+        &aligned,
+        taps,
+        ms_to_samples(DOUBLE_TALK_HOLD_MS, sample_rate),
+    )
+}
+
+/// Samples by which the echo in `mic` trails `reference`; negative when the reference
+/// stream started late. `None` when the two are not correlated.
+fn estimate_delay(mic: &[f32], reference: &[f32], sample_rate: u32) -> Option<i64> {
+    let factor = (sample_rate / ALIGN_RATE).max(1) as usize;
+    let limit = ALIGN_SECONDS * ALIGN_RATE as usize;
+    let mic = decimate(mic, factor, limit);
+    let reference = decimate(reference, factor, limit);
+    let rate = i64::from(sample_rate) / factor as i64;
+    let max_lag = MAX_LAG_MS * rate / 1000;
+    let max_lead = MAX_LEAD_MS * rate / 1000;
+
+    let energy = |signal: &[f32]| signal.iter().map(|x| x * x).sum::<f32>();
+    let norm = (energy(&mic) * energy(&reference)).sqrt();
+    if norm <= 0.0 {
+        return None;
+    }
+    let (lag, correlation) = (-max_lead..=max_lag)
+        .map(|lag| {
+            let sum: f32 = mic
+                .iter()
+                .enumerate()
+                .filter_map(|(n, m)| {
+                    let index = n as i64 - lag;
+                    (index >= 0)
+                        .then(|| reference.get(index as usize))
+                        .flatten()
+                        .map(|r| m * r)
+                })
+                .sum();
+            (lag, sum.abs() / norm)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    (correlation >= MIN_CORRELATION).then_some(lag * factor as i64)
+}
+
+/// Normalized LMS echo canceller over an aligned reference.
+fn nlms(mic: &[f32], reference: &[f32], taps: usize, hold: usize) -> Vec<f32> {
+    let mut weights = vec![0.0f32; taps];
+    // Most recent reference sample first.
+    let mut window = vec![0.0f32; taps];
+    let mut energy = 0.0f32;
+    let mut held = 0usize;
+    let mut out = Vec::with_capacity(mic.len());
+    for (&d, &x) in mic.iter().zip(reference) {
+        let oldest = window[taps - 1];
+        energy += x * x - oldest * oldest;
+        window.rotate_right(1);
+        window[0] = x;
+
+        let estimate: f32 = weights.iter().zip(&window).map(|(w, x)| w * x).sum();
+        let error = d - estimate;
+        out.push(error);
+
+        let peak = window.iter().fold(0.0f32, |max, x| max.max(x.abs()));
+        if d.abs() > DOUBLE_TALK_RATIO * peak {
+            held = hold;
+        }
+        if held > 0 {
+            held -= 1;
+            continue;
+        }
+        let step = STEP_SIZE * error / (energy.max(0.0) + 1e-6);
+        for (w, x) in weights.iter_mut().zip(&window) {
+            *w += step * x;
+        }
+    }
+    out
+}
+
+/// Every `factor`-th block average of the first `limit` output samples.
+fn decimate(signal: &[f32], factor: usize, limit: usize) -> Vec<f32> {
+    signal
+        .chunks(factor)
+        .take(limit)
+        .map(|block| block.iter().sum::<f32>() / block.len() as f32)
+        .collect()
+}
+
+fn ms_to_samples(ms: u32, sample_rate: u32) -> usize {
+    (u64::from(ms) * u64::from(sample_rate) / 1000) as usize
+}
@@ -0,0 +1,474 @@
+//! Which application has keyboard focus, for per-app rules, and bringing a chosen window to
+//! the front. Best effort: `None` where the platform does not expose it, such as most
+//! Wayland compositors.
+
+use serde::{Deserialize, Serialize};
+
+/// Remote desktop, screen sharing and virtual machine viewers, by their name on each
+/// platform.
+const REMOTE_VIEWERS: &[&str] = &[
+    // Remote desktop
+    "mstsc",
+    "msrdc",
+    "Microsoft Remote Desktop",
+    "Windows App",
+    "remmina",
+    "xfreerdp",
+    "wlfreerdp",
+    "krdc",
+    "vinagre",
+    "Royal TSX",
+    "Jump Desktop",
+    "Citrix Viewer",
+    "wfica32",
+    "CDViewer",
+    // VNC and screen sharing
+    "vncviewer",
+    "tvnviewer",
+    "Screen Sharing",
+    "AnyDesk",
+    "TeamViewer",
+    "rustdesk",
+    "nxplayer",
+    "parsecd",
+    // Virtual machines
+    "vmconnect",
+    "VirtualBoxVM",
+    "VirtualBox VM",
+    "vmware",
+    "vmplayer",
+    "VMware Fusion",
+    "Parallels Desktop",
+    "prl_client_app",
+    "UTM",
+    "virt-viewer",
+    "remote-viewer",
+];
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::process::Command;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrontmostApp {
+    /// Executable or application name, e.g. `zoom`, `Slack`, `code`.
+    pub name: String,
+    pub title: Option<String>,
+    /// Runs with a higher integrity level than VType, as an app started as administrator
+    /// does on Windows. Windows silently drops keys VType sends it.
+    pub elevated: bool,
+}
+
+impl FrontmostApp {
+    /// Case-insensitive comparison with an app name from the settings; `.exe` is ignored so
+    /// the same rule works on every platform.
+    pub fn matches(&self, pattern: &str) -> bool {
+        same_app(&self.name, pattern)
+    }
+
+    pub fn is_remote_viewer(&self) -> bool {
+        REMOTE_VIEWERS.iter().any(|viewer| self.matches(viewer))
+    }
+}
+
+fn same_app(name: &str, pattern: &str) -> bool {
+    let normalize = |name: &str| {
+        let name = name.trim().to_lowercase();
+        name.strip_suffix(".exe")
+            .map(str::to_string)
+            .unwrap_or(name)
+    };
+    normalize(name) == normalize(pattern)
+}
+
+/// Picks a window by part of its title, ignoring case, by its app's name as in
+/// [`FrontmostApp::matches`], or by both.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WindowMatcher {
+    pub title: Option<String>,
+    pub process: Option<String>,
+}
+
+impl WindowMatcher {
+    pub fn validate(&self) -> Result<(), String> {
+        let set = |field: &Option<String>| field.as_deref().is_some_and(|s| !s.trim().is_empty());
+        if set(&self.title) || set(&self.process) {
+            Ok(())
+        } else {
+            Err("A window matcher needs a title or a process".to_string())
+        }
+    }
+
+    pub fn matches(&self, app: &str, title: &str) -> bool {
+        let title_matches = self
+            .title
+            .as_deref()
+            .is_none_or(|part| title.to_lowercase().contains(&part.trim().to_lowercase()));
+        title_matches
+            && self
+                .process
+                .as_deref()
+                .is_none_or(|pattern| same_app(app, pattern))
+    }
+
+    fn describe(&self) -> String {
+        match (&self.title, &self.process) {
+            (Some(title), Some(process)) => format!("\"{}\" in {}", title, process),
+            (Some(title), None) => format!("\"{}\"", title),
+            (None, Some(process)) => process.clone(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+fn no_window(matcher: &WindowMatcher) -> String {
+    format!("No window matches {}", matcher.describe())
+}
+
+/// Brings the first window `matcher` picks to the front and gives it keyboard focus.
+#[cfg(target_os = "linux")]
+pub fn activate(matcher: &WindowMatcher) -> Result<(), String> {
+    matcher.validate()?;
+    if std::env::var_os("DISPLAY").is_none() {
+        return Err("Activating a window needs X11 or XWayland".to_string());
+    }
+    // xdotool narrows the windows down by title, ignoring case; the process is checked here.
+    let pattern = matcher
+        .title
+        .as_deref()
+        .map(|title| regex_escape(title.trim()))
+        .unwrap_or_else(|| ".".to_string());
+    let output = Command::new("xdotool")
+        .args(["search", "--onlyvisible", "--name", &pattern])
+        .output()
+        .map_err(|err| err.to_string())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let window = stdout
+        .lines()
+        .map(str::trim)
+        .find(|window| {
+            matcher.process.as_deref().is_none_or(|pattern| {
+                window_app(window).is_some_and(|name| same_app(&name, pattern))
+            })
+        })
+        .ok_or_else(|| no_window(matcher))?;
+    let status = Command::new("xdotool")
+        .args(["windowactivate", "--sync", window])
+        .status()
+        .map_err(|err| err.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err("xdotool failed to activate the window".to_string())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn window_app(window: &str) -> Option<String> {
+    let output = Command::new("xdotool")
+        .args(["getwindowpid", window])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let pid = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<u32>()
+        .ok()?;
+    let name = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    Some(name.trim().to_string())
+}
+
+/// `text` as a POSIX extended regex matching itself.
+#[cfg(target_os = "linux")]
+fn regex_escape(text: &str) -> String {
+    text.chars().fold(String::new(), |mut escaped, ch| {
+        if "\\.^$|?*+()[]{}".contains(ch) {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+        escaped
+    })
+}
+
+/// Looks through the windows of every app in the Dock, which needs the accessibility
+/// permission. The matcher is passed as arguments rather than spliced into the script.
+#[cfg(target_os = "macos")]
+pub fn activate(matcher: &WindowMatcher) -> Result<(), String> {
+    const SCRIPT: &str = r#"on run {wanted, processName}
+    tell application "System Events"
+        repeat with proc in (processes whose background only is false)
+            if processName is "" or name of proc is processName then
+                repeat with win in windows of proc
+                    if wanted is "" or name of win contains wanted then
+                        set frontmost of proc to true
+                        perform action "AXRaise" of win
+                        return name of proc
+                    end if
+                end repeat
+            end if
+        end repeat
+    end tell
+    return ""
+end run"#;
+
+    matcher.validate()?;
+    let process = matcher.process.as_deref().unwrap_or("").trim();
+    let output = Command::new("osascript")
+        .args(["-e", SCRIPT])
+        .arg(matcher.title.as_deref().unwrap_or("").trim())
+        .arg(process.strip_suffix(".app").unwrap_or(process))
+        .output()
+        .map_err(|err| err.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to activate the window: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    if String::from_utf8_lossy(&output.stdout).trim().is_empty() {
+        return Err(no_window(matcher));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "windows")]
+pub fn activate(matcher: &WindowMatcher) -> Result<(), String> {
+    use windows_sys::Win32::Foundation::{HWND, LPARAM};
+    use windows_sys::Win32::System::Threading::{AttachThreadInput, GetCurrentThreadId};
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        BringWindowToTop, EnumWindows, GetForegroundWindow, GetWindowTextW,
+        GetWindowThreadProcessId, IsIconic, IsWindowVisible, SetForegroundWindow, ShowWindow,
+        SW_RESTORE,
+    };
+
+    unsafe extern "system" fn collect(window: HWND, windows: LPARAM) -> i32 {
+        // SAFETY: `windows` is the vector passed to EnumWindows below, alive for its call.
+        unsafe { (*(windows as *mut Vec<HWND>)).push(window) };
+        1
+    }
+
+    matcher.validate()?;
+    let mut windows: Vec<HWND> = Vec::new();
+    // SAFETY: the callback only pushes onto `windows`, which outlives the call; the other
+    // calls query or focus window handles EnumWindows just returned, with buffers whose
+    // lengths are passed alongside them.
+    unsafe {
+        EnumWindows(Some(collect), &mut windows as *mut Vec<HWND> as LPARAM);
+        let window = windows
+            .into_iter()
+            .find(|&window| {
+                if IsWindowVisible(window) == 0 {
+                    return false;
+                }
+                let mut title = [0u16; 512];
+                let len = GetWindowTextW(window, title.as_mut_ptr(), title.len() as i32);
+                if len <= 0 {
+                    return false;
+                }
+                let mut pid = 0u32;
+                GetWindowThreadProcessId(window, &mut pid);
+                let title = String::from_utf16_lossy(&title[..len as usize]);
+                process_name(pid).is_some_and(|name| matcher.matches(&name, &title))
+            })
+            .ok_or_else(|| no_window(matcher))?;
+        if IsIconic(window) != 0 {
+            ShowWindow(window, SW_RESTORE);
+        }
+        // Windows only lets the foreground thread hand focus on; joining its input queue
+        // for the call makes VType count as that thread.
+        let foreground = GetWindowThreadProcessId(GetForegroundWindow(), std::ptr::null_mut());
+        let own = GetCurrentThreadId();
+        let attached = foreground != own && AttachThreadInput(own, foreground, 1) != 0;
+        BringWindowToTop(window);
+        let focused = SetForegroundWindow(window) != 0;
+        if attached {
+            AttachThreadInput(own, foreground, 0);
+        }
+        if focused {
+            Ok(())
+        } else {
+            Err("Windows refused to bring the window to the front".to_string())
+        }
+    }
+}
+
+/// The executable name of process `pid`, without `.exe`.
+#[cfg(target_os = "windows")]
+fn process_name(pid: u32) -> Option<String> {
+    use std::path::Path;
+
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    // SAFETY: the handle is opened and closed here; the buffer outlives the call and its
+    // length is passed alongside it.
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            return None;
+        }
+        let mut path = [0u16; 1024];
+        let mut path_len = path.len() as u32;
+        let ok = QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            path.as_mut_ptr(),
+            &mut path_len,
+        );
+        CloseHandle(process);
+        if ok == 0 {
+            return None;
+        }
+        let path = String::from_utf16_lossy(&path[..path_len as usize]);
+        Some(Path::new(&path).file_stem()?.to_string_lossy().to_string())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn activate(_matcher: &WindowMatcher) -> Result<(), String> {
+    Err("Activating windows is not supported on this platform".to_string())
+}
+
+#[cfg(target_os = "linux")]
+pub fn detect() -> Option<FrontmostApp> {
+    // xdotool only sees X11 (and XWayland) windows.
+    std::env::var_os("DISPLAY")?;
+    let output = Command::new("xdotool")
+        .args(["getactivewindow", "getwindowpid", "getwindowname"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    let pid = lines.next()?.trim().parse::<u32>().ok()?;
+    let name = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    Some(FrontmostApp {
+        name: name.trim().to_string(),
+        title: lines.next().map(str::to_string),
+        elevated: false,
+    })
+}
+
+#[cfg(target_os = "macos")]
+pub fn detect() -> Option<FrontmostApp> {
+    let output = Command::new("osascript")
+        .args([
+            "-e",
+            "tell application \"System Events\" to get name of first application process whose frontmost is true",
+        ])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    // Window titles need the accessibility permission; the app name is enough for rules.
+    (!name.is_empty()).then_some(FrontmostApp {
+        name,
+        title: None,
+        elevated: false,
+    })
+}
+
+#[cfg(target_os = "windows")]
+pub fn detect() -> Option<FrontmostApp> {
+    use std::path::Path;
+
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentProcess, OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+        PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        GetForegroundWindow, GetWindowTextW, GetWindowThreadProcessId,
+    };
+
+    // SAFETY: plain Win32 queries on handles we obtain here; buffers outlive the calls and
+    // their lengths are passed alongside them.
+    unsafe {
+        let window = GetForegroundWindow();
+        if window.is_null() {
+            return None;
+        }
+        let mut pid = 0u32;
+        GetWindowThreadProcessId(window, &mut pid);
+        let mut title = [0u16; 512];
+        let title_len = GetWindowTextW(window, title.as_mut_ptr(), title.len() as i32);
+
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            return None;
+        }
+        let mut path = [0u16; 1024];
+        let mut path_len = path.len() as u32;
+        let ok = QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            path.as_mut_ptr(),
+            &mut path_len,
+        );
+        let elevated = match (
+            integrity_level(process),
+            integrity_level(GetCurrentProcess()),
+        ) {
+            (Some(target), Some(own)) => target > own,
+            _ => false,
+        };
+        CloseHandle(process);
+        if ok == 0 {
+            return None;
+        }
+        let path = String::from_utf16_lossy(&path[..path_len as usize]);
+        let name = Path::new(&path).file_stem()?.to_string_lossy().to_string();
+        Some(FrontmostApp {
+            name,
+            title: (title_len > 0).then(|| String::from_utf16_lossy(&title[..title_len as usize])),
+            elevated,
+        })
+    }
+}
+
+/// The mandatory integrity level of a process, e.g. `0x2000` for an ordinary app and
+/// `0x3000` for one run as administrator.
+///
+/// # Safety
+///
+/// `process` must be a valid handle opened with `PROCESS_QUERY_LIMITED_INFORMATION`.
+#[cfg(target_os = "windows")]
+unsafe fn integrity_level(process: windows_sys::Win32::Foundation::HANDLE) -> Option<u32> {
+    use windows_sys::Win32::Foundation::CloseHandle;
+    use windows_sys::Win32::Security::{
+        GetSidSubAuthority, GetSidSubAuthorityCount, GetTokenInformation, TokenIntegrityLevel,
+        TOKEN_MANDATORY_LABEL, TOKEN_QUERY,
+    };
+    use windows_sys::Win32::System::Threading::OpenProcessToken;
+
+    let mut token = std::ptr::null_mut();
+    if OpenProcessToken(process, TOKEN_QUERY, &mut token) == 0 {
+        return None;
+    }
+    // The label is followed by the SID it points to; u64s keep the pointer aligned.
+    let mut buffer = [0u64; 8];
+    let mut length = 0;
+    let ok = GetTokenInformation(
+        token,
+        TokenIntegrityLevel,
+        buffer.as_mut_ptr().cast(),
+        std::mem::size_of_val(&buffer) as u32,
+        &mut length,
+    );
+    CloseHandle(token);
+    if ok == 0 {
+        return None;
+    }
+    let label = &*(buffer.as_ptr() as *const TOKEN_MANDATORY_LABEL);
+    let count = u32::from(*GetSidSubAuthorityCount(label.Label.Sid));
+    Some(*GetSidSubAuthority(label.Label.Sid, count.checked_sub(1)?))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn detect() -> Option<FrontmostApp> {
+    None
+}
@@ -0,0 +1,784 @@
+//! Transcription history in SQLite, with an FTS5 index over the text for search.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::settings::HistorySettings;
+use crate::{audio, opus, redact};
+
+pub const HISTORY_FILE: &str = "history.sqlite3";
+/// Retained recordings live here, next to the database.
+pub const AUDIO_DIR: &str = "audio";
+/// Marks around matched terms in search snippets. Control characters never occur in
+/// transcripts, so the UI can split on them without escaping.
+pub const MATCH_START: &str = "\u{2}";
+pub const MATCH_END: &str = "\u{3}";
+pub const DEFAULT_LIMIT: u32 = 50;
+pub const MAX_LIMIT: u32 = 500;
+/// Entries the quick picker lists unless asked for another number.
+pub const QUICK_PICK_LIMIT: u32 = 10;
+pub const QUICK_PICK_LABEL_CHARS: usize = 80;
+const SNIPPET_TOKENS: u32 = 16;
+/// Typing speed assumed when estimating time saved.
+pub const TYPING_WPM: f64 = 40.0;
+
+/// Schema upgrades applied in order; the count applied is kept in `PRAGMA user_version`.
+/// Append only; never edit a shipped step.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE entries (
+        id INTEGER PRIMARY KEY,
+        created_at INTEGER NOT NULL,
+        text TEXT NOT NULL,
+        backend TEXT NOT NULL,
+        language TEXT,
+        profile TEXT
+    );
+    CREATE INDEX entries_created_at ON entries(created_at);
+    CREATE VIRTUAL TABLE entries_fts USING fts5(
+        text,
+        content = 'entries',
+        content_rowid = 'id',
+        tokenize = 'unicode61 remove_diacritics 2'
+    );
+    CREATE TRIGGER entries_ai AFTER INSERT ON entries BEGIN
+        INSERT INTO entries_fts(rowid, text) VALUES (new.id, new.text);
+    END;
+    CREATE TRIGGER entries_ad AFTER DELETE ON entries BEGIN
+        INSERT INTO entries_fts(entries_fts, rowid, text) VALUES ('delete', old.id, old.text);
+    END;
+    CREATE TRIGGER entries_au AFTER UPDATE OF text ON entries BEGIN
+        INSERT INTO entries_fts(entries_fts, rowid, text) VALUES ('delete', old.id, old.text);
+        INSERT INTO entries_fts(rowid, text) VALUES (new.id, new.text);
+    END;
+",
+    "
+    ALTER TABLE entries ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;
+    CREATE INDEX entries_pinned ON entries(pinned) WHERE pinned = 1;
+",
+    "
+    ALTER TABLE entries ADD COLUMN audio_ms INTEGER;
+    ALTER TABLE entries ADD COLUMN latency_ms INTEGER;
+    -- Kept separately so pruning and clearing entries leave the totals intact.
+    CREATE TABLE daily_usage (
+        day INTEGER PRIMARY KEY,
+        utterances INTEGER NOT NULL,
+        words INTEGER NOT NULL,
+        audio_ms INTEGER NOT NULL,
+        latency_ms INTEGER NOT NULL
+    );
+",
+    "
+    ALTER TABLE entries ADD COLUMN audio_file TEXT;
+    CREATE TABLE revisions (
+        id INTEGER PRIMARY KEY,
+        entry_id INTEGER NOT NULL REFERENCES entries(id) ON DELETE CASCADE,
+        created_at INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        text TEXT NOT NULL,
+        backend TEXT
+    );
+    CREATE INDEX revisions_entry ON revisions(entry_id);
+",
+    "
+    ALTER TABLE entries ADD COLUMN pasted_text TEXT;
+",
+    "
+    ALTER TABLE entries ADD COLUMN fallback TEXT;
+",
+    "
+    ALTER TABLE entries ADD COLUMN model TEXT;
+",
+];
+
+const ENTRY_COLUMNS: &str =
+    "e.id, e.created_at, e.text, e.backend, e.language, e.profile, e.pinned, e.audio_ms, \
+     e.latency_ms, e.audio_file IS NOT NULL, e.pasted_text, e.fallback, \
+     e.model";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub id: i64,
+    /// Unix time in milliseconds.
+    pub created_at: i64,
+    pub text: String,
+    pub backend: String,
+    pub language: Option<String>,
+    pub profile: Option<String>,
+    /// Kept at hand for reuse (addresses, boilerplate replies).
+    pub pinned: bool,
+    /// Length of the recording, when it could be read from the WAV.
+    pub audio_ms: Option<i64>,
+    /// Time spent in the backend.
+    pub latency_ms: Option<i64>,
+    /// Whether the recording was retained and can be re-transcribed.
+    pub has_audio: bool,
+    /// What reached the focused app, when only part of the text was pasted or it was
+    /// changed first.
+    pub pasted_text: Option<String>,
+    /// What the worker fell back to after running out of memory or failing to load the
+    /// model, such as `smaller` or `cpu`.
+    pub fallback: Option<String>,
+    /// Such as `nemo-parakeet-tdt-0.6b-v3 (int8)`; `None` for entries from before models were
+    /// recorded.
+    pub model: Option<String>,
+}
+
+/// A past transcript offered for pasting again, as the quick picker lists it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuickPick {
+    pub id: i64,
+    /// Unix time in milliseconds.
+    pub created_at: i64,
+    /// The first line, shortened to [`QUICK_PICK_LABEL_CHARS`].
+    pub label: String,
+    pub words: usize,
+    pub pinned: bool,
+}
+
+/// Why a revision of an entry exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RevisionKind {
+    /// The backend output as first recorded, kept when the user corrects the entry.
+    Original,
+    /// An earlier user correction, superseded by a later one.
+    Edit,
+    /// The retained audio was run through a backend again.
+    Retranscription,
+}
+
+impl RevisionKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            RevisionKind::Original => "original",
+            RevisionKind::Edit => "edit",
+            RevisionKind::Retranscription => "retranscription",
+        }
+    }
+
+    fn parse(value: &str) -> rusqlite::Result<RevisionKind> {
+        match value {
+            "original" => Ok(RevisionKind::Original),
+            "edit" => Ok(RevisionKind::Edit),
+            "retranscription" => Ok(RevisionKind::Retranscription),
+            other => Err(rusqlite::Error::InvalidColumnType(
+                3,
+                format!("revision kind {}", other),
+                rusqlite::types::Type::Text,
+            )),
+        }
+    }
+}
+
+/// An alternative text for a history entry, linked to it and deleted with it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Revision {
+    pub id: i64,
+    pub entry_id: i64,
+    pub created_at: i64,
+    pub kind: RevisionKind,
+    pub text: String,
+    pub backend: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub entry: HistoryEntry,
+    /// Excerpt around the best match, with matches wrapped in [`MATCH_START`]/[`MATCH_END`].
+    pub snippet: String,
+    /// BM25 score; lower is a better match.
+    pub rank: f64,
+}
+
+/// A finished dictation as the pipeline reports it.
+#[derive(Debug, Clone)]
+pub struct NewEntry {
+    pub text: String,
+    pub backend: String,
+    pub language: Option<String>,
+    pub profile: Option<String>,
+    pub audio_ms: Option<u64>,
+    pub latency_ms: u64,
+    pub fallback: Option<String>,
+    pub model: Option<String>,
+}
+
+/// Dictation totals over some period.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageSummary {
+    pub utterances: u64,
+    pub words: u64,
+    pub audio_seconds: f64,
+    pub average_latency_ms: f64,
+    /// Typing the same words at [`TYPING_WPM`], minus the time spent speaking and waiting.
+    pub estimated_seconds_saved: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyUsage {
+    /// Start of the UTC day in Unix milliseconds.
+    pub day_start: i64,
+    #[serde(flatten)]
+    pub usage: UsageSummary,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageStats {
+    pub days: Vec<DailyUsage>,
+    pub total: UsageSummary,
+}
+
+/// A span of time in Unix milliseconds; an open end is unbounded.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TimeRange {
+    /// Inclusive.
+    pub from: Option<i64>,
+    /// Exclusive.
+    pub to: Option<i64>,
+}
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+static HISTORY: OnceLock<Mutex<Connection>> = OnceLock::new();
+static AUDIO_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Opens (or creates) the history database in `dir` and brings its schema up to date.
+pub fn init(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    let mut db = Connection::open(dir.join(HISTORY_FILE)).map_err(|err| err.to_string())?;
+    db.pragma_update(None, "journal_mode", "WAL")
+        .map_err(|err| err.to_string())?;
+    // Deleted dictations are overwritten rather than left in free pages.
+    db.pragma_update(None, "secure_delete", true)
+        .map_err(|err| err.to_string())?;
+    db.pragma_update(None, "foreign_keys", true)
+        .map_err(|err| err.to_string())?;
+    migrate(&mut db).map_err(|err| format!("History migration failed: {}", err))?;
+    let _ = AUDIO_PATH.set(dir.join(AUDIO_DIR));
+    HISTORY
+        .set(Mutex::new(db))
+        .map_err(|_| "History already initialized".to_string())
+}
+
+fn migrate(db: &mut Connection) -> rusqlite::Result<()> {
+    let applied: i64 = db.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, sql) in MIGRATIONS.iter().enumerate().skip(applied as usize) {
+        let tx = db.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", index as i64 + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn with_db<T>(f: impl FnOnce(&Connection) -> rusqlite::Result<T>) -> Result<T, String> {
+    let db = HISTORY
+        .get()
+        .ok_or_else(|| "History not initialized".to_string())?
+        .lock()
+        .map_err(|_| "History lock poisoned".to_string())?;
+    f(&db).map_err(|err| err.to_string())
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+fn entry_from_row(row: &Row) -> rusqlite::Result<HistoryEntry> {
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        created_at: row.get(1)?,
+        text: row.get(2)?,
+        backend: row.get(3)?,
+        language: row.get(4)?,
+        profile: row.get(5)?,
+        pinned: row.get(6)?,
+        audio_ms: row.get(7)?,
+        latency_ms: row.get(8)?,
+        has_audio: row.get(9)?,
+        pasted_text: row.get(10)?,
+        fallback: row.get(11)?,
+        model: row.get(12)?,
+    })
+}
+
+/// Stores a dictation, adds it to the day's usage totals and returns its id.
+pub fn record(entry: NewEntry) -> Result<i64, String> {
+    let text = redact::stored(&entry.text);
+    let created_at = now_ms();
+    let words = entry.text.split_whitespace().count() as i64;
+    let audio_ms = entry.audio_ms.map(|ms| ms as i64);
+    let latency_ms = entry.latency_ms as i64;
+    with_db(|db| {
+        let tx = db.unchecked_transaction()?;
+        tx.prepare_cached(
+            "INSERT INTO entries
+                 (created_at, text, backend, language, profile, audio_ms, latency_ms, fallback,
+                  model)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        )?
+        .execute(params![
+            created_at,
+            text,
+            entry.backend,
+            entry.language,
+            entry.profile,
+            audio_ms,
+            latency_ms,
+            entry.fallback,
+            entry.model
+        ])?;
+        let id = tx.last_insert_rowid();
+        tx.prepare_cached(
+            "INSERT INTO daily_usage (day, utterances, words, audio_ms, latency_ms)
+             VALUES (?1, 1, ?2, ?3, ?4)
+             ON CONFLICT(day) DO UPDATE SET
+                 utterances = utterances + 1,
+                 words = words + excluded.words,
+                 audio_ms = audio_ms + excluded.audio_ms,
+                 latency_ms = latency_ms + excluded.latency_ms",
+        )?
+        .execute(params![
+            created_at.div_euclid(DAY_MS),
+            words,
+            audio_ms.unwrap_or(0),
+            latency_ms
+        ])?;
+        tx.commit()?;
+        Ok(id)
+    })
+}
+
+/// Newest entries first; pass the last id seen as `before` to page further back.
+pub fn recent(limit: Option<u32>, before: Option<i64>) -> Result<Vec<HistoryEntry>, String> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    with_db(|db| {
+        let mut stmt = db.prepare_cached(&format!(
+            "SELECT {} FROM entries e WHERE ?1 IS NULL OR e.id < ?1 ORDER BY e.id DESC LIMIT ?2",
+            ENTRY_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![before, limit], entry_from_row)?;
+        rows.collect()
+    })
+}
+
+/// The newest `limit` distinct transcripts, each as its latest entry, for picking one to
+/// paste again.
+pub fn quick_pick(limit: Option<u32>) -> Result<Vec<QuickPick>, String> {
+    let limit = limit.unwrap_or(QUICK_PICK_LIMIT).clamp(1, MAX_LIMIT);
+    let entries: Vec<HistoryEntry> = with_db(|db| {
+        let mut stmt = db.prepare_cached(&format!(
+            "SELECT {} FROM entries e WHERE e.id IN
+                 (SELECT MAX(id) FROM entries WHERE trim(text) != '' GROUP BY text)
+             ORDER BY e.id DESC LIMIT ?1",
+            ENTRY_COLUMNS
+        ))?;
+        let rows = stmt.query_map([limit], entry_from_row)?;
+        rows.collect()
+    })?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| QuickPick {
+            id: entry.id,
+            created_at: entry.created_at,
+            label: label(&entry.text, QUICK_PICK_LABEL_CHARS),
+            words: entry.text.split_whitespace().count(),
+            pinned: entry.pinned,
+        })
+        .collect())
+}
+
+/// The first line of `text`, cut to `max_chars` with an ellipsis when anything is left out.
+pub fn label(text: &str, max_chars: usize) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    if line.chars().count() > max_chars || line.len() < text.len() {
+        let short: String = line.chars().take(max_chars).collect();
+        format!("{}…", short.trim_end())
+    } else {
+        line.to_string()
+    }
+}
+
+/// Notes what was pasted of an entry, such as a selected excerpt; the entry keeps its full
+/// text. Pasting the text unchanged clears the note.
+pub fn record_paste(id: i64, pasted: &str) -> Result<(), String> {
+    let pasted = redact::stored(pasted);
+    let changed = with_db(|db| {
+        db.prepare_cached(
+            "UPDATE entries SET pasted_text = CASE WHEN text = ?2 THEN NULL ELSE ?2 END
+             WHERE id = ?1",
+        )?
+        .execute(params![id, pasted])
+    })?;
+    if changed == 0 {
+        return Err(format!("No history entry {}", id));
+    }
+    Ok(())
+}
+
+pub fn set_pinned(id: i64, pinned: bool) -> Result<(), String> {
+    let changed = with_db(|db| {
+        db.prepare_cached("UPDATE entries SET pinned = ?2 WHERE id = ?1")?
+            .execute(params![id, pinned])
+    })?;
+    if changed == 0 {
+        return Err(format!("No history entry {}", id));
+    }
+    Ok(())
+}
+
+/// Pinned entries, most recently dictated first.
+pub fn pinned() -> Result<Vec<HistoryEntry>, String> {
+    with_db(|db| {
+        let mut stmt = db.prepare_cached(&format!(
+            "SELECT {} FROM entries e WHERE e.pinned = 1 ORDER BY e.id DESC",
+            ENTRY_COLUMNS
+        ))?;
+        let rows = stmt.query_map([], entry_from_row)?;
+        rows.collect()
+    })
+}
+
+pub fn get(id: i64) -> Result<HistoryEntry, String> {
+    with_db(|db| {
+        db.prepare_cached(&format!(
+            "SELECT {} FROM entries e WHERE e.id = ?1",
+            ENTRY_COLUMNS
+        ))?
+        .query_row([id], entry_from_row)
+    })
+    .map_err(|err| format!("No history entry {}: {}", id, err))
+}
+
+/// Applies the retention limits and returns how many entries were deleted.
+pub fn prune(limits: &HistorySettings) -> Result<usize, String> {
+    let cutoff = limits
+        .max_age_days
+        .map(|days| now_ms() - i64::from(days) * DAY_MS);
+    let removed = with_db(|db| {
+        let mut removed = Vec::new();
+        if let Some(cutoff) = cutoff {
+            removed.extend(delete_returning_audio(
+                db,
+                "DELETE FROM entries WHERE pinned = 0 AND created_at < ?1 RETURNING audio_file",
+                [cutoff],
+            )?);
+        }
+        if let Some(max) = limits.max_entries {
+            removed.extend(delete_returning_audio(
+                db,
+                "DELETE FROM entries WHERE pinned = 0 AND id NOT IN
+                 (SELECT id FROM entries WHERE pinned = 0 ORDER BY id DESC LIMIT ?1)
+                 RETURNING audio_file",
+                [max],
+            )?);
+        }
+        Ok(removed)
+    })?;
+    Ok(remove_audio_files(removed))
+}
+
+/// Deletes every entry in `range`, pinned or not, and returns how many were removed.
+pub fn clear(range: TimeRange) -> Result<usize, String> {
+    let removed = with_db(|db| {
+        delete_returning_audio(
+            db,
+            "DELETE FROM entries
+             WHERE (?1 IS NULL OR created_at >= ?1) AND (?2 IS NULL OR created_at < ?2)
+             RETURNING audio_file",
+            params![range.from, range.to],
+        )
+    })?;
+    Ok(remove_audio_files(removed))
+}
+
+fn delete_returning_audio<P: rusqlite::Params>(
+    db: &Connection,
+    sql: &str,
+    params: P,
+) -> rusqlite::Result<Vec<Option<String>>> {
+    let mut stmt = db.prepare_cached(sql)?;
+    let rows = stmt.query_map(params, |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Removes the retained recordings of deleted entries; returns how many entries that was.
+fn remove_audio_files(removed: Vec<Option<String>>) -> usize {
+    if let Some(dir) = AUDIO_PATH.get() {
+        for file in removed.iter().flatten() {
+            let _ = fs::remove_file(dir.join(file));
+        }
+    }
+    removed.len()
+}
+
+/// Keeps the recording for entry `id` so it can be played back or re-transcribed. Clips
+/// are stored as Ogg Opus; a WAV that cannot be decoded is kept as is.
+pub fn save_audio(id: i64, wav: &[u8]) -> Result<(), String> {
+    let dir = AUDIO_PATH
+        .get()
+        .ok_or_else(|| "History not initialized".to_string())?;
+    fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    let encoded = audio::decode_wav(wav)
+        .ok_or_else(|| "Unsupported WAV encoding".to_string())
+        .and_then(|(samples, rate)| opus::encode(&samples, rate));
+    let (file, bytes) = match encoded {
+        Ok(ogg) => (format!("{}.opus", id), ogg),
+        Err(err) => {
+            let _ = crate::log::write(format!("Keeping entry {} audio as WAV: {}", id, err));
+            (format!("{}.wav", id), wav.to_vec())
+        }
+    };
+    crate::safe_io::write(&dir.join(&file), bytes).map_err(|err| err.to_string())?;
+    with_db(|db| {
+        db.prepare_cached("UPDATE entries SET audio_file = ?2 WHERE id = ?1")?
+            .execute(params![id, file])
+    })?;
+    Ok(())
+}
+
+/// The retained recording for entry `id` as a WAV, for the backends.
+pub fn load_audio(id: i64) -> Result<Vec<u8>, String> {
+    let (file, bytes) = stored_audio(id)?;
+    if !file.ends_with(".opus") {
+        return Ok(bytes);
+    }
+    let (samples, rate) = opus::decode(&bytes)?;
+    Ok(audio::wav_from_f32(&samples, rate))
+}
+
+/// The retained recording for entry `id` as stored, Ogg Opus or WAV, for playback.
+pub fn entry_audio(id: i64) -> Result<Vec<u8>, String> {
+    stored_audio(id).map(|(_, bytes)| bytes)
+}
+
+fn stored_audio(id: i64) -> Result<(String, Vec<u8>), String> {
+    let file: Option<String> = with_db(|db| {
+        db.prepare_cached("SELECT audio_file FROM entries WHERE id = ?1")?
+            .query_row([id], |row| row.get(0))
+    })
+    .map_err(|err| format!("No history entry {}: {}", id, err))?;
+    let file = file.ok_or_else(|| format!("No audio was kept for history entry {}", id))?;
+    let dir = AUDIO_PATH
+        .get()
+        .ok_or_else(|| "History not initialized".to_string())?;
+    let bytes = fs::read(dir.join(&file)).map_err(|err| err.to_string())?;
+    Ok((file, bytes))
+}
+
+pub fn add_revision(
+    entry_id: i64,
+    kind: RevisionKind,
+    text: &str,
+    backend: Option<&str>,
+) -> Result<Revision, String> {
+    let text = redact::stored(text);
+    let created_at = now_ms();
+    let id = with_db(|db| {
+        db.prepare_cached(
+            "INSERT INTO revisions (entry_id, created_at, kind, text, backend)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?
+        .execute(params![entry_id, created_at, kind.as_str(), &text, backend])?;
+        Ok(db.last_insert_rowid())
+    })?;
+    Ok(Revision {
+        id,
+        entry_id,
+        created_at,
+        kind,
+        text,
+        backend: backend.map(str::to_string),
+    })
+}
+
+/// Replaces an entry's text with the user's correction. The text it replaces is kept as a
+/// revision: [`RevisionKind::Original`] the first time, [`RevisionKind::Edit`] after that.
+pub fn update_text(id: i64, text: &str) -> Result<HistoryEntry, String> {
+    let text = redact::stored(text.trim());
+    if text.is_empty() {
+        return Err("Corrected text must not be empty".to_string());
+    }
+    with_db(|db| {
+        let tx = db.unchecked_transaction()?;
+        let previous: String = tx
+            .prepare_cached("SELECT text FROM entries WHERE id = ?1")?
+            .query_row([id], |row| row.get(0))?;
+        if previous != text {
+            let edited_before: bool = tx
+                .prepare_cached(
+                    "SELECT EXISTS(SELECT 1 FROM revisions WHERE entry_id = ?1 AND kind = ?2)",
+                )?
+                .query_row(params![id, RevisionKind::Original.as_str()], |row| {
+                    row.get(0)
+                })?;
+            let kind = if edited_before {
+                RevisionKind::Edit
+            } else {
+                RevisionKind::Original
+            };
+            tx.prepare_cached(
+                "INSERT INTO revisions (entry_id, created_at, kind, text) VALUES (?1, ?2, ?3, ?4)",
+            )?
+            .execute(params![id, now_ms(), kind.as_str(), previous])?;
+            tx.prepare_cached("UPDATE entries SET text = ?2 WHERE id = ?1")?
+                .execute(params![id, text])?;
+        }
+        tx.commit()
+    })
+    .map_err(|err| format!("Failed to update history entry {}: {}", id, err))?;
+    get(id)
+}
+
+/// `(original backend output, current text)` for every entry the user has corrected.
+pub fn corrections() -> Result<Vec<(String, String)>, String> {
+    with_db(|db| {
+        let mut stmt = db.prepare_cached(
+            "SELECT r.text, e.text FROM revisions r JOIN entries e ON e.id = r.entry_id
+             WHERE r.kind = ?1",
+        )?;
+        let rows = stmt.query_map([RevisionKind::Original.as_str()], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+        rows.collect()
+    })
+}
+
+/// Revisions of entry `entry_id`, oldest first.
+pub fn revisions(entry_id: i64) -> Result<Vec<Revision>, String> {
+    with_db(|db| {
+        let mut stmt = db.prepare_cached(
+            "SELECT id, entry_id, created_at, kind, text, backend FROM revisions
+             WHERE entry_id = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map([entry_id], |row| {
+            Ok(Revision {
+                id: row.get(0)?,
+                entry_id: row.get(1)?,
+                created_at: row.get(2)?,
+                kind: RevisionKind::parse(&row.get::<_, String>(3)?)?,
+                text: row.get(4)?,
+                backend: row.get(5)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+/// Per-day usage for the UTC days overlapping `range`, oldest first, plus the overall total.
+pub fn usage_stats(range: TimeRange) -> Result<UsageStats, String> {
+    let from_day = range.from.map(|from| from.div_euclid(DAY_MS));
+    let to_day = range.to.map(|to| (to + DAY_MS - 1).div_euclid(DAY_MS));
+    let rows = with_db(|db| {
+        let mut stmt = db.prepare_cached(
+            "SELECT day, utterances, words, audio_ms, latency_ms FROM daily_usage
+             WHERE (?1 IS NULL OR day >= ?1) AND (?2 IS NULL OR day < ?2)
+             ORDER BY day",
+        )?;
+        let rows = stmt.query_map(params![from_day, to_day], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                [
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, i64>(4)?,
+                ],
+            ))
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+    })?;
+
+    let mut sums = [0i64; 4];
+    let days = rows
+        .into_iter()
+        .map(|(day, counts)| {
+            sums.iter_mut().zip(counts).for_each(|(sum, n)| *sum += n);
+            DailyUsage {
+                day_start: day * DAY_MS,
+                usage: summarize(counts),
+            }
+        })
+        .collect();
+    Ok(UsageStats {
+        days,
+        total: summarize(sums),
+    })
+}
+
+fn summarize([utterances, words, audio_ms, latency_ms]: [i64; 4]) -> UsageSummary {
+    let typing_seconds = words as f64 / TYPING_WPM * 60.0;
+    let dictating_seconds = (audio_ms + latency_ms) as f64 / 1000.0;
+    UsageSummary {
+        utterances: utterances as u64,
+        words: words as u64,
+        audio_seconds: audio_ms as f64 / 1000.0,
+        average_latency_ms: if utterances > 0 {
+            latency_ms as f64 / utterances as f64
+        } else {
+            0.0
+        },
+        estimated_seconds_saved: (typing_seconds - dictating_seconds).max(0.0),
+    }
+}
+
+/// Full-text search, best matches first. Every word must match; the last may be a prefix
+/// so results update while the user is typing.
+pub fn search(query: &str, limit: Option<u32>) -> Result<Vec<SearchHit>, String> {
+    let Some(fts_query) = fts_query(query) else {
+        return Ok(Vec::new());
+    };
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    with_db(|db| {
+        let mut stmt = db.prepare_cached(&format!(
+            "SELECT {}, snippet(entries_fts, 0, ?2, ?3, '…', ?4), bm25(entries_fts)
+             FROM entries_fts JOIN entries e ON e.id = entries_fts.rowid
+             WHERE entries_fts MATCH ?1
+             ORDER BY bm25(entries_fts)
+             LIMIT ?5",
+            ENTRY_COLUMNS
+        ))?;
+        let rows = stmt.query_map(
+            params![fts_query, MATCH_START, MATCH_END, SNIPPET_TOKENS, limit],
+            |row| {
+                Ok(SearchHit {
+                    entry: entry_from_row(row)?,
+                    snippet: row.get(11)?,
+                    rank: row.get(12)?,
+                })
+            },
+        )?;
+        rows.collect()
+    })
+}
+
+/// Turns free text into an FTS5 query: each word becomes a quoted phrase so user input
+/// can never be parsed as query syntax. `None` when there is nothing to search for.
+fn fts_query(query: &str) -> Option<String> {
+    let words: Vec<String> = query
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+    let last = words.last()?;
+    let mut terms = words[..words.len() - 1].to_vec();
+    terms.push(format!("{}*", last));
+    Some(terms.join(" "))
+}
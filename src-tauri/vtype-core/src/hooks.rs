@@ -0,0 +1,186 @@
+//! User scripts run at points of a dictation, as set in `hooks`. Each is a shell command
+//! that gets the text on its stdin and in `VTYPE_TEXT`, and the hook's name in
+//! `VTYPE_HOOK`. Unless `appContext` is off, the focused app's name and window title are in
+//! `VTYPE_APP` and `VTYPE_WINDOW_TITLE` as well. A hook still running after
+//! `hooks.timeoutMs` is stopped. Only the pre-paste hook is waited for, since it can change
+//! the text or keep it from being pasted.
+
+use std::io::{Read, Write};
+use std::process::{Child, ExitStatus, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::frontmost::{self, FrontmostApp};
+use crate::settings::{self, HookSettings, Settings};
+use crate::{log, process};
+
+/// How often a running hook is checked on.
+const POLL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    PostTranscribe,
+    PrePaste,
+    PostPaste,
+}
+
+impl Hook {
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::PostTranscribe => "post-transcribe",
+            Hook::PrePaste => "pre-paste",
+            Hook::PostPaste => "post-paste",
+        }
+    }
+
+    fn command(self, hooks: &HookSettings) -> Option<&str> {
+        match self {
+            Hook::PostTranscribe => hooks.post_transcribe.as_deref(),
+            Hook::PrePaste => hooks.pre_paste.as_deref(),
+            Hook::PostPaste => hooks.post_paste.as_deref(),
+        }
+        .filter(|command| !command.trim().is_empty())
+    }
+}
+
+/// How a hook ended.
+enum Outcome {
+    Exited { success: bool, stdout: String },
+    TimedOut,
+}
+
+/// `text` as the pre-paste hook leaves it: replaced by what the hook prints, the same when
+/// it prints nothing, and `None` when it exits with an error to veto the paste. A hook
+/// that cannot be run or runs out of time is logged and the text pasted unchanged, so a
+/// broken script does not lose dictations.
+pub fn pre_paste(text: &str) -> Option<String> {
+    let settings = settings::current();
+    let Some(command) = Hook::PrePaste.command(&settings.hooks) else {
+        return Some(text.to_string());
+    };
+    let app = context(&settings);
+    match run(
+        Hook::PrePaste,
+        command,
+        text,
+        app.as_ref(),
+        timeout(&settings.hooks),
+    ) {
+        Ok(Outcome::Exited { success: false, .. }) => None,
+        Ok(Outcome::Exited { stdout, .. }) if !stdout.is_empty() => {
+            // Scripts end what they print with a line break the text did not have.
+            let stdout = stdout.strip_suffix('\n').unwrap_or(&stdout);
+            Some(stdout.strip_suffix('\r').unwrap_or(stdout).to_string())
+        }
+        Ok(Outcome::Exited { .. }) => Some(text.to_string()),
+        Ok(Outcome::TimedOut) => {
+            failed(Hook::PrePaste, "timed out");
+            Some(text.to_string())
+        }
+        Err(err) => {
+            failed(Hook::PrePaste, &err);
+            Some(text.to_string())
+        }
+    }
+}
+
+/// Runs `hook` with `text` on a thread of its own, if one is set; what it prints is
+/// ignored.
+pub fn notify(hook: Hook, text: &str) {
+    let settings = settings::current();
+    let Some(command) = hook.command(&settings.hooks).map(str::to_string) else {
+        return;
+    };
+    let text = text.to_string();
+    std::thread::spawn(move || {
+        let app = context(&settings);
+        let timeout = timeout(&settings.hooks);
+        match run(hook, &command, &text, app.as_ref(), timeout) {
+            Ok(Outcome::Exited { success: false, .. }) => failed(hook, "exited with an error"),
+            Ok(Outcome::Exited { .. }) => {}
+            Ok(Outcome::TimedOut) => failed(hook, "timed out"),
+            Err(err) => failed(hook, &err),
+        }
+    });
+}
+
+/// The focused app, when the settings allow hooks to know it.
+fn context(settings: &Settings) -> Option<FrontmostApp> {
+    settings.app_context.then(frontmost::detect).flatten()
+}
+
+fn timeout(hooks: &HookSettings) -> Duration {
+    Duration::from_millis(hooks.timeout_ms)
+}
+
+fn failed(hook: Hook, err: &str) {
+    let _ = log::write(format!("The {} hook {}", hook.name(), err));
+}
+
+fn run(
+    hook: Hook,
+    command: &str,
+    text: &str,
+    app: Option<&FrontmostApp>,
+    timeout: Duration,
+) -> Result<Outcome, String> {
+    let mut shell = process::shell(command);
+    process::configure_background_command(&mut shell);
+    if let Some(app) = app {
+        shell.env("VTYPE_APP", &app.name);
+        if let Some(title) = app.title.as_deref() {
+            shell.env("VTYPE_WINDOW_TITLE", title);
+        }
+    }
+    let mut child = shell
+        .env("VTYPE_TEXT", text)
+        .env("VTYPE_HOOK", hook.name())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("failed to start: {}", err))?;
+    // Written and read on threads of their own so a hook that fills a pipe before reading
+    // or writing the other cannot stall it.
+    if let Some(mut stdin) = child.stdin.take() {
+        let text = text.to_string();
+        // A hook that ignores its input may close it before this is written.
+        std::thread::spawn(move || stdin.write_all(text.as_bytes()));
+    }
+    let (output, read) = mpsc::channel();
+    if let Some(mut stdout) = child.stdout.take() {
+        std::thread::spawn(move || {
+            let mut bytes = Vec::new();
+            let _ = stdout.read_to_end(&mut bytes);
+            let _ = output.send(bytes);
+        });
+    }
+    let deadline = Instant::now() + timeout;
+    let Some(status) = wait(&mut child, deadline)? else {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Ok(Outcome::TimedOut);
+    };
+    // Something the hook started in the background may hold its output open.
+    let stdout = read
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()).max(POLL))
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .unwrap_or_default();
+    Ok(Outcome::Exited {
+        success: status.success(),
+        stdout,
+    })
+}
+
+/// The exit status, or `None` when `child` is still running at `deadline`.
+fn wait(child: &mut Child, deadline: Instant) -> Result<Option<ExitStatus>, String> {
+    loop {
+        if let Some(status) = child.try_wait().map_err(|err| err.to_string())? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Ok(None);
+        }
+        std::thread::sleep(POLL);
+    }
+}
@@ -0,0 +1,147 @@
+//! The little HTTP/1.1 the local servers need: one request per connection, read whole,
+//! and one response, after which the connection closes.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+const MAX_HEAD_BYTES: usize = 8 * 1024;
+/// Larger bodies are turned down.
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Default)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Decoded query parameters.
+    pub query: BTreeMap<String, String>,
+    /// Keyed by lowercase name.
+    pub headers: BTreeMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+/// Reads a request from `stream`, with the body its `Content-Length` gives. Nothing past
+/// the body is read, so a WebSocket that follows is left for [`crate::websocket`] whole.
+pub fn read_request<R: Read>(stream: &mut R) -> Result<Request, String> {
+    let mut head = Vec::new();
+    let line = read_line(stream, &mut head)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(format!("Not an HTTP request: {}", line.trim()));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(name, value)| (percent_decode(name), percent_decode(value)))
+            .collect(),
+        ..Request::default()
+    };
+    loop {
+        let line = read_line(stream, &mut head)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            request
+                .headers
+                .insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+    let len = match request.header("content-length") {
+        Some(len) => len
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid Content-Length: {}", len))?,
+        None => 0,
+    };
+    if len > MAX_BODY_BYTES {
+        return Err("Request body too large".to_string());
+    }
+    request.body = vec![0; len];
+    stream
+        .read_exact(&mut request.body)
+        .map_err(|err| err.to_string())?;
+    Ok(request)
+}
+
+/// The next line of the head, a byte at a time so none of what follows it is taken. `head`
+/// holds the whole head so far, to keep it under [`MAX_HEAD_BYTES`].
+fn read_line<R: Read>(stream: &mut R, head: &mut Vec<u8>) -> Result<String, String> {
+    let start = head.len();
+    let mut byte = [0u8; 1];
+    while head.last() != Some(&b'\n') || head.len() == start {
+        if head.len() >= MAX_HEAD_BYTES {
+            return Err("Request head too large".to_string());
+        }
+        match stream.read(&mut byte) {
+            Ok(0) => return Err("Request ended before its headers".to_string()),
+            Ok(_) => head.push(byte[0]),
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+    Ok(String::from_utf8_lossy(&head[start..]).into_owned())
+}
+
+/// Answers with `status`, such as `200 OK`, and `body`.
+pub fn respond<W: Write>(
+    stream: &mut W,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> Result<(), String> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )
+    .and_then(|()| stream.write_all(body))
+    .map_err(|err| err.to_string())
+}
+
+/// Turns the request down with an HTTP error such as `401 Unauthorized`.
+pub fn reject<W: Write>(stream: &mut W, status: &str) {
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        status
+    );
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes
+            .get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                index += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
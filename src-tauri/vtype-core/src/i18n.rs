@@ -0,0 +1,206 @@
+//! Translations of the messages the backend shows users, keyed by code. Errors are
+//! written `CODE: message`, so the UI can still recognise one whatever the language. The
+//! language is `locale` from the settings, or else the system's.
+
+use crate::settings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    De,
+    Fr,
+    Es,
+}
+
+impl Locale {
+    /// Matches tags such as `de`, `fr-CA` or `es_ES.UTF-8` by their language.
+    pub fn parse(tag: &str) -> Option<Locale> {
+        let language = tag.split(['-', '_', '.', '@']).next()?.to_ascii_lowercase();
+        match language.as_str() {
+            "en" => Some(Locale::En),
+            "de" => Some(Locale::De),
+            "fr" => Some(Locale::Fr),
+            "es" => Some(Locale::Es),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    /// `{0}` is the app's name.
+    ElevatedTarget,
+    NoMicrophone,
+    NoMicrophoneSandboxed,
+    PythonNotFound,
+    /// `{0}` is the timeout in seconds.
+    TranscriptionTimeout,
+    NothingTranscribed,
+    RecordingStarted,
+    Transcribing,
+    PastedOneWord,
+    /// `{0}` is the number of words.
+    PastedWords,
+    CopiedOneWord,
+    /// `{0}` is the number of words.
+    CopiedWords,
+    TranscriptionFailed,
+}
+
+impl Message {
+    pub fn code(self) -> &'static str {
+        match self {
+            Message::ElevatedTarget => "ELEVATED_TARGET",
+            Message::NoMicrophone | Message::NoMicrophoneSandboxed => "NO_MICROPHONE",
+            Message::PythonNotFound => "PYTHON_NOT_FOUND",
+            Message::TranscriptionTimeout => "TRANSCRIPTION_TIMEOUT",
+            Message::NothingTranscribed => "NOTHING_TRANSCRIBED",
+            Message::RecordingStarted => "RECORDING_STARTED",
+            Message::Transcribing => "TRANSCRIBING",
+            Message::PastedOneWord | Message::PastedWords => "PASTED",
+            Message::CopiedOneWord | Message::CopiedWords => "COPIED",
+            Message::TranscriptionFailed => "TRANSCRIPTION_FAILED",
+        }
+    }
+
+    fn template(self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (Message::ElevatedTarget, Locale::En) => {
+                "{0} runs as administrator, so Windows blocks VType's keystrokes. The transcript \
+                 is on the clipboard; paste it with Ctrl+V, or run VType as administrator too."
+            }
+            (Message::ElevatedTarget, Locale::De) => {
+                "{0} läuft als Administrator, daher blockiert Windows die Tastenanschläge von \
+                 VType. Die Transkription liegt in der Zwischenablage; füge sie mit Strg+V ein \
+                 oder starte VType ebenfalls als Administrator."
+            }
+            (Message::ElevatedTarget, Locale::Fr) => {
+                "{0} s'exécute en tant qu'administrateur, donc Windows bloque les frappes de \
+                 VType. La transcription est dans le presse-papiers ; collez-la avec Ctrl+V, ou \
+                 lancez aussi VType en tant qu'administrateur."
+            }
+            (Message::ElevatedTarget, Locale::Es) => {
+                "{0} se ejecuta como administrador, así que Windows bloquea las pulsaciones de \
+                 VType. La transcripción está en el portapapeles; pégala con Ctrl+V o ejecuta \
+                 también VType como administrador."
+            }
+            (Message::NoMicrophone, Locale::En) => "No microphone found",
+            (Message::NoMicrophone, Locale::De) => "Kein Mikrofon gefunden",
+            (Message::NoMicrophone, Locale::Fr) => "Aucun microphone trouvé",
+            (Message::NoMicrophone, Locale::Es) => "No se encontró ningún micrófono",
+            (Message::NoMicrophoneSandboxed, Locale::En) => {
+                "No microphone found. The sandbox needs access to the PulseAudio socket \
+                 (--socket=pulseaudio)"
+            }
+            (Message::NoMicrophoneSandboxed, Locale::De) => {
+                "Kein Mikrofon gefunden. Die Sandbox braucht Zugriff auf den PulseAudio-Socket \
+                 (--socket=pulseaudio)"
+            }
+            (Message::NoMicrophoneSandboxed, Locale::Fr) => {
+                "Aucun microphone trouvé. Le bac à sable doit avoir accès au socket PulseAudio \
+                 (--socket=pulseaudio)"
+            }
+            (Message::NoMicrophoneSandboxed, Locale::Es) => {
+                "No se encontró ningún micrófono. El entorno aislado necesita acceso al socket \
+                 de PulseAudio (--socket=pulseaudio)"
+            }
+            (Message::PythonNotFound, Locale::En) => {
+                "Python interpreter not found (tried python3, python)"
+            }
+            (Message::PythonNotFound, Locale::De) => {
+                "Kein Python-Interpreter gefunden (versucht: python3, python)"
+            }
+            (Message::PythonNotFound, Locale::Fr) => {
+                "Interpréteur Python introuvable (essayé : python3, python)"
+            }
+            (Message::PythonNotFound, Locale::Es) => {
+                "No se encontró el intérprete de Python (se probó python3 y python)"
+            }
+            (Message::TranscriptionTimeout, Locale::En) => "Transcription timed out after {0}s",
+            (Message::TranscriptionTimeout, Locale::De) => {
+                "Zeitüberschreitung bei der Transkription nach {0} s"
+            }
+            (Message::TranscriptionTimeout, Locale::Fr) => "La transcription a expiré après {0} s",
+            (Message::TranscriptionTimeout, Locale::Es) => {
+                "La transcripción superó el tiempo límite de {0} s"
+            }
+            (Message::NothingTranscribed, Locale::En) => "Nothing has been transcribed yet",
+            (Message::NothingTranscribed, Locale::De) => "Bisher wurde nichts transkribiert",
+            (Message::NothingTranscribed, Locale::Fr) => "Rien n'a encore été transcrit",
+            (Message::NothingTranscribed, Locale::Es) => "Todavía no se ha transcrito nada",
+            (Message::RecordingStarted, Locale::En) => "Recording started",
+            (Message::RecordingStarted, Locale::De) => "Aufnahme gestartet",
+            (Message::RecordingStarted, Locale::Fr) => "Enregistrement démarré",
+            (Message::RecordingStarted, Locale::Es) => "Grabación iniciada",
+            (Message::Transcribing, Locale::En) => "Transcribing",
+            (Message::Transcribing, Locale::De) => "Wird transkribiert",
+            (Message::Transcribing, Locale::Fr) => "Transcription en cours",
+            (Message::Transcribing, Locale::Es) => "Transcribiendo",
+            (Message::PastedOneWord, Locale::En) => "Pasted 1 word",
+            (Message::PastedOneWord, Locale::De) => "1 Wort eingefügt",
+            (Message::PastedOneWord, Locale::Fr) => "1 mot collé",
+            (Message::PastedOneWord, Locale::Es) => "1 palabra pegada",
+            (Message::PastedWords, Locale::En) => "Pasted {0} words",
+            (Message::PastedWords, Locale::De) => "{0} Wörter eingefügt",
+            (Message::PastedWords, Locale::Fr) => "{0} mots collés",
+            (Message::PastedWords, Locale::Es) => "{0} palabras pegadas",
+            (Message::CopiedOneWord, Locale::En) => "Copied 1 word to the clipboard",
+            (Message::CopiedOneWord, Locale::De) => "1 Wort in die Zwischenablage kopiert",
+            (Message::CopiedOneWord, Locale::Fr) => "1 mot copié dans le presse-papiers",
+            (Message::CopiedOneWord, Locale::Es) => "1 palabra copiada al portapapeles",
+            (Message::CopiedWords, Locale::En) => "Copied {0} words to the clipboard",
+            (Message::CopiedWords, Locale::De) => "{0} Wörter in die Zwischenablage kopiert",
+            (Message::CopiedWords, Locale::Fr) => "{0} mots copiés dans le presse-papiers",
+            (Message::CopiedWords, Locale::Es) => "{0} palabras copiadas al portapapeles",
+            (Message::TranscriptionFailed, Locale::En) => "Transcription failed",
+            (Message::TranscriptionFailed, Locale::De) => "Transkription fehlgeschlagen",
+            (Message::TranscriptionFailed, Locale::Fr) => "Échec de la transcription",
+            (Message::TranscriptionFailed, Locale::Es) => "La transcripción falló",
+        }
+    }
+}
+
+/// `message` in the user's language, with `{0}`, `{1}`… replaced by `args`.
+pub fn text(message: Message, args: &[&str]) -> String {
+    args.iter()
+        .enumerate()
+        .fold(message.template(locale()).to_string(), |text, (i, arg)| {
+            text.replace(&format!("{{{}}}", i), arg)
+        })
+}
+
+/// `message` as an error: its code, then the text in the user's language.
+pub fn error(message: Message, args: &[&str]) -> String {
+    format!("{}: {}", message.code(), text(message, args))
+}
+
+/// The settings' `locale`, the system's, or English when neither is translated.
+pub fn locale() -> Locale {
+    settings::current()
+        .locale
+        .as_deref()
+        .and_then(Locale::parse)
+        .or_else(|| system_locale().as_deref().and_then(Locale::parse))
+        .unwrap_or(Locale::En)
+}
+
+#[cfg(target_os = "windows")]
+fn system_locale() -> Option<String> {
+    use windows_sys::Win32::Globalization::GetUserDefaultLocaleName;
+
+    // LOCALE_NAME_MAX_LENGTH.
+    let mut name = [0u16; 85];
+    // SAFETY: the buffer length passed is the buffer's own.
+    let len = unsafe { GetUserDefaultLocaleName(name.as_mut_ptr(), name.len() as i32) };
+    // The length includes the terminating null.
+    (len > 1).then(|| String::from_utf16_lossy(&name[..len as usize - 1]))
+}
+
+/// The POSIX variables, in order of precedence; also set on macOS in a terminal.
+#[cfg(not(target_os = "windows"))]
+fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty() && value != "C" && value != "POSIX")
+}
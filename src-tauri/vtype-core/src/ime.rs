@@ -0,0 +1,220 @@
+//! Input method editors for Japanese, Chinese and Korean hold keystrokes while composing and
+//! often swallow a simulated paste chord. This finds out whether one is on in the focused
+//! app and switches it off around an injection. Best effort: where the platform does not
+//! say, no input method is reported.
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::process::{Command, Stdio};
+
+/// Keeps the input method switched off; dropping it switches it back on.
+pub struct Suspended {
+    restore: Restore,
+}
+
+impl Drop for Suspended {
+    fn drop(&mut self) {
+        restore(&self.restore);
+    }
+}
+
+#[cfg(target_os = "windows")]
+type Restore = windows_sys::Win32::Foundation::HWND;
+
+/// Not defined by windows-sys alongside `IMC_SETOPENSTATUS`.
+#[cfg(target_os = "windows")]
+const IMC_GETOPENSTATUS: u32 = 0x0005;
+
+#[cfg(target_os = "windows")]
+pub fn active() -> bool {
+    ime_window()
+        .and_then(|window| ime_control(window, IMC_GETOPENSTATUS, 0))
+        .is_some_and(|open| open != 0)
+}
+
+#[cfg(target_os = "windows")]
+pub fn suspend() -> Option<Suspended> {
+    use windows_sys::Win32::UI::Input::Ime::IMC_SETOPENSTATUS;
+
+    let window = ime_window()?;
+    if ime_control(window, IMC_GETOPENSTATUS, 0)? == 0 {
+        return None;
+    }
+    ime_control(window, IMC_SETOPENSTATUS, 0)?;
+    Some(Suspended { restore: window })
+}
+
+#[cfg(target_os = "windows")]
+fn restore(window: &Restore) {
+    use windows_sys::Win32::UI::Input::Ime::IMC_SETOPENSTATUS;
+
+    ime_control(*window, IMC_SETOPENSTATUS, 1);
+}
+
+/// The default IME window of the foreground window's thread.
+#[cfg(target_os = "windows")]
+fn ime_window() -> Option<Restore> {
+    use windows_sys::Win32::UI::Input::Ime::ImmGetDefaultIMEWnd;
+    use windows_sys::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+    // SAFETY: both calls take and return plain window handles.
+    unsafe {
+        let focused = GetForegroundWindow();
+        if focused.is_null() {
+            return None;
+        }
+        let window = ImmGetDefaultIMEWnd(focused);
+        (!window.is_null()).then_some(window)
+    }
+}
+
+/// Sends an `IMC_*` request to an IME window. Unlike `ImmGetContext`, which only sees the
+/// calling thread, this reaches input methods in other processes.
+#[cfg(target_os = "windows")]
+fn ime_control(window: Restore, command: u32, value: isize) -> Option<usize> {
+    use windows_sys::Win32::UI::WindowsAndMessaging::{
+        SendMessageTimeoutW, SMTO_ABORTIFHUNG, WM_IME_CONTROL,
+    };
+
+    let mut result = 0;
+    // SAFETY: `result` outlives the call; a hung target times out instead of blocking.
+    let sent = unsafe {
+        SendMessageTimeoutW(
+            window,
+            WM_IME_CONTROL,
+            command as usize,
+            value,
+            SMTO_ABORTIFHUNG,
+            200,
+            &mut result,
+        )
+    };
+    (sent != 0).then_some(result)
+}
+
+#[cfg(target_os = "linux")]
+enum Restore {
+    /// The fcitx remote that switched it off.
+    Fcitx(&'static str),
+    /// The IBus engine that was selected.
+    Ibus(String),
+}
+
+#[cfg(target_os = "linux")]
+const FCITX_REMOTES: [&str; 2] = ["fcitx5-remote", "fcitx-remote"];
+
+#[cfg(target_os = "linux")]
+pub fn active() -> bool {
+    fcitx_active().is_some() || ibus_engine().is_some()
+}
+
+#[cfg(target_os = "linux")]
+pub fn suspend() -> Option<Suspended> {
+    if let Some(remote) = fcitx_active() {
+        run(remote, &["-c"])?;
+        return Some(Suspended {
+            restore: Restore::Fcitx(remote),
+        });
+    }
+    let engine = ibus_engine()?;
+    let layout = ibus_layout()?;
+    run("ibus", &["engine", &layout])?;
+    Some(Suspended {
+        restore: Restore::Ibus(engine),
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn restore(restore: &Restore) {
+    match restore {
+        Restore::Fcitx(remote) => run(remote, &["-o"]),
+        Restore::Ibus(engine) => run("ibus", &["engine", engine]),
+    };
+}
+
+/// The fcitx remote whose daemon reports an input method composing (state 2).
+#[cfg(target_os = "linux")]
+fn fcitx_active() -> Option<&'static str> {
+    FCITX_REMOTES
+        .into_iter()
+        .find(|remote| run(remote, &[]).as_deref() == Some("2"))
+}
+
+/// The selected IBus engine, when it is an input method rather than a keyboard layout.
+#[cfg(target_os = "linux")]
+fn ibus_engine() -> Option<String> {
+    run("ibus", &["engine"]).filter(|engine| !engine.is_empty() && !engine.starts_with("xkb:"))
+}
+
+/// The first keyboard layout among the user's IBus engines, to switch to during a paste.
+#[cfg(target_os = "linux")]
+fn ibus_layout() -> Option<String> {
+    let engines = run(
+        "gsettings",
+        &["get", "org.freedesktop.ibus.general", "preload-engines"],
+    )?;
+    engines
+        .split(['[', ']', ',', '\'', ' '])
+        .find(|engine| engine.starts_with("xkb:"))
+        .map(str::to_string)
+}
+
+#[cfg(target_os = "macos")]
+enum Restore {}
+
+/// The selected input source is an input mode rather than a keyboard layout while an IME
+/// is on; its Roman mode passes keys through untouched.
+#[cfg(target_os = "macos")]
+pub fn active() -> bool {
+    run(
+        "defaults",
+        &["read", "com.apple.HIToolbox", "AppleSelectedInputSources"],
+    )
+    .is_some_and(|sources| {
+        sources
+            .lines()
+            .any(|line| line.contains("\"Input Mode\" =") && !line.contains(".Roman"))
+    })
+}
+
+/// Input sources can only be switched through the Text Input Sources API. ⌘V does not
+/// need it: key equivalents reach the app before the input method sees them.
+#[cfg(target_os = "macos")]
+pub fn suspend() -> Option<Suspended> {
+    None
+}
+
+#[cfg(target_os = "macos")]
+fn restore(restore: &Restore) {
+    match *restore {}
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+enum Restore {}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+pub fn active() -> bool {
+    false
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+pub fn suspend() -> Option<Suspended> {
+    None
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux", target_os = "macos")))]
+fn restore(restore: &Restore) {
+    match *restore {}
+}
+
+/// Runs a query tool, returning its trimmed output when it succeeds.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,209 @@
+//! Sorts transcripts by how hard they are to inject as keystrokes. Key simulators handle
+//! ASCII everywhere, most handle single BMP characters, and few cope with emoji or other
+//! text built from several code points per visible character. Long transcripts can also be
+//! cut into sentence-sized chunks for apps that cannot keep up with them, and key chords
+//! such as `Ctrl+Shift+T` are parsed here for each platform to press.
+
+/// What a transcript contains, from easiest to hardest to type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TextKind {
+    Ascii,
+    /// Characters outside ASCII that are each a single BMP code point, such as accented
+    /// Latin, Cyrillic or CJK.
+    Unicode,
+    /// Emoji, characters beyond the BMP, or sequences joined by combining marks, joiners or
+    /// variation selectors.
+    Composed,
+}
+
+pub fn classify(text: &str) -> TextKind {
+    text.chars()
+        .map(|ch| {
+            if ch.is_ascii() {
+                TextKind::Ascii
+            } else if u32::from(ch) > 0xFFFF || is_joining(ch) {
+                TextKind::Composed
+            } else {
+                TextKind::Unicode
+            }
+        })
+        .max()
+        .unwrap_or(TextKind::Ascii)
+}
+
+/// Splits `text` into chunks of whole sentences of up to `max_chars` characters, for apps
+/// that drop input arriving all at once. The whitespace after a sentence stays with it, so
+/// the chunks join back into `text`; a sentence longer than `max_chars` is split between
+/// words.
+pub fn sentence_chunks(text: &str, max_chars: usize) -> Vec<&str> {
+    let pieces = sentences(text).into_iter().flat_map(|sentence| {
+        if sentence.chars().count() > max_chars {
+            sentence.split_inclusive(char::is_whitespace).collect()
+        } else {
+            vec![sentence]
+        }
+    });
+    let mut chunks = Vec::new();
+    let (mut start, mut end, mut chars) = (0, 0, 0);
+    for piece in pieces {
+        let piece_chars = piece.chars().count();
+        if chars > 0 && chars + piece_chars > max_chars {
+            chunks.push(&text[start..end]);
+            start = end;
+            chars = 0;
+        }
+        end += piece.len();
+        chars += piece_chars;
+    }
+    if end > start {
+        chunks.push(&text[start..end]);
+    }
+    chunks
+}
+
+/// Each sentence with the whitespace after it. A sentence ends at a line break, or at a
+/// full stop, question or exclamation mark followed by whitespace, so `3.5` and `e.g.x`
+/// stay whole; closing quotes and brackets after the mark belong to the sentence.
+fn sentences(text: &str) -> Vec<&str> {
+    #[derive(PartialEq)]
+    enum Seen {
+        Text,
+        Mark,
+        End,
+    }
+
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut seen = Seen::Text;
+    for (index, ch) in text.char_indices() {
+        if seen == Seen::End && !ch.is_whitespace() {
+            sentences.push(&text[start..index]);
+            start = index;
+            seen = Seen::Text;
+        }
+        seen = match ch {
+            '\n' | '。' | '！' | '？' => Seen::End,
+            '.' | '!' | '?' | '…' => Seen::Mark,
+            '"' | '\'' | '”' | '’' | ')' | ']' if seen == Seen::Mark => Seen::Mark,
+            ch if ch.is_whitespace() && seen != Seen::Text => Seen::End,
+            _ => Seen::Text,
+        };
+    }
+    if start < text.len() {
+        sentences.push(&text[start..]);
+    }
+    sentences
+}
+
+/// Code points that only make sense attached to their neighbours.
+fn is_joining(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{200C}' | '\u{200D}' // zero-width non-joiner and joiner
+            | '\u{FE00}'..='\u{FE0F}' // variation selectors
+            | '\u{0300}'..='\u{036F}' // combining diacritical marks
+            | '\u{1AB0}'..='\u{1AFF}'
+            | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}' // combining marks for symbols, incl. the keycap
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    Control,
+    Shift,
+    Alt,
+    /// Command on macOS, the Windows key elsewhere.
+    Super,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    /// A lowercase ASCII letter or a digit.
+    Char(char),
+    Return,
+    Tab,
+    Escape,
+    Backspace,
+    Delete,
+    Space,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    /// F1 to F12.
+    Function(u8),
+}
+
+/// A key pressed while holding modifiers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chord {
+    pub modifiers: Vec<Modifier>,
+    pub key: Key,
+}
+
+impl Chord {
+    pub fn key(key: Key) -> Chord {
+        Chord {
+            modifiers: Vec::new(),
+            key,
+        }
+    }
+
+    /// Reads chords written like hotkeys, e.g. `Ctrl+Shift+T`, `CmdOrCtrl+Enter` or `F5`.
+    pub fn parse(chord: &str) -> Result<Chord, String> {
+        let invalid = || format!("Invalid key chord {}", chord);
+        let mut parts: Vec<&str> = chord.split('+').map(str::trim).collect();
+        let key = parts
+            .pop()
+            .filter(|key| !key.is_empty())
+            .ok_or_else(invalid)?;
+        let modifiers = parts
+            .into_iter()
+            .map(|part| match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => Ok(Modifier::Control),
+                "shift" => Ok(Modifier::Shift),
+                "alt" | "option" => Ok(Modifier::Alt),
+                "super" | "cmd" | "command" | "meta" | "win" => Ok(Modifier::Super),
+                "cmdorctrl" | "commandorcontrol" if cfg!(target_os = "macos") => {
+                    Ok(Modifier::Super)
+                }
+                "cmdorctrl" | "commandorcontrol" => Ok(Modifier::Control),
+                _ => Err(invalid()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let lower = key.to_ascii_lowercase();
+        let key = match lower.as_str() {
+            "enter" | "return" => Key::Return,
+            "tab" => Key::Tab,
+            "esc" | "escape" => Key::Escape,
+            "backspace" => Key::Backspace,
+            "delete" | "del" => Key::Delete,
+            "space" => Key::Space,
+            "up" => Key::Up,
+            "down" => Key::Down,
+            "left" => Key::Left,
+            "right" => Key::Right,
+            "home" => Key::Home,
+            "end" => Key::End,
+            "pageup" => Key::PageUp,
+            "pagedown" => Key::PageDown,
+            _ => match lower.strip_prefix('f').map(str::parse::<u8>) {
+                Some(Ok(number)) if (1..=12).contains(&number) => Key::Function(number),
+                _ => {
+                    let mut chars = lower.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(ch), None) if ch.is_ascii_alphanumeric() => Key::Char(ch),
+                        _ => return Err(invalid()),
+                    }
+                }
+            },
+        };
+        Ok(Chord { modifiers, key })
+    }
+}
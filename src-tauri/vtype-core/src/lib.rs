@@ -0,0 +1,62 @@
+//! Transcription pipeline behind VType: the Python worker protocol and its supervisor,
+//! request options, settings, transcript post-processing and the HTTP and WebSocket the
+//! local servers speak. Nothing here depends on Tauri, so the desktop shell stays a thin
+//! command layer.
+
+pub mod activity;
+pub mod audio;
+pub mod audit;
+pub mod backend;
+pub mod batch;
+pub mod benchmark;
+pub mod bidi;
+pub mod code;
+pub mod crash;
+pub mod decode;
+pub mod dnd;
+pub mod download;
+pub mod early;
+pub mod echo;
+pub mod frontmost;
+pub mod history;
+pub mod hooks;
+pub mod http;
+pub mod i18n;
+pub mod ime;
+pub mod inject;
+pub mod log;
+pub mod macros;
+pub mod media;
+pub mod metrics;
+pub mod mock;
+pub mod models;
+pub mod monitor;
+pub mod options;
+pub mod opus;
+pub mod pairing;
+pub mod paste_stats;
+pub mod paths;
+pub mod pipeline;
+pub mod plugins;
+pub mod postprocess;
+pub mod power;
+pub mod preprocess;
+pub mod process;
+pub mod protocol;
+pub mod recording;
+pub mod redact;
+pub mod registration;
+pub mod retention;
+pub mod safe_io;
+pub mod settings;
+pub mod spacing;
+pub mod speech;
+pub mod subtitle;
+pub mod terminal;
+pub mod transfer;
+pub mod venv;
+pub mod vocabulary;
+pub mod voice;
+pub mod watch;
+pub mod websocket;
+pub mod worker;
@@ -0,0 +1,24 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Appends a timestamped line to `vtype.log` (see [`crate::paths::log_file`]), masked as
+/// [`crate::redact::stored`] says.
+pub fn write(message: String) -> Result<(), String> {
+    let message = crate::redact::stored(&message);
+    let path = crate::paths::log_file();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let line = format!("[{}] {}\n", timestamp, message);
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|err| err.to_string())
+}
@@ -0,0 +1,187 @@
+//! Voice macros: phrases that, said as a whole dictation, run a sequence of steps instead of
+//! being pasted, which makes VType a voice launcher. They are read from `macros.toml` beside
+//! the settings on every dictation, so edits apply straight away:
+//!
+//! ```toml
+//! [[macro]]
+//! phrases = ["new note"]
+//! steps = [
+//!     { type = "launch", app = "obsidian" },
+//!     { type = "wait", ms = 800 },
+//!     { type = "keys", keys = "CmdOrCtrl+N" },
+//!     { type = "snippet", text = "Notes from today" },
+//! ]
+//! ```
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::inject::Chord;
+
+pub const MACROS_FILE: &str = "macros.toml";
+
+#[derive(Debug, Default, Deserialize)]
+struct MacroFile {
+    #[serde(default, rename = "macro")]
+    macros: Vec<Macro>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Macro {
+    /// Matched against the whole dictation, ignoring case and punctuation.
+    pub phrases: Vec<String>,
+    pub steps: Vec<Step>,
+}
+
+impl Macro {
+    /// What its launch, HTTP and shell steps do, which runs or reaches beyond VType and so
+    /// may need the user's approval first.
+    pub fn shell_commands(&self) -> Vec<String> {
+        self.steps
+            .iter()
+            .filter_map(|step| match step {
+                Step::Launch { app } => Some(format!("launch {}", app)),
+                Step::Http { url, method, .. } => Some(format!("{} {}", method, url)),
+                Step::Shell { command } => Some(command.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Step {
+    /// A key chord written like a hotkey, e.g. `Ctrl+Shift+T`.
+    Keys {
+        keys: String,
+    },
+    /// An app by name, or on Linux the command that starts it.
+    Launch {
+        app: String,
+    },
+    /// Text delivered like a transcript.
+    Snippet {
+        text: String,
+    },
+    /// A request made with `curl`, waited for up to 15 seconds.
+    Http {
+        url: String,
+        #[serde(default = "default_method")]
+        method: String,
+        #[serde(default)]
+        body: Option<String>,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+    },
+    /// A shell command, started without waiting for it.
+    Shell {
+        command: String,
+    },
+    Wait {
+        ms: u64,
+    },
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+/// Every macro in the file, none when there is no file.
+pub fn load() -> Result<Vec<Macro>, String> {
+    let Some(path) = path() else {
+        return Ok(Vec::new());
+    };
+    let toml = match std::fs::read_to_string(&path) {
+        Ok(toml) => toml,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(format!("Failed to read {}: {}", path.display(), err)),
+    };
+    let file: MacroFile = toml::from_str(&toml)
+        .map_err(|err| format!("Invalid {}: {}", MACROS_FILE, err.message()))?;
+    for (index, found) in file.macros.iter().enumerate() {
+        validate(found)
+            .map_err(|err| format!("Macro {} in {}: {}", index + 1, MACROS_FILE, err))?;
+    }
+    Ok(file.macros)
+}
+
+/// Whether the contents of a macros file launch programs, run shell commands or make HTTP
+/// requests, assumed when they can't be read.
+pub fn has_program_steps(toml: &str) -> bool {
+    toml::from_str::<MacroFile>(toml).map_or(true, |file| {
+        file.macros
+            .iter()
+            .flat_map(|found| &found.steps)
+            .any(|step| {
+                matches!(
+                    step,
+                    Step::Launch { .. } | Step::Shell { .. } | Step::Http { .. }
+                )
+            })
+    })
+}
+
+fn validate(found: &Macro) -> Result<(), String> {
+    if found
+        .phrases
+        .iter()
+        .all(|phrase| normalize(phrase).is_empty())
+    {
+        return Err("no phrase".to_string());
+    }
+    for step in &found.steps {
+        match step {
+            Step::Keys { keys } => {
+                Chord::parse(keys)?;
+            }
+            Step::Http { url, .. } if !url.contains("://") => {
+                return Err(format!("URL is not absolute: {}", url));
+            }
+            Step::Launch { app } if app.trim().is_empty() => {
+                return Err("empty app name".to_string());
+            }
+            Step::Shell { command } if command.trim().is_empty() => {
+                return Err("empty shell command".to_string());
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The first macro one of whose phrases is the whole of `text`. A broken file is logged and
+/// treated as empty, so dictation keeps working.
+pub fn find(text: &str) -> Option<Macro> {
+    let macros = load()
+        .inspect_err(|err| {
+            let _ = crate::log::write(err.clone());
+        })
+        .ok()?;
+    let said = normalize(text);
+    if said.is_empty() {
+        return None;
+    }
+    macros
+        .into_iter()
+        .find(|found| found.phrases.iter().any(|phrase| normalize(phrase) == said))
+}
+
+/// Lowercase words separated by single spaces.
+fn normalize(text: &str) -> String {
+    text.split(|ch: char| !ch.is_alphanumeric() && ch != '\'')
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Beside the settings file.
+pub fn path() -> Option<PathBuf> {
+    crate::settings::path()?
+        .parent()
+        .map(|dir| dir.join(MACROS_FILE))
+}
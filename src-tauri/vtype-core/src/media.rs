@@ -0,0 +1,183 @@
+//! Pausing whatever media is playing while the user dictates, and resuming only what was
+//! paused. Players are asked for their state first so a stopped player is never started.
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::process::Command;
+
+/// A player this module paused, to be resumed later.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PausedPlayer(String);
+
+/// Pauses every player that is currently playing.
+#[cfg(target_os = "linux")]
+pub fn pause_playing() -> Vec<PausedPlayer> {
+    mpris_players()
+        .into_iter()
+        .filter(|name| mpris_status(name).as_deref() == Some("Playing"))
+        .filter(|name| mpris_call(name, "Pause"))
+        .map(PausedPlayer)
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+pub fn resume(players: &[PausedPlayer]) {
+    for PausedPlayer(name) in players {
+        mpris_call(name, "Play");
+    }
+}
+
+/// MPRIS bus names, e.g. `org.mpris.MediaPlayer2.spotify`.
+#[cfg(target_os = "linux")]
+fn mpris_players() -> Vec<String> {
+    let Some(reply) = dbus_send(&[
+        "--print-reply",
+        "--dest=org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus.ListNames",
+    ]) else {
+        return Vec::new();
+    };
+    reply
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("string \""))
+        .filter_map(|name| name.strip_suffix('"'))
+        .filter(|name| name.starts_with("org.mpris.MediaPlayer2."))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn mpris_status(name: &str) -> Option<String> {
+    let reply = dbus_send(&[
+        "--print-reply",
+        &format!("--dest={}", name),
+        "/org/mpris/MediaPlayer2",
+        "org.freedesktop.DBus.Properties.Get",
+        "string:org.mpris.MediaPlayer2.Player",
+        "string:PlaybackStatus",
+    ])?;
+    // `variant       string "Playing"`
+    let start = reply.rfind("string \"")? + "string \"".len();
+    let status = &reply[start..];
+    Some(status[..status.find('"')?].to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn mpris_call(name: &str, method: &str) -> bool {
+    dbus_send(&[
+        &format!("--dest={}", name),
+        "/org/mpris/MediaPlayer2",
+        &format!("org.mpris.MediaPlayer2.Player.{}", method),
+    ])
+    .is_some()
+}
+
+#[cfg(target_os = "linux")]
+fn dbus_send(args: &[&str]) -> Option<String> {
+    let output = Command::new("dbus-send")
+        .args(["--session", "--type=method_call"])
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Apps with an AppleScript player state; macOS has no public system-wide now-playing API.
+#[cfg(target_os = "macos")]
+const MAC_PLAYERS: &[&str] = &["Music", "Spotify"];
+
+#[cfg(target_os = "macos")]
+pub fn pause_playing() -> Vec<PausedPlayer> {
+    MAC_PLAYERS
+        .iter()
+        .filter(|app| {
+            osascript(&format!(
+                "if application \"{0}\" is running then tell application \"{0}\"\n\
+                 if player state is playing then\npause\nreturn \"paused\"\nend if\nend tell",
+                app
+            ))
+            .as_deref()
+                == Some("paused")
+        })
+        .map(|app| PausedPlayer(app.to_string()))
+        .collect()
+}
+
+#[cfg(target_os = "macos")]
+pub fn resume(players: &[PausedPlayer]) {
+    for PausedPlayer(app) in players {
+        osascript(&format!("tell application \"{}\" to play", app));
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn osascript(script: &str) -> Option<String> {
+    let output = Command::new("osascript")
+        .args(["-e", script])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Uses the system media transport controls, which every app that shows in the volume
+/// flyout reports to, identified by its app id.
+#[cfg(target_os = "windows")]
+pub fn pause_playing() -> Vec<PausedPlayer> {
+    use windows::Media::Control::GlobalSystemMediaTransportControlsSessionPlaybackStatus as Status;
+
+    let Some(sessions) = windows_sessions() else {
+        return Vec::new();
+    };
+    sessions
+        .into_iter()
+        .filter(|session| {
+            session
+                .GetPlaybackInfo()
+                .and_then(|info| info.PlaybackStatus())
+                .is_ok_and(|status| status == Status::Playing)
+        })
+        .filter(|session| {
+            session
+                .TryPauseAsync()
+                .and_then(|op| op.get())
+                .unwrap_or(false)
+        })
+        .filter_map(|session| session.SourceAppUserModelId().ok())
+        .map(|id| PausedPlayer(id.to_string()))
+        .collect()
+}
+
+#[cfg(target_os = "windows")]
+pub fn resume(players: &[PausedPlayer]) {
+    let Some(sessions) = windows_sessions() else {
+        return;
+    };
+    for session in sessions {
+        let paused_by_us = session
+            .SourceAppUserModelId()
+            .is_ok_and(|id| players.iter().any(|PausedPlayer(paused)| id == *paused));
+        if paused_by_us {
+            let _ = session.TryPlayAsync().and_then(|op| op.get());
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn windows_sessions(
+) -> Option<Vec<windows::Media::Control::GlobalSystemMediaTransportControlsSession>> {
+    use windows::Media::Control::GlobalSystemMediaTransportControlsSessionManager as Manager;
+
+    let manager = Manager::RequestAsync().and_then(|op| op.get()).ok()?;
+    let sessions = manager.GetSessions().ok()?;
+    Some(sessions.into_iter().collect())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn pause_playing() -> Vec<PausedPlayer> {
+    Vec::new()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn resume(_players: &[PausedPlayer]) {}
@@ -0,0 +1,140 @@
+//! Opt-in usage counters, kept locally so users can choose to share them: transcriptions per
+//! day by backend and errors per day by category, nothing else. No transcript, file name or
+//! app name is ever recorded, and nothing leaves the machine unless the user exports it.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::FailureKind;
+
+const METRICS_FILE: &str = "metrics.json";
+/// Older days are dropped so the file stays small.
+const MAX_DAYS: usize = 90;
+
+/// Loaded on the first count.
+static DAYS: Mutex<Option<Vec<DayCounts>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCategory {
+    InvalidOptions,
+    /// The configured backend is unknown or cannot start.
+    Backend,
+    OutOfMemory,
+    ModelLoad,
+    /// Any other failure of the worker process or its protocol.
+    Worker,
+    Timeout,
+    /// The transcript could not be pasted or typed.
+    Output,
+    ElevatedTarget,
+}
+
+impl From<FailureKind> for ErrorCategory {
+    fn from(kind: FailureKind) -> Self {
+        match kind {
+            FailureKind::OutOfMemory => ErrorCategory::OutOfMemory,
+            FailureKind::ModelLoad => ErrorCategory::ModelLoad,
+            FailureKind::Other => ErrorCategory::Worker,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DayCounts {
+    /// Local date, `YYYY-MM-DD`.
+    pub date: String,
+    /// By backend name.
+    pub transcriptions: BTreeMap<String, u64>,
+    pub errors: BTreeMap<ErrorCategory, u64>,
+}
+
+/// What [`export`] hands over: the counters and the platform they came from.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsReport {
+    pub version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    /// Oldest first.
+    pub days: Vec<DayCounts>,
+}
+
+pub fn count_transcription(backend: &str) {
+    update(|day| *day.transcriptions.entry(backend.to_string()).or_default() += 1);
+}
+
+pub fn count_error(category: ErrorCategory) {
+    update(|day| *day.errors.entry(category).or_default() += 1);
+}
+
+/// Everything recorded so far, for the user to review and share. `version` is the app's.
+pub fn export(version: &'static str) -> Result<MetricsReport, String> {
+    let mut days = DAYS.lock().map_err(|err| err.to_string())?;
+    Ok(MetricsReport {
+        version,
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        days: days.get_or_insert_with(load).clone(),
+    })
+}
+
+/// Forgets every counter.
+pub fn clear() -> Result<(), String> {
+    let mut days = DAYS.lock().map_err(|err| err.to_string())?;
+    *days = Some(Vec::new());
+    match path() {
+        Some(path) if path.exists() => std::fs::remove_file(path).map_err(|err| err.to_string()),
+        _ => Ok(()),
+    }
+}
+
+/// Applies `change` to today's counts and saves them, when the user opted in.
+fn update(change: impl FnOnce(&mut DayCounts)) {
+    if !crate::settings::current().usage_metrics {
+        return;
+    }
+    let Ok(mut days) = DAYS.lock() else {
+        return;
+    };
+    let days = days.get_or_insert_with(load);
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    if days.last().is_none_or(|day| day.date != today) {
+        days.push(DayCounts {
+            date: today,
+            ..DayCounts::default()
+        });
+        let excess = days.len().saturating_sub(MAX_DAYS);
+        days.drain(..excess);
+    }
+    if let Some(day) = days.last_mut() {
+        change(day);
+    }
+    if let Err(err) = save(days) {
+        let _ = crate::log::write(format!("Failed to save usage metrics: {}", err));
+    }
+}
+
+fn load() -> Vec<DayCounts> {
+    path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save(days: &[DayCounts]) -> Result<(), String> {
+    let path = path().ok_or("No settings directory")?;
+    let json = serde_json::to_vec_pretty(days).map_err(|err| err.to_string())?;
+    crate::safe_io::write(&path, json).map_err(|err| err.to_string())
+}
+
+/// Beside the settings file.
+fn path() -> Option<PathBuf> {
+    crate::settings::path()?
+        .parent()
+        .map(|dir| dir.join(METRICS_FILE))
+}
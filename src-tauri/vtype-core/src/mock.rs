@@ -0,0 +1,34 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::settings;
+
+const DELAY_ENV: &str = "VTYPE_MOCK_DELAY_MS";
+const DEFAULT_TRANSCRIPTS: [&str; 3] = [
+    "The quick brown fox jumps over the lazy dog.",
+    "Testing one two three.",
+    "This transcript came from the mock backend.",
+];
+
+static NEXT_TRANSCRIPT: AtomicUsize = AtomicUsize::new(0);
+
+/// Sleeps for the configured delay, then returns the next canned transcript in rotation.
+pub async fn transcribe(wav_bytes: &[u8]) -> Result<String, String> {
+    if wav_bytes.is_empty() {
+        return Err("ERROR: empty audio".to_string());
+    }
+    let mock = settings::current().mock;
+    let delay_ms = std::env::var(DELAY_ENV)
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .unwrap_or(mock.delay_ms);
+    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
+
+    let index = NEXT_TRANSCRIPT.fetch_add(1, Ordering::Relaxed);
+    let text = if mock.transcripts.is_empty() {
+        DEFAULT_TRANSCRIPTS[index % DEFAULT_TRANSCRIPTS.len()].to_string()
+    } else {
+        mock.transcripts[index % mock.transcripts.len()].clone()
+    };
+    Ok(text)
+}
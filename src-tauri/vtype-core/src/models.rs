@@ -0,0 +1,95 @@
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use serde::Serialize;
+use sysinfo::System;
+
+pub const QUANTIZATION_AUTO: &str = "auto";
+pub const QUANTIZATION_FP32: &str = "fp32";
+pub const QUANTIZATION_INT8: &str = "int8";
+
+const GIB: u64 = 1024 * 1024 * 1024;
+/// Full-precision parakeet needs about 2.5 GB resident.
+const FULL_PRECISION_RAM: u64 = 5 * GIB / 2;
+/// Free memory left over for the rest of the desktop once it is loaded.
+const RAM_HEADROOM: u64 = 2 * GIB;
+const FULL_PRECISION_MIN_VRAM: u64 = 4 * GIB;
+
+/// GPU memory does not change while VType runs, and `nvidia-smi` is slow to start.
+static VRAM: OnceLock<Option<u64>> = OnceLock::new();
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuantizationSuggestion {
+    pub total_ram_bytes: u64,
+    pub available_ram_bytes: u64,
+    pub vram_bytes: Option<u64>,
+    pub suggested: &'static str,
+    pub configured: String,
+    pub effective: String,
+}
+
+pub fn validate_quantization(value: &str) -> Result<(), String> {
+    match value {
+        QUANTIZATION_AUTO | QUANTIZATION_FP32 | QUANTIZATION_INT8 => Ok(()),
+        other => Err(format!("Unknown quantization: {}", other)),
+    }
+}
+
+pub fn suggest(configured: &str) -> QuantizationSuggestion {
+    let mut system = System::new();
+    system.refresh_memory();
+    let total_ram_bytes = system.total_memory();
+    let available_ram_bytes = system.available_memory();
+    let vram_bytes = *VRAM.get_or_init(detect_vram);
+
+    let suggested = if vram_bytes.is_some_and(|vram| vram >= FULL_PRECISION_MIN_VRAM)
+        || available_ram_bytes >= FULL_PRECISION_RAM + RAM_HEADROOM
+    {
+        QUANTIZATION_FP32
+    } else {
+        QUANTIZATION_INT8
+    };
+    let effective = if configured == QUANTIZATION_AUTO {
+        suggested.to_string()
+    } else {
+        configured.to_string()
+    };
+
+    QuantizationSuggestion {
+        total_ram_bytes,
+        available_ram_bytes,
+        vram_bytes,
+        suggested,
+        configured: configured.to_string(),
+        effective,
+    }
+}
+
+/// Value passed to the worker's `onnx_asr.load_model(quantization=...)`; `None` loads fp32.
+pub fn worker_quantization(configured: &str) -> Option<String> {
+    let effective = suggest(configured).effective;
+    if effective == QUANTIZATION_INT8 {
+        Some(effective)
+    } else {
+        None
+    }
+}
+
+fn detect_vram() -> Option<u64> {
+    // Only NVIDIA exposes a portable query; other GPUs fall back to the RAM heuristic.
+    let mut command = Command::new("nvidia-smi");
+    command
+        .args(["--query-gpu=memory.total", "--format=csv,noheader,nounits"])
+        .stderr(Stdio::null());
+    crate::process::configure_background_command(&mut command);
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.trim().parse::<u64>().ok())
+        .max()
+        .map(|mib| mib * 1024 * 1024)
+}
@@ -0,0 +1,108 @@
+//! Watches the worker process's memory and CPU use. A sample is taken every few seconds
+//! while a worker runs; with `workerMemoryLimitMb` set, a worker past the limit is
+//! restarted by the supervisor once it has nothing else to do.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+const MIB: u64 = 1024 * 1024;
+
+/// Process id of the running worker; 0 when there is none.
+static WORKER_PID: AtomicU32 = AtomicU32::new(0);
+static STARTED: AtomicBool = AtomicBool::new(false);
+static LATEST: Mutex<Option<WorkerStats>> = Mutex::new(None);
+static MEMORY_RESTARTS: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkerStats {
+    pub pid: u32,
+    /// Resident memory.
+    pub memory_bytes: u64,
+    /// Percent of one core, so above 100 while several are busy.
+    pub cpu_percent: f32,
+    pub uptime_seconds: u64,
+    pub memory_limit_bytes: Option<u64>,
+    /// Restarts for passing the memory limit since VType started.
+    pub memory_restarts: u32,
+}
+
+/// Starts sampling `pid`, or stops with `None` once the worker is gone.
+pub fn watch(pid: Option<u32>) {
+    WORKER_PID.store(pid.unwrap_or(0), Ordering::SeqCst);
+    if let Ok(mut latest) = LATEST.lock() {
+        *latest = None;
+    }
+    if pid.is_some() && !STARTED.swap(true, Ordering::SeqCst) {
+        std::thread::spawn(sample_forever);
+    }
+}
+
+/// The latest sample, a few seconds old at most; `None` while no worker runs.
+pub fn latest() -> Option<WorkerStats> {
+    LATEST.lock().ok().and_then(|latest| latest.clone())
+}
+
+pub(crate) fn count_memory_restart() {
+    MEMORY_RESTARTS.fetch_add(1, Ordering::Relaxed);
+}
+
+fn sample_forever() {
+    let mut system = System::new();
+    // Asked for once per worker, so a restart that is slow to happen is not asked again.
+    let mut restart_requested = 0;
+    loop {
+        std::thread::sleep(SAMPLE_INTERVAL);
+        let pid = WORKER_PID.load(Ordering::SeqCst);
+        if pid == 0 {
+            continue;
+        }
+        let stats = sample(&mut system, pid);
+        let exceeded = stats.as_ref().and_then(|stats| {
+            let limit = stats.memory_limit_bytes?;
+            (stats.memory_bytes > limit).then_some((stats.memory_bytes, limit))
+        });
+        if let Some((memory, limit)) = exceeded.filter(|_| restart_requested != pid) {
+            restart_requested = pid;
+            let _ = crate::log::write(format!(
+                "ASR worker uses {} MB, over the {} MB limit; restarting it when idle",
+                memory / MIB,
+                limit / MIB
+            ));
+            if let Err(err) = crate::worker::recycle() {
+                let _ = crate::log::write(format!("Failed to restart ASR worker: {}", err));
+            }
+        }
+        if let Ok(mut latest) = LATEST.lock() {
+            // The worker may have been replaced while this sample was taken.
+            if WORKER_PID.load(Ordering::SeqCst) == pid {
+                *latest = stats;
+            }
+        }
+    }
+}
+
+fn sample(system: &mut System, pid: u32) -> Option<WorkerStats> {
+    let pid = Pid::from_u32(pid);
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing().with_memory().with_cpu(),
+    );
+    let process = system.process(pid)?;
+    Some(WorkerStats {
+        pid: pid.as_u32(),
+        memory_bytes: process.memory(),
+        cpu_percent: process.cpu_usage(),
+        uptime_seconds: process.run_time(),
+        memory_limit_bytes: crate::settings::current()
+            .worker_memory_limit_mb
+            .map(|limit| limit * MIB),
+        memory_restarts: MEMORY_RESTARTS.load(Ordering::Relaxed),
+    })
+}
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+use crate::backend::Backend;
+use crate::frontmost::FrontmostApp;
+use crate::settings;
+
+/// Per-utterance tuning sent by the frontend. Unset fields keep the worker defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TranscribeOptions {
+    pub language: Option<String>,
+    pub task: Option<String>,
+    pub temperature: Option<f32>,
+    pub beam_size: Option<u32>,
+    pub best_of: Option<u32>,
+    pub initial_prompt: Option<String>,
+    pub backend: Option<String>,
+}
+
+impl TranscribeOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(task) = self.task.as_deref() {
+            if task != "transcribe" && task != "translate" {
+                return Err(format!("Unknown task: {}", task));
+            }
+        }
+        settings::validate_decoding(self.temperature, self.beam_size, self.best_of)?;
+        if let Some(backend) = self.backend.as_deref() {
+            Backend::parse(backend)?;
+        }
+        Ok(())
+    }
+
+    /// Fills fields the request left unset from the active profile, and the prompt from the
+    /// profile listing `app`, the one being dictated into, if any.
+    pub fn with_profile_defaults(
+        mut self,
+        settings: &settings::Settings,
+        app: Option<&FrontmostApp>,
+    ) -> Self {
+        if self.language.is_none() {
+            self.language = settings.effective_language();
+        }
+        if self.initial_prompt.is_none() {
+            self.initial_prompt = settings.effective_initial_prompt(app);
+        }
+        self
+    }
+
+    /// Fills decoding parameters the request left unset from the user's settings, then from
+    /// the defaults for the shipped model.
+    pub fn with_decoding_defaults(mut self, decoding: &settings::DecodingSettings) -> Self {
+        let decoding = decoding.resolve(settings::DEFAULT_MODEL);
+        self.temperature = self.temperature.or(decoding.temperature);
+        self.beam_size = self.beam_size.or(decoding.beam_size);
+        self.best_of = self.best_of.or(decoding.best_of);
+        self
+    }
+
+    pub fn to_worker_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "language": self.language,
+            "task": self.task,
+            "temperature": self.temperature,
+            "beam_size": self.beam_size,
+            "best_of": self.best_of,
+            "initial_prompt": self.initial_prompt,
+        })
+    }
+}
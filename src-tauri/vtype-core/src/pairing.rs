@@ -0,0 +1,52 @@
+//! The one-time codes a companion device pairs with: each is good for a few minutes and a
+//! few wrong guesses, and is used up by the first right one.
+
+use std::time::{Duration, Instant};
+
+/// How long a pairing code can be used.
+pub const PAIRING_TIMEOUT: Duration = Duration::from_secs(120);
+/// Wrong codes after which pairing has to be started again.
+pub const PAIRING_ATTEMPTS: u32 = 5;
+
+#[derive(Debug, Clone)]
+pub struct Pairing {
+    code: String,
+    expires: Instant,
+    attempts: u32,
+}
+
+impl Pairing {
+    pub fn new(code: String, now: Instant) -> Pairing {
+        Pairing {
+            code,
+            expires: now + PAIRING_TIMEOUT,
+            attempts: 0,
+        }
+    }
+}
+
+/// Whether `code` is the code of `pairing` at `now`, which it then uses up. A code that has
+/// expired or been guessed at too often is dropped.
+pub fn take(pairing: &mut Option<Pairing>, code: &str, now: Instant) -> bool {
+    let Some(current) = pairing.as_mut() else {
+        return false;
+    };
+    if current.expires < now {
+        *pairing = None;
+        return false;
+    }
+    if current.code.is_empty() || !same(current.code.as_bytes(), code.as_bytes()) {
+        current.attempts += 1;
+        if current.attempts >= PAIRING_ATTEMPTS {
+            *pairing = None;
+        }
+        return false;
+    }
+    *pairing = None;
+    true
+}
+
+/// Compares in time that does not depend on where the inputs differ.
+pub fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,120 @@
+//! How pasting and typing went in each app, so the user can find the apps that need
+//! `outputMode: "type"` in a profile. Kept in `paste-stats.json` beside the settings and
+//! never sent anywhere.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::settings::OutputMode;
+
+const STATS_FILE: &str = "paste-stats.json";
+/// Deliveries that failed or needed a retry before typing is suggested.
+const SUGGEST_AFTER: u64 = 2;
+
+/// Loaded on the first delivery.
+static APPS: Mutex<Option<BTreeMap<String, AppPasteStats>>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppPasteStats {
+    pub app: String,
+    /// Transcripts pasted or typed without trouble.
+    pub delivered: u64,
+    /// Those that only went through on the retry.
+    pub retried: u64,
+    pub failed: u64,
+    /// The output mode of the latest delivery.
+    pub last_mode: Option<OutputMode>,
+    pub last_error: Option<String>,
+    /// Unix time in milliseconds.
+    pub last_trouble_at: Option<i64>,
+    /// Pastes into this app fail or need retries often enough that a profile typing into
+    /// it would likely do better.
+    pub suggest_typing: bool,
+}
+
+/// How a delivery into an app went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Delivered,
+    Retried,
+    Failed(String),
+}
+
+/// Counts a paste or typed delivery into `app`.
+pub fn record(app: &str, mode: OutputMode, outcome: Outcome) {
+    let Ok(mut apps) = APPS.lock() else {
+        return;
+    };
+    let apps = apps.get_or_insert_with(load);
+    let stats = apps
+        .entry(app.to_string())
+        .or_insert_with(|| AppPasteStats {
+            app: app.to_string(),
+            ..AppPasteStats::default()
+        });
+    stats.last_mode = Some(mode);
+    match outcome {
+        Outcome::Delivered => stats.delivered += 1,
+        Outcome::Retried => {
+            stats.retried += 1;
+            stats.last_trouble_at = Some(now_ms());
+        }
+        Outcome::Failed(err) => {
+            stats.failed += 1;
+            stats.last_error = Some(err);
+            stats.last_trouble_at = Some(now_ms());
+        }
+    }
+    // A fifth of pastes or more in trouble; retries count as they leave the app waiting.
+    let trouble = stats.retried + stats.failed;
+    stats.suggest_typing = mode == OutputMode::Paste
+        && trouble >= SUGGEST_AFTER
+        && trouble * 5 >= trouble + stats.delivered;
+    if let Err(err) = save(apps) {
+        let _ = crate::log::write(format!("Failed to save paste stats: {}", err));
+    }
+}
+
+/// Every app delivered into, the most troubled first.
+pub fn export() -> Result<Vec<AppPasteStats>, String> {
+    let mut apps = APPS.lock().map_err(|err| err.to_string())?;
+    let mut stats: Vec<AppPasteStats> = apps.get_or_insert_with(load).values().cloned().collect();
+    stats.sort_by_key(|stats| std::cmp::Reverse(stats.retried + stats.failed));
+    Ok(stats)
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+fn load() -> BTreeMap<String, AppPasteStats> {
+    path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str::<Vec<AppPasteStats>>(&json).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|stats| (stats.app.clone(), stats))
+        .collect()
+}
+
+fn save(apps: &BTreeMap<String, AppPasteStats>) -> Result<(), String> {
+    let path = path().ok_or("No settings directory")?;
+    let stats: Vec<&AppPasteStats> = apps.values().collect();
+    let json = serde_json::to_vec_pretty(&stats).map_err(|err| err.to_string())?;
+    crate::safe_io::write(&path, json).map_err(|err| err.to_string())
+}
+
+/// Beside the settings file.
+fn path() -> Option<PathBuf> {
+    crate::settings::path()?
+        .parent()
+        .map(|dir| dir.join(STATS_FILE))
+}
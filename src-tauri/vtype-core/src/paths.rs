@@ -0,0 +1,87 @@
+//! Where VType keeps its files. Normally settings live in the OS config dir, logs and
+//! working files in the app's cache dir or `workingDir`, and models in local app data; in
+//! portable mode everything goes in a `vtype-data` directory beside the executable so the
+//! app can run from a USB stick.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// Presence of this file next to the executable turns on portable mode.
+pub const PORTABLE_FLAG_FILE: &str = "vtype.portable";
+pub const PORTABLE_ARG: &str = "--portable";
+pub const PORTABLE_DATA_DIR: &str = "vtype-data";
+
+static PORTABLE_DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+/// The app's cache directory, named once at startup.
+static DEFAULT_WORKING_DIR: OnceLock<PathBuf> = OnceLock::new();
+/// `workingDir` from the settings. Kept here rather than read from them so a crash report
+/// can find the log directory without the settings lock.
+static CHOSEN_WORKING_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+fn detect_portable_dir() -> Option<PathBuf> {
+    let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
+    let requested = std::env::args().skip(1).any(|arg| arg == PORTABLE_ARG)
+        || exe_dir.join(PORTABLE_FLAG_FILE).is_file();
+    requested.then(|| exe_dir.join(PORTABLE_DATA_DIR))
+}
+
+/// The data directory when running portable, detected once per process.
+pub fn portable_dir() -> Option<&'static Path> {
+    PORTABLE_DIR.get_or_init(detect_portable_dir).as_deref()
+}
+
+pub fn is_portable() -> bool {
+    portable_dir().is_some()
+}
+
+/// Directory for settings and other per-user state; `os_default` unless portable.
+pub fn config_dir(os_default: PathBuf) -> PathBuf {
+    portable_dir().map(Path::to_path_buf).unwrap_or(os_default)
+}
+
+/// Only the first call wins.
+pub fn set_default_working_dir(dir: PathBuf) {
+    let _ = DEFAULT_WORKING_DIR.set(dir);
+}
+
+/// Follows `workingDir`; `None` goes back to the default.
+pub fn set_working_dir(dir: Option<PathBuf>) {
+    if let Ok(mut chosen) = CHOSEN_WORKING_DIR.lock() {
+        *chosen = dir;
+    }
+}
+
+/// Where logs, the last recording and files handed to the worker go: `workingDir` when
+/// set, else the portable data directory or the app's cache directory. The system temp
+/// directory stands in until startup names one.
+pub fn working_dir() -> PathBuf {
+    if let Some(dir) = CHOSEN_WORKING_DIR
+        .lock()
+        .ok()
+        .and_then(|chosen| chosen.clone())
+    {
+        return dir;
+    }
+    portable_dir()
+        .or_else(|| DEFAULT_WORKING_DIR.get().map(PathBuf::as_path))
+        .map(Path::to_path_buf)
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+pub fn log_file() -> PathBuf {
+    working_dir().join("logs").join("vtype.log")
+}
+
+/// Base directory the worker downloads models and caches into, passed as `VTYPE_DATA_DIR`.
+/// `None` leaves the worker on its platform default.
+pub fn worker_data_dir() -> Option<PathBuf> {
+    portable_dir().map(|dir| dir.join("worker"))
+}
+
+/// Where the worker keeps its files, matching `app_base_path` in `transcribe_wav.py`.
+pub fn worker_dir() -> PathBuf {
+    worker_data_dir().unwrap_or_else(|| match std::env::var_os("LOCALAPPDATA") {
+        Some(dir) => PathBuf::from(dir).join("vtype"),
+        None => std::env::temp_dir().join("vtype"),
+    })
+}
@@ -0,0 +1,300 @@
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::activity::{self, ActivityKind};
+use crate::backend::Backend;
+use crate::frontmost::{self, FrontmostApp};
+use crate::hooks::{self, Hook};
+use crate::metrics::ErrorCategory;
+use crate::options::TranscribeOptions;
+use crate::settings::Settings;
+use crate::worker::{Fallback, Priority};
+use crate::{audio, backend, history, metrics, plugins, postprocess, settings};
+
+/// The most recent dictation, whether or not history is kept.
+static LAST_TRANSCRIPT: Mutex<Option<LastTranscript>> = Mutex::new(None);
+
+#[derive(Debug, Clone)]
+pub struct LastTranscript {
+    pub text: String,
+    /// Its history entry; `None` when saving it failed.
+    pub entry_id: Option<i64>,
+    /// See [`Utterance::confidence`].
+    pub confidence: Option<f32>,
+}
+
+/// The opening part of a dictation, transcribed while it was still recorded.
+#[derive(Debug, Clone)]
+pub struct Part {
+    pub text: String,
+    pub confidence: Option<f32>,
+}
+
+/// A transcript and how it came about, for the overlay to show beside it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Utterance {
+    pub text: String,
+    /// Length of the recording, when it could be read from the WAV.
+    pub duration_ms: Option<u64>,
+    /// Such as `nemo-parakeet-tdt-0.6b-v3`, or `mock`.
+    pub model: String,
+    /// The language asked for, by the request or the profile; `None` when the model was
+    /// left to detect it, since the worker does not report what it detected.
+    pub language: Option<String>,
+    /// Time spent in the backend, for a dictation transcribed while recording only the
+    /// part after its last early part.
+    pub processing_ms: u64,
+    pub backend: String,
+    /// How sure the model was, from 0 to 1: of the least certain part, for a dictation
+    /// transcribed in parts. `None` when the model does not say.
+    pub confidence: Option<f32>,
+}
+
+/// Runs one WAV clip through the worker with the user's settings applied and returns the
+/// post-processed transcript. Interactive dictations are also saved to history.
+pub async fn transcribe(
+    priority: Priority,
+    wav_bytes: Vec<u8>,
+    options: TranscribeOptions,
+) -> Result<Utterance, String> {
+    let settings = settings::current();
+    let retained = settings.history.retain_audio.then(|| wav_bytes.clone());
+    // A dictation goes to the app that has focus, whose profile may prime the backend.
+    let target = (priority == Priority::Interactive)
+        .then(frontmost::detect)
+        .flatten();
+    let result = run(
+        &settings,
+        priority,
+        wav_bytes,
+        &[],
+        options,
+        target.as_ref(),
+    )
+    .await?;
+    if priority == Priority::Interactive {
+        keep(&settings, &result, retained);
+    }
+    Ok(result.utterance())
+}
+
+/// Transcribes the opening part of a dictation still being recorded, for
+/// [`transcribe_rest`] to join up; see [`crate::early`]. The text is the backend's own,
+/// since the transcript is cleaned up once whole.
+pub async fn transcribe_part(wav_bytes: Vec<u8>) -> Result<Part, String> {
+    let settings = settings::current();
+    let target = frontmost::detect();
+    let (options, backend) = resolve(&settings, TranscribeOptions::default(), target.as_ref())?;
+    let response = backend::transcribe(
+        backend,
+        Priority::Interactive,
+        options.to_worker_json(),
+        wav_bytes,
+    )
+    .await?;
+    Ok(Part {
+        text: response.text,
+        confidence: response.confidence,
+    })
+}
+
+/// [`transcribe`] for a dictation whose opening was transcribed by [`transcribe_part`]
+/// while it was recorded: `earlier` holds those parts in order and `tail` the audio after
+/// them. `wav_bytes` is the whole recording, for history.
+pub async fn transcribe_rest(
+    wav_bytes: Vec<u8>,
+    earlier: Vec<Part>,
+    tail: Vec<u8>,
+) -> Result<Utterance, String> {
+    let settings = settings::current();
+    let target = frontmost::detect();
+    let mut result = run(
+        &settings,
+        Priority::Interactive,
+        tail,
+        &earlier,
+        TranscribeOptions::default(),
+        target.as_ref(),
+    )
+    .await?;
+    result.audio_ms = audio::wav_duration_ms(&wav_bytes);
+    keep(
+        &settings,
+        &result,
+        settings.history.retain_audio.then_some(wav_bytes),
+    );
+    Ok(result.utterance())
+}
+
+/// Records a dictation in history, with its audio when `retained`, and runs the
+/// post-transcribe hook.
+fn keep(settings: &Settings, result: &Transcript, retained: Option<Vec<u8>>) {
+    if !result.text.is_empty() {
+        let entry = history::NewEntry {
+            text: result.text.clone(),
+            backend: result.backend.name().to_string(),
+            model: Some(result.model.clone()),
+            language: result.options.language.clone(),
+            profile: settings.active_profile.clone(),
+            audio_ms: result.audio_ms,
+            latency_ms: result.latency_ms,
+            fallback: result.fallback.map(|fallback| fallback.name().to_string()),
+        };
+        let recorded = history::record(entry);
+        let saved = recorded.clone().and_then(|id| match retained {
+            Some(wav) => history::save_audio(id, &wav),
+            None => Ok(()),
+        });
+        if let Err(err) = saved {
+            let _ = crate::log::write(format!("Failed to save history entry: {}", err));
+        }
+        if let Ok(mut last) = LAST_TRANSCRIPT.lock() {
+            *last = Some(LastTranscript {
+                text: result.text.clone(),
+                entry_id: recorded.ok(),
+                confidence: result.confidence,
+            });
+        }
+        hooks::notify(Hook::PostTranscribe, &result.text);
+    }
+}
+
+pub fn last_transcript() -> Option<LastTranscript> {
+    LAST_TRANSCRIPT.lock().ok().and_then(|last| last.clone())
+}
+
+/// Runs a history entry's retained recording through the current backend again and stores
+/// the result as a revision of that entry; the entry's own text is left as it was.
+pub async fn retranscribe(
+    entry_id: i64,
+    options: TranscribeOptions,
+) -> Result<history::Revision, String> {
+    let wav_bytes = history::load_audio(entry_id)?;
+    let result = run(
+        &settings::current(),
+        Priority::Background,
+        wav_bytes,
+        &[],
+        options,
+        None,
+    )
+    .await?;
+    history::add_revision(
+        entry_id,
+        history::RevisionKind::Retranscription,
+        &result.text,
+        Some(result.backend.name()),
+    )
+}
+
+struct Transcript {
+    text: String,
+    backend: Backend,
+    model: String,
+    options: TranscribeOptions,
+    audio_ms: Option<u64>,
+    latency_ms: u64,
+    fallback: Option<Fallback>,
+    confidence: Option<f32>,
+}
+
+impl Transcript {
+    fn utterance(self) -> Utterance {
+        Utterance {
+            text: self.text,
+            duration_ms: self.audio_ms,
+            model: self.model,
+            language: self.options.language,
+            processing_ms: self.latency_ms,
+            backend: self.backend.name().to_string(),
+            confidence: self.confidence,
+        }
+    }
+}
+
+/// The options a request runs with and the backend they pick.
+fn resolve(
+    settings: &Settings,
+    options: TranscribeOptions,
+    target: Option<&FrontmostApp>,
+) -> Result<(TranscribeOptions, Backend), String> {
+    let options = options
+        .with_profile_defaults(settings, target)
+        .with_decoding_defaults(&settings.decoding);
+    options
+        .validate()
+        .inspect_err(|_| metrics::count_error(ErrorCategory::InvalidOptions))?;
+    let backend = backend::active(options.backend.as_deref())
+        .inspect_err(|_| metrics::count_error(ErrorCategory::Backend))?;
+    Ok((options, backend))
+}
+
+/// Transcribes `wav_bytes` and cleans up the text, joined after `earlier`, the parts
+/// transcribed before it with their raw text.
+async fn run(
+    settings: &Settings,
+    priority: Priority,
+    wav_bytes: Vec<u8>,
+    earlier: &[Part],
+    options: TranscribeOptions,
+    target: Option<&FrontmostApp>,
+) -> Result<Transcript, String> {
+    let (options, backend) = resolve(settings, options, target)?;
+    let audio_ms = audio::wav_duration_ms(&wav_bytes);
+    let started = Instant::now();
+    let response = backend::transcribe(backend, priority, options.to_worker_json(), wav_bytes)
+        .await
+        .inspect_err(|err| {
+            activity::record(ActivityKind::TranscriptionFailed, None, Some(err.clone()))
+        })?;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    metrics::count_transcription(backend.name());
+    let raw = earlier
+        .iter()
+        .map(|part| &part.text)
+        .chain([&response.text])
+        .map(|text| text.trim())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let text = postprocess::clean_transcript(&raw, settings.effective_code_mode(target));
+    let context = target.filter(|_| settings.app_context);
+    let metadata = plugins::Metadata {
+        language: options.language.clone(),
+        profile: settings
+            .output_profile(target)
+            .map(|profile| profile.name.clone()),
+        backend: backend.name().to_string(),
+        app: context.map(|app| app.name.clone()),
+        title: context.and_then(|app| app.title.clone()),
+    };
+    let text = plugins::apply(settings, text, &metadata);
+    let confidence = earlier
+        .iter()
+        .map(|part| part.confidence)
+        .chain([response.confidence])
+        .flatten()
+        .reduce(f32::min);
+    activity::record(
+        ActivityKind::Transcribed,
+        Some(started.elapsed()),
+        Some(format!(
+            "{}, {} chars",
+            backend.name(),
+            text.chars().count()
+        )),
+    );
+    Ok(Transcript {
+        text,
+        backend,
+        model: response.model,
+        options,
+        audio_ms,
+        latency_ms,
+        fallback: response.fallback,
+        confidence,
+    })
+}
@@ -0,0 +1,198 @@
+//! WebAssembly plugins that rework a transcript, a safer way to extend post-processing than
+//! shell hooks: a plugin sees the text and what VType tells it about the dictation, and
+//! nothing of the files, network or programs on the machine. Plugins are the `.wasm` files
+//! in `plugins` beside the settings; those named in `plugins.enabled` run, in that order,
+//! once the transcript is cleaned up.
+//!
+//! A plugin imports nothing and exports:
+//!
+//! - `memory`;
+//! - `alloc(len: i32) -> i32`, where VType may write `len` bytes;
+//! - `transform(text: i32, text_len: i32, meta: i32, meta_len: i32) -> i64`, given the
+//!   UTF-8 transcript and a JSON object of [`Metadata`], returning where its UTF-8 result is
+//!   as `ptr << 32 | len`.
+//!
+//! A plugin that fails to load, traps, runs out of fuel or returns invalid UTF-8 is logged
+//! and skipped, leaving the text as it was.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use serde::Serialize;
+use wasmtime::{Config, Engine, Instance, Memory, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::settings::Settings;
+
+pub const PLUGINS_DIR: &str = "plugins";
+/// Roughly a second of work; a plugin stuck in a loop is stopped here.
+const FUEL: u64 = 500_000_000;
+const MAX_MEMORY_BYTES: usize = 64 << 20;
+const MAX_OUTPUT_BYTES: usize = 1 << 20;
+
+static ENGINE: OnceLock<Result<Engine, String>> = OnceLock::new();
+/// Compiled modules by path, with the modification time they were compiled at.
+static MODULES: Mutex<BTreeMap<PathBuf, (SystemTime, Module)>> = Mutex::new(BTreeMap::new());
+
+/// A plugin found in the plugins directory.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Plugin {
+    /// The file name without `.wasm`, as `plugins.enabled` lists it.
+    pub name: String,
+    pub path: String,
+    pub enabled: bool,
+}
+
+/// What a plugin is told about the dictation.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metadata {
+    /// The language asked for, if any.
+    pub language: Option<String>,
+    pub profile: Option<String>,
+    pub backend: String,
+    /// The focused app's name and window title, when `appContext` allows.
+    pub app: Option<String>,
+    pub title: Option<String>,
+}
+
+pub fn dir() -> Option<PathBuf> {
+    crate::settings::path()?
+        .parent()
+        .map(|dir| dir.join(PLUGINS_DIR))
+}
+
+/// Every plugin in the directory, by name; none when there is no directory.
+pub fn list(settings: &Settings) -> Result<Vec<Plugin>, String> {
+    let Some(dir) = dir() else {
+        return Ok(Vec::new());
+    };
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(format!("Failed to list {}: {}", dir.display(), err)),
+    };
+    let mut plugins: Vec<Plugin> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_str()?.to_string();
+            Some(Plugin {
+                enabled: settings.plugins.enabled.contains(&name),
+                path: path.display().to_string(),
+                name,
+            })
+        })
+        .collect();
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(plugins)
+}
+
+/// `text` after every enabled plugin has had its turn.
+pub fn apply(settings: &Settings, text: String, metadata: &Metadata) -> String {
+    if settings.plugins.enabled.is_empty() || text.is_empty() {
+        return text;
+    }
+    let Some(dir) = dir() else {
+        return text;
+    };
+    let Ok(metadata) = serde_json::to_string(metadata) else {
+        return text;
+    };
+    settings.plugins.enabled.iter().fold(text, |text, name| {
+        let path = dir.join(format!("{}.wasm", name));
+        match transform(&path, &text, &metadata) {
+            Ok(transformed) => transformed,
+            Err(err) => {
+                let _ = crate::log::write(format!("Plugin {} failed: {}", name, err));
+                text
+            }
+        }
+    })
+}
+
+/// Runs the plugin at `path` once over `text`, with `metadata` as JSON.
+pub fn transform(path: &Path, text: &str, metadata: &str) -> Result<String, String> {
+    let module = load(path)?;
+    let mut store = Store::new(
+        engine()?,
+        StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY_BYTES)
+            .instances(1)
+            .build(),
+    );
+    store.limiter(|limits: &mut StoreLimits| limits);
+    store.set_fuel(FUEL).map_err(|err| err.to_string())?;
+    // No imports are offered, so a plugin asking for any fails to instantiate.
+    let instance = Instance::new(&mut store, &module, &[]).map_err(|err| err.to_string())?;
+    let memory = instance
+        .get_memory(&mut store, "memory")
+        .ok_or("No memory exported")?;
+    let alloc = instance
+        .get_typed_func::<i32, i32>(&mut store, "alloc")
+        .map_err(|err| err.to_string())?;
+    let run = instance
+        .get_typed_func::<(i32, i32, i32, i32), i64>(&mut store, "transform")
+        .map_err(|err| err.to_string())?;
+    let (text_ptr, text_len) = write(&mut store, &memory, &alloc, text.as_bytes())?;
+    let (meta_ptr, meta_len) = write(&mut store, &memory, &alloc, metadata.as_bytes())?;
+    let result = run
+        .call(&mut store, (text_ptr, text_len, meta_ptr, meta_len))
+        .map_err(|err| err.to_string())? as u64;
+    let (ptr, len) = ((result >> 32) as usize, (result & 0xffff_ffff) as usize);
+    if len > MAX_OUTPUT_BYTES {
+        return Err(format!("Result of {} bytes is too long", len));
+    }
+    let bytes = memory
+        .data(&store)
+        .get(ptr..ptr + len)
+        .ok_or("Result outside the plugin's memory")?;
+    String::from_utf8(bytes.to_vec()).map_err(|_| "Result is not UTF-8".to_string())
+}
+
+fn write<T>(
+    store: &mut Store<T>,
+    memory: &Memory,
+    alloc: &wasmtime::TypedFunc<i32, i32>,
+    bytes: &[u8],
+) -> Result<(i32, i32), String> {
+    let len = i32::try_from(bytes.len()).map_err(|_| "Input too long".to_string())?;
+    let ptr = alloc
+        .call(&mut *store, len)
+        .map_err(|err| err.to_string())?;
+    memory
+        .write(&mut *store, ptr as u32 as usize, bytes)
+        .map_err(|_| "alloc returned memory outside the plugin's".to_string())?;
+    Ok((ptr, len))
+}
+
+fn engine() -> Result<&'static Engine, String> {
+    ENGINE
+        .get_or_init(|| {
+            let mut config = Config::new();
+            config.consume_fuel(true);
+            Engine::new(&config).map_err(|err| err.to_string())
+        })
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+/// The compiled plugin, compiled again when the file changed since.
+fn load(path: &Path) -> Result<Module, String> {
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+    let mut modules = MODULES.lock().map_err(|err| err.to_string())?;
+    if let Some((compiled_at, module)) = modules.get(path) {
+        if *compiled_at == modified {
+            return Ok(module.clone());
+        }
+    }
+    let module = Module::from_file(engine()?, path).map_err(|err| err.to_string())?;
+    modules.insert(path.to_path_buf(), (modified, module.clone()));
+    Ok(module)
+}
@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+
+use regex::{Captures, Regex, RegexBuilder};
+
+use crate::code;
+use crate::settings::{self, CodeLanguage, DictionaryRule, TemplateSettings};
+
+/// Placeholder for the dictation in a template.
+pub const TEXT_PLACEHOLDER: &str = "{text}";
+
+static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+
+/// Compiled form of the dictionary, rebuilt whenever the rules in settings change.
+struct CompiledDictionary {
+    rules: Vec<DictionaryRule>,
+    patterns: Vec<(Regex, String)>,
+}
+
+static DICTIONARY: Mutex<Option<CompiledDictionary>> = Mutex::new(None);
+
+/// Normalizes raw worker output into the text that gets pasted, as code with `code_mode`.
+pub fn clean_transcript(raw: &str, code_mode: Option<CodeLanguage>) -> String {
+    let text = raw.trim().to_string();
+    let text = apply_dictionary(&text, &settings::current().effective_dictionary());
+    match code_mode {
+        Some(language) => code::apply(&text, language),
+        None => text,
+    }
+}
+
+pub fn apply_dictionary(text: &str, rules: &[DictionaryRule]) -> String {
+    if rules.is_empty() {
+        return text.to_string();
+    }
+    let mut guard = match DICTIONARY.lock() {
+        Ok(guard) => guard,
+        Err(_) => return text.to_string(),
+    };
+    let stale = guard
+        .as_ref()
+        .is_none_or(|compiled| compiled.rules != rules);
+    if stale {
+        *guard = Some(CompiledDictionary {
+            rules: rules.to_vec(),
+            patterns: compile(rules),
+        });
+    }
+    let Some(compiled) = guard.as_ref() else {
+        return text.to_string();
+    };
+    compiled
+        .patterns
+        .iter()
+        .fold(text.to_string(), |text, (pattern, replacement)| {
+            pattern
+                .replace_all(&text, regex::NoExpand(replacement))
+                .into_owned()
+        })
+}
+
+fn compile(rules: &[DictionaryRule]) -> Vec<(Regex, String)> {
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let pattern = format!(r"\b{}\b", regex::escape(rule.from.trim()));
+            RegexBuilder::new(&pattern)
+                .case_insensitive(true)
+                .build()
+                .ok()
+                .map(|regex| (regex, rule.to.clone()))
+        })
+        .collect()
+}
+
+/// `text` put into the template its first words name, without them, or else into the active
+/// template. Without either it is returned as it is.
+pub fn apply_template(text: &str, settings: &TemplateSettings) -> String {
+    let spoken = settings.templates.iter().find_map(|template| {
+        template
+            .phrases
+            .iter()
+            .find_map(|phrase| phrase_end(text, phrase))
+            .map(|end| (template, &text[end..]))
+    });
+    let chosen = spoken.or_else(|| {
+        let name = settings.active.as_deref()?;
+        settings
+            .templates
+            .iter()
+            .find(|template| template.name == name)
+            .map(|template| (template, text))
+    });
+    match chosen {
+        Some((template, body)) => expand_template(&template.text, body, &settings.variables),
+        None => text.to_string(),
+    }
+}
+
+/// Where `phrase` ends if `text` starts with it as whole words, with the punctuation and
+/// spaces after it.
+fn phrase_end(text: &str, phrase: &str) -> Option<usize> {
+    let words: Vec<String> = phrase.split_whitespace().map(regex::escape).collect();
+    if words.is_empty() {
+        return None;
+    }
+    let pattern = format!(r"^\s*{}\b[\s\p{{P}}]*", words.join(r"[\s,]+"));
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .ok()?;
+    regex.find(text).map(|found| found.end())
+}
+
+/// `template` with `text` for [`TEXT_PLACEHOLDER`], `variables` for theirs, and today's date
+/// and the time for `{date}` and `{time}`. Unknown placeholders are left in.
+pub fn expand_template(template: &str, text: &str, variables: &BTreeMap<String, String>) -> String {
+    let now = chrono::Local::now();
+    PLACEHOLDER
+        .get_or_init(|| Regex::new(r"\{([A-Za-z_][A-Za-z0-9_]*)\}").expect("valid pattern"))
+        .replace_all(template, |captures: &Captures| {
+            let name = &captures[1];
+            match name {
+                "text" => text.to_string(),
+                _ => match variables.get(name) {
+                    Some(value) => value.clone(),
+                    None if name == "date" => now.format("%Y-%m-%d").to_string(),
+                    None if name == "time" => now.format("%H:%M").to_string(),
+                    None => captures[0].to_string(),
+                },
+            }
+        })
+        .into_owned()
+}
@@ -0,0 +1,163 @@
+//! Saving power on laptops, as `lowPower` asks. While the machine runs on battery, or
+//! always, the worker loads the smaller int8 model and is stopped once it has been idle for
+//! `lowPower.idleShutdownSecs`. The power source is checked every [`POLL`].
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::settings::{self, LowPowerMode};
+use crate::{log, worker};
+
+const POLL: Duration = Duration::from_secs(30);
+
+static SOURCE: Mutex<PowerSource> = Mutex::new(PowerSource::Unknown);
+static WATCHING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PowerSource {
+    /// Mains power, or a machine without a battery.
+    Ac,
+    Battery,
+    /// Not found out yet, or not on this platform.
+    #[default]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PowerState {
+    pub source: PowerSource,
+    /// Whether the worker is saving power.
+    pub low_power: bool,
+}
+
+pub fn current() -> PowerState {
+    let source = SOURCE.lock().map_or(PowerSource::Unknown, |source| *source);
+    let low_power = match settings::current().low_power.mode {
+        LowPowerMode::Off => false,
+        LowPowerMode::OnBattery => source == PowerSource::Battery,
+        LowPowerMode::Always => true,
+    };
+    PowerState { source, low_power }
+}
+
+/// Whether the worker should load the int8 model whatever `model.quantization` says.
+pub fn smaller_model() -> bool {
+    current().low_power && settings::current().low_power.smaller_model
+}
+
+/// Starts checking the power source. Only the first call does anything.
+pub fn watch() {
+    if WATCHING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    std::thread::spawn(|| loop {
+        check();
+        std::thread::sleep(POLL);
+    });
+}
+
+fn check() {
+    let source = detect();
+    let previous = SOURCE
+        .lock()
+        .map(|mut current| std::mem::replace(&mut *current, source))
+        .unwrap_or(source);
+    if previous != source {
+        let _ = log::write(format!("Power source: {:?}", source));
+        // The worker loads the model that suits the new source with its next job.
+        let _ = worker::report_status();
+    }
+    if !current().low_power {
+        return;
+    }
+    if let Some(secs) = settings::current().low_power.idle_shutdown_secs {
+        let _ = worker::stop_if_idle(Duration::from_secs(secs));
+    }
+}
+
+/// From the system batteries, leaving out those of attached devices such as mice. Mains
+/// power online or a battery that is not discharging counts as AC.
+#[cfg(target_os = "linux")]
+fn detect() -> PowerSource {
+    let Ok(supplies) = std::fs::read_dir("/sys/class/power_supply") else {
+        return PowerSource::Unknown;
+    };
+    let mut battery = false;
+    let mut mains_online = false;
+    let mut discharging = false;
+    for supply in supplies.flatten() {
+        let path = supply.path();
+        let read = |name: &str| {
+            std::fs::read_to_string(path.join(name))
+                .map(|value| value.trim().to_string())
+                .unwrap_or_default()
+        };
+        match read("type").as_str() {
+            "Mains" | "USB" => mains_online |= read("online") == "1",
+            "Battery" if read("scope") != "Device" => {
+                battery = true;
+                discharging |= read("status") == "Discharging";
+            }
+            _ => {}
+        }
+    }
+    if battery && discharging && !mains_online {
+        PowerSource::Battery
+    } else {
+        PowerSource::Ac
+    }
+}
+
+/// `pmset -g batt` starts with "Now drawing from 'Battery Power'" or "'AC Power'".
+#[cfg(target_os = "macos")]
+fn detect() -> PowerSource {
+    let mut command = std::process::Command::new("pmset");
+    command
+        .args(["-g", "batt"])
+        .stderr(std::process::Stdio::null());
+    crate::process::configure_background_command(&mut command);
+    let Ok(output) = command.output() else {
+        return PowerSource::Unknown;
+    };
+    let output = String::from_utf8_lossy(&output.stdout);
+    if output.contains("'Battery Power'") {
+        PowerSource::Battery
+    } else if output.contains("'AC Power'") {
+        PowerSource::Ac
+    } else {
+        PowerSource::Unknown
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn detect() -> PowerSource {
+    use windows_sys::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status = SYSTEM_POWER_STATUS {
+        ACLineStatus: 0,
+        BatteryFlag: 0,
+        BatteryLifePercent: 0,
+        SystemStatusFlag: 0,
+        BatteryLifeTime: 0,
+        BatteryFullLifeTime: 0,
+    };
+    // SAFETY: `status` is a valid SYSTEM_POWER_STATUS for the call to fill in.
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return PowerSource::Unknown;
+    }
+    match status.ACLineStatus {
+        0 => PowerSource::Battery,
+        1 => PowerSource::Ac,
+        _ => PowerSource::Unknown,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn detect() -> PowerSource {
+    PowerSource::Unknown
+}
@@ -0,0 +1,47 @@
+//! Turns captured audio into the mono 16 kHz clip the backends expect.
+
+use crate::settings::CaptureSettings;
+use crate::{audio, echo};
+
+/// The rate every backend is fed.
+pub const TARGET_SAMPLE_RATE: u32 = 16_000;
+
+/// Interleaved samples as the device delivered them.
+#[derive(Debug, Clone, Default)]
+pub struct Captured {
+    pub samples: Vec<f32>,
+    pub channels: u16,
+    pub sample_rate: u32,
+    /// Channels to mix down, numbered from 1; empty mixes every channel carrying signal.
+    pub selection: Vec<u16>,
+}
+
+impl Captured {
+    pub fn duration_secs(&self) -> f32 {
+        self.samples.len() as f32
+            / (f32::from(self.channels.max(1)) * self.sample_rate.max(1) as f32)
+    }
+}
+
+/// Runs the preprocessing chain and returns mono samples at [`TARGET_SAMPLE_RATE`]. `mic`
+/// holds one segment per device the recording used, in order; `reference` is the loopback
+/// recording of the speakers, when one was captured.
+pub fn process(
+    mic: Vec<Captured>,
+    reference: Option<Captured>,
+    capture: &CaptureSettings,
+) -> Vec<f32> {
+    let mut samples: Vec<f32> = mic
+        .iter()
+        .flat_map(|segment| {
+            let mono = audio::downmix(&segment.samples, segment.channels, &segment.selection);
+            audio::resample(&mono, segment.sample_rate, TARGET_SAMPLE_RATE)
+        })
+        .collect();
+    if let Some(reference) = reference.filter(|_| capture.echo_cancellation) {
+        let mono = audio::downmix(&reference.samples, reference.channels, &reference.selection);
+        let reference = audio::resample(&mono, reference.sample_rate, TARGET_SAMPLE_RATE);
+        samples = echo::cancel(&samples, &reference, TARGET_SAMPLE_RATE);
+    }
+    samples
+}
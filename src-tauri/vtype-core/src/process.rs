@@ -0,0 +1,29 @@
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+use std::process::Command;
+
+#[cfg(target_os = "windows")]
+pub fn configure_background_command(command: &mut Command) {
+    // CREATE_NO_WINDOW
+    command.creation_flags(0x08000000);
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn configure_background_command(_command: &mut Command) {}
+
+/// `command` run through the platform's shell.
+pub fn shell(command: &str) -> Command {
+    #[cfg(target_os = "windows")]
+    let shell = {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    };
+    #[cfg(not(target_os = "windows"))]
+    let shell = {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    };
+    shell
+}
@@ -0,0 +1,281 @@
+//! Framing between the app and the Python worker.
+//!
+//! Requests are two length-prefixed frames (JSON options carrying a request `id`, then the
+//! WAV). Responses are `MAGIC | id | len | payload`, all integers little-endian `u32`. The
+//! magic and id let the reader skip garbage (stray prints from native libraries) and stale
+//! replies; anything it cannot recover from within a bounded budget is reported as a
+//! protocol error so the caller restarts the worker rather than trusting the stream.
+//!
+//! Before any of that the worker prints one line, `ready` followed by a JSON [`Handshake`],
+//! so a script left over from another build is caught before it is sent a request, or
+//! `failed` followed by a JSON [`WorkerFailure`] when it could not load the model. Failed
+//! requests are answered with `ERROR:` and a failure in the same shape. When there is more to
+//! say than the transcript, how sure the model was or decoding options it does not take, the
+//! worker answers with `RESULT:` and a JSON [`StructuredText`] instead of the bare text.
+
+use std::io::{Read, Write};
+
+use serde::Deserialize;
+
+pub const RESPONSE_MAGIC: [u8; 4] = *b"VTR1";
+/// Transcripts are text; anything larger means the length prefix is corrupt.
+pub const MAX_RESPONSE_BYTES: usize = 1024 * 1024;
+/// How many bytes of garbage or stale frames to skip before giving up on a stream.
+pub const MAX_RESYNC_BYTES: usize = 4 * MAX_RESPONSE_BYTES;
+const WORKER_ERROR_PREFIX: &str = "ERROR:";
+const STRUCTURED_PREFIX: &str = "RESULT:";
+const READY: &str = "ready";
+const FAILED: &str = "failed";
+
+/// Protocol version this build speaks; `WORKER_VERSION` in `transcribe_wav.py` must match.
+pub const WORKER_VERSION: u32 = 2;
+/// What the worker has to support: empty-frame pings, decoding options in the header,
+/// [`WorkerFailure`]s and `VTYPE_EXECUTION_PROVIDER`.
+pub const REQUIRED_CAPABILITIES: &[&str] = &[
+    "ping",
+    "decoding-options",
+    "structured-errors",
+    "execution-provider",
+];
+
+/// What the worker announces on its ready line.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Handshake {
+    pub version: u32,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+impl Handshake {
+    /// Why this worker cannot serve this build, if it cannot.
+    pub fn incompatibility(&self) -> Option<String> {
+        if self.version != WORKER_VERSION {
+            return Some(format!(
+                "speaks protocol version {} instead of {}",
+                self.version, WORKER_VERSION
+            ));
+        }
+        let missing: Vec<&str> = REQUIRED_CAPABILITIES
+            .iter()
+            .copied()
+            .filter(|required| !self.capabilities.iter().any(|offered| offered == required))
+            .collect();
+        (!missing.is_empty()).then(|| format!("lacks {}", missing.join(", ")))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Text(String),
+    Structured(StructuredText),
+    /// The worker handled the request but failed (bad audio, inference error). The stream
+    /// is still in sync.
+    WorkerError(WorkerFailure),
+}
+
+/// A transcript with what the worker has to say about it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct StructuredText {
+    pub text: String,
+    /// The geometric mean of the token probabilities, from 0 to 1, when the model reports
+    /// them.
+    #[serde(default)]
+    pub confidence: Option<f32>,
+    /// Options in the request the loaded model does not take, left out of decoding.
+    #[serde(default)]
+    pub ignored: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// An allocation failed, on the GPU or in RAM.
+    OutOfMemory,
+    /// The model could not be loaded, e.g. a GPU runtime that does not work.
+    ModelLoad,
+    #[serde(other)]
+    Other,
+}
+
+/// Why the worker could not start or serve a request.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct WorkerFailure {
+    pub kind: FailureKind,
+    pub message: String,
+}
+
+impl WorkerFailure {
+    pub fn other(message: impl Into<String>) -> WorkerFailure {
+        WorkerFailure {
+            kind: FailureKind::Other,
+            message: message.into(),
+        }
+    }
+
+    /// Reads the JSON after a worker's `ERROR:` or `failed`; anything else, such as a plain
+    /// message from an older script, is kept as the message.
+    fn parse(text: &str) -> WorkerFailure {
+        let text = text.trim();
+        serde_json::from_str(text).unwrap_or_else(|_| WorkerFailure::other(text))
+    }
+}
+
+impl From<String> for WorkerFailure {
+    fn from(message: String) -> WorkerFailure {
+        WorkerFailure::other(message)
+    }
+}
+
+impl From<&str> for WorkerFailure {
+    fn from(message: &str) -> WorkerFailure {
+        WorkerFailure::other(message)
+    }
+}
+
+/// Reads the worker's ready line. A bare `ready` comes from a script that predates the
+/// handshake and counts as version 0 with no capabilities; `Err` is the worker's `failed`
+/// line, or any other line it printed instead.
+pub fn parse_ready(line: &str) -> Result<Handshake, WorkerFailure> {
+    let line = line.trim();
+    if let Some(failure) = line.strip_prefix(FAILED) {
+        return Err(WorkerFailure::parse(failure));
+    }
+    let Some(rest) = line.strip_prefix(READY) else {
+        return Err(WorkerFailure::other(format!(
+            "ASR worker not ready: {}",
+            line
+        )));
+    };
+    let rest = rest.trim();
+    if rest.is_empty() {
+        return Ok(Handshake {
+            version: 0,
+            capabilities: Vec::new(),
+        });
+    }
+    serde_json::from_str(rest)
+        .map_err(|err| WorkerFailure::other(format!("ASR worker handshake unreadable: {}", err)))
+}
+
+pub fn write_request<W: Write>(
+    writer: &mut W,
+    id: u32,
+    options: &serde_json::Value,
+    wav_bytes: &[u8],
+) -> Result<(), String> {
+    let mut options = options.clone();
+    if let Some(map) = options.as_object_mut() {
+        map.insert("id".to_string(), id.into());
+    }
+    let header = serde_json::to_vec(&options).map_err(|err| err.to_string())?;
+    write_frame(writer, &header)?;
+    write_frame(writer, wav_bytes)?;
+    writer.flush().map_err(|err| err.to_string())
+}
+
+fn write_frame<W: Write>(writer: &mut W, payload: &[u8]) -> Result<(), String> {
+    let len = u32::try_from(payload.len()).map_err(|_| "Frame too large".to_string())?;
+    writer
+        .write_all(&len.to_le_bytes())
+        .map_err(|err| err.to_string())?;
+    writer.write_all(payload).map_err(|err| err.to_string())
+}
+
+/// Reads until the response for `expected_id` arrives.
+///
+/// `Err` means the stream is desynced or closed and must not be reused.
+pub fn read_response<R: Read>(reader: &mut R, expected_id: u32) -> Result<Reply, String> {
+    read_response_into(reader, expected_id, &mut Vec::new())
+}
+
+/// [`read_response`] reading each frame into `payload`, so a caller keeping it between
+/// requests allocates only the transcript.
+pub fn read_response_into<R: Read>(
+    reader: &mut R,
+    expected_id: u32,
+    payload: &mut Vec<u8>,
+) -> Result<Reply, String> {
+    let mut skipped = 0usize;
+    loop {
+        if skipped > MAX_RESYNC_BYTES {
+            return Err(format!(
+                "Worker protocol desynced: no valid response after {} bytes",
+                skipped
+            ));
+        }
+        skipped += seek_magic(reader, MAX_RESYNC_BYTES - skipped.min(MAX_RESYNC_BYTES))?;
+
+        let id = read_u32(reader)?;
+        let len = read_u32(reader)? as usize;
+        if len > MAX_RESPONSE_BYTES {
+            // Corrupt prefix that happened to follow a magic; keep scanning after it.
+            skipped += RESPONSE_MAGIC.len() + 8;
+            continue;
+        }
+        payload.clear();
+        payload.resize(len, 0);
+        reader
+            .read_exact(payload)
+            .map_err(|err| format!("Worker response truncated: {}", err))?;
+        if id != expected_id {
+            skipped += RESPONSE_MAGIC.len() + 8 + len;
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(payload).into_owned();
+        if let Some(failure) = text.strip_prefix(WORKER_ERROR_PREFIX) {
+            return Ok(Reply::WorkerError(WorkerFailure::parse(failure)));
+        }
+        if let Some(structured) = text.strip_prefix(STRUCTURED_PREFIX) {
+            // The frame itself arrived whole, so the stream is still in sync.
+            return Ok(serde_json::from_str(structured)
+                .map(Reply::Structured)
+                .unwrap_or_else(|err| {
+                    Reply::WorkerError(WorkerFailure::other(format!(
+                        "Worker result unreadable: {}",
+                        err
+                    )))
+                }));
+        }
+        return Ok(Reply::Text(text));
+    }
+}
+
+/// Consumes bytes up to and including the next magic; returns how many non-magic bytes
+/// were skipped.
+fn seek_magic<R: Read>(reader: &mut R, budget: usize) -> Result<usize, String> {
+    let mut window = [0u8; 4];
+    let mut filled = 0usize;
+    let mut skipped = 0usize;
+    let mut byte = [0u8; 1];
+    loop {
+        reader
+            .read_exact(&mut byte)
+            .map_err(|err| format!("Worker stream closed: {}", err))?;
+        if filled < window.len() {
+            window[filled] = byte[0];
+            filled += 1;
+        } else {
+            window.rotate_left(1);
+            window[3] = byte[0];
+            skipped += 1;
+        }
+        if filled == window.len() && window == RESPONSE_MAGIC {
+            return Ok(skipped);
+        }
+        if skipped > budget {
+            return Err(format!(
+                "Worker protocol desynced: no frame marker in {} bytes",
+                skipped
+            ));
+        }
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, String> {
+    let mut buf = [0u8; 4];
+    reader
+        .read_exact(&mut buf)
+        .map_err(|err| format!("Worker response truncated: {}", err))?;
+    Ok(u32::from_le_bytes(buf))
+}
@@ -0,0 +1,32 @@
+//! Side effects tied to the start and end of a recording, whichever layer captures audio.
+
+use std::sync::Mutex;
+
+use crate::{media, settings};
+
+static PAUSED_MEDIA: Mutex<Vec<media::PausedPlayer>> = Mutex::new(Vec::new());
+
+/// Called when the microphone starts capturing for a dictation.
+pub fn started() {
+    if !settings::current().capture.pause_media {
+        return;
+    }
+    // Querying players spawns processes; keep that off the caller's thread.
+    std::thread::spawn(|| {
+        let paused = media::pause_playing();
+        if let Ok(mut state) = PAUSED_MEDIA.lock() {
+            state.extend(paused);
+        }
+    });
+}
+
+/// Called when capture ends, whether the clip is transcribed or discarded.
+pub fn stopped() {
+    let paused = match PAUSED_MEDIA.lock() {
+        Ok(mut state) => std::mem::take(&mut *state),
+        Err(_) => return,
+    };
+    if !paused.is_empty() {
+        std::thread::spawn(move || media::resume(&paused));
+    }
+}
@@ -0,0 +1,196 @@
+//! Finds sensitive text in transcripts, as `redaction` asks: card numbers, email addresses,
+//! phone numbers and the user's own patterns. What is found can be masked before history
+//! and the log keep it, and can hold a dictation for review before it is pasted.
+
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::settings::{self, RedactionSettings};
+
+static CARD_NUMBER: OnceLock<Regex> = OnceLock::new();
+static EMAIL: OnceLock<Regex> = OnceLock::new();
+static PHONE_NUMBER: OnceLock<Regex> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    CardNumber,
+    Email,
+    PhoneNumber,
+    /// A match of one of `redaction.patterns`.
+    Custom,
+}
+
+impl Kind {
+    /// What a match is replaced with.
+    fn mask(self) -> &'static str {
+        match self {
+            Kind::CardNumber => "[card number]",
+            Kind::Email => "[email]",
+            Kind::PhoneNumber => "[phone number]",
+            Kind::Custom => "[redacted]",
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Kind::CardNumber => "a card number",
+            Kind::Email => "an email address",
+            Kind::PhoneNumber => "a phone number",
+            Kind::Custom => "a match for a redaction pattern",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Found {
+    pub kind: Kind,
+    pub range: Range<usize>,
+}
+
+/// Everything in `text` the settings look for, in order and without overlaps. Where two
+/// overlap, the first to start wins, and of those starting together the detectors come
+/// in the order of [`Kind`].
+pub fn find(text: &str, redaction: &RedactionSettings) -> Vec<Found> {
+    let mut found = Vec::new();
+    if redaction.card_numbers {
+        let pattern = CARD_NUMBER.get_or_init(|| {
+            Regex::new(r"\d(?:[ -]?\d){12,18}").expect("card number pattern is valid")
+        });
+        found.extend(
+            pattern
+                .find_iter(text)
+                .filter(|found| standalone(text, found.range()) && luhn(found.as_str()))
+                .map(|found| Found {
+                    kind: Kind::CardNumber,
+                    range: found.range(),
+                }),
+        );
+    }
+    if redaction.emails {
+        let pattern = EMAIL.get_or_init(|| {
+            Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}")
+                .expect("email pattern is valid")
+        });
+        found.extend(pattern.find_iter(text).map(|found| Found {
+            kind: Kind::Email,
+            range: found.range(),
+        }));
+    }
+    if redaction.phone_numbers {
+        let pattern = PHONE_NUMBER.get_or_init(|| {
+            Regex::new(
+                r"\+\d{1,3}(?:[ .-]?\(?\d{1,4}\)?){2,5}|(?:\+?1[ .-]?)?(?:\(\d{3}\)|\d{3})[ .-]?\d{3}[ .-]?\d{4}",
+            )
+            .expect("phone number pattern is valid")
+        });
+        found.extend(
+            pattern
+                .find_iter(text)
+                .filter(|found| {
+                    let digits = found.as_str().chars().filter(char::is_ascii_digit).count();
+                    standalone(text, found.range()) && (7..=15).contains(&digits)
+                })
+                .map(|found| Found {
+                    kind: Kind::PhoneNumber,
+                    range: found.range(),
+                }),
+        );
+    }
+    for pattern in &redaction.patterns {
+        // Invalid patterns are turned down when the settings are saved.
+        let Ok(pattern) = Regex::new(pattern) else {
+            continue;
+        };
+        found.extend(
+            pattern
+                .find_iter(text)
+                .filter(|found| !found.is_empty())
+                .map(|found| Found {
+                    kind: Kind::Custom,
+                    range: found.range(),
+                }),
+        );
+    }
+    // Stable, so detectors keep their order among matches starting at the same place.
+    found.sort_by_key(|found| found.range.start);
+    let mut kept: Vec<Found> = Vec::with_capacity(found.len());
+    for found in found {
+        if kept
+            .last()
+            .is_none_or(|last| last.range.end <= found.range.start)
+        {
+            kept.push(found);
+        }
+    }
+    kept
+}
+
+/// `text` with everything found replaced by a label such as `[email]`.
+pub fn mask(text: &str, redaction: &RedactionSettings) -> String {
+    let mut masked = String::with_capacity(text.len());
+    let mut at = 0;
+    for found in find(text, redaction) {
+        masked.push_str(&text[at..found.range.start]);
+        masked.push_str(found.kind.mask());
+        at = found.range.end;
+    }
+    masked.push_str(&text[at..]);
+    masked
+}
+
+/// `text` as history and the log keep it: masked when `redaction.maskHistory` is set.
+pub fn stored(text: &str) -> String {
+    let redaction = settings::current().redaction;
+    if redaction.mask_history {
+        mask(text, &redaction)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Why a dictation is held before pasting, if `redaction.warnBeforePaste` finds a reason:
+/// "Contains a card number and an email address".
+pub fn warning(text: &str, redaction: &RedactionSettings) -> Option<String> {
+    if !redaction.warn_before_paste {
+        return None;
+    }
+    let mut kinds: Vec<Kind> = Vec::new();
+    for found in find(text, redaction) {
+        if !kinds.contains(&found.kind) {
+            kinds.push(found.kind);
+        }
+    }
+    let descriptions: Vec<&str> = kinds.into_iter().map(Kind::description).collect();
+    let listed = match descriptions.as_slice() {
+        [] => return None,
+        [only] => only.to_string(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    };
+    Some(format!("Contains {}", listed))
+}
+
+/// Whether the match is not part of a longer run of letters and digits, as the number in
+/// an order id is.
+fn standalone(text: &str, range: Range<usize>) -> bool {
+    let before = text[..range.start].chars().next_back();
+    let after = text[range.end..].chars().next();
+    !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+}
+
+/// The Luhn checksum card numbers carry, which rules out most other long numbers.
+fn luhn(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|ch| ch.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, digit)| match (index % 2, digit * 2) {
+            (0, _) => *digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
@@ -0,0 +1,243 @@
+//! Registrations VType keeps with Windows: the `vtype://` link protocol and the optional
+//! start-at-login entry, both per user. The installer runs VType with [`REGISTER_ARG`] and
+//! the uninstaller with [`UNREGISTER_ARG`]; the settings UI checks them and repairs entries
+//! left pointing at an old install. Elsewhere the bundle declares what it needs.
+
+use serde::Serialize;
+
+/// Registers the link protocol and exits.
+pub const REGISTER_ARG: &str = "--register";
+/// Removes every registration and exits.
+pub const UNREGISTER_ARG: &str = "--unregister";
+
+#[cfg(target_os = "windows")]
+const PROTOCOL_KEY: &str = r"Software\Classes\vtype";
+#[cfg(target_os = "windows")]
+const COMMAND_KEY: &str = r"Software\Classes\vtype\shell\open\command";
+#[cfg(target_os = "windows")]
+const RUN_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
+#[cfg(target_os = "windows")]
+const RUN_VALUE: &str = "VType";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RegistrationState {
+    Missing,
+    Registered,
+    /// Registered for another executable, such as a moved or replaced install.
+    Stale,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Registrations {
+    /// False where there is nothing for VType to manage.
+    pub supported: bool,
+    pub protocol: RegistrationState,
+    pub autostart: RegistrationState,
+}
+
+/// Carries out an installer argument. `Some` when one was given, after which VType exits.
+pub fn run_installer_args() -> Option<Result<(), String>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == UNREGISTER_ARG) {
+        Some(unregister())
+    } else if args.iter().any(|arg| arg == REGISTER_ARG) {
+        Some(register_protocol())
+    } else {
+        None
+    }
+}
+
+/// Points stale entries at this executable and adds the protocol when it is missing. A
+/// missing autostart entry is left alone, since it is off by choice.
+pub fn repair() -> Result<Registrations, String> {
+    let current = query();
+    if !current.supported {
+        return Err(unsupported());
+    }
+    if current.protocol != RegistrationState::Registered {
+        register_protocol()?;
+    }
+    if current.autostart == RegistrationState::Stale {
+        set_autostart(true)?;
+    }
+    Ok(query())
+}
+
+#[cfg(target_os = "windows")]
+pub fn query() -> Registrations {
+    let state = |actual: Option<String>, expected: Result<String, String>| match (actual, expected)
+    {
+        (None, _) => RegistrationState::Missing,
+        (Some(actual), Ok(expected)) if actual.eq_ignore_ascii_case(&expected) => {
+            RegistrationState::Registered
+        }
+        (Some(_), _) => RegistrationState::Stale,
+    };
+    Registrations {
+        supported: true,
+        protocol: state(registry::read(COMMAND_KEY, None), open_command()),
+        autostart: state(
+            registry::read(RUN_KEY, Some(RUN_VALUE)),
+            autostart_command(),
+        ),
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn register_protocol() -> Result<(), String> {
+    let command = open_command()?;
+    registry::write(PROTOCOL_KEY, None, "URL:VType")?;
+    registry::write(PROTOCOL_KEY, Some("URL Protocol"), "")?;
+    registry::write(COMMAND_KEY, None, &command)
+}
+
+#[cfg(target_os = "windows")]
+pub fn set_autostart(enabled: bool) -> Result<(), String> {
+    if enabled {
+        registry::write(RUN_KEY, Some(RUN_VALUE), &autostart_command()?)
+    } else {
+        registry::delete_value(RUN_KEY, RUN_VALUE)
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn unregister() -> Result<(), String> {
+    registry::delete_tree(PROTOCOL_KEY)?;
+    registry::delete_value(RUN_KEY, RUN_VALUE)
+}
+
+/// Windows hands the link to a new VType process as its only argument.
+#[cfg(target_os = "windows")]
+fn open_command() -> Result<String, String> {
+    Ok(format!("\"{}\" \"%1\"", executable()?))
+}
+
+/// Portable installs keep their data beside the executable on every start.
+#[cfg(target_os = "windows")]
+fn autostart_command() -> Result<String, String> {
+    let executable = format!("\"{}\"", executable()?);
+    if crate::paths::is_portable() {
+        Ok(format!("{} {}", executable, crate::paths::PORTABLE_ARG))
+    } else {
+        Ok(executable)
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn executable() -> Result<String, String> {
+    std::env::current_exe()
+        .map(|path| path.display().to_string())
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn query() -> Registrations {
+    Registrations {
+        supported: false,
+        protocol: RegistrationState::Missing,
+        autostart: RegistrationState::Missing,
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn register_protocol() -> Result<(), String> {
+    Err(unsupported())
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn set_autostart(_enabled: bool) -> Result<(), String> {
+    Err(unsupported())
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn unregister() -> Result<(), String> {
+    Err(unsupported())
+}
+
+fn unsupported() -> String {
+    "Registrations are only managed by VType on Windows".to_string()
+}
+
+/// String values under `HKEY_CURRENT_USER`, which needs no elevation.
+#[cfg(target_os = "windows")]
+mod registry {
+    use windows_sys::Win32::Foundation::{ERROR_FILE_NOT_FOUND, ERROR_SUCCESS, WIN32_ERROR};
+    use windows_sys::Win32::System::Registry::{
+        RegDeleteKeyValueW, RegDeleteTreeW, RegGetValueW, RegSetKeyValueW, HKEY_CURRENT_USER,
+        REG_SZ, RRF_RT_REG_SZ,
+    };
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    /// Reads a value; `None` for the key's default value.
+    pub fn read(key: &str, value: Option<&str>) -> Option<String> {
+        let key = wide(key);
+        let value = value.map(wide);
+        let mut buffer = vec![0u16; 2048];
+        let mut size = (buffer.len() * 2) as u32;
+        // SAFETY: the strings are NUL-terminated and `size` is the buffer's length in bytes.
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                key.as_ptr(),
+                value
+                    .as_ref()
+                    .map_or(std::ptr::null(), |value| value.as_ptr()),
+                RRF_RT_REG_SZ,
+                std::ptr::null_mut(),
+                buffer.as_mut_ptr().cast(),
+                &mut size,
+            )
+        };
+        if status != ERROR_SUCCESS {
+            return None;
+        }
+        // `size` counts the terminating NUL.
+        let length = (size as usize / 2).saturating_sub(1);
+        Some(String::from_utf16_lossy(&buffer[..length]))
+    }
+
+    /// Writes a value, creating the key as needed.
+    pub fn write(key: &str, value: Option<&str>, data: &str) -> Result<(), String> {
+        let key = wide(key);
+        let value = value.map(wide);
+        let data = wide(data);
+        // SAFETY: the strings are NUL-terminated and the data length is given in bytes.
+        check(unsafe {
+            RegSetKeyValueW(
+                HKEY_CURRENT_USER,
+                key.as_ptr(),
+                value
+                    .as_ref()
+                    .map_or(std::ptr::null(), |value| value.as_ptr()),
+                REG_SZ,
+                data.as_ptr().cast(),
+                (data.len() * 2) as u32,
+            )
+        })
+    }
+
+    pub fn delete_tree(key: &str) -> Result<(), String> {
+        let key = wide(key);
+        // SAFETY: `key` is NUL-terminated.
+        check(unsafe { RegDeleteTreeW(HKEY_CURRENT_USER, key.as_ptr()) })
+    }
+
+    pub fn delete_value(key: &str, value: &str) -> Result<(), String> {
+        let (key, value) = (wide(key), wide(value));
+        // SAFETY: both strings are NUL-terminated.
+        check(unsafe { RegDeleteKeyValueW(HKEY_CURRENT_USER, key.as_ptr(), value.as_ptr()) })
+    }
+
+    /// Something already gone counts as removed.
+    fn check(status: WIN32_ERROR) -> Result<(), String> {
+        match status {
+            ERROR_SUCCESS | ERROR_FILE_NOT_FOUND => Ok(()),
+            status => Err(std::io::Error::from_raw_os_error(status as i32).to_string()),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::time::Duration;
+
+use crate::{history, settings};
+
+/// Age limits are checked this often; count limits are also applied on every change.
+const INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Prunes history by the retention settings now, hourly, and whenever they change.
+pub fn start() {
+    settings::on_change(|previous, current| {
+        if previous.history != current.history {
+            run(&current.history);
+        }
+    });
+    std::thread::spawn(|| loop {
+        run(&settings::current().history);
+        std::thread::sleep(INTERVAL);
+    });
+}
+
+fn run(limits: &settings::HistorySettings) {
+    if limits.max_entries.is_none() && limits.max_age_days.is_none() {
+        return;
+    }
+    match history::prune(limits) {
+        Ok(0) => {}
+        Ok(deleted) => {
+            let _ = crate::log::write(format!("Pruned {} history entries", deleted));
+        }
+        Err(err) => {
+            let _ = crate::log::write(format!("History pruning failed: {}", err));
+        }
+    }
+}
@@ -0,0 +1,60 @@
+//! Writes a crash or power cut cannot leave half done. The contents go to a temporary file
+//! beside the target and are flushed to disk before that file is renamed over the target,
+//! so readers find the old file or the new one and never a mix.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Tells apart the temporary files of writes running at once.
+static NEXT: AtomicU64 = AtomicU64::new(0);
+
+/// Replaces `path` with `contents` atomically, as [`fs::write`] would non-atomically.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let partial = partial_path(path);
+    let written =
+        write_synced(&partial, contents.as_ref()).and_then(|()| fs::rename(&partial, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&partial);
+        return written;
+    }
+    sync_dir(path);
+    Ok(())
+}
+
+fn write_synced(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Hidden and in the same directory, since a rename cannot cross file systems.
+fn partial_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(
+        ".{}.{}.{}.partial",
+        name,
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Makes the rename itself durable. Windows cannot open a directory to flush it, so there
+/// the rename is left to the file system.
+#[cfg(unix)]
+fn sync_dir(path: &Path) {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) {}
@@ -0,0 +1,1806 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub const DEFAULT_MODEL: &str = "nemo-parakeet-tdt-0.6b-v3";
+#[cfg(target_os = "macos")]
+pub const DEFAULT_HOTKEY: &str = "Command+Option+R";
+#[cfg(not(target_os = "macos"))]
+pub const DEFAULT_HOTKEY: &str = "Ctrl+Alt+R";
+pub const SETTINGS_FILE: &str = "settings.json";
+/// Schema version written to new files; one past the last entry in [`MIGRATIONS`].
+pub const SETTINGS_VERSION: u64 = MIGRATIONS.len() as u64;
+
+pub const TEMPERATURE_RANGE: std::ops::RangeInclusive<f32> = 0.0..=1.0;
+pub const BEAM_SIZE_RANGE: std::ops::RangeInclusive<u32> = 1..=16;
+pub const TYPING_RATE_RANGE: std::ops::RangeInclusive<u32> = 1..=1000;
+/// Below this the worker could not even hold the int8 model.
+pub const MIN_WORKER_MEMORY_LIMIT_MB: u64 = 512;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    /// Schema version of the file this was read from; see [`migrate`].
+    pub version: u64,
+    /// Accelerator that toggles recording, e.g. `Ctrl+Alt+R`.
+    pub hotkey: String,
+    /// `onnx` or `mock`; `VTYPE_ASR_BACKEND` overrides it at runtime.
+    pub backend: String,
+    pub decoding: DecodingSettings,
+    pub model: ModelSettings,
+    /// Restarts the worker between jobs once its resident memory passes this many MB, to
+    /// undo slow leaks in a long-running Python process. `None` never does.
+    pub worker_memory_limit_mb: Option<u64>,
+    pub low_power: LowPowerSettings,
+    pub mock: MockSettings,
+    /// Replacements applied to every transcript, in order.
+    pub dictionary: Vec<DictionaryRule>,
+    pub output_mode: OutputMode,
+    pub ime_injection: ImeInjection,
+    pub elevated_targets: ElevatedTargets,
+    pub remote_viewers: RemoteViewerSettings,
+    pub directional_marks: DirectionalMarks,
+    pub spacing: SpacingSettings,
+    pub chunking: ChunkingSettings,
+    pub typing: TypingSettings,
+    pub voice_commands: VoiceCommandSettings,
+    pub code_mode: CodeModeSettings,
+    pub templates: TemplateSettings,
+    pub hooks: HookSettings,
+    pub redaction: RedactionSettings,
+    pub profiles: Vec<Profile>,
+    /// Name of the profile layered over the global settings; `None` uses them as-is.
+    pub active_profile: Option<String>,
+    /// Optional accelerator that cycles through profiles.
+    pub profile_hotkey: Option<String>,
+    /// Optional accelerator that transcribes the audio file copied to the clipboard.
+    pub clipboard_hotkey: Option<String>,
+    /// Optional accelerator that pastes the transcript held for review.
+    pub confirm_hotkey: Option<String>,
+    /// Optional accelerator that pauses and resumes the recording under way.
+    pub pause_hotkey: Option<String>,
+    /// Optional accelerator that turns code mode on and off.
+    pub code_mode_hotkey: Option<String>,
+    /// Optional accelerator that lists recent transcripts to paste one again.
+    pub quick_pick_hotkey: Option<String>,
+    pub busy_hotkey: BusyHotkey,
+    /// Read announcements such as "Recording started" aloud with the system voice.
+    pub announce_aloud: bool,
+    /// macOS: a hardware key that toggles recording alongside `hotkey`.
+    pub hardware_trigger: HardwareTrigger,
+    pub review: ReviewSettings,
+    pub history: HistorySettings,
+    pub overlay: OverlaySettings,
+    /// macOS: run as an accessory app with only the menu bar item, out of the Dock and ⌘Tab.
+    pub menu_bar_only: bool,
+    pub do_not_disturb: DoNotDisturbSettings,
+    pub capture: CaptureSettings,
+    /// Send long dictations to the backend in parts at pauses while still recording, so
+    /// only the last part is left when recording stops.
+    pub transcribe_while_recording: bool,
+    /// Directory for logs, the last recording and files handed to the worker; the app's
+    /// cache directory when unset. See [`crate::paths::working_dir`].
+    pub working_dir: Option<String>,
+    /// Language of the messages VType shows, such as `de` or `fr-CA`; the system's when
+    /// unset. English, German, French and Spanish are translated.
+    pub locale: Option<String>,
+    /// Keep coarse usage counters on this machine for `export_metrics`; off unless chosen.
+    pub usage_metrics: bool,
+    /// Give prompts and hooks the focused app's name and window title. Profiles still
+    /// follow the focused app when this is off.
+    pub app_context: bool,
+    pub sync: SyncSettings,
+    pub companion: CompanionSettings,
+    pub mcp: McpSettings,
+    pub grpc: GrpcSettings,
+    pub plugins: PluginSettings,
+    pub audit: AuditSettings,
+    pub updates: UpdateSettings,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CaptureSettings {
+    /// Microphone to record from, by name; the system default when unset.
+    pub input_device: Option<String>,
+    /// Input channels to record, numbered from 1. When empty, every channel carrying
+    /// signal is mixed down.
+    pub channels: Vec<u16>,
+    /// Record from another input instead of a Bluetooth headset, whose microphone only
+    /// works in the narrowband hands-free profile.
+    pub avoid_bluetooth_input: bool,
+    /// Pause playing media while recording and resume it afterwards.
+    pub pause_media: bool,
+    /// Subtract what the speakers are playing from the microphone signal.
+    pub echo_cancellation: bool,
+    /// Input device recording the speaker output, for echo cancellation. By default the
+    /// system output is recorded in loopback where the platform supports it, or else the
+    /// first input whose name contains "monitor" is used.
+    pub loopback_device: Option<String>,
+}
+
+/// Suppresses the dictation hotkey during quiet hours or while certain apps are focused.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DoNotDisturbSettings {
+    pub enabled: bool,
+    pub schedule: Vec<QuietHours>,
+    /// App names (as in [`crate::frontmost::FrontmostApp::name`]) that block dictation
+    /// while focused, e.g. `zoom`.
+    pub apps: Vec<String>,
+}
+
+/// A daily window in local time. `end` before `start` spans midnight.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuietHours {
+    /// `HH:MM`, inclusive.
+    pub start: String,
+    /// `HH:MM`, exclusive.
+    pub end: String,
+    /// ISO weekdays the window starts on (1 = Monday ... 7 = Sunday); empty means every day.
+    #[serde(default)]
+    pub days: Vec<u8>,
+}
+
+impl QuietHours {
+    pub fn validate(&self) -> Result<(), String> {
+        parse_clock(&self.start)?;
+        parse_clock(&self.end)?;
+        if let Some(day) = self.days.iter().find(|day| !(1..=7).contains(*day)) {
+            return Err(format!("Invalid weekday {} (use 1-7, Monday first)", day));
+        }
+        Ok(())
+    }
+}
+
+/// Minutes since midnight for an `HH:MM` time.
+pub fn parse_clock(value: &str) -> Result<u32, String> {
+    let invalid = || format!("Invalid time {} (expected HH:MM)", value);
+    let (hours, minutes) = value.trim().split_once(':').ok_or_else(invalid)?;
+    let hours: u32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u32 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 23 || minutes > 59 {
+        return Err(invalid());
+    }
+    Ok(hours * 60 + minutes)
+}
+
+/// Window behaviour of the recording overlay.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OverlaySettings {
+    pub always_on_top: bool,
+    /// Let clicks pass through to the window underneath.
+    pub click_through: bool,
+    /// Hide the overlay when recording has not started this long after a hotkey showed it.
+    pub start_timeout_ms: Option<u64>,
+    /// Hide the overlay this long after a dictation ends, pasted or failed.
+    pub hide_after_ms: Option<u64>,
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        OverlaySettings {
+            always_on_top: true,
+            click_through: false,
+            start_timeout_ms: Some(5000),
+            hide_after_ms: Some(3000),
+        }
+    }
+}
+
+/// How consecutive dictations into the same app are joined.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SpacingSettings {
+    /// Add a space after each transcript, taken back when the next starts with punctuation.
+    pub trailing_space: bool,
+    /// Capitalize a transcript that follows a finished sentence.
+    pub capitalize_sentences: bool,
+}
+
+impl Default for SpacingSettings {
+    fn default() -> Self {
+        SpacingSettings {
+            trailing_space: true,
+            capitalize_sentences: false,
+        }
+    }
+}
+
+/// Delivering long transcripts a few sentences at a time, for web editors that drop
+/// characters when a lot of input arrives at once.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChunkingSettings {
+    pub enabled: bool,
+    /// Only transcripts longer than this many characters are split.
+    pub min_chars: usize,
+    /// Sentences are gathered into chunks of up to this many characters.
+    pub max_chunk_chars: usize,
+    /// Pause between chunks, for the app to catch up.
+    pub delay_ms: u64,
+}
+
+impl Default for ChunkingSettings {
+    fn default() -> Self {
+        ChunkingSettings {
+            enabled: false,
+            min_chars: 400,
+            max_chunk_chars: 200,
+            delay_ms: 150,
+        }
+    }
+}
+
+impl ChunkingSettings {
+    /// `text` as a single chunk unless chunking is on and it is long enough.
+    pub fn split<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        if !self.enabled || text.chars().count() <= self.min_chars {
+            return vec![text];
+        }
+        crate::inject::sentence_chunks(text, self.max_chunk_chars)
+    }
+}
+
+/// The pace of simulated typing, for apps that rate-limit or animate input and remote
+/// sessions that drop keys sent too fast. Unset, text is typed as fast as the system takes it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TypingSettings {
+    pub chars_per_second: Option<u32>,
+    /// Up to this much extra pause, chosen at random, between words.
+    pub word_jitter_ms: u32,
+}
+
+impl TypingSettings {
+    pub fn paced(&self) -> bool {
+        self.chars_per_second.is_some() || self.word_jitter_ms > 0
+    }
+}
+
+/// Phrases said at the end of a dictation that act on the text before them; see
+/// [`crate::voice`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct VoiceCommandSettings {
+    pub enabled: bool,
+    /// Checked in order; the first whose phrase ends the dictation applies.
+    pub directives: Vec<Directive>,
+    /// Whether directives and macros may run shell commands, and macros launch apps and make
+    /// HTTP requests.
+    pub shell_actions: ShellPolicy,
+}
+
+impl Default for VoiceCommandSettings {
+    fn default() -> Self {
+        let directive = |phrases: &[&str], action| Directive {
+            phrases: phrases.iter().map(|phrase| phrase.to_string()).collect(),
+            action,
+        };
+        VoiceCommandSettings {
+            enabled: false,
+            directives: vec![
+                directive(
+                    &["send that to chatgpt", "ask chatgpt"],
+                    DirectiveAction::OpenUrl {
+                        url: "https://chatgpt.com/?q={text}".to_string(),
+                    },
+                ),
+                directive(&["send it", "send that"], DirectiveAction::PressEnter),
+                directive(
+                    &["search that", "search for that"],
+                    DirectiveAction::OpenUrl {
+                        url: "https://duckduckgo.com/?q={text}".to_string(),
+                    },
+                ),
+            ],
+            shell_actions: ShellPolicy::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Directive {
+    /// Matched as whole words, ignoring case.
+    pub phrases: Vec<String>,
+    pub action: DirectiveAction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum DirectiveAction {
+    /// Deliver the text as usual, then press Enter.
+    PressEnter,
+    /// Open `url`, with `{text}` replaced by the text, instead of delivering it.
+    OpenUrl { url: String },
+    /// Run `command` through the shell instead of delivering the text, which it gets in
+    /// `VTYPE_TEXT` and on stdin.
+    Shell { command: String },
+}
+
+/// Remote desktop and virtual machine viewers, which forward keys to another machine and
+/// may keep the paste chord for themselves or paste a clipboard that has not synced yet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RemoteViewerSettings {
+    /// Type into known viewers instead of pasting, unless a profile listing the app sets
+    /// its own output mode.
+    pub type_into: bool,
+    /// Further app names to treat as viewers.
+    pub apps: Vec<String>,
+}
+
+impl Default for RemoteViewerSettings {
+    fn default() -> Self {
+        RemoteViewerSettings {
+            type_into: true,
+            apps: Vec::new(),
+        }
+    }
+}
+
+impl RemoteViewerSettings {
+    pub fn covers(&self, app: &crate::frontmost::FrontmostApp) -> bool {
+        self.type_into
+            && (app.is_remote_viewer() || self.apps.iter().any(|pattern| app.matches(pattern)))
+    }
+}
+
+/// Holding transcripts in the overlay for approval before they are pasted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReviewSettings {
+    /// Show each transcript for editing and paste it only once confirmed.
+    pub enabled: bool,
+    /// Paste a held transcript as is after this many seconds without a decision.
+    pub auto_confirm_secs: Option<u32>,
+    /// Read each held transcript aloud.
+    pub read_aloud: bool,
+    /// Hold transcripts the model was less sure of than this, from 0 to 1, for review even
+    /// when review is off, so a glitch in the microphone is not pasted as garbage.
+    pub min_confidence: Option<f32>,
+}
+
+/// Automatic pruning of dictation history. Pinned entries are never pruned.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HistorySettings {
+    /// Keep at most this many unpinned entries.
+    pub max_entries: Option<u32>,
+    /// Delete unpinned entries older than this.
+    pub max_age_days: Option<u32>,
+    /// Keep each dictation's recording so it can be re-transcribed later.
+    pub retain_audio: bool,
+}
+
+/// How a finished transcript reaches the focused application.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    /// Put the text on the clipboard and send the paste chord.
+    #[default]
+    Paste,
+    /// Simulate typing the text key by key.
+    Type,
+    /// Only copy the text; the user pastes it themselves.
+    Clipboard,
+}
+
+/// A phone on the same network used as a wireless microphone: it streams audio over a
+/// WebSocket, and the transcript is pasted here.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CompanionSettings {
+    pub enabled: bool,
+    /// TCP port listened on, on every interface, for connections from the local network.
+    pub port: u16,
+    /// Advertise the server on the network over mDNS as `_vtype._tcp`, so companion apps
+    /// find it without the address being typed in.
+    pub advertise: bool,
+    pub devices: Vec<PairedDevice>,
+}
+
+impl Default for CompanionSettings {
+    fn default() -> Self {
+        CompanionSettings {
+            enabled: false,
+            port: 47_600,
+            advertise: true,
+            devices: Vec::new(),
+        }
+    }
+}
+
+/// A Model Context Protocol server through which AI agents on this machine can transcribe
+/// files and read history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct McpSettings {
+    pub enabled: bool,
+    /// TCP port listened on, on the loopback interface only.
+    pub port: u16,
+}
+
+impl Default for McpSettings {
+    fn default() -> Self {
+        McpSettings {
+            enabled: false,
+            port: 47_601,
+        }
+    }
+}
+
+/// A gRPC service through which IDE plugins and other apps on this machine transcribe and
+/// paste; the definition is `proto/vtype.proto`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GrpcSettings {
+    pub enabled: bool,
+    /// TCP port listened on, on the loopback interface only.
+    pub port: u16,
+}
+
+impl Default for GrpcSettings {
+    fn default() -> Self {
+        GrpcSettings {
+            enabled: false,
+            port: 47_602,
+        }
+    }
+}
+
+/// WebAssembly plugins that rework transcripts; see [`crate::plugins`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PluginSettings {
+    /// The plugins that run, by file name without `.wasm`, in this order. A plugin added
+    /// to the directory stays off until listed.
+    pub enabled: Vec<String>,
+}
+
+/// Checking for and installing new releases of VType.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct UpdateSettings {
+    /// Check for an update at startup and offer it in the overlay.
+    pub auto_check: bool,
+    pub channel: UpdateChannel,
+}
+
+/// Which releases an update may come from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateChannel {
+    #[default]
+    Stable,
+    /// Pre-releases as well, published ahead of the stable ones.
+    Beta,
+}
+
+/// An append-only record of what was typed or pasted where, kept apart from the debug log;
+/// see [`crate::audit`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuditSettings {
+    pub enabled: bool,
+    /// Record the text itself too, masked as `redaction.maskHistory` asks. Only its
+    /// length is recorded otherwise.
+    pub include_text: bool,
+}
+
+/// A phone that completed pairing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PairedDevice {
+    pub name: String,
+    /// Proves the device is this one when it connects.
+    pub token: String,
+    /// Unix time in milliseconds.
+    pub paired_at: i64,
+}
+
+/// Sharing settings, the dictionary and macros with other machines through a folder that
+/// a tool such as Dropbox or Syncthing keeps in step; see [`sync`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SyncSettings {
+    /// The shared folder; syncing is off without one.
+    pub folder: Option<String>,
+    /// Names this machine's file in the folder; the host name when unset.
+    pub device_name: Option<String>,
+}
+
+/// Sensitive text to mask or warn about; see [`crate::redact`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RedactionSettings {
+    /// Look for card numbers that pass the Luhn check.
+    pub card_numbers: bool,
+    pub emails: bool,
+    /// Look for North American numbers and international ones starting with `+`.
+    pub phone_numbers: bool,
+    /// Regular expressions for anything else, such as `\bMRN-\d{6}\b`.
+    pub patterns: Vec<String>,
+    /// Mask what is found in history and the log.
+    pub mask_history: bool,
+    /// Hold a dictation with anything found in the overlay until the user pastes it.
+    pub warn_before_paste: bool,
+}
+
+/// Shell commands run with the transcript at points of a dictation; see [`crate::hooks`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HookSettings {
+    /// Run once a dictation is transcribed, whatever happens to it next.
+    pub post_transcribe: Option<String>,
+    /// Run before text is pasted, and waited for: its output replaces the text, and a
+    /// non-zero exit keeps it from being pasted.
+    pub pre_paste: Option<String>,
+    /// Run once text has been pasted.
+    pub post_paste: Option<String>,
+    /// A hook still running after this long is stopped.
+    pub timeout_ms: u64,
+}
+
+impl Default for HookSettings {
+    fn default() -> Self {
+        HookSettings {
+            post_transcribe: None,
+            pre_paste: None,
+            post_paste: None,
+            timeout_ms: 5_000,
+        }
+    }
+}
+
+/// Structures such as an email's greeting and sign-off that dictations are put into; see
+/// [`crate::postprocess::apply_template`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TemplateSettings {
+    /// Name of the template every dictation goes into unless it picks another by voice.
+    pub active: Option<String>,
+    /// Substituted for `{name}` placeholders, before the built-in `{date}` and `{time}`.
+    pub variables: BTreeMap<String, String>,
+    pub templates: Vec<Template>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Template {
+    pub name: String,
+    /// Said at the start of a dictation to put the rest into this template.
+    pub phrases: Vec<String>,
+    /// The structure, with the dictation in place of `{text}`.
+    pub text: String,
+}
+
+/// Spoken forms turned into symbols, for dictating code; see [`crate::code`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CodeModeSettings {
+    /// Toggled by `codeModeHotkey`. A profile with a `codeMode` turns it on for its apps
+    /// either way.
+    pub enabled: bool,
+    pub language: CodeLanguage,
+}
+
+/// Which spoken tokens code mode knows besides the common ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CodeLanguage {
+    #[default]
+    Generic,
+    Rust,
+    Python,
+    /// Also TypeScript.
+    JavaScript,
+}
+
+/// Whether a voice command may run a shell command, which can do anything the user can.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellPolicy {
+    /// Show the command and run it only once the user agrees.
+    #[default]
+    Ask,
+    Allow,
+    Deny,
+}
+
+/// What pressing the dictation hotkey does while a transcript is on its way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BusyHotkey {
+    /// Start the next recording once the transcript is delivered.
+    #[default]
+    Queue,
+    /// Drop the transcript and start recording again straight away.
+    Restart,
+    /// Do nothing but play a short sound.
+    Ignore,
+}
+
+/// Keys on Mac keyboards that no accelerator can name, watched through an event tap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HardwareTrigger {
+    #[default]
+    Off,
+    /// The microphone key in the function row, which VType takes from Apple Dictation.
+    DictationKey,
+    /// Pressing Fn, or Globe, twice in a row. Apple Dictation's own shortcut for this has
+    /// to be turned off in System Settings.
+    FnDoublePress,
+}
+
+/// How injection copes with an input method editor (Japanese, Chinese or Korean input) in
+/// the target app, which can swallow the simulated paste chord while it is on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImeInjection {
+    /// Switch an active input method off while the transcript is injected.
+    #[default]
+    Auto,
+    /// As `Auto`, and type the transcript instead of pasting it while an input method is
+    /// on, for apps that still drop the paste chord.
+    Type,
+    /// Leave the input method alone.
+    Off,
+}
+
+/// What becomes of a transcript for an app run as administrator, which Windows shields from
+/// the keys an unelevated VType sends. Either way it is left on the clipboard.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ElevatedTargets {
+    /// Report the paste as failed, so the user knows to paste by hand.
+    #[default]
+    Fail,
+    /// Treat copying as enough, like clipboard output mode.
+    Clipboard,
+}
+
+/// Output for terminals, where a line break is Enter and runs the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TerminalSafety {
+    /// Drop trailing line breaks and always paste, so a shell with bracketed paste takes
+    /// the lines in between as text.
+    Paste,
+    /// Also join the lines with spaces, so nothing runs until Enter is pressed by hand.
+    ManualEnter,
+}
+
+/// Invisible marks added around transcripts containing right-to-left text, so they keep
+/// their order next to text of the other direction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DirectionalMarks {
+    Off,
+    /// LRM or RLM at both ends, which every text field understands.
+    #[default]
+    Marks,
+    /// Directional isolates, which shield the surrounding text too but show as boxes in
+    /// some older apps.
+    Isolates,
+}
+
+/// A named bundle of overrides, e.g. "Work" vs "Personal". Unset fields inherit the
+/// global settings; dictionary rules are applied after the global ones.
+///
+/// A profile listing apps also supplies the output options whenever one of them is
+/// focused, whichever profile is active.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Profile {
+    pub name: String,
+    pub language: Option<String>,
+    pub dictionary: Vec<DictionaryRule>,
+    pub output_mode: Option<OutputMode>,
+    pub ime_injection: Option<ImeInjection>,
+    /// Set for profiles covering terminal apps.
+    pub terminal: Option<TerminalSafety>,
+    pub backend: Option<String>,
+    /// Primes the backend for what is dictated into this profile's apps, e.g. the style or
+    /// subject of the text. `{app}` and `{title}` stand for the focused app's name and window
+    /// title, left out when unknown or when `appContext` is off.
+    pub initial_prompt: Option<String>,
+    /// Terms the backend should expect, such as `kubectl` in an editor or drug names in a
+    /// medical records app; added to the prompt.
+    pub vocabulary: Vec<String>,
+    /// Turns code mode on, with these tokens, while one of the profile's apps is focused.
+    pub code_mode: Option<CodeLanguage>,
+    /// App names, matched as in [`crate::frontmost::FrontmostApp::matches`].
+    pub apps: Vec<String>,
+}
+
+/// Replaces whole-word, case-insensitive matches of `from` with `to`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DictionaryRule {
+    pub from: String,
+    pub to: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            version: SETTINGS_VERSION,
+            hotkey: DEFAULT_HOTKEY.to_string(),
+            backend: crate::backend::Backend::Onnx.name().to_string(),
+            decoding: DecodingSettings::default(),
+            model: ModelSettings::default(),
+            worker_memory_limit_mb: None,
+            low_power: LowPowerSettings::default(),
+            mock: MockSettings::default(),
+            dictionary: Vec::new(),
+            output_mode: OutputMode::default(),
+            ime_injection: ImeInjection::default(),
+            elevated_targets: ElevatedTargets::default(),
+            remote_viewers: RemoteViewerSettings::default(),
+            directional_marks: DirectionalMarks::default(),
+            spacing: SpacingSettings::default(),
+            chunking: ChunkingSettings::default(),
+            typing: TypingSettings::default(),
+            voice_commands: VoiceCommandSettings::default(),
+            code_mode: CodeModeSettings::default(),
+            templates: TemplateSettings::default(),
+            hooks: HookSettings::default(),
+            redaction: RedactionSettings::default(),
+            profiles: Vec::new(),
+            active_profile: None,
+            profile_hotkey: None,
+            clipboard_hotkey: None,
+            confirm_hotkey: None,
+            pause_hotkey: None,
+            code_mode_hotkey: None,
+            quick_pick_hotkey: None,
+            busy_hotkey: BusyHotkey::default(),
+            announce_aloud: false,
+            hardware_trigger: HardwareTrigger::default(),
+            review: ReviewSettings::default(),
+            history: HistorySettings::default(),
+            overlay: OverlaySettings::default(),
+            menu_bar_only: false,
+            do_not_disturb: DoNotDisturbSettings::default(),
+            capture: CaptureSettings::default(),
+            transcribe_while_recording: true,
+            working_dir: None,
+            locale: None,
+            usage_metrics: false,
+            app_context: true,
+            sync: SyncSettings::default(),
+            companion: CompanionSettings::default(),
+            mcp: McpSettings::default(),
+            grpc: GrpcSettings::default(),
+            plugins: PluginSettings::default(),
+            audit: AuditSettings::default(),
+            updates: UpdateSettings::default(),
+        }
+    }
+}
+
+/// How the worker saves power on laptops; see [`crate::power`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LowPowerSettings {
+    pub mode: LowPowerMode,
+    /// Load the smaller int8 model while saving power.
+    pub smaller_model: bool,
+    /// Stop the worker once it has had nothing to do for this many seconds while saving
+    /// power. `None` keeps it loaded.
+    pub idle_shutdown_secs: Option<u64>,
+}
+
+impl Default for LowPowerSettings {
+    fn default() -> Self {
+        LowPowerSettings {
+            mode: LowPowerMode::default(),
+            smaller_model: true,
+            idle_shutdown_secs: Some(300),
+        }
+    }
+}
+
+/// When the worker saves power.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LowPowerMode {
+    Off,
+    /// While the machine runs on battery.
+    #[default]
+    OnBattery,
+    Always,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MockSettings {
+    /// Simulated inference time; `VTYPE_MOCK_DELAY_MS` overrides it.
+    pub delay_ms: u64,
+    /// Returned in rotation; empty uses built-in sample sentences.
+    pub transcripts: Vec<String>,
+}
+
+impl Default for MockSettings {
+    fn default() -> Self {
+        MockSettings {
+            delay_ms: 400,
+            transcripts: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ModelSettings {
+    /// `auto`, `fp32` or `int8`; `auto` picks based on available memory.
+    pub quantization: String,
+    pub fallback: ModelFallback,
+}
+
+impl Default for ModelSettings {
+    fn default() -> Self {
+        ModelSettings {
+            quantization: crate::models::QUANTIZATION_AUTO.to_string(),
+            fallback: ModelFallback::default(),
+        }
+    }
+}
+
+/// What the worker retries with when it runs out of memory or cannot load the model.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ModelFallback {
+    /// The smaller int8 model or the CPU, whichever suits the failure first, then both.
+    #[default]
+    Auto,
+    /// Only the int8 model.
+    Smaller,
+    /// Only the CPU.
+    Cpu,
+    Off,
+}
+
+/// Decoding parameters applied to every utterance. `None` falls back to the model's default
+/// from [`DecodingSettings::model_defaults`]; the worker reports the ones the loaded model
+/// does not take.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DecodingSettings {
+    pub temperature: Option<f32>,
+    pub beam_size: Option<u32>,
+    pub best_of: Option<u32>,
+}
+
+impl DecodingSettings {
+    /// Defaults tuned per model: Parakeet's transducer decodes greedily, and models VType
+    /// doesn't know keep their own.
+    pub fn model_defaults(model: &str) -> DecodingSettings {
+        if model.starts_with("nemo-parakeet") {
+            DecodingSettings {
+                temperature: Some(0.0),
+                beam_size: Some(1),
+                best_of: None,
+            }
+        } else {
+            DecodingSettings::default()
+        }
+    }
+
+    pub fn resolve(&self, model: &str) -> DecodingSettings {
+        let defaults = DecodingSettings::model_defaults(model);
+        DecodingSettings {
+            temperature: self.temperature.or(defaults.temperature),
+            beam_size: self.beam_size.or(defaults.beam_size),
+            best_of: self.best_of.or(defaults.best_of),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        validate_decoding(self.temperature, self.beam_size, self.best_of)
+    }
+}
+
+pub fn validate_decoding(
+    temperature: Option<f32>,
+    beam_size: Option<u32>,
+    best_of: Option<u32>,
+) -> Result<(), String> {
+    if let Some(temperature) = temperature {
+        if !TEMPERATURE_RANGE.contains(&temperature) {
+            return Err(format!("Temperature out of range (0-1): {}", temperature));
+        }
+    }
+    if let Some(beam_size) = beam_size {
+        if !BEAM_SIZE_RANGE.contains(&beam_size) {
+            return Err(format!("Beam size out of range (1-16): {}", beam_size));
+        }
+    }
+    if let Some(best_of) = best_of {
+        if !BEAM_SIZE_RANGE.contains(&best_of) {
+            return Err(format!("Best-of out of range (1-16): {}", best_of));
+        }
+    }
+    Ok(())
+}
+
+impl Settings {
+    pub fn active_profile(&self) -> Option<&Profile> {
+        let name = self.active_profile.as_deref()?;
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    pub fn effective_backend(&self) -> &str {
+        self.active_profile()
+            .and_then(|profile| profile.backend.as_deref())
+            .unwrap_or(&self.backend)
+    }
+
+    pub fn effective_language(&self) -> Option<String> {
+        self.active_profile()
+            .and_then(|profile| profile.language.clone())
+    }
+
+    /// The first profile listing `app`.
+    pub fn app_profile(&self, app: &crate::frontmost::FrontmostApp) -> Option<&Profile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.apps.iter().any(|pattern| app.matches(pattern)))
+    }
+
+    /// The profile whose output options apply while `app` is focused: the first one
+    /// listing it, else the active profile.
+    pub fn output_profile(&self, app: Option<&crate::frontmost::FrontmostApp>) -> Option<&Profile> {
+        app.and_then(|app| self.app_profile(app))
+            .or_else(|| self.active_profile())
+    }
+
+    /// A profile listing the app decides first; remote viewers are typed into before the
+    /// active profile is consulted.
+    pub fn effective_output_mode(
+        &self,
+        app: Option<&crate::frontmost::FrontmostApp>,
+    ) -> OutputMode {
+        if let Some(mode) = app
+            .and_then(|app| self.app_profile(app))
+            .and_then(|profile| profile.output_mode)
+        {
+            return mode;
+        }
+        if app.is_some_and(|app| self.remote_viewers.covers(app)) {
+            return OutputMode::Type;
+        }
+        self.output_profile(app)
+            .and_then(|profile| profile.output_mode)
+            .unwrap_or(self.output_mode)
+    }
+
+    pub fn effective_terminal(
+        &self,
+        app: Option<&crate::frontmost::FrontmostApp>,
+    ) -> Option<TerminalSafety> {
+        self.output_profile(app)
+            .and_then(|profile| profile.terminal)
+    }
+
+    pub fn effective_ime_injection(
+        &self,
+        app: Option<&crate::frontmost::FrontmostApp>,
+    ) -> ImeInjection {
+        self.output_profile(app)
+            .and_then(|profile| profile.ime_injection)
+            .unwrap_or(self.ime_injection)
+    }
+
+    /// The prompt and vocabulary of the profile listing `app`, else of the active profile.
+    pub fn effective_initial_prompt(
+        &self,
+        app: Option<&crate::frontmost::FrontmostApp>,
+    ) -> Option<String> {
+        let profile = self.output_profile(app)?;
+        let prompt = profile
+            .initial_prompt
+            .as_deref()
+            .map(|prompt| self.with_app_context(prompt, app))
+            .filter(|prompt| !prompt.is_empty());
+        let terms: Vec<&str> = profile
+            .vocabulary
+            .iter()
+            .map(|term| term.trim())
+            .filter(|term| !term.is_empty())
+            .collect();
+        match (prompt, terms.is_empty()) {
+            (Some(prompt), true) => Some(prompt),
+            (Some(prompt), false) => Some(format!("{} {}.", prompt, terms.join(", "))),
+            (None, false) => Some(format!("{}.", terms.join(", "))),
+            (None, true) => None,
+        }
+    }
+
+    /// `prompt` with `{app}` and `{title}` filled in from `app`, as far as `app_context`
+    /// allows.
+    fn with_app_context(
+        &self,
+        prompt: &str,
+        app: Option<&crate::frontmost::FrontmostApp>,
+    ) -> String {
+        let app = app.filter(|_| self.app_context);
+        prompt
+            .replace("{app}", app.map_or("", |app| app.name.as_str()))
+            .replace(
+                "{title}",
+                app.and_then(|app| app.title.as_deref()).unwrap_or(""),
+            )
+            .trim()
+            .to_string()
+    }
+
+    /// The tokens code mode uses for `app`, if it is on there.
+    pub fn effective_code_mode(
+        &self,
+        app: Option<&crate::frontmost::FrontmostApp>,
+    ) -> Option<CodeLanguage> {
+        self.output_profile(app)
+            .and_then(|profile| profile.code_mode)
+            .or(self.code_mode.enabled.then_some(self.code_mode.language))
+    }
+
+    pub fn effective_dictionary(&self) -> Vec<DictionaryRule> {
+        let mut rules = self.dictionary.clone();
+        if let Some(profile) = self.active_profile() {
+            rules.extend(profile.dictionary.iter().cloned());
+        }
+        rules
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.hotkey.trim().is_empty() {
+            return Err("Hotkey must not be empty".to_string());
+        }
+        let profile_rules = self.profiles.iter().flat_map(|profile| &profile.dictionary);
+        if let Some(rule) = self
+            .dictionary
+            .iter()
+            .chain(profile_rules)
+            .find(|rule| rule.from.trim().is_empty())
+        {
+            return Err(format!(
+                "Dictionary rule has an empty phrase (-> {})",
+                rule.to
+            ));
+        }
+        if let Some(name) = self.plugins.enabled.iter().find(|name| {
+            name.trim().is_empty() || name.starts_with('.') || name.contains(['/', '\\'])
+        }) {
+            return Err(format!("Invalid plugin name: {}", name));
+        }
+        for (index, profile) in self.profiles.iter().enumerate() {
+            if profile.name.trim().is_empty() {
+                return Err("Profile name must not be empty".to_string());
+            }
+            if self.profiles[..index]
+                .iter()
+                .any(|other| other.name == profile.name)
+            {
+                return Err(format!("Duplicate profile name: {}", profile.name));
+            }
+            if let Some(backend) = profile.backend.as_deref() {
+                crate::backend::Backend::parse(backend)?;
+            }
+            if profile.apps.iter().any(|app| app.trim().is_empty()) {
+                return Err(format!("Profile {} lists an empty app name", profile.name));
+            }
+        }
+        if self
+            .remote_viewers
+            .apps
+            .iter()
+            .any(|app| app.trim().is_empty())
+        {
+            return Err("Remote viewer app names must not be empty".to_string());
+        }
+        if let Some(name) = self.active_profile.as_deref() {
+            if self.active_profile().is_none() {
+                return Err(format!("Unknown profile: {}", name));
+            }
+        }
+        for hours in &self.do_not_disturb.schedule {
+            hours.validate()?;
+        }
+        if self.capture.channels.contains(&0) {
+            return Err("Capture channels are numbered from 1".to_string());
+        }
+        if self.chunking.max_chunk_chars == 0 {
+            return Err("Chunks must hold at least 1 character".to_string());
+        }
+        if let Some(rate) = self.typing.chars_per_second {
+            if !TYPING_RATE_RANGE.contains(&rate) {
+                return Err(format!("Typing speed out of range (1-1000): {}", rate));
+            }
+        }
+        for directive in &self.voice_commands.directives {
+            if directive
+                .phrases
+                .iter()
+                .all(|phrase| phrase.trim().is_empty())
+            {
+                return Err("Voice command has no phrase".to_string());
+            }
+            match &directive.action {
+                DirectiveAction::OpenUrl { url } if !url.contains("://") => {
+                    return Err(format!("Voice command URL is not absolute: {}", url));
+                }
+                DirectiveAction::Shell { command } if command.trim().is_empty() => {
+                    return Err("Voice command has an empty shell command".to_string());
+                }
+                _ => {}
+            }
+        }
+        for (index, template) in self.templates.templates.iter().enumerate() {
+            if template.name.trim().is_empty() {
+                return Err("Template name must not be empty".to_string());
+            }
+            if self.templates.templates[..index]
+                .iter()
+                .any(|other| other.name == template.name)
+            {
+                return Err(format!("Duplicate template name: {}", template.name));
+            }
+            if !template.text.contains(crate::postprocess::TEXT_PLACEHOLDER) {
+                return Err(format!(
+                    "Template {} has no {} placeholder",
+                    template.name,
+                    crate::postprocess::TEXT_PLACEHOLDER
+                ));
+            }
+        }
+        if let Some(name) = self.templates.active.as_deref() {
+            if !self
+                .templates
+                .templates
+                .iter()
+                .any(|template| template.name == name)
+            {
+                return Err(format!("Unknown template: {}", name));
+            }
+        }
+        if let Some(dir) = self.working_dir.as_deref() {
+            if !Path::new(dir).is_absolute() {
+                return Err(format!("Working directory is not absolute: {}", dir));
+            }
+        }
+        for pattern in &self.redaction.patterns {
+            regex::Regex::new(pattern)
+                .map_err(|err| format!("Invalid redaction pattern {}: {}", pattern, err))?;
+        }
+        if self.hooks.timeout_ms == 0 {
+            return Err("Hook timeout must be greater than 0".to_string());
+        }
+        if self.companion.port == 0 {
+            return Err("Companion port must not be 0".to_string());
+        }
+        if self
+            .companion
+            .devices
+            .iter()
+            .any(|device| device.token.is_empty())
+        {
+            return Err("Paired companion devices need a token".to_string());
+        }
+        if self.mcp.port == 0 {
+            return Err("MCP port must not be 0".to_string());
+        }
+        if self.grpc.port == 0 {
+            return Err("gRPC port must not be 0".to_string());
+        }
+        if let Some(folder) = self.sync.folder.as_deref() {
+            if !Path::new(folder).is_absolute() {
+                return Err(format!("Sync folder must be an absolute path: {}", folder));
+            }
+        }
+        if self.review.auto_confirm_secs == Some(0) {
+            return Err("Auto-confirm delay must be at least 1 second".to_string());
+        }
+        if let Some(confidence) = self.review.min_confidence {
+            if !(0.0..=1.0).contains(&confidence) {
+                return Err(format!(
+                    "Minimum confidence must be between 0 and 1: {}",
+                    confidence
+                ));
+            }
+        }
+        if self.history.max_entries == Some(0) || self.history.max_age_days == Some(0) {
+            return Err("History limits must be at least 1".to_string());
+        }
+        if self
+            .worker_memory_limit_mb
+            .is_some_and(|limit| limit < MIN_WORKER_MEMORY_LIMIT_MB)
+        {
+            return Err(format!(
+                "Worker memory limit must be at least {} MB",
+                MIN_WORKER_MEMORY_LIMIT_MB
+            ));
+        }
+        if self.low_power.idle_shutdown_secs == Some(0) {
+            return Err("Idle shutdown must be at least 1 second".to_string());
+        }
+        crate::backend::Backend::parse(&self.backend)?;
+        self.decoding.validate()?;
+        crate::models::validate_quantization(&self.model.quantization)
+    }
+}
+
+type ChangeListener = Box<dyn Fn(&Settings, &Settings) + Send + Sync>;
+
+static SETTINGS: OnceLock<Mutex<Settings>> = OnceLock::new();
+static SETTINGS_PATH: OnceLock<PathBuf> = OnceLock::new();
+static LISTENERS: Mutex<Vec<ChangeListener>> = Mutex::new(Vec::new());
+
+fn settings_state() -> &'static Mutex<Settings> {
+    SETTINGS.get_or_init(|| Mutex::new(Settings::default()))
+}
+
+/// Registers a callback invoked with `(previous, current)` whenever settings change,
+/// whether through [`update`] or an external edit picked up by [`reload`].
+pub fn on_change<F>(listener: F)
+where
+    F: Fn(&Settings, &Settings) + Send + Sync + 'static,
+{
+    if let Ok(mut listeners) = LISTENERS.lock() {
+        listeners.push(Box::new(listener));
+    }
+}
+
+pub fn path() -> Option<&'static Path> {
+    SETTINGS_PATH.get().map(PathBuf::as_path)
+}
+
+type Migration = fn(&mut Map<String, Value>);
+
+/// Upgrades from version `i` to `i + 1`. Append only; never edit a shipped step.
+const MIGRATIONS: &[Migration] = &[
+    // 0 -> 1: files written before versioning already match the first versioned shape.
+    |_| {},
+];
+
+/// Upgrades a raw settings object to [`SETTINGS_VERSION`] in place and returns the
+/// version it started at. A missing `version` means a file from before versioning.
+pub fn migrate(value: &mut Value) -> Result<u64, String> {
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| "Settings must be a JSON object".to_string())?;
+    let from = match object.get("version") {
+        None => 0,
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| format!("Invalid settings version: {}", version))?,
+    };
+    if from > SETTINGS_VERSION {
+        return Err(format!(
+            "Settings version {} is newer than this VType supports ({})",
+            from, SETTINGS_VERSION
+        ));
+    }
+    for step in &MIGRATIONS[from as usize..] {
+        step(object);
+    }
+    object.insert("version".to_string(), SETTINGS_VERSION.into());
+    Ok(from)
+}
+
+/// Reads and validates `path`, migrating it if needed. Once the upgraded settings are
+/// accepted, the original of a migrated file is kept as `settings.v<old>.bak` and the
+/// upgraded version is written back; a file that fails validation is left as it is.
+fn read_file(path: &Path) -> Result<Settings, String> {
+    let raw = match fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Settings::default()),
+        Err(err) => return Err(err.to_string()),
+    };
+    let mut value: Value = serde_json::from_str(&raw).map_err(|err| err.to_string())?;
+    let from = migrate(&mut value)?;
+    let settings: Settings = serde_json::from_value(value).map_err(|err| err.to_string())?;
+    settings.validate()?;
+    if from < SETTINGS_VERSION {
+        let backup = path.with_extension(format!("v{}.bak", from));
+        crate::safe_io::write(&backup, &raw).map_err(|err| err.to_string())?;
+        write_file(path, &settings)?;
+        crate::log::write(format!(
+            "Migrated settings from version {} to {}, backup at {}",
+            from,
+            SETTINGS_VERSION,
+            backup.display()
+        ))?;
+    }
+    Ok(settings)
+}
+
+fn write_file(path: &Path, settings: &Settings) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    let raw = serde_json::to_string_pretty(settings).map_err(|err| err.to_string())?;
+    crate::safe_io::write(path, raw).map_err(|err| err.to_string())
+}
+
+/// Loads settings from `dir`, which becomes the location all later updates are written to.
+pub fn init(dir: &Path) -> Result<(), String> {
+    let path = SETTINGS_PATH.get_or_init(|| dir.join(SETTINGS_FILE));
+    let loaded = read_file(path)?;
+    *settings_state()
+        .lock()
+        .map_err(|_| "Settings lock poisoned".to_string())? = loaded;
+    Ok(())
+}
+
+pub fn current() -> Settings {
+    settings_state()
+        .lock()
+        .map(|settings| settings.clone())
+        .unwrap_or_default()
+}
+
+pub fn update(mut settings: Settings) -> Result<Settings, String> {
+    settings.validate()?;
+    // Anything written by this build is in the current shape, whatever the caller sent.
+    settings.version = SETTINGS_VERSION;
+    let path = SETTINGS_PATH
+        .get()
+        .ok_or_else(|| "Settings not initialized".to_string())?;
+    write_file(path, &settings)?;
+    replace(settings.clone())?;
+    Ok(settings)
+}
+
+/// Switches the active profile (`None` for the global settings) and persists it.
+pub fn set_active_profile(name: Option<String>) -> Result<Settings, String> {
+    let mut settings = current();
+    settings.active_profile = name;
+    update(settings)
+}
+
+/// Turns code mode on or off and persists it.
+pub fn toggle_code_mode() -> Result<Settings, String> {
+    let mut settings = current();
+    settings.code_mode.enabled = !settings.code_mode.enabled;
+    update(settings)
+}
+
+/// Activates the profile after the current one, wrapping back to the global settings.
+pub fn cycle_profile() -> Result<Settings, String> {
+    let settings = current();
+    let next = match settings.active_profile.as_deref() {
+        None => settings.profiles.first(),
+        Some(name) => settings
+            .profiles
+            .iter()
+            .skip_while(|profile| profile.name != name)
+            .nth(1),
+    };
+    set_active_profile(next.map(|profile| profile.name.clone()))
+}
+
+/// Re-reads the settings file after an external edit. Invalid files are rejected and the
+/// running configuration is kept. Returns whether anything changed.
+pub fn reload() -> Result<bool, String> {
+    let path = SETTINGS_PATH
+        .get()
+        .ok_or_else(|| "Settings not initialized".to_string())?;
+    replace(read_file(path)?)
+}
+
+fn replace(settings: Settings) -> Result<bool, String> {
+    let previous = {
+        let mut guard = settings_state()
+            .lock()
+            .map_err(|_| "Settings lock poisoned".to_string())?;
+        if *guard == settings {
+            return Ok(false);
+        }
+        std::mem::replace(&mut *guard, settings.clone())
+    };
+    if let Ok(listeners) = LISTENERS.lock() {
+        for listener in listeners.iter() {
+            listener(&previous, &settings);
+        }
+    }
+    Ok(true)
+}
+
+/// Folder inside `sync.folder` holding one file per machine, so no two machines ever write
+/// the same file and the sync tool has no conflicts of its own to resolve.
+pub const SYNC_DIR: &str = "vtype-sync";
+/// How often the shared folder is checked for other machines' changes.
+const SYNC_INTERVAL: Duration = Duration::from_secs(30);
+/// Top-level settings that describe this machine rather than the user's preferences.
+/// Hotkeys are among them since the modifier keys differ between platforms, and hooks
+/// since they name scripts on this machine.
+pub(crate) const LOCAL_KEYS: &[&str] = &[
+    "version",
+    "sync",
+    "companion",
+    "mcp",
+    "grpc",
+    // Name files in this machine's plugins directory.
+    "plugins",
+    "audit",
+    "workingDir",
+    "hooks",
+    "hotkey",
+    "profileHotkey",
+    "clipboardHotkey",
+    "confirmHotkey",
+    "pauseHotkey",
+    "codeModeHotkey",
+    "quickPickHotkey",
+    "backend",
+    "model",
+    "workerMemoryLimitMb",
+    "lowPower",
+    "mock",
+    "capture",
+    "hardwareTrigger",
+    "updates",
+    // Able to run shell commands, so a synced folder could not be used to plant them.
+    "voiceCommands",
+];
+const DICTIONARY_KEY_PREFIX: &str = "dictionary/";
+const MACROS_KEY: &str = "macros";
+
+type SyncListener = Box<dyn Fn(&SyncReport) + Send + Sync>;
+
+static SYNCING: Mutex<()> = Mutex::new(());
+static SYNC_LISTENER: OnceLock<SyncListener> = OnceLock::new();
+
+/// What a sync changed, by key: a top-level setting such as `templates`, a dictionary rule
+/// as `dictionary/<from>`, or `macros` for the macros file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncReport {
+    /// Taken from another machine.
+    pub pulled: Vec<String>,
+    /// Changed here and shared.
+    pub pushed: Vec<String>,
+    /// Changed here and on another machine since the last sync; the later change won.
+    pub conflicts: Vec<String>,
+}
+
+impl SyncReport {
+    pub fn is_empty(&self) -> bool {
+        self.pulled.is_empty() && self.pushed.is_empty() && self.conflicts.is_empty()
+    }
+}
+
+/// One machine's file in the shared folder: every synced value, as the machine last merged
+/// it, with when and where it was last changed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct SyncFile {
+    entries: BTreeMap<String, SyncEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SyncEntry {
+    /// `null` for a deleted dictionary rule or a missing macros file.
+    value: Value,
+    /// Unix time in milliseconds.
+    modified_at: i64,
+    device: String,
+}
+
+impl SyncEntry {
+    fn newer_than(&self, other: &SyncEntry) -> bool {
+        (self.modified_at, &self.device) > (other.modified_at, &other.device)
+    }
+}
+
+/// Registers the callback told about every sync that changed something.
+pub fn on_sync<F>(listener: F)
+where
+    F: Fn(&SyncReport) + Send + Sync + 'static,
+{
+    let _ = SYNC_LISTENER.set(Box::new(listener));
+}
+
+/// Syncs now, whenever the settings change and every [`SYNC_INTERVAL`], while a folder is
+/// set.
+pub fn start_sync() {
+    on_change(|previous, current| {
+        if current.sync.folder.is_some() && previous != current {
+            std::thread::spawn(sync_and_report);
+        }
+    });
+    std::thread::spawn(|| loop {
+        sync_and_report();
+        std::thread::sleep(SYNC_INTERVAL);
+    });
+}
+
+fn sync_and_report() {
+    match sync() {
+        Ok(report) if !report.is_empty() => {
+            let _ = crate::log::write(format!(
+                "Synced settings: {} pulled, {} pushed, {} conflicts",
+                report.pulled.len(),
+                report.pushed.len(),
+                report.conflicts.len()
+            ));
+            if let Some(listener) = SYNC_LISTENER.get() {
+                listener(&report);
+            }
+        }
+        Ok(_) => {}
+        Err(err) => {
+            let _ = crate::log::write(format!("Settings sync failed: {}", err));
+        }
+    }
+}
+
+/// Merges the settings, dictionary rules and macros file with the other machines' files in
+/// `sync.folder`. For each key the latest change wins; a change made here since the last
+/// sync counts as made now. Does nothing without a folder.
+pub fn sync() -> Result<SyncReport, String> {
+    let _syncing = SYNCING
+        .lock()
+        .map_err(|_| "Sync lock poisoned".to_string())?;
+    let settings = current();
+    let Some(folder) = settings.sync.folder.as_deref() else {
+        return Ok(SyncReport::default());
+    };
+    let device = sync_device(&settings.sync);
+    let dir = Path::new(folder).join(SYNC_DIR);
+    fs::create_dir_all(&dir)
+        .map_err(|err| format!("Failed to create {}: {}", dir.display(), err))?;
+    let own_path = dir.join(format!("{}.json", device));
+    let own = read_sync_file(&own_path)?.unwrap_or_default();
+    let others = other_sync_files(&dir, &own_path)?;
+    let local = sync_values(&settings)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as i64)
+        .unwrap_or(0);
+
+    let keys: BTreeSet<&String> = local
+        .keys()
+        .chain(own.entries.keys())
+        .chain(others.iter().flat_map(|file| file.entries.keys()))
+        // Left out of `local` only when they have shell steps; see `shareable_macros`.
+        .filter(|key| key.as_str() != MACROS_KEY || local.contains_key(MACROS_KEY))
+        .collect();
+    let mut report = SyncReport::default();
+    let mut merged = SyncFile::default();
+    for key in keys {
+        let here = local.get(key).cloned().unwrap_or(Value::Null);
+        let theirs = others
+            .iter()
+            .filter_map(|file| file.entries.get(key))
+            .filter(|entry| key.as_str() != MACROS_KEY || shareable_macros(&entry.value))
+            .reduce(|newest, entry| {
+                if entry.newer_than(newest) {
+                    entry
+                } else {
+                    newest
+                }
+            });
+        let mine = || SyncEntry {
+            value: here.clone(),
+            modified_at: now,
+            device: device.clone(),
+        };
+        let entry = match (own.entries.get(key), theirs) {
+            // Changed here since the last sync.
+            (Some(last), theirs) if last.value != here => {
+                let mine = mine();
+                match theirs {
+                    Some(theirs)
+                        if theirs.modified_at > last.modified_at && theirs.value != here =>
+                    {
+                        report.conflicts.push(key.clone());
+                        if theirs.newer_than(&mine) {
+                            theirs.clone()
+                        } else {
+                            mine
+                        }
+                    }
+                    _ => mine,
+                }
+            }
+            (Some(last), Some(theirs)) if theirs.newer_than(last) => theirs.clone(),
+            (Some(last), _) => last.clone(),
+            // Joining the folder: what other machines share takes precedence.
+            (None, Some(theirs)) => theirs.clone(),
+            (None, None) => mine(),
+        };
+        if entry.value != here {
+            report.pulled.push(key.clone());
+        } else if entry.device == device && entry.modified_at == now {
+            report.pushed.push(key.clone());
+        }
+        merged.entries.insert(key.clone(), entry);
+    }
+
+    if !report.pulled.is_empty() {
+        apply_sync_values(&settings, &merged)?;
+    }
+    if merged != own {
+        write_sync_file(&own_path, &merged)?;
+    }
+    Ok(report)
+}
+
+/// `sync.deviceName` or the host name, as a file name.
+fn sync_device(sync: &SyncSettings) -> String {
+    let name = sync
+        .device_name
+        .clone()
+        .or_else(sysinfo::System::host_name)
+        .unwrap_or_else(|| "device".to_string());
+    name.chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.') {
+                ch
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+fn read_sync_file(path: &Path) -> Result<Option<SyncFile>, String> {
+    match fs::read_to_string(path) {
+        Ok(raw) => serde_json::from_str(&raw)
+            .map(Some)
+            .map_err(|err| format!("Invalid {}: {}", path.display(), err)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(format!("Failed to read {}: {}", path.display(), err)),
+    }
+}
+
+/// The files of the other machines. One that cannot be read, perhaps because the sync tool
+/// is still writing it, is left for the next sync.
+fn other_sync_files(dir: &Path, own_path: &Path) -> Result<Vec<SyncFile>, String> {
+    let entries =
+        fs::read_dir(dir).map_err(|err| format!("Failed to list {}: {}", dir.display(), err))?;
+    Ok(entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path != own_path && path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| {
+            read_sync_file(&path)
+                .inspect_err(|err| {
+                    let _ = crate::log::write(err.clone());
+                })
+                .ok()
+                .flatten()
+        })
+        .collect())
+}
+
+/// Written atomically, so other machines never see half a file.
+fn write_sync_file(path: &Path, file: &SyncFile) -> Result<(), String> {
+    let raw = serde_json::to_string_pretty(file).map_err(|err| err.to_string())?;
+    crate::safe_io::write(path, raw)
+        .map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+}
+
+/// Everything this machine shares, by key.
+fn sync_values(settings: &Settings) -> Result<BTreeMap<String, Value>, String> {
+    let Value::Object(object) = serde_json::to_value(settings).map_err(|err| err.to_string())?
+    else {
+        return Err("Settings are not an object".to_string());
+    };
+    let mut values: BTreeMap<String, Value> = object
+        .into_iter()
+        .filter(|(key, _)| key != "dictionary" && !LOCAL_KEYS.contains(&key.as_str()))
+        .collect();
+    for rule in &settings.dictionary {
+        values.insert(
+            format!("{}{}", DICTIONARY_KEY_PREFIX, rule.from),
+            serde_json::to_value(rule).map_err(|err| err.to_string())?,
+        );
+    }
+    let macros = read_macros()?;
+    if shareable_macros(&macros) {
+        values.insert(MACROS_KEY.to_string(), macros);
+    }
+    Ok(values)
+}
+
+/// Macros that launch programs, run shell commands or make HTTP requests stay on the machine
+/// they were written on, like hooks: they are neither shared nor taken from other machines.
+fn shareable_macros(value: &Value) -> bool {
+    value
+        .as_str()
+        .is_none_or(|toml| !crate::macros::has_program_steps(toml))
+}
+
+fn read_macros() -> Result<Value, String> {
+    let Some(path) = crate::macros::path() else {
+        return Ok(Value::Null);
+    };
+    match fs::read_to_string(&path) {
+        Ok(toml) => Ok(Value::String(toml)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Value::Null),
+        Err(err) => Err(format!("Failed to read {}: {}", path.display(), err)),
+    }
+}
+
+/// Makes `settings` and the macros file match `merged`. Dictionary rules keep their order
+/// here, with rules from other machines added at the end.
+fn apply_sync_values(settings: &Settings, merged: &SyncFile) -> Result<(), String> {
+    let Value::Object(mut object) =
+        serde_json::to_value(settings).map_err(|err| err.to_string())?
+    else {
+        return Err("Settings are not an object".to_string());
+    };
+    let mut dictionary: Vec<DictionaryRule> = Vec::new();
+    let mut known = BTreeSet::new();
+    for rule in &settings.dictionary {
+        let key = format!("{}{}", DICTIONARY_KEY_PREFIX, rule.from);
+        if let Some(entry) = merged.entries.get(&key) {
+            if let Ok(rule) = serde_json::from_value(entry.value.clone()) {
+                dictionary.push(rule);
+            }
+        }
+        known.insert(key);
+    }
+    for (key, entry) in &merged.entries {
+        if key == MACROS_KEY {
+            continue;
+        }
+        if key.starts_with(DICTIONARY_KEY_PREFIX) {
+            if !known.contains(key) {
+                if let Ok(rule) = serde_json::from_value(entry.value.clone()) {
+                    dictionary.push(rule);
+                }
+            }
+        } else {
+            object.insert(key.clone(), entry.value.clone());
+        }
+    }
+    object.insert(
+        "dictionary".to_string(),
+        serde_json::to_value(&dictionary).map_err(|err| err.to_string())?,
+    );
+    let synced: Settings = serde_json::from_value(Value::Object(object))
+        .map_err(|err| format!("Invalid synced settings: {}", err))?;
+    if let Some(entry) = merged.entries.get(MACROS_KEY) {
+        if !shareable_macros(&entry.value) {
+            let _ = crate::log::write(format!(
+                "Not taking synced macros from {}: they run shell commands",
+                entry.device
+            ));
+        } else if entry.value != read_macros()? {
+            write_macros(&entry.value)?;
+        }
+    }
+    if synced != *settings {
+        update(synced)?;
+    }
+    Ok(())
+}
+
+fn write_macros(value: &Value) -> Result<(), String> {
+    let Some(path) = crate::macros::path() else {
+        return Ok(());
+    };
+    let result = match value.as_str() {
+        Some(toml) => crate::safe_io::write(&path, toml),
+        None => match fs::remove_file(&path) {
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        },
+    };
+    result.map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+}
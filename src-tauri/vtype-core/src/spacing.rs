@@ -0,0 +1,109 @@
+//! Joins consecutive dictations into running text. Each transcript gets a trailing space,
+//! except after an opening bracket or quote and in scripts written without spaces, and the
+//! space is taken back when the next dictation starts with punctuation. What the previous
+//! dictation ended with is remembered per target app.
+
+use std::sync::Mutex;
+
+use crate::settings::SpacingSettings;
+
+static PREVIOUS: Mutex<Option<Previous>> = Mutex::new(None);
+
+struct Previous {
+    app: Option<String>,
+    trailing_space: bool,
+    ends_sentence: bool,
+}
+
+/// A transcript ready for injection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spaced {
+    pub text: String,
+    /// Backspace over the space added after the previous dictation before injecting.
+    pub retract_space: bool,
+}
+
+/// Spaces `text` against the previous dictation into `app` and remembers how it ends.
+pub fn apply(text: &str, app: Option<&str>, settings: &SpacingSettings) -> Spaced {
+    let mut previous = PREVIOUS.lock().ok();
+    let before = previous
+        .as_deref()
+        .and_then(Option::as_ref)
+        .filter(|previous| previous.app.as_deref() == app);
+    let retract_space = before.is_some_and(|before| before.trailing_space)
+        && text.chars().next().is_some_and(attaches);
+    let mut text = text.to_string();
+    if settings.capitalize_sentences && before.is_some_and(|before| before.ends_sentence) {
+        text = capitalize(&text);
+    }
+    let trailing_space = settings.trailing_space && wants_space(&text);
+    let ends_sentence = ends_sentence(&text);
+    if trailing_space {
+        text.push(' ');
+    }
+    if let Some(previous) = previous.as_mut() {
+        **previous = Some(Previous {
+            app: app.map(str::to_string),
+            trailing_space,
+            ends_sentence,
+        });
+    }
+    Spaced {
+        text,
+        retract_space,
+    }
+}
+
+/// Punctuation written against the word before it.
+fn attaches(ch: char) -> bool {
+    matches!(
+        ch,
+        ',' | '.' | ';' | ':' | '!' | '?' | ')' | ']' | '}' | '%' | '…' | '”' | '’' | '»'
+    )
+}
+
+fn opens(ch: char) -> bool {
+    matches!(ch, '(' | '[' | '{' | '“' | '‘' | '«' | '¿' | '¡')
+}
+
+fn wants_space(text: &str) -> bool {
+    text.chars()
+        .next_back()
+        .is_some_and(|last| !last.is_whitespace() && !opens(last) && !is_unspaced(last))
+}
+
+/// Sentence-final punctuation, possibly followed by closing quotes or brackets.
+fn ends_sentence(text: &str) -> bool {
+    text.trim_end()
+        .chars()
+        .rev()
+        .find(|ch| !matches!(ch, '"' | '\'' | ')' | ']' | '”' | '’' | '»'))
+        .is_some_and(|ch| matches!(ch, '.' | '!' | '?' | '…' | '。' | '！' | '？'))
+}
+
+/// Uppercases the first letter, past any opening punctuation.
+fn capitalize(text: &str) -> String {
+    match text.char_indices().find(|(_, ch)| ch.is_alphanumeric()) {
+        Some((index, ch)) if ch.is_lowercase() => {
+            let rest = &text[index + ch.len_utf8()..];
+            format!("{}{}{}", &text[..index], ch.to_uppercase(), rest)
+        }
+        _ => text.to_string(),
+    }
+}
+
+/// Characters of scripts written without spaces between words or sentences: CJK, kana,
+/// Thai, Lao, Khmer and their full-width punctuation.
+fn is_unspaced(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{0E00}'..='\u{0EFF}' // Thai and Lao
+            | '\u{1780}'..='\u{17FF}' // Khmer
+            | '\u{3000}'..='\u{30FF}' // CJK punctuation, hiragana, katakana
+            | '\u{3400}'..='\u{4DBF}'
+            | '\u{4E00}'..='\u{9FFF}'
+            | '\u{F900}'..='\u{FAFF}'
+            | '\u{FF00}'..='\u{FFEF}' // full-width forms
+            | '\u{20000}'..='\u{3134F}'
+    )
+}
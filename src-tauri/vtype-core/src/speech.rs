@@ -0,0 +1,117 @@
+//! Reads text aloud with the platform's speech synthesizer, so a transcript can be checked
+//! without looking at it.
+
+use std::process::{Child, Command};
+use std::sync::Mutex;
+
+/// The utterance being spoken; a new one interrupts it.
+static SPEAKING: Mutex<Option<Child>> = Mutex::new(None);
+
+/// Starts speaking `text` and returns without waiting for it to finish.
+pub fn speak(text: &str) -> Result<(), String> {
+    if text.trim().is_empty() {
+        return Err("Nothing to read aloud".to_string());
+    }
+    stop();
+    let child = spawn(text)?;
+    if let Ok(mut speaking) = SPEAKING.lock() {
+        *speaking = Some(child);
+    }
+    Ok(())
+}
+
+/// Speaks `text` and returns once it has been said, for speech that must not overlap what
+/// comes next.
+pub fn speak_and_wait(text: &str) -> Result<(), String> {
+    stop();
+    spawn(text)?
+        .wait()
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+/// Cuts off whatever is being spoken.
+pub fn stop() {
+    let Ok(mut speaking) = SPEAKING.lock() else {
+        return;
+    };
+    if let Some(mut child) = speaking.take() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn spawn(text: &str) -> Result<Child, String> {
+    // `say` reads the text from stdin when given none on the command line.
+    with_stdin(Command::new("say"), text)
+}
+
+#[cfg(target_os = "windows")]
+fn spawn(text: &str) -> Result<Child, String> {
+    let mut command = Command::new("powershell");
+    command.args([
+        "-NoProfile",
+        "-NonInteractive",
+        "-Command",
+        "Add-Type -AssemblyName System.Speech; \
+         (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak([Console]::In.ReadToEnd())",
+    ]);
+    crate::process::configure_background_command(&mut command);
+    with_stdin(command, text)
+}
+
+/// speech-dispatcher, with eSpeak for systems that lack it.
+#[cfg(target_os = "linux")]
+fn spawn(text: &str) -> Result<Child, String> {
+    let mut dispatcher = Command::new("spd-say");
+    dispatcher.args(["--wait", "--"]).arg(text);
+    match spawn_quiet(dispatcher) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        result => return result.map_err(|err| err.to_string()),
+    }
+    for espeak in ["espeak-ng", "espeak"] {
+        let mut command = Command::new(espeak);
+        command.arg("--stdin");
+        match with_stdin(command, text) {
+            Err(_) => continue,
+            result => return result,
+        }
+    }
+    Err("No speech synthesizer found; install speech-dispatcher or espeak-ng".to_string())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn spawn(_text: &str) -> Result<Child, String> {
+    Err("Reading aloud is not supported on this platform".to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn spawn_quiet(mut command: Command) -> std::io::Result<Child> {
+    use std::process::Stdio;
+
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+fn with_stdin(mut command: Command, text: &str) -> Result<Child, String> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|err| err.to_string())?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|err| err.to_string())?;
+    }
+    Ok(child)
+}
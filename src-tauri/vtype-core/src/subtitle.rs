@@ -0,0 +1,116 @@
+//! Splits long recordings at pauses and formats the transcribed pieces as subtitles.
+//!
+//! The backends return text without timestamps, so each cue is transcribed on its own and
+//! takes its timing from where the split fell.
+
+use std::ops::Range;
+
+use serde::Serialize;
+
+pub(crate) const FRAME_MS: usize = 20;
+/// A pause this long ends a cue once the cue has run for [`MIN_CUE_MS`].
+const PAUSE_MS: usize = 400;
+const MIN_CUE_MS: usize = 2_000;
+/// Cues still running at this length are cut at their quietest frame past the halfway mark.
+const MAX_CUE_MS: usize = 15_000;
+/// Audio kept either side of speech so word edges are not clipped.
+const PAD_MS: usize = 200;
+/// Frames quieter than the noise floor times this count as pause.
+const PAUSE_RATIO: f32 = 2.0;
+/// Pause threshold for recordings whose noise floor is digital silence (-60 dBFS).
+const MIN_PAUSE_RMS: f32 = 0.001;
+
+/// One timed piece of a transcript.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cue {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+}
+
+/// Sample ranges of mono `samples` that hold speech, split at pauses into cue-sized pieces.
+/// Leading, trailing and long inner silences are left out.
+pub fn split(samples: &[f32], sample_rate: u32) -> Vec<Range<usize>> {
+    let frame = (sample_rate as usize * FRAME_MS / 1000).max(1);
+    let energy: Vec<f32> = samples.chunks(frame).map(crate::audio::rms).collect();
+    let threshold = pause_threshold(&energy);
+    let frames = |ms: usize| ms / FRAME_MS;
+
+    let mut cues: Vec<Range<usize>> = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut quiet = 0;
+    let mut last_end = 0;
+    for (index, &rms) in energy.iter().enumerate() {
+        let is_quiet = rms < threshold;
+        let Some(first) = start else {
+            if !is_quiet {
+                start = Some(index.saturating_sub(frames(PAD_MS)).max(last_end));
+                quiet = 0;
+            }
+            continue;
+        };
+        quiet = if is_quiet { quiet + 1 } else { 0 };
+        let len = index + 1 - first;
+        if quiet >= frames(PAUSE_MS) && len - quiet >= frames(MIN_CUE_MS) {
+            let end = index + 1 - quiet + frames(PAD_MS);
+            cues.push(first..end);
+            last_end = end;
+            start = None;
+        } else if len >= frames(MAX_CUE_MS) {
+            let cut = (first + len / 2..=index)
+                .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+                .unwrap_or(index);
+            cues.push(first..cut);
+            last_end = cut;
+            start = Some(cut);
+            quiet = 0;
+        }
+    }
+    if let Some(first) = start {
+        let end = (energy.len() - quiet + frames(PAD_MS)).min(energy.len());
+        cues.push(first..end);
+    }
+    cues.into_iter()
+        .map(|cue| cue.start * frame..(cue.end * frame).min(samples.len()))
+        .filter(|cue| !cue.is_empty())
+        .collect()
+}
+
+/// The RMS below which a frame of [`FRAME_MS`] counts as pause, from the noise floor of
+/// `energy`, the frames' RMS in order.
+pub(crate) fn pause_threshold(energy: &[f32]) -> f32 {
+    let mut sorted = energy.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let floor = sorted
+        .get(sorted.len().saturating_sub(1) / 10)
+        .copied()
+        .unwrap_or(0.0);
+    (floor * PAUSE_RATIO).max(MIN_PAUSE_RMS)
+}
+
+/// Formats cues as a SubRip file.
+pub fn to_srt(cues: &[Cue]) -> String {
+    cues.iter()
+        .enumerate()
+        .map(|(index, cue)| {
+            format!(
+                "{}\n{} --> {}\n{}\n\n",
+                index + 1,
+                timestamp(cue.start_ms),
+                timestamp(cue.end_ms),
+                cue.text
+            )
+        })
+        .collect()
+}
+
+fn timestamp(ms: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
@@ -0,0 +1,25 @@
+//! Prepares transcripts for terminals, where a line break reaches the shell as Enter and
+//! runs whatever is on the command line. Nothing is quoted or escaped: the text lands as
+//! it was spoken, for the user to finish and run.
+
+use crate::settings::TerminalSafety;
+
+pub fn sanitize(text: &str, safety: TerminalSafety) -> String {
+    // Control characters other than line breaks and tabs would arrive as keys; ESC could
+    // also end a bracketed paste early.
+    let text: String = text
+        .replace("\r\n", "\n")
+        .chars()
+        .filter(|ch| !ch.is_control() || matches!(ch, '\n' | '\t'))
+        .collect();
+    let text = text.trim_end_matches('\n');
+    match safety {
+        TerminalSafety::Paste => text.to_string(),
+        TerminalSafety::ManualEnter => text
+            .split('\n')
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
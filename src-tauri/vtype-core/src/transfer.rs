@@ -0,0 +1,105 @@
+//! Settings export files for moving a configuration between machines.
+//!
+//! An export wraps the settings in `{format, version, settings}`. Older files are migrated
+//! step by step on import; a bare `settings.json` copied by hand counts as version 0. The
+//! settings inside then go through the regular schema migrations in [`settings::migrate`].
+//! What belongs to one machine, the keys that settings sync leaves alone, is neither
+//! exported nor imported: paired companion devices and their tokens, hooks, voice commands
+//! and hotkeys stay where they are.
+
+use std::fs;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::settings::{self, Settings};
+
+pub const EXPORT_FORMAT: &str = "vtype-settings";
+pub const EXPORT_VERSION: u64 = 1;
+
+/// Writes the current settings to `path`.
+pub fn export(path: &Path) -> Result<(), String> {
+    let raw = serde_json::to_string_pretty(&document(&settings::current())?)
+        .map_err(|err| err.to_string())?;
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    crate::safe_io::write(path, raw).map_err(|err| err.to_string())
+}
+
+/// The export document for `settings`, without the machine's own settings.
+pub fn document(settings: &Settings) -> Result<Value, String> {
+    let mut value = serde_json::to_value(settings).map_err(|err| err.to_string())?;
+    if let Some(object) = value.as_object_mut() {
+        // The version stays, since import migrates by it.
+        for key in settings::LOCAL_KEYS.iter().filter(|key| **key != "version") {
+            object.remove(*key);
+        }
+    }
+    Ok(json!({
+        "format": EXPORT_FORMAT,
+        "version": EXPORT_VERSION,
+        "settings": value,
+    }))
+}
+
+/// Reads and migrates an export file, keeping this machine's own settings from `local`
+/// whatever the file holds. The result is validated but not applied.
+pub fn read(path: &Path, local: &Settings) -> Result<Settings, String> {
+    let raw = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let document: Value = serde_json::from_str(&raw).map_err(|err| err.to_string())?;
+    let mut value = migrate(document)?;
+    settings::migrate(&mut value)?;
+    let local = serde_json::to_value(local).map_err(|err| err.to_string())?;
+    if let (Some(object), Some(local)) = (value.as_object_mut(), local.as_object()) {
+        for key in settings::LOCAL_KEYS {
+            match local.get(*key) {
+                Some(kept) => object.insert(key.to_string(), kept.clone()),
+                None => object.remove(*key),
+            };
+        }
+    }
+    let settings: Settings = serde_json::from_value(value).map_err(|err| err.to_string())?;
+    settings.validate()?;
+    Ok(settings)
+}
+
+/// Upgrades an export document to the current version and returns its settings object.
+fn migrate(mut document: Value) -> Result<Value, String> {
+    let mut version = document_version(&document)?;
+    if version > EXPORT_VERSION {
+        return Err(format!(
+            "Settings file version {} was written by a newer VType (supports up to {})",
+            version, EXPORT_VERSION
+        ));
+    }
+    while version < EXPORT_VERSION {
+        document = match version {
+            0 => json!({
+                "format": EXPORT_FORMAT,
+                "version": 1,
+                "settings": document,
+            }),
+            _ => unreachable!("every version below EXPORT_VERSION has a migration"),
+        };
+        version += 1;
+    }
+    document
+        .get_mut("settings")
+        .map(Value::take)
+        .ok_or_else(|| "Settings file has no settings".to_string())
+}
+
+fn document_version(document: &Value) -> Result<u64, String> {
+    let object = document
+        .as_object()
+        .ok_or_else(|| "Settings file must contain a JSON object".to_string())?;
+    match object.get("format").and_then(Value::as_str) {
+        Some(EXPORT_FORMAT) => object
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| "Settings file has no version".to_string()),
+        Some(other) => Err(format!("Not a VType settings file: {}", other)),
+        None => Ok(0),
+    }
+}
@@ -0,0 +1,163 @@
+//! A Python environment of VType's own, so the worker does not depend on what happens to be
+//! installed system-wide. [`setup`] creates a venv in the worker's data directory and
+//! installs the pinned requirements into it; from then on the worker runs on its interpreter.
+
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use crate::i18n::{self, Message};
+
+const REQUIREMENTS: &str = include_str!("../worker-requirements.txt");
+const ENV_DIR: &str = "venv";
+/// Written once the requirements are installed, holding the requirements it was set up
+/// with; an environment without a current one is not used.
+const INSTALLED_FILE: &str = "vtype-requirements.txt";
+/// pip's line as it starts on each package.
+const COLLECTING_PREFIX: &str = "Collecting ";
+const INSTALLING_PREFIX: &str = "Installing collected packages";
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Reported while the environment is set up.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "stage", rename_all = "camelCase")]
+pub enum SetupProgress {
+    /// Creating the venv with the system Python.
+    Creating,
+    /// pip is resolving and downloading `package`.
+    Collecting { package: String },
+    /// Everything is downloaded and being installed.
+    Installing,
+}
+
+enum Event {
+    Progress(SetupProgress),
+    Finished(Result<PathBuf, String>),
+}
+
+/// The environment's interpreter, once it is set up with the current requirements.
+pub fn python() -> Option<PathBuf> {
+    let dir = env_dir();
+    let installed = std::fs::read_to_string(dir.join(INSTALLED_FILE)).ok()?;
+    let python = interpreter(&dir);
+    (installed == REQUIREMENTS && python.is_file()).then_some(python)
+}
+
+/// Creates the environment, or brings an existing one up to the pinned requirements, and
+/// returns its interpreter.
+pub async fn setup(mut progress: impl FnMut(SetupProgress)) -> Result<PathBuf, String> {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return Err("The Python environment is already being set up".to_string());
+    }
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        let result = install(&env_dir(), &sender);
+        let _ = sender.send(Event::Finished(result));
+    });
+    let result = loop {
+        match receiver.recv().await {
+            Some(Event::Progress(update)) => progress(update),
+            Some(Event::Finished(result)) => break result,
+            None => break Err("Setup thread stopped".to_string()),
+        }
+    };
+    RUNNING.store(false, Ordering::SeqCst);
+    result
+}
+
+fn install(dir: &Path, events: &UnboundedSender<Event>) -> Result<PathBuf, String> {
+    let system =
+        crate::worker::system_python().ok_or_else(|| i18n::error(Message::PythonNotFound, &[]))?;
+    let installed = dir.join(INSTALLED_FILE);
+    let _ = std::fs::remove_file(&installed);
+
+    let _ = events.send(Event::Progress(SetupProgress::Creating));
+    let mut create = Command::new(system);
+    create.args(["-m", "venv"]).arg(dir);
+    run(create, |_| {})?;
+
+    let python = interpreter(dir);
+    let requirements = dir.join("requirements.txt");
+    std::fs::write(&requirements, REQUIREMENTS).map_err(|err| err.to_string())?;
+    let mut pip = Command::new(&python);
+    pip.args([
+        "-m",
+        "pip",
+        "install",
+        "--disable-pip-version-check",
+        "--progress-bar",
+        "off",
+        "-r",
+    ])
+    .arg(&requirements);
+    run(pip, |line| {
+        let update = if let Some(package) = line.strip_prefix(COLLECTING_PREFIX) {
+            SetupProgress::Collecting {
+                package: package.trim().to_string(),
+            }
+        } else if line.starts_with(INSTALLING_PREFIX) {
+            SetupProgress::Installing
+        } else {
+            return;
+        };
+        let _ = events.send(Event::Progress(update));
+    })?;
+
+    std::fs::write(&installed, REQUIREMENTS).map_err(|err| err.to_string())?;
+    Ok(python)
+}
+
+/// Runs `command`, handing each line it prints to `on_line`. A failure is reported with the
+/// last error line it wrote.
+fn run(mut command: Command, mut on_line: impl FnMut(&str)) -> Result<(), String> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    crate::process::configure_background_command(&mut command);
+    let mut child = command.spawn().map_err(|err| err.to_string())?;
+    let mut stderr = child.stderr.take();
+    let errors = std::thread::spawn(move || {
+        let mut text = String::new();
+        if let Some(stderr) = stderr.as_mut() {
+            let _ = stderr.read_to_string(&mut text);
+        }
+        text
+    });
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            on_line(&line);
+        }
+    }
+    let status = child.wait().map_err(|err| err.to_string())?;
+    let errors = errors.join().unwrap_or_default();
+    if status.success() {
+        return Ok(());
+    }
+    let reason = errors
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("Python environment setup failed");
+    Err(reason.to_string())
+}
+
+fn env_dir() -> PathBuf {
+    crate::paths::worker_dir().join(ENV_DIR)
+}
+
+#[cfg(target_os = "windows")]
+fn interpreter(dir: &Path) -> PathBuf {
+    dir.join("Scripts").join("python.exe")
+}
+
+#[cfg(not(target_os = "windows"))]
+fn interpreter(dir: &Path) -> PathBuf {
+    dir.join("bin").join("python3")
+}
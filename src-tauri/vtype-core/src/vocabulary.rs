@@ -0,0 +1,132 @@
+//! Dictionary suggestions mined from the corrections users make to history entries.
+//!
+//! Each corrected entry is diffed word by word against the backend's original output. A
+//! short run of words replaced by another short run ("cube control" -> "kubectl") is a
+//! candidate rule; candidates seen often enough that no dictionary rule covers yet are
+//! suggested.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{history, settings};
+
+/// Corrections needed before a replacement is suggested.
+pub const MIN_OCCURRENCES: u32 = 2;
+/// Longer rewrites are edits of meaning, not misrecognized terms.
+const MAX_SPAN_WORDS: usize = 4;
+/// Bounds the word-level diff on very long dictations.
+const MAX_DIFF_CELLS: usize = 250_000;
+const SENTENCE_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', '"', '\'', '(', ')'];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DictionarySuggestion {
+    pub from: String,
+    pub to: String,
+    /// How many corrections made this replacement.
+    pub occurrences: u32,
+}
+
+/// Suggested rules, most frequent first.
+pub fn suggestions() -> Result<Vec<DictionarySuggestion>, String> {
+    let settings = settings::current();
+    let known: Vec<String> = settings
+        .effective_dictionary()
+        .iter()
+        .map(|rule| rule.from.to_lowercase())
+        .collect();
+
+    // Keyed by the lowercased heard phrase; the spelling users type most often wins.
+    let mut counts: HashMap<String, HashMap<String, u32>> = HashMap::new();
+    for (original, corrected) in history::corrections()? {
+        for (from, to) in replacements(&original, &corrected) {
+            *counts.entry(from).or_default().entry(to).or_default() += 1;
+        }
+    }
+
+    let mut suggestions: Vec<DictionarySuggestion> = counts
+        .into_iter()
+        .filter(|(from, _)| !known.contains(from))
+        .filter_map(|(from, spellings)| {
+            let occurrences = spellings.values().sum();
+            let (to, _) = spellings
+                .into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))?;
+            Some(DictionarySuggestion {
+                from,
+                to,
+                occurrences,
+            })
+        })
+        .filter(|suggestion| suggestion.occurrences >= MIN_OCCURRENCES)
+        .collect();
+    suggestions.sort_by(|a, b| {
+        b.occurrences
+            .cmp(&a.occurrences)
+            .then_with(|| a.from.cmp(&b.from))
+    });
+    Ok(suggestions)
+}
+
+/// Short replaced spans between two texts as `(lowercased original, corrected)` pairs.
+fn replacements(original: &str, corrected: &str) -> Vec<(String, String)> {
+    let before = words(original);
+    let after = words(corrected);
+    if before.is_empty() || after.is_empty() || before.len() * after.len() > MAX_DIFF_CELLS {
+        return Vec::new();
+    }
+    let keys_before: Vec<String> = before.iter().map(|word| word.to_lowercase()).collect();
+    let keys_after: Vec<String> = after.iter().map(|word| word.to_lowercase()).collect();
+
+    // Longest common subsequence table, filled from the end so the walk below runs forward.
+    let (n, m) = (before.len(), after.len());
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if keys_before[i] == keys_after[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut found = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    let (mut removed_from, mut added_from) = (0, 0);
+    let mut flush = |i: usize, j: usize, removed_from: usize, added_from: usize| {
+        let (removed, added) = (&before[removed_from..i], &after[added_from..j]);
+        if !removed.is_empty()
+            && !added.is_empty()
+            && removed.len() <= MAX_SPAN_WORDS
+            && added.len() <= MAX_SPAN_WORDS
+        {
+            found.push((removed.join(" ").to_lowercase(), added.join(" ")));
+        }
+    };
+    while i < n || j < m {
+        if i < n && j < m && keys_before[i] == keys_after[j] {
+            flush(i, j, removed_from, added_from);
+            i += 1;
+            j += 1;
+            removed_from = i;
+            added_from = j;
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            j += 1;
+        } else {
+            i += 1;
+        }
+    }
+    flush(i, j, removed_from, added_from);
+    found
+}
+
+/// Words with surrounding sentence punctuation removed, so "kubectl," matches "kubectl"
+/// while "C++" keeps its symbols.
+fn words(text: &str) -> Vec<&str> {
+    text.split_whitespace()
+        .map(|word| word.trim_matches(SENTENCE_PUNCTUATION))
+        .filter(|word| !word.is_empty())
+        .collect()
+}
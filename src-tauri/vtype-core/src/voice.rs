@@ -0,0 +1,87 @@
+//! The voice-command interpreter: a phrase said at the end of a dictation, such as "send it"
+//! or "search that", picks an action for the text before it. The phrases and their actions
+//! are the user's, in `voiceCommands.directives`.
+
+use std::io::Write;
+use std::process::Stdio;
+
+use regex::RegexBuilder;
+
+use crate::settings::{Directive, DirectiveAction};
+use crate::{log, process};
+
+/// Placeholder in an `openUrl` action's URL.
+pub const TEXT_PLACEHOLDER: &str = "{text}";
+
+/// The text before the first directive `text` ends with, and that directive's action. Case,
+/// the punctuation after the phrase and the commas before it are ignored, so "Hello, send
+/// it." gives "Hello".
+pub fn interpret(text: &str, directives: &[Directive]) -> Option<(String, DirectiveAction)> {
+    directives.iter().find_map(|directive| {
+        let start = directive
+            .phrases
+            .iter()
+            .find_map(|phrase| phrase_start(text, phrase))?;
+        let rest = text[..start]
+            .trim_end_matches(|ch: char| ch.is_whitespace() || matches!(ch, ',' | ';' | ':' | '-'));
+        Some((rest.to_string(), directive.action.clone()))
+    })
+}
+
+/// Where `phrase` starts if it ends `text` as whole words.
+fn phrase_start(text: &str, phrase: &str) -> Option<usize> {
+    let words: Vec<String> = phrase.split_whitespace().map(regex::escape).collect();
+    if words.is_empty() {
+        return None;
+    }
+    let pattern = format!(r"\b{}[\s\p{{P}}]*$", words.join(r"[\s,]+"));
+    let regex = RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .ok()?;
+    regex.find(text).map(|found| found.start())
+}
+
+/// `url` with [`TEXT_PLACEHOLDER`] replaced by `text`, percent-encoded.
+pub fn expand_url(url: &str, text: &str) -> String {
+    url.replace(TEXT_PLACEHOLDER, &percent_encode(text))
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes().fold(String::new(), |mut encoded, byte| {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+        encoded
+    })
+}
+
+/// Starts `command` through the shell with `text` in `VTYPE_TEXT` and on its stdin, without
+/// waiting for it; a failure is only logged.
+pub fn run_shell(command: &str, text: &str) -> Result<(), String> {
+    let mut shell = process::shell(command);
+    process::configure_background_command(&mut shell);
+    let mut child = shell
+        .env("VTYPE_TEXT", text)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .map_err(|err| format!("Failed to run {}: {}", command, err))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A command that ignores its input may close it before this is written.
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    let command = command.to_string();
+    std::thread::spawn(move || match child.wait() {
+        Ok(status) if !status.success() => {
+            let _ = log::write(format!("Voice command {} exited with {}", command, status));
+        }
+        Ok(_) => {}
+        Err(err) => {
+            let _ = log::write(format!("Voice command {} failed: {}", command, err));
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use notify::{RecursiveMode, Watcher};
+
+use crate::settings;
+
+/// Editors and sync tools often write a file in several steps; wait for them to settle.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Watches the settings file and reloads it when it changes on disk.
+///
+/// The parent directory is watched rather than the file itself so atomic
+/// replace-by-rename saves are seen too.
+pub fn start() -> Result<(), String> {
+    let path = settings::path().ok_or_else(|| "Settings not initialized".to_string())?;
+    let dir = path
+        .parent()
+        .ok_or_else(|| "Settings path has no parent".to_string())?;
+    std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_os_string())
+        .ok_or_else(|| "Settings path has no file name".to_string())?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(|err| err.to_string())?;
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .map_err(|err| err.to_string())?;
+
+    std::thread::spawn(move || {
+        // Keep the watcher alive for as long as the thread runs.
+        let _watcher = watcher;
+        let touches_settings = |event: &notify::Event| {
+            event
+                .paths
+                .iter()
+                .any(|changed: &PathBuf| changed.file_name() == Some(file_name.as_os_str()))
+        };
+        while let Ok(event) = receiver.recv() {
+            match event {
+                Ok(event) if touches_settings(&event) => {}
+                Ok(_) => continue,
+                Err(err) => {
+                    let _ = crate::log::write(format!("Settings watcher error: {}", err));
+                    continue;
+                }
+            }
+            std::thread::sleep(DEBOUNCE);
+            while receiver.try_recv().is_ok() {}
+            match settings::reload() {
+                Ok(true) => {
+                    let _ = crate::log::write("Settings reloaded from disk".to_string());
+                }
+                Ok(false) => {}
+                Err(err) => {
+                    let _ = crate::log::write(format!("Ignoring invalid settings file: {}", err));
+                }
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,216 @@
+//! The server side of RFC 6455 WebSockets, as much as the companion connection needs: the
+//! opening handshake, messages split over continuation frames, pings and closing. Clients
+//! must mask their frames; extensions and subprotocols are not offered.
+
+use std::io::{Read, Write};
+
+use base64::engine::general_purpose;
+use base64::Engine as _;
+
+use crate::http::Request;
+
+/// Appended to the client's key before hashing, as the RFC fixes it.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Larger messages close the connection.
+pub const MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+    /// The client closed the connection; the close has been answered.
+    Close,
+}
+
+/// Whether `request` asks to open a WebSocket.
+pub fn is_upgrade(request: &Request) -> bool {
+    key(request).is_some()
+}
+
+/// Completes the handshake for `request`.
+pub fn upgrade<W: Write>(stream: &mut W, request: &Request) -> Result<(), String> {
+    let key = key(request).ok_or("Not a WebSocket upgrade")?;
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    )
+    .map_err(|err| err.to_string())
+}
+
+/// The `Sec-WebSocket-Accept` answering the client's `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    general_purpose::STANDARD.encode(sha1(format!("{}{}", key, ACCEPT_GUID).as_bytes()))
+}
+
+fn key(request: &Request) -> Option<&str> {
+    let upgrading = request.method == "GET"
+        && request
+            .header("upgrade")
+            .is_some_and(|upgrade| upgrade.eq_ignore_ascii_case("websocket"));
+    request.header("sec-websocket-key").filter(|_| upgrading)
+}
+
+/// The next message from the client. Pings are answered on the way.
+pub fn read_message<S: Read + Write>(stream: &mut S) -> Result<Message, String> {
+    let mut message: Option<(u8, Vec<u8>)> = None;
+    loop {
+        let (fin, opcode, payload) = read_frame(stream)?;
+        match opcode {
+            // Control frames may come between the fragments of a message, and end nothing.
+            OP_PING => {
+                write_frame(stream, OP_PONG, &payload)?;
+                continue;
+            }
+            OP_PONG => continue,
+            OP_CLOSE => {
+                let _ = write_frame(stream, OP_CLOSE, &payload[..payload.len().min(2)]);
+                return Ok(Message::Close);
+            }
+            OP_TEXT | OP_BINARY if message.is_none() => message = Some((opcode, payload)),
+            OP_CONTINUATION if message.is_some() => {
+                if let Some((_, data)) = message.as_mut() {
+                    if data.len() + payload.len() > MAX_MESSAGE_BYTES {
+                        return Err("Message too large".to_string());
+                    }
+                    data.extend_from_slice(&payload);
+                }
+            }
+            _ => return Err(format!("Unexpected frame with opcode {}", opcode)),
+        }
+        if fin {
+            if let Some((opcode, data)) = message.take() {
+                return if opcode == OP_TEXT {
+                    String::from_utf8(data)
+                        .map(Message::Text)
+                        .map_err(|_| "Text message is not UTF-8".to_string())
+                } else {
+                    Ok(Message::Binary(data))
+                };
+            }
+        }
+    }
+}
+
+pub fn send_text<W: Write>(stream: &mut W, text: &str) -> Result<(), String> {
+    write_frame(stream, OP_TEXT, text.as_bytes())
+}
+
+pub fn send_close<W: Write>(stream: &mut W) {
+    let _ = write_frame(stream, OP_CLOSE, &1000u16.to_be_bytes());
+}
+
+fn read_frame<R: Read>(stream: &mut R) -> Result<(bool, u8, Vec<u8>), String> {
+    let mut head = [0u8; 2];
+    stream
+        .read_exact(&mut head)
+        .map_err(|err| err.to_string())?;
+    let fin = head[0] & 0x80 != 0;
+    let opcode = head[0] & 0x0F;
+    if head[1] & 0x80 == 0 {
+        return Err("Client frames must be masked".to_string());
+    }
+    let len = match head[1] & 0x7F {
+        126 => {
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len).map_err(|err| err.to_string())?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            stream.read_exact(&mut len).map_err(|err| err.to_string())?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    if len > MAX_MESSAGE_BYTES as u64 {
+        return Err("Message too large".to_string());
+    }
+    let mut mask = [0u8; 4];
+    stream
+        .read_exact(&mut mask)
+        .map_err(|err| err.to_string())?;
+    let mut payload = vec![0u8; len as usize];
+    stream
+        .read_exact(&mut payload)
+        .map_err(|err| err.to_string())?;
+    for (index, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[index % 4];
+    }
+    Ok((fin, opcode, payload))
+}
+
+fn write_frame<W: Write>(stream: &mut W, opcode: u8, payload: &[u8]) -> Result<(), String> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame).map_err(|err| err.to_string())
+}
+
+/// SHA-1, which the handshake requires. Nothing that needs to be secure relies on it.
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut words = [0u32; 80];
+        for (index, word) in block.chunks(4).enumerate() {
+            words[index] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for index in 16..80 {
+            words[index] =
+                (words[index - 3] ^ words[index - 8] ^ words[index - 14] ^ words[index - 16])
+                    .rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (index, word) in words.iter().enumerate() {
+            let (f, k) = match index {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let next = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = next;
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+    let mut digest = [0u8; 20];
+    for (chunk, value) in digest.chunks_mut(4).zip(state) {
+        chunk.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
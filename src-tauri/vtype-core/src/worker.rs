@@ -0,0 +1,710 @@
+use std::collections::VecDeque;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::activity::{self, ActivityKind};
+use crate::i18n::{self, Message};
+use crate::metrics::ErrorCategory;
+use crate::protocol::{self, FailureKind, Handshake, Reply, WorkerFailure};
+use crate::settings::ModelFallback;
+
+// Covers a cold model download/load in front of the request as well as the inference itself.
+const TRANSCRIBE_TIMEOUT: Duration = Duration::from_secs(600);
+const SCRIPT: &str = include_str!("../transcribe_wav.py");
+const SCRIPT_FILE: &str = "transcribe_wav.py";
+/// Spare WAV buffers kept for reuse; more are freed.
+const MAX_SPARE_BUFFERS: usize = 4;
+/// Buffers that grew larger, for a long file, are freed rather than kept.
+const MAX_SPARE_BUFFER_BYTES: usize = 16 * 1024 * 1024;
+
+struct AsrWorker {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<std::process::ChildStdout>,
+    /// Request id of the last frame sent; id 0 is reserved for pings.
+    next_id: u32,
+    /// Responses are read into this, kept from one request to the next.
+    response: Vec<u8>,
+    /// Started with the smaller model to save power; see [`crate::power`].
+    power_saving: bool,
+    /// What `onnx_asr.load_model` was given; `None` loads fp32.
+    quantization: Option<String>,
+    /// Decoding options the model last said it does not take, logged once per change.
+    ignored_options: Vec<String>,
+}
+
+impl AsrWorker {
+    /// The model this worker loaded, as a transcript's metadata names it.
+    fn model(&self) -> String {
+        match &self.quantization {
+            Some(quantization) => format!("{} ({})", crate::settings::DEFAULT_MODEL, quantization),
+            None => crate::settings::DEFAULT_MODEL.to_string(),
+        }
+    }
+
+    fn report_ignored(&mut self, ignored: Vec<String>) {
+        if ignored != self.ignored_options && !ignored.is_empty() {
+            let _ = crate::log::write(format!(
+                "The model does not take these decoding options, which are left out: {}",
+                ignored.join(", ")
+            ));
+        }
+        self.ignored_options = ignored;
+    }
+}
+
+/// Moves to a fresh copy in the worker data directory when the one found does not match.
+static SCRIPT_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+static SUPERVISOR: OnceLock<mpsc::UnboundedSender<(Priority, Job)>> = OnceLock::new();
+static STATUS_LISTENER: OnceLock<StatusListener> = OnceLock::new();
+/// WAV buffers of finished requests; see [`take_buffer`].
+static SPARE_BUFFERS: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+
+type StatusListener = Box<dyn Fn(&SupervisorStatus) + Send + Sync>;
+
+/// Registers the callback the supervisor reports state changes to. Only the first call wins.
+pub fn set_status_listener<F>(listener: F)
+where
+    F: Fn(&SupervisorStatus) + Send + Sync + 'static,
+{
+    let _ = STATUS_LISTENER.set(Box::new(listener));
+}
+
+pub fn init() -> Result<(), String> {
+    let path =
+        ensure_embedded_script(&crate::paths::working_dir().join("vtype_transcribe_wav.py"))?;
+    set_script_path(path)
+}
+
+fn script_path() -> Result<PathBuf, String> {
+    SCRIPT_PATH
+        .lock()
+        .map_err(|err| err.to_string())?
+        .clone()
+        .ok_or_else(|| "transcribe_wav.py not initialized".to_string())
+}
+
+fn set_script_path(path: PathBuf) -> Result<(), String> {
+    *SCRIPT_PATH.lock().map_err(|err| err.to_string())? = Some(path);
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Live dictation; always served before anything else that is waiting.
+    Interactive,
+    /// File, batch or meeting transcription that can yield to dictation.
+    Background,
+}
+
+/// Messages handled by the supervisor thread.
+enum Job {
+    WarmUp,
+    /// Stops the running worker so the next job starts one with current settings.
+    Restart,
+    /// Stops the worker without starting a new one, e.g. after switching backends.
+    Shutdown,
+    /// Replaces a worker that outgrew its memory limit once nothing is waiting for it.
+    Recycle,
+    /// Stops the worker if it has had nothing to do for this long.
+    StopIfIdle(Duration),
+    /// Reports the state again once nothing is waiting, after a change the supervisor
+    /// has no part in, such as the power source.
+    Report,
+    Transcribe(TranscribeJob),
+}
+
+struct TranscribeJob {
+    header: serde_json::Value,
+    wav_bytes: Vec<u8>,
+    reply: oneshot::Sender<Result<Transcription, String>>,
+}
+
+/// A transcript, the model that made it and what the worker had to fall back to for it.
+#[derive(Debug, Clone)]
+pub struct Transcription {
+    pub text: String,
+    /// From 0 to 1; `None` when the model does not say.
+    pub confidence: Option<f32>,
+    /// Such as `nemo-parakeet-tdt-0.6b-v3 (int8)`.
+    pub model: String,
+    pub fallback: Option<Fallback>,
+}
+
+/// How the worker runs after running out of memory or failing to load the model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Fallback {
+    /// The int8 model, about a quarter the size.
+    Smaller,
+    /// On the CPU instead of the GPU.
+    Cpu,
+    SmallerOnCpu,
+}
+
+impl Fallback {
+    pub fn name(self) -> &'static str {
+        match self {
+            Fallback::Smaller => "smaller",
+            Fallback::Cpu => "cpu",
+            Fallback::SmallerOnCpu => "smallerOnCpu",
+        }
+    }
+
+    fn smaller(self) -> bool {
+        matches!(self, Fallback::Smaller | Fallback::SmallerOnCpu)
+    }
+
+    fn cpu(self) -> bool {
+        matches!(self, Fallback::Cpu | Fallback::SmallerOnCpu)
+    }
+
+    /// The next step beyond `current` that `policy` allows for `kind`, in the order most
+    /// likely to help: memory is saved by the smaller model, a GPU runtime that will not load
+    /// is avoided on the CPU. A step keeps whatever `current` already gave up.
+    /// `already_smaller` when the settings load the int8 model anyway.
+    fn next(
+        current: Option<Fallback>,
+        kind: FailureKind,
+        policy: ModelFallback,
+        already_smaller: bool,
+    ) -> Option<Fallback> {
+        let order = match kind {
+            FailureKind::OutOfMemory => [Fallback::Smaller, Fallback::Cpu, Fallback::SmallerOnCpu],
+            FailureKind::ModelLoad => [Fallback::Cpu, Fallback::Smaller, Fallback::SmallerOnCpu],
+            FailureKind::Other => return None,
+        };
+        order.into_iter().find(|step| {
+            let allowed = match policy {
+                ModelFallback::Auto => true,
+                ModelFallback::Smaller => *step == Fallback::Smaller,
+                ModelFallback::Cpu => *step == Fallback::Cpu,
+                ModelFallback::Off => false,
+            };
+            let beyond = current.is_none_or(|current| {
+                *step != current
+                    && (step.smaller() || !current.smaller())
+                    && (step.cpu() || !current.cpu())
+            });
+            allowed && beyond && !(already_smaller && step.smaller())
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SupervisorStatus {
+    pub state: &'static str,
+    pub queue_length: usize,
+    pub power: crate::power::PowerState,
+}
+
+/// Owns the single ASR worker process on a dedicated thread.
+///
+/// Commands talk to it over a channel, so nothing blocks the async runtime while a model
+/// loads or runs. Pending work is drained into per-priority queues between jobs: interactive
+/// dictations run first, repeated warm-ups collapse into one, and background jobs fill gaps.
+/// A running inference is never interrupted, so preemption happens at job boundaries.
+///
+/// When the worker runs out of memory or cannot load the model, it is started again with
+/// the next [`Fallback`] the settings allow and kept that way until it is restarted.
+struct Supervisor {
+    worker: Option<AsrWorker>,
+    interactive: VecDeque<TranscribeJob>,
+    background: VecDeque<TranscribeJob>,
+    warm_up_pending: bool,
+    recycle_pending: bool,
+    fallback: Option<Fallback>,
+    /// When the worker last finished a job or warm-up.
+    last_active: Instant,
+}
+
+fn supervisor() -> &'static mpsc::UnboundedSender<(Priority, Job)> {
+    SUPERVISOR.get_or_init(|| {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            Supervisor {
+                worker: None,
+                interactive: VecDeque::new(),
+                background: VecDeque::new(),
+                warm_up_pending: false,
+                recycle_pending: false,
+                fallback: None,
+                last_active: Instant::now(),
+            }
+            .run(receiver)
+        });
+        sender
+    })
+}
+
+impl Supervisor {
+    fn run(mut self, mut receiver: mpsc::UnboundedReceiver<(Priority, Job)>) {
+        loop {
+            if self.is_empty() && self.recycle_pending {
+                self.recycle_pending = false;
+                if self.worker.is_some() {
+                    crate::monitor::count_memory_restart();
+                    self.stop_worker();
+                    self.warm_up_pending = true;
+                }
+            }
+            if self.is_empty() {
+                self.emit_status("idle");
+                match receiver.blocking_recv() {
+                    Some(message) => self.enqueue(message),
+                    None => return,
+                }
+            }
+            while let Ok(message) = receiver.try_recv() {
+                self.enqueue(message);
+            }
+
+            if let Some(job) = self.interactive.pop_front() {
+                self.run_job(job);
+            } else if self.warm_up_pending {
+                self.warm_up_pending = false;
+                // Asked for on every dictation, mostly with the worker already up.
+                if self.worker.is_none() {
+                    self.emit_status("starting");
+                }
+                if let Err(err) =
+                    self.with_fallback(|supervisor| supervisor.ensure_worker().map(|_| ()))
+                {
+                    let _ = crate::log::write(format!("ASR warm-up failed: {}", err));
+                }
+                self.last_active = Instant::now();
+            } else if let Some(job) = self.background.pop_front() {
+                self.run_job(job);
+            }
+        }
+    }
+
+    fn enqueue(&mut self, (priority, job): (Priority, Job)) {
+        match (job, priority) {
+            (Job::WarmUp, _) => self.warm_up_pending = true,
+            (Job::Restart, _) => {
+                self.stop_worker();
+                self.fallback = None;
+                self.warm_up_pending = true;
+            }
+            (Job::Shutdown, _) => {
+                self.stop_worker();
+                self.fallback = None;
+                self.warm_up_pending = false;
+            }
+            (Job::Recycle, _) => self.recycle_pending = true,
+            (Job::StopIfIdle(idle), _) => {
+                if self.is_empty() && self.worker.is_some() && self.last_active.elapsed() >= idle {
+                    let _ =
+                        crate::log::write("Stopped the idle ASR worker to save power".to_string());
+                    self.stop_worker();
+                }
+            }
+            (Job::Report, _) => {}
+            (Job::Transcribe(job), Priority::Interactive) => self.interactive.push_back(job),
+            (Job::Transcribe(job), Priority::Background) => self.background.push_back(job),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.warm_up_pending && self.interactive.is_empty() && self.background.is_empty()
+    }
+
+    fn queue_length(&self) -> usize {
+        self.interactive.len() + self.background.len()
+    }
+
+    fn emit_status(&self, state: &'static str) {
+        let status = SupervisorStatus {
+            state,
+            queue_length: self.queue_length(),
+            power: crate::power::current(),
+        };
+        crate::crash::record_status(&status);
+        if let Some(listener) = STATUS_LISTENER.get() {
+            listener(&status);
+        }
+    }
+
+    fn run_job(&mut self, job: TranscribeJob) {
+        // The caller gave up (timeout or dropped future) before we got to it.
+        if !job.reply.is_closed() {
+            self.emit_status("busy");
+            let result = self.transcribe(&job.header, &job.wav_bytes);
+            let _ = job.reply.send(result);
+            self.last_active = Instant::now();
+        }
+        give_back(job.wav_bytes);
+    }
+
+    fn stop_worker(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            crate::monitor::watch(None);
+            stop(worker);
+        }
+    }
+
+    fn ensure_worker(&mut self) -> Result<&mut AsrWorker, WorkerFailure> {
+        let alive = match self.worker.as_mut() {
+            Some(worker) => worker
+                .child
+                .try_wait()
+                .map_err(|err| err.to_string())?
+                .is_none(),
+            None => false,
+        };
+        let power_saving = crate::power::smaller_model();
+        let suited = self
+            .worker
+            .as_ref()
+            .is_some_and(|worker| worker.power_saving == power_saving);
+        if !alive || !suited {
+            if alive {
+                let _ = crate::log::write(
+                    "Restarting the ASR worker with the model for the power source".to_string(),
+                );
+            }
+            self.stop_worker();
+            let started = Instant::now();
+            let worker = start_worker(self.fallback).inspect_err(|failure| {
+                activity::record(
+                    ActivityKind::WorkerFailed,
+                    None,
+                    Some(failure.message.clone()),
+                )
+            })?;
+            activity::record(
+                ActivityKind::WorkerStarted,
+                Some(started.elapsed()),
+                self.fallback.map(|fallback| fallback.name().to_string()),
+            );
+            crate::monitor::watch(Some(worker.child.id()));
+            self.worker = Some(worker);
+        }
+        self.worker
+            .as_mut()
+            .ok_or_else(|| WorkerFailure::other("ASR worker not available"))
+    }
+
+    /// Runs `attempt`, falling back and running it again for as long as the worker runs
+    /// out of memory or fails to load the model and the settings allow another step.
+    fn with_fallback<T>(
+        &mut self,
+        mut attempt: impl FnMut(&mut Self) -> Result<T, WorkerFailure>,
+    ) -> Result<T, String> {
+        loop {
+            let failure = match attempt(self) {
+                Ok(value) => return Ok(value),
+                Err(failure) => failure,
+            };
+            let model = crate::settings::current().model;
+            let already_smaller = crate::power::smaller_model()
+                || crate::models::worker_quantization(&model.quantization)
+                    .is_some_and(|quantization| quantization == crate::models::QUANTIZATION_INT8);
+            let Some(next) =
+                Fallback::next(self.fallback, failure.kind, model.fallback, already_smaller)
+            else {
+                crate::metrics::count_error(failure.kind.into());
+                return Err(failure.message);
+            };
+            let _ = crate::log::write(format!(
+                "ASR worker failed ({}); retrying with fallback {}",
+                failure.message,
+                next.name()
+            ));
+            self.stop_worker();
+            self.fallback = Some(next);
+        }
+    }
+
+    fn transcribe(
+        &mut self,
+        header: &serde_json::Value,
+        wav_bytes: &[u8],
+    ) -> Result<Transcription, String> {
+        let (text, confidence, model) = self.with_fallback(|supervisor| {
+            let worker = supervisor.ensure_worker()?;
+            match send_wav(worker, header, wav_bytes) {
+                Ok(Reply::Text(text)) => Ok((text, None, worker.model())),
+                Ok(Reply::Structured(structured)) => {
+                    worker.report_ignored(structured.ignored);
+                    Ok((structured.text, structured.confidence, worker.model()))
+                }
+                Ok(Reply::WorkerError(failure)) => Err(failure),
+                Err(err) => {
+                    // The stream can no longer be trusted: replace the worker instead of
+                    // handing the next request a desynced pipe.
+                    let _ = crate::log::write(format!("ASR worker protocol error: {}", err));
+                    supervisor.stop_worker();
+                    supervisor.warm_up_pending = true;
+                    Err(WorkerFailure::other(err))
+                }
+            }
+        })?;
+        Ok(Transcription {
+            text,
+            confidence,
+            model,
+            fallback: self.fallback,
+        })
+    }
+}
+
+pub fn warm_up() -> Result<(), String> {
+    supervisor()
+        .send((Priority::Background, Job::WarmUp))
+        .map_err(|_| "ASR supervisor stopped".to_string())
+}
+
+pub fn restart() -> Result<(), String> {
+    supervisor()
+        .send((Priority::Background, Job::Restart))
+        .map_err(|_| "ASR supervisor stopped".to_string())
+}
+
+pub fn recycle() -> Result<(), String> {
+    supervisor()
+        .send((Priority::Background, Job::Recycle))
+        .map_err(|_| "ASR supervisor stopped".to_string())
+}
+
+pub(crate) fn stop_if_idle(idle: Duration) -> Result<(), String> {
+    supervisor()
+        .send((Priority::Background, Job::StopIfIdle(idle)))
+        .map_err(|_| "ASR supervisor stopped".to_string())
+}
+
+pub(crate) fn report_status() -> Result<(), String> {
+    supervisor()
+        .send((Priority::Background, Job::Report))
+        .map_err(|_| "ASR supervisor stopped".to_string())
+}
+
+pub fn shutdown() -> Result<(), String> {
+    supervisor()
+        .send((Priority::Background, Job::Shutdown))
+        .map_err(|_| "ASR supervisor stopped".to_string())
+}
+
+/// An empty buffer to put a WAV for [`transcribe`] in. It is one an earlier request handed
+/// back when there is one, so steady dictation does not allocate a buffer per utterance.
+pub fn take_buffer() -> Vec<u8> {
+    SPARE_BUFFERS
+        .lock()
+        .ok()
+        .and_then(|mut spare| spare.pop())
+        .unwrap_or_default()
+}
+
+fn give_back(mut buffer: Vec<u8>) {
+    if buffer.capacity() == 0 || buffer.capacity() > MAX_SPARE_BUFFER_BYTES {
+        return;
+    }
+    buffer.clear();
+    if let Ok(mut spare) = SPARE_BUFFERS.lock() {
+        if spare.len() < MAX_SPARE_BUFFERS {
+            spare.push(buffer);
+        }
+    }
+}
+
+pub async fn transcribe(
+    priority: Priority,
+    header: serde_json::Value,
+    wav_bytes: Vec<u8>,
+) -> Result<Transcription, String> {
+    let (reply, response) = oneshot::channel();
+    let job = TranscribeJob {
+        header,
+        wav_bytes,
+        reply,
+    };
+    supervisor()
+        .send((priority, Job::Transcribe(job)))
+        .map_err(|_| "ASR supervisor stopped".to_string())?;
+    match tokio::time::timeout(TRANSCRIBE_TIMEOUT, response).await {
+        Ok(Ok(result)) => result,
+        Ok(Err(_)) => {
+            crate::metrics::count_error(ErrorCategory::Worker);
+            Err("ASR supervisor dropped the request".to_string())
+        }
+        Err(_) => {
+            crate::metrics::count_error(ErrorCategory::Timeout);
+            Err(i18n::error(
+                Message::TranscriptionTimeout,
+                &[&TRANSCRIBE_TIMEOUT.as_secs().to_string()],
+            ))
+        }
+    }
+}
+
+/// Starts the worker, and when the script found speaks another protocol version or lacks a
+/// capability, starts it again from a fresh copy of the bundled script in the worker's data
+/// directory; the shared temp copy may have been overwritten by another VType build.
+fn start_worker(fallback: Option<Fallback>) -> Result<AsrWorker, WorkerFailure> {
+    let script = script_path()?;
+    let (worker, handshake) = spawn_worker(&script, fallback)?;
+    let Some(problem) = handshake.incompatibility() else {
+        return Ok(worker);
+    };
+    stop(worker);
+    let repaired = ensure_embedded_script(&crate::paths::worker_dir().join(SCRIPT_FILE))?;
+    let _ = crate::log::write(format!(
+        "ASR worker script {} {}; using the bundled copy at {}",
+        script.display(),
+        problem,
+        repaired.display()
+    ));
+    let (worker, handshake) = spawn_worker(&repaired, fallback)?;
+    if let Some(problem) = handshake.incompatibility() {
+        stop(worker);
+        return Err(WorkerFailure::other(format!(
+            "ASR worker script {}",
+            problem
+        )));
+    }
+    set_script_path(repaired)?;
+    Ok(worker)
+}
+
+fn spawn_worker(
+    script: &Path,
+    fallback: Option<Fallback>,
+) -> Result<(AsrWorker, Handshake), WorkerFailure> {
+    let python = resolve_python().ok_or_else(|| i18n::error(Message::PythonNotFound, &[]))?;
+
+    let mut worker_cmd = Command::new(python);
+    worker_cmd
+        .arg(script)
+        .arg("--worker")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let power_saving = crate::power::smaller_model();
+    let quantization = if power_saving || fallback.is_some_and(Fallback::smaller) {
+        Some(crate::models::QUANTIZATION_INT8.to_string())
+    } else {
+        crate::models::worker_quantization(&crate::settings::current().model.quantization)
+    };
+    worker_cmd.env(
+        "VTYPE_MODEL_QUANTIZATION",
+        quantization.clone().unwrap_or_default(),
+    );
+    if fallback.is_some_and(Fallback::cpu) {
+        worker_cmd.env("VTYPE_EXECUTION_PROVIDER", "cpu");
+    }
+    if let Some(dir) = crate::paths::worker_data_dir() {
+        worker_cmd.env("VTYPE_DATA_DIR", dir);
+    }
+    crate::process::configure_background_command(&mut worker_cmd);
+    let mut child = worker_cmd.spawn().map_err(|err| err.to_string())?;
+
+    let stdin = child.stdin.take().ok_or("Failed to open ASR stdin")?;
+    let stdout = child.stdout.take().ok_or("Failed to open ASR stdout")?;
+    let mut stderr = child.stderr.take();
+    let mut reader = BufReader::new(stdout);
+    let mut ready = String::new();
+    reader
+        .read_line(&mut ready)
+        .map_err(|err| err.to_string())?;
+    let handshake = match protocol::parse_ready(&ready) {
+        Ok(handshake) => handshake,
+        Err(mut failure) => {
+            let mut err_buf = String::new();
+            if let Some(ref mut stderr) = stderr {
+                let _ = stderr.read_to_string(&mut err_buf);
+            }
+            if failure.kind == FailureKind::Other && !err_buf.trim().is_empty() {
+                failure.message = format!("{} {}", failure.message, err_buf.trim());
+            }
+            let _ = child.wait();
+            return Err(failure);
+        }
+    };
+    // Keeps the pipe from filling up and holds the latest lines for a crash report.
+    if let Some(stderr) = stderr {
+        std::thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                crate::crash::record_worker_stderr(line);
+            }
+        });
+    }
+
+    let worker = AsrWorker {
+        child,
+        stdin,
+        stdout: reader,
+        next_id: 0,
+        response: Vec::new(),
+        power_saving,
+        quantization,
+        ignored_options: Vec::new(),
+    };
+    Ok((worker, handshake))
+}
+
+fn stop(mut worker: AsrWorker) {
+    let _ = worker.child.kill();
+    let _ = worker.child.wait();
+}
+
+fn send_wav(
+    worker: &mut AsrWorker,
+    options: &serde_json::Value,
+    wav_bytes: &[u8],
+) -> Result<Reply, String> {
+    worker.next_id = worker.next_id.wrapping_add(1).max(1);
+    protocol::write_request(&mut worker.stdin, worker.next_id, options, wav_bytes)?;
+    protocol::read_response_into(&mut worker.stdout, worker.next_id, &mut worker.response)
+}
+
+/// Writes the bundled script to `path` unless it is already there unchanged.
+fn ensure_embedded_script(path: &Path) -> Result<PathBuf, String> {
+    match fs::read_to_string(path) {
+        Ok(found) if found == SCRIPT => return Ok(path.to_path_buf()),
+        Ok(_) => {
+            let _ = crate::log::write(format!(
+                "Replacing {}, which does not match this build",
+                path.display()
+            ));
+        }
+        Err(_) => {}
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    crate::safe_io::write(path, SCRIPT).map_err(|err| err.to_string())?;
+    Ok(path.to_path_buf())
+}
+
+/// The worker's interpreter: VType's own environment once it is set up, else the system's.
+fn resolve_python() -> Option<OsString> {
+    crate::venv::python()
+        .map(OsString::from)
+        .or_else(|| system_python().map(OsString::from))
+}
+
+pub(crate) fn system_python() -> Option<&'static str> {
+    let candidates = ["python3", "python"];
+    for candidate in candidates {
+        let mut version_cmd = Command::new(candidate);
+        version_cmd
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        crate::process::configure_background_command(&mut version_cmd);
+        if version_cmd.status().is_ok() {
+            return Some(candidate);
+        }
+    }
+    None
+}
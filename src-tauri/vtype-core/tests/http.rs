@@ -0,0 +1,53 @@
+use std::io::{Cursor, Read};
+
+use vtype_core::http;
+
+#[test]
+fn parses_the_request_line_headers_and_body() {
+    let mut stream = Cursor::new(
+        b"POST /pair?code=123%20456&name=Ann+Phone HTTP/1.1\r\nHost: vtype\r\nContent-Length: 5\r\nX-Thing:  spaced \r\n\r\nhello"
+            .to_vec(),
+    );
+    let request = http::read_request(&mut stream).unwrap();
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.path, "/pair");
+    assert_eq!(request.query["code"], "123 456");
+    assert_eq!(request.query["name"], "Ann Phone");
+    assert_eq!(request.header("host"), Some("vtype"));
+    assert_eq!(request.header("x-thing"), Some("spaced"));
+    assert_eq!(request.body, b"hello");
+}
+
+#[test]
+fn leaves_what_follows_the_request_unread() {
+    let mut stream = Cursor::new(
+        b"GET /stream HTTP/1.1\r\nUpgrade: websocket\r\n\r\n\x81\x82first frame".to_vec(),
+    );
+    http::read_request(&mut stream).unwrap();
+    let mut rest = Vec::new();
+    stream.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"\x81\x82first frame");
+}
+
+#[test]
+fn refuses_broken_and_oversize_requests() {
+    for raw in [
+        b"".to_vec(),
+        b"GET\r\n\r\n".to_vec(),
+        b"GET / HTTP/1.1\r\nHost: vtype\r\n".to_vec(),
+        b"GET / HTTP/1.1\r\nContent-Length: many\r\n\r\n".to_vec(),
+        b"POST / HTTP/1.1\r\nContent-Length: 99999999\r\n\r\n".to_vec(),
+        [
+            &b"GET / HTTP/1.1\r\nX-Long: "[..],
+            &[b'a'; 9000],
+            b"\r\n\r\n",
+        ]
+        .concat(),
+    ] {
+        assert!(
+            http::read_request(&mut Cursor::new(raw.clone())).is_err(),
+            "{}",
+            String::from_utf8_lossy(&raw[..raw.len().min(40)])
+        );
+    }
+}
@@ -0,0 +1,28 @@
+use vtype_core::macros;
+
+#[test]
+fn macros_that_run_programs_are_kept_local() {
+    for step in [
+        r#"{ type = "launch", app = "/sync/payload" }"#,
+        r#"{ type = "shell", command = "rm -rf ~" }"#,
+        r#"{ type = "http", url = "https://example.com" }"#,
+    ] {
+        let toml = format!("[[macro]]\nphrases = [\"go\"]\nsteps = [{}]\n", step);
+        assert!(macros::has_program_steps(&toml), "{}", step);
+    }
+    assert!(macros::has_program_steps("not toml ["));
+}
+
+#[test]
+fn keys_and_snippets_are_shareable() {
+    let toml = r#"
+[[macro]]
+phrases = ["sign off"]
+steps = [
+    { type = "keys", keys = "CmdOrCtrl+End" },
+    { type = "snippet", text = "Thanks" },
+    { type = "wait", ms = 100 },
+]
+"#;
+    assert!(!macros::has_program_steps(toml));
+}
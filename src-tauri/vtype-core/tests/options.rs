@@ -0,0 +1,30 @@
+use vtype_core::options::TranscribeOptions;
+use vtype_core::settings::DecodingSettings;
+
+#[test]
+fn request_best_of_is_range_checked() {
+    for best_of in [0, 10_000] {
+        let options = TranscribeOptions {
+            best_of: Some(best_of),
+            ..TranscribeOptions::default()
+        };
+        assert!(options.validate().is_err(), "{}", best_of);
+    }
+    let options = TranscribeOptions {
+        best_of: Some(5),
+        ..TranscribeOptions::default()
+    };
+    assert!(options.validate().is_ok());
+}
+
+#[test]
+fn unset_decoding_options_take_the_models_defaults() {
+    let decoding = DecodingSettings {
+        temperature: Some(0.4),
+        ..DecodingSettings::default()
+    };
+    let options = TranscribeOptions::default().with_decoding_defaults(&decoding);
+    assert_eq!(options.temperature, Some(0.4));
+    assert_eq!(options.beam_size, Some(1));
+    assert_eq!(options.best_of, None);
+}
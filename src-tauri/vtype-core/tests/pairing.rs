@@ -0,0 +1,46 @@
+use std::time::{Duration, Instant};
+
+use vtype_core::pairing::{self, Pairing, PAIRING_ATTEMPTS, PAIRING_TIMEOUT};
+
+#[test]
+fn the_right_code_pairs_once() {
+    let now = Instant::now();
+    let mut current = Some(Pairing::new("123456".to_string(), now));
+    assert!(pairing::take(&mut current, "123456", now));
+    assert!(current.is_none());
+    assert!(!pairing::take(&mut current, "123456", now));
+}
+
+#[test]
+fn wrong_codes_use_up_the_attempts() {
+    let now = Instant::now();
+    let mut current = Some(Pairing::new("123456".to_string(), now));
+    for _ in 1..PAIRING_ATTEMPTS {
+        assert!(!pairing::take(&mut current, "000000", now));
+        assert!(current.is_some());
+    }
+    assert!(!pairing::take(&mut current, "12345", now));
+    assert!(current.is_none());
+    assert!(!pairing::take(&mut current, "123456", now));
+}
+
+#[test]
+fn codes_expire() {
+    let now = Instant::now();
+    let mut current = Some(Pairing::new("123456".to_string(), now));
+    assert!(pairing::take(
+        &mut current.clone(),
+        "123456",
+        now + PAIRING_TIMEOUT
+    ));
+    let later = now + PAIRING_TIMEOUT + Duration::from_secs(1);
+    assert!(!pairing::take(&mut current, "123456", later));
+    assert!(current.is_none());
+}
+
+#[test]
+fn an_empty_code_never_pairs() {
+    let now = Instant::now();
+    let mut current = Some(Pairing::new(String::new(), now));
+    assert!(!pairing::take(&mut current, "", now));
+}
@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::PathBuf;
+
+use vtype_core::plugins;
+
+/// Returns the metadata it was given when the text is `meta`, else the text with its first
+/// byte upper-cased. Allocates by bumping from 1024.
+const PLUGIN: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (global $next (mut i32) (i32.const 1024))
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local.set $ptr (global.get $next))
+    (global.set $next (i32.add (global.get $next) (local.get $len)))
+    (local.get $ptr))
+  (func (export "transform") (param $text i32) (param $text_len i32)
+                             (param $meta i32) (param $meta_len i32) (result i64)
+    (if (i32.eq (i32.load (local.get $text)) (i32.const 0x6174656d))
+      (then (return (i64.or
+        (i64.shl (i64.extend_i32_u (local.get $meta)) (i64.const 32))
+        (i64.extend_i32_u (local.get $meta_len))))))
+    (i32.store8 (local.get $text)
+      (i32.sub (i32.load8_u (local.get $text)) (i32.const 32)))
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $text)) (i64.const 32))
+      (i64.extend_i32_u (local.get $text_len)))))
+"#;
+
+const SPINS: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "alloc") (param i32) (result i32) (i32.const 0))
+  (func (export "transform") (param i32 i32 i32 i32) (result i64)
+    (loop $forever (br $forever))
+    (i64.const 0)))
+"#;
+
+const IMPORTS: &str = r#"
+(module
+  (import "env" "open" (func (param i32)))
+  (memory (export "memory") 1))
+"#;
+
+fn plugin_file(name: &str, wat: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("vtype-plugin-{}-{}.wasm", std::process::id(), name));
+    fs::write(&path, wat::parse_str(wat).unwrap()).unwrap();
+    path
+}
+
+#[test]
+fn transforms_text_and_reads_metadata() {
+    let path = plugin_file("upper", PLUGIN);
+    let upper = plugins::transform(&path, "hello there", "{}");
+    let meta = plugins::transform(&path, "meta", r#"{"language":"en"}"#);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(upper.unwrap(), "Hello there");
+    assert_eq!(meta.unwrap(), r#"{"language":"en"}"#);
+}
+
+#[test]
+fn runaway_plugins_are_stopped() {
+    let path = plugin_file("spins", SPINS);
+    let result = plugins::transform(&path, "hello", "{}");
+    fs::remove_file(&path).unwrap();
+    assert!(result.is_err());
+}
+
+#[test]
+fn plugins_get_no_imports() {
+    let path = plugin_file("imports", IMPORTS);
+    let result = plugins::transform(&path, "hello", "{}");
+    fs::remove_file(&path).unwrap();
+    assert!(result.is_err());
+}
@@ -0,0 +1,233 @@
+use std::io::Cursor;
+
+use vtype_core::protocol::{
+    parse_ready, read_response, write_request, FailureKind, Reply, StructuredText, WorkerFailure,
+    MAX_RESPONSE_BYTES, MAX_RESYNC_BYTES, REQUIRED_CAPABILITIES, RESPONSE_MAGIC, WORKER_VERSION,
+};
+
+/// Scripts the stdout of a worker that may print junk, answer late, or corrupt frames.
+#[derive(Default)]
+struct FakeWorker {
+    stdout: Vec<u8>,
+}
+
+impl FakeWorker {
+    fn reply(mut self, id: u32, payload: &[u8]) -> Self {
+        self.stdout.extend_from_slice(&RESPONSE_MAGIC);
+        self.stdout.extend_from_slice(&id.to_le_bytes());
+        self.stdout
+            .extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        self.stdout.extend_from_slice(payload);
+        self
+    }
+
+    fn raw(mut self, bytes: &[u8]) -> Self {
+        self.stdout.extend_from_slice(bytes);
+        self
+    }
+
+    fn corrupt_length(mut self, id: u32, len: u32) -> Self {
+        self.stdout.extend_from_slice(&RESPONSE_MAGIC);
+        self.stdout.extend_from_slice(&id.to_le_bytes());
+        self.stdout.extend_from_slice(&len.to_le_bytes());
+        self
+    }
+
+    fn stream(self) -> Cursor<Vec<u8>> {
+        Cursor::new(self.stdout)
+    }
+}
+
+#[test]
+fn request_carries_id_and_both_frames() {
+    let mut out = Vec::new();
+    let options = serde_json::json!({ "language": "en" });
+    write_request(&mut out, 7, &options, b"RIFFdata").unwrap();
+
+    let header_len = u32::from_le_bytes(out[0..4].try_into().unwrap()) as usize;
+    let header: serde_json::Value = serde_json::from_slice(&out[4..4 + header_len]).unwrap();
+    assert_eq!(header["id"], 7);
+    assert_eq!(header["language"], "en");
+    let wav_start = 4 + header_len;
+    let wav_len = u32::from_le_bytes(out[wav_start..wav_start + 4].try_into().unwrap());
+    assert_eq!(wav_len, 8);
+    assert_eq!(&out[wav_start + 4..], b"RIFFdata");
+}
+
+#[test]
+fn reads_matching_reply() {
+    let mut stream = FakeWorker::default().reply(1, b"hello world").stream();
+    assert_eq!(
+        read_response(&mut stream, 1).unwrap(),
+        Reply::Text("hello world".to_string())
+    );
+}
+
+#[test]
+fn worker_errors_are_not_protocol_errors() {
+    let mut stream = FakeWorker::default()
+        .reply(3, b"ERROR: Input WAV must be 16000Hz")
+        .reply(4, b"next")
+        .stream();
+    assert!(matches!(
+        read_response(&mut stream, 3).unwrap(),
+        Reply::WorkerError(_)
+    ));
+    assert_eq!(
+        read_response(&mut stream, 4).unwrap(),
+        Reply::Text("next".to_string())
+    );
+}
+
+#[test]
+fn skips_stray_output_before_frame() {
+    let mut stream = FakeWorker::default()
+        .raw(b"onnxruntime warning: something printed to stdout\n")
+        .reply(2, b"text")
+        .stream();
+    assert_eq!(
+        read_response(&mut stream, 2).unwrap(),
+        Reply::Text("text".to_string())
+    );
+}
+
+#[test]
+fn skips_stale_replies_from_earlier_requests() {
+    let mut stream = FakeWorker::default()
+        .reply(4, b"late answer")
+        .reply(5, b"current answer")
+        .stream();
+    assert_eq!(
+        read_response(&mut stream, 5).unwrap(),
+        Reply::Text("current answer".to_string())
+    );
+}
+
+#[test]
+fn resyncs_after_oversized_length_prefix() {
+    let mut stream = FakeWorker::default()
+        .corrupt_length(6, (MAX_RESPONSE_BYTES + 1) as u32)
+        .raw(b"garbage")
+        .reply(6, b"recovered")
+        .stream();
+    assert_eq!(
+        read_response(&mut stream, 6).unwrap(),
+        Reply::Text("recovered".to_string())
+    );
+}
+
+#[test]
+fn truncated_reply_is_a_protocol_error() {
+    let mut stream = FakeWorker::default()
+        .corrupt_length(1, 100)
+        .raw(b"short")
+        .stream();
+    assert!(read_response(&mut stream, 1).is_err());
+}
+
+#[test]
+fn closed_stream_is_a_protocol_error() {
+    let mut stream = FakeWorker::default().stream();
+    assert!(read_response(&mut stream, 1).is_err());
+}
+
+#[test]
+fn gives_up_on_endless_garbage() {
+    let garbage = vec![b'x'; MAX_RESYNC_BYTES + 16];
+    let mut stream = FakeWorker::default()
+        .raw(&garbage)
+        .reply(1, b"too late")
+        .stream();
+    let err = read_response(&mut stream, 1).unwrap_err();
+    assert!(err.contains("desynced"), "{}", err);
+}
+
+#[test]
+fn invalid_utf8_is_replaced_not_fatal() {
+    let mut stream = FakeWorker::default().reply(1, &[b'o', b'k', 0xff]).stream();
+    assert_eq!(
+        read_response(&mut stream, 1).unwrap(),
+        Reply::Text("ok\u{fffd}".to_string())
+    );
+}
+
+#[test]
+fn structured_worker_errors_keep_their_kind() {
+    let mut stream = FakeWorker::default()
+        .reply(
+            2,
+            br#"ERROR: {"kind": "out_of_memory", "message": "Failed to allocate memory"}"#,
+        )
+        .stream();
+    assert_eq!(
+        read_response(&mut stream, 2).unwrap(),
+        Reply::WorkerError(WorkerFailure {
+            kind: FailureKind::OutOfMemory,
+            message: "Failed to allocate memory".to_string(),
+        })
+    );
+}
+
+#[test]
+fn structured_results_carry_confidence_and_ignored_options() {
+    let mut stream = FakeWorker::default()
+        .reply(
+            3,
+            br#"RESULT:{"text": "hello there", "confidence": 0.5, "ignored": ["beam_size"]}"#,
+        )
+        .reply(4, b"RESULT:not json")
+        .stream();
+    assert_eq!(
+        read_response(&mut stream, 3).unwrap(),
+        Reply::Structured(StructuredText {
+            text: "hello there".to_string(),
+            confidence: Some(0.5),
+            ignored: vec!["beam_size".to_string()],
+        })
+    );
+    assert!(matches!(
+        read_response(&mut stream, 4).unwrap(),
+        Reply::WorkerError(_)
+    ));
+}
+
+#[test]
+fn failed_model_load_is_reported_instead_of_ready() {
+    let failure = parse_ready(r#"failed {"kind": "model_load", "message": "CUDA driver too old"}"#)
+        .unwrap_err();
+    assert_eq!(failure.kind, FailureKind::ModelLoad);
+    assert_eq!(failure.message, "CUDA driver too old");
+}
+
+#[test]
+fn handshake_matching_this_build_is_compatible() {
+    let line = format!(
+        "ready {}\n",
+        serde_json::json!({ "version": WORKER_VERSION, "capabilities": REQUIRED_CAPABILITIES })
+    );
+    let handshake = parse_ready(&line).unwrap();
+    assert_eq!(handshake.version, WORKER_VERSION);
+    assert_eq!(handshake.incompatibility(), None);
+}
+
+#[test]
+fn bare_ready_is_an_outdated_script() {
+    let handshake = parse_ready("ready\n").unwrap();
+    assert_eq!(handshake.version, 0);
+    assert!(handshake.incompatibility().is_some());
+}
+
+#[test]
+fn missing_capabilities_are_named() {
+    let line = format!(
+        "ready {}",
+        serde_json::json!({ "version": WORKER_VERSION, "capabilities": ["ping"] })
+    );
+    let problem = parse_ready(&line).unwrap().incompatibility().unwrap();
+    assert!(problem.contains("decoding-options"), "{}", problem);
+}
+
+#[test]
+fn other_first_lines_are_not_ready() {
+    assert!(parse_ready("Traceback (most recent call last):").is_err());
+}
@@ -0,0 +1,48 @@
+use std::fs;
+use std::path::PathBuf;
+
+use vtype_core::settings::{PairedDevice, Settings};
+use vtype_core::transfer;
+
+fn with_paired_phone() -> Settings {
+    let mut settings = Settings::default();
+    settings.companion.devices.push(PairedDevice {
+        name: "Phone".to_string(),
+        token: "secret-bearer-token".to_string(),
+        paired_at: 0,
+    });
+    settings.hooks.post_transcribe = Some("notify-send done".to_string());
+    settings
+}
+
+fn scratch_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "vtype-transfer-{}-{}.json",
+        std::process::id(),
+        name
+    ))
+}
+
+#[test]
+fn export_leaves_out_paired_devices() {
+    let raw = serde_json::to_string(&transfer::document(&with_paired_phone()).unwrap()).unwrap();
+    assert!(!raw.contains("token"));
+    assert!(!raw.contains("secret-bearer-token"));
+    assert!(!raw.contains("notify-send"));
+}
+
+#[test]
+fn import_keeps_this_machines_devices() {
+    let path = scratch_file("import");
+    let document = serde_json::json!({
+        "format": transfer::EXPORT_FORMAT,
+        "version": transfer::EXPORT_VERSION,
+        "settings": serde_json::to_value(with_paired_phone()).unwrap(),
+    });
+    fs::write(&path, document.to_string()).unwrap();
+    let imported = transfer::read(&path, &Settings::default());
+    fs::remove_file(&path).unwrap();
+    let imported = imported.unwrap();
+    assert!(imported.companion.devices.is_empty());
+    assert_eq!(imported.hooks, Settings::default().hooks);
+}
@@ -0,0 +1,196 @@
+use std::io::{Cursor, Read, Write};
+
+use vtype_core::http::Request;
+use vtype_core::websocket::{self, Message, MAX_MESSAGE_BYTES};
+
+/// Frames from a client on one side, what the server sends on the other.
+struct Connection {
+    incoming: Cursor<Vec<u8>>,
+    sent: Vec<u8>,
+}
+
+impl Connection {
+    fn new(frames: &[Vec<u8>]) -> Connection {
+        Connection {
+            incoming: Cursor::new(frames.concat()),
+            sent: Vec::new(),
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.incoming.read(buf)
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.sent.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A frame as a client sends it, masked.
+fn frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mask = [0x12, 0x34, 0x56, 0x78];
+    let mut frame = vec![if fin { 0x80 } else { 0 } | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(0x80 | len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(0x80 | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(0x80 | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(&mask);
+    frame.extend(
+        payload
+            .iter()
+            .enumerate()
+            .map(|(index, byte)| byte ^ mask[index % 4]),
+    );
+    frame
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[test]
+fn sha1_matches_known_digests() {
+    assert_eq!(
+        hex(&websocket::sha1(b"")),
+        "da39a3ee5e6b4b0d3255bfef95601890afd80709"
+    );
+    assert_eq!(
+        hex(&websocket::sha1(b"abc")),
+        "a9993e364706816aba3e25717850c26c9cd0d89d"
+    );
+    // Two blocks once padded.
+    assert_eq!(
+        hex(&websocket::sha1(
+            b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+        )),
+        "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+    );
+}
+
+#[test]
+fn accept_key_matches_the_rfc_sample() {
+    assert_eq!(
+        websocket::accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+}
+
+#[test]
+fn upgrade_needs_a_websocket_get() {
+    let mut request = Request {
+        method: "GET".to_string(),
+        ..Request::default()
+    };
+    request.headers.insert(
+        "sec-websocket-key".to_string(),
+        "dGhlIHNhbXBsZSBub25jZQ==".to_string(),
+    );
+    assert!(!websocket::is_upgrade(&request));
+    request
+        .headers
+        .insert("upgrade".to_string(), "WebSocket".to_string());
+    assert!(websocket::is_upgrade(&request));
+    let mut sent = Vec::new();
+    websocket::upgrade(&mut sent, &request).unwrap();
+    let sent = String::from_utf8(sent).unwrap();
+    assert!(sent.starts_with("HTTP/1.1 101 "));
+    assert!(sent.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+}
+
+#[test]
+fn reads_masked_messages() {
+    let long = vec![7u8; 70_000];
+    let mut connection = Connection::new(&[
+        frame(true, 0x1, b"hello"),
+        frame(true, 0x2, &[1, 2, 3]),
+        frame(true, 0x2, &long),
+    ]);
+    assert_eq!(
+        websocket::read_message(&mut connection).unwrap(),
+        Message::Text("hello".to_string())
+    );
+    assert_eq!(
+        websocket::read_message(&mut connection).unwrap(),
+        Message::Binary(vec![1, 2, 3])
+    );
+    assert_eq!(
+        websocket::read_message(&mut connection).unwrap(),
+        Message::Binary(long)
+    );
+}
+
+#[test]
+fn joins_fragments_and_answers_pings_between_them() {
+    let mut connection = Connection::new(&[
+        frame(false, 0x1, b"hel"),
+        frame(true, 0x9, b"ping"),
+        frame(false, 0x0, b"lo "),
+        frame(true, 0x0, b"there"),
+    ]);
+    assert_eq!(
+        websocket::read_message(&mut connection).unwrap(),
+        Message::Text("hello there".to_string())
+    );
+    assert_eq!(connection.sent, [&[0x8A, 4][..], b"ping"].concat());
+}
+
+#[test]
+fn answers_a_close() {
+    let mut connection = Connection::new(&[frame(true, 0x8, &1000u16.to_be_bytes())]);
+    assert_eq!(
+        websocket::read_message(&mut connection).unwrap(),
+        Message::Close
+    );
+    assert_eq!(connection.sent, [0x88, 2, 0x03, 0xE8]);
+}
+
+#[test]
+fn refuses_unmasked_and_out_of_order_frames() {
+    let mut unmasked = Connection::new(&[vec![0x81, 2, b'h', b'i']]);
+    assert!(websocket::read_message(&mut unmasked).is_err());
+    let mut stray = Connection::new(&[frame(true, 0x0, b"hi")]);
+    assert!(websocket::read_message(&mut stray).is_err());
+    let mut interleaved = Connection::new(&[frame(false, 0x1, b"a"), frame(true, 0x1, b"b")]);
+    assert!(websocket::read_message(&mut interleaved).is_err());
+}
+
+#[test]
+fn refuses_oversize_messages() {
+    // Only the header: the length alone must be enough to refuse it.
+    let mut header = vec![0x82, 0x80 | 127];
+    header.extend_from_slice(&(MAX_MESSAGE_BYTES as u64 + 1).to_be_bytes());
+    let mut oversize = Connection::new(&[header]);
+    assert_eq!(
+        websocket::read_message(&mut oversize).unwrap_err(),
+        "Message too large"
+    );
+
+    let half = vec![0u8; MAX_MESSAGE_BYTES / 2 + 1];
+    let mut fragmented = Connection::new(&[frame(false, 0x2, &half), frame(true, 0x0, &half)]);
+    assert_eq!(
+        websocket::read_message(&mut fragmented).unwrap_err(),
+        "Message too large"
+    );
+}
+
+#[test]
+fn sends_unmasked_text() {
+    let mut sent = Vec::new();
+    websocket::send_text(&mut sent, "hi").unwrap();
+    assert_eq!(sent, [0x81, 2, b'h', b'i']);
+}
@@ -0,0 +1,254 @@
+import inspect
+import json
+import struct
+import sys
+import wave
+from io import BytesIO
+from pathlib import Path
+import os
+import tempfile
+import shutil
+
+import numpy as np
+
+SAMPLE_RATE = 16000
+MODEL_NAME = "nemo-parakeet-tdt-0.6b-v3"
+# Announced on the ready line; keep in step with protocol::WORKER_VERSION and
+# protocol::REQUIRED_CAPABILITIES in the app.
+WORKER_VERSION = 2
+CAPABILITIES = (
+    "ping",
+    "decoding-options",
+    "structured-errors",
+    "execution-provider",
+    "confidence",
+)
+# What onnxruntime and CUDA say when an allocation fails.
+OUT_OF_MEMORY_MARKERS = (
+    "out of memory",
+    "failed to allocate",
+    "bfcarena",
+    "cudnn_status_alloc_failed",
+    "cublas_status_alloc_failed",
+)
+
+
+def app_base_path() -> Path:
+    # Set by the app in portable mode so models stay beside the executable.
+    data_dir = os.getenv("VTYPE_DATA_DIR")
+    if data_dir:
+        return Path(data_dir)
+    local_appdata = os.getenv("LOCALAPPDATA")
+    if local_appdata:
+        return Path(local_appdata) / "vtype"
+    return Path(tempfile.gettempdir()) / "vtype"
+
+
+def configure_hf_cache() -> None:
+    base = app_base_path()
+    hf_home = base / "hf"
+    hf_hub_cache = hf_home / "hub"
+    xdg_cache = base / "xdg-cache"
+
+    hf_home.mkdir(parents=True, exist_ok=True)
+    hf_hub_cache.mkdir(parents=True, exist_ok=True)
+    xdg_cache.mkdir(parents=True, exist_ok=True)
+
+    # Keep caches in a trusted local app directory on Windows.
+    os.environ.setdefault("HF_HOME", str(hf_home))
+    os.environ.setdefault("HUGGINGFACE_HUB_CACHE", str(hf_hub_cache))
+    os.environ.setdefault("XDG_CACHE_HOME", str(xdg_cache))
+
+
+configure_hf_cache()
+import onnx_asr
+
+
+def model_path() -> Path:
+    override = os.getenv("VTYPE_MODEL_PATH")
+    if override:
+        return Path(override)
+
+    base = app_base_path() / "models"
+    return base / MODEL_NAME
+
+
+def model_quantization():
+    # Set by the app from the memory-based model selection; unset loads full precision.
+    return os.getenv("VTYPE_MODEL_QUANTIZATION") or None
+
+
+def model_providers():
+    # Set by the app to fall back to the CPU; unset lets onnxruntime pick, GPU first.
+    if os.getenv("VTYPE_EXECUTION_PROVIDER") == "cpu":
+        return ["CPUExecutionProvider"]
+    return None
+
+
+def is_out_of_memory(exc: BaseException) -> bool:
+    if isinstance(exc, MemoryError):
+        return True
+    message = str(exc).lower()
+    return any(marker in message for marker in OUT_OF_MEMORY_MARKERS)
+
+
+def failure(kind: str, exc: BaseException) -> str:
+    # Parsed by protocol::WorkerFailure in the app.
+    if is_out_of_memory(exc):
+        kind = "out_of_memory"
+    return json.dumps({"kind": kind, "message": str(exc) or type(exc).__name__})
+
+
+def load_asr_model():
+    path = model_path()
+    path.parent.mkdir(parents=True, exist_ok=True)
+    quantization = model_quantization()
+    options = {"path": path, "quantization": quantization}
+    providers = model_providers()
+    if providers:
+        options["providers"] = providers
+    try:
+        return onnx_asr.load_model(MODEL_NAME, **options)
+    except Exception as exc:
+        # If a partial/corrupt model directory exists, clear and retry once. Running out
+        # of memory says nothing about the files.
+        if path.exists() and not is_out_of_memory(exc):
+            shutil.rmtree(path, ignore_errors=True)
+            return onnx_asr.load_model(MODEL_NAME, **options)
+        raise
+
+
+def decode_wav_bytes(wav_bytes: bytes) -> np.ndarray:
+    with wave.open(BytesIO(wav_bytes), "rb") as wf:
+        if wf.getnchannels() != 1 or wf.getsampwidth() != 2:
+            raise ValueError("Input WAV must be mono 16-bit PCM")
+        if wf.getframerate() != SAMPLE_RATE:
+            raise ValueError(f"Input WAV must be {SAMPLE_RATE}Hz")
+        pcm = wf.readframes(wf.getnframes())
+    return np.frombuffer(pcm, dtype=np.int16).astype(np.float32) / 32768.0
+
+
+RECOGNIZE_OPTIONS = (
+    "language",
+    "task",
+    "temperature",
+    "beam_size",
+    "best_of",
+    "initial_prompt",
+)
+
+
+def supported_options(recognizer) -> frozenset:
+    # The options recognize() takes by name; one taking any keyword is given them all.
+    try:
+        parameters = inspect.signature(recognizer.recognize).parameters.values()
+    except (TypeError, ValueError):
+        return frozenset()
+    if any(parameter.kind is inspect.Parameter.VAR_KEYWORD for parameter in parameters):
+        return frozenset(RECOGNIZE_OPTIONS)
+    return frozenset(parameter.name for parameter in parameters) & frozenset(RECOGNIZE_OPTIONS)
+
+
+def timestamped(model):
+    # Newer onnx-asr versions report token log-probabilities with timestamped results.
+    try:
+        return model.with_timestamps()
+    except AttributeError:
+        return None
+
+
+def reply_payload(recognizer, supported: frozenset, audio: np.ndarray, options: dict) -> bytes:
+    # A protocol::StructuredText when there is a confidence or an ignored option to report,
+    # else the bare transcript.
+    requested = [key for key in RECOGNIZE_OPTIONS if options.get(key) is not None]
+    kwargs = {key: options[key] for key in requested if key in supported}
+    ignored = [key for key in requested if key not in supported]
+    result = recognizer.recognize(audio, sample_rate=SAMPLE_RATE, **kwargs)
+    logprobs = getattr(result, "logprobs", None)
+    text = getattr(result, "text", result)
+    if isinstance(text, list):
+        text = " ".join(str(item) for item in text)
+    text = str(text).strip()
+    confidence = None
+    if logprobs is not None and len(logprobs) > 0:
+        confidence = float(np.exp(np.mean(logprobs)))
+    if confidence is None and not ignored:
+        return text.encode("utf-8")
+    structured = {"text": text, "confidence": confidence, "ignored": ignored}
+    return f"RESULT:{json.dumps(structured)}".encode("utf-8")
+
+
+def read_frame(buf):
+    header = buf.read(4)
+    if len(header) < 4:
+        return None
+    length = struct.unpack("<I", header)[0]
+    payload = buf.read(length)
+    if len(payload) < length:
+        return None
+    return payload
+
+
+RESPONSE_MAGIC = b"VTR1"
+
+
+def write_response(out, request_id: int, payload: bytes) -> None:
+    out.write(RESPONSE_MAGIC)
+    out.write(struct.pack("<II", request_id, len(payload)))
+    out.write(payload)
+    out.flush()
+
+
+def run_worker() -> int:
+    try:
+        model = load_asr_model()
+    except Exception as exc:
+        sys.stdout.write(f"failed {failure('model_load', exc)}\n")
+        sys.stdout.flush()
+        return 1
+    recognizer = timestamped(model) or model
+    supported = supported_options(recognizer)
+    handshake = {"version": WORKER_VERSION, "capabilities": list(CAPABILITIES)}
+    sys.stdout.write(f"ready {json.dumps(handshake)}\n")
+    sys.stdout.flush()
+
+    buf = sys.stdin.buffer
+    out = sys.stdout.buffer
+
+    while True:
+        options_frame = read_frame(buf)
+        if options_frame is None:
+            break
+        if not options_frame:
+            write_response(out, 0, b"")
+            continue
+        payload = read_frame(buf)
+        if payload is None:
+            break
+        request_id = 0
+        try:
+            options = json.loads(options_frame.decode("utf-8"))
+            request_id = int(options.get("id", 0))
+            audio = decode_wav_bytes(payload)
+            write_response(out, request_id, reply_payload(recognizer, supported, audio, options))
+        except Exception as exc:
+            write_response(out, request_id, f"ERROR: {failure('other', exc)}".encode("utf-8"))
+    return 0
+
+
+def main() -> int:
+    if "--worker" in sys.argv:
+        return run_worker()
+    wav_bytes = sys.stdin.buffer.read()
+    if not wav_bytes:
+        print("Missing input wav bytes on stdin", file=sys.stderr)
+        return 1
+    audio = decode_wav_bytes(wav_bytes)
+    model = load_asr_model()
+    text = model.recognize(audio, sample_rate=SAMPLE_RATE)
+    print(text)
+    return 0
+
+
+if __name__ == "__main__":
+    raise SystemExit(main())
@@ -1,49 +1,175 @@
 import { useEffect, useRef, useState } from "react";
 import { invoke } from "@tauri-apps/api/core";
-import { listen } from "@tauri-apps/api/event";
+import { emit, listen } from "@tauri-apps/api/event";
 import "./App.css";
 
-type Status = "idle" | "recording" | "processing" | "error";
-
-type MicState = "unknown" | "granted" | "denied";
-
-const TARGET_SAMPLE_RATE = 16000;
+type Status =
+  | "idle"
+  | "recording"
+  | "processing"
+  | "reviewing"
+  | "picking"
+  | "capturing"
+  | "error";
+
+type Announcement = {
+  kind: string;
+  text: string;
+  words: number | null;
+};
+
+type ShellConfirmation = {
+  id: number;
+  commands: string[];
+};
+
+type QuickPick = {
+  id: number;
+  createdAt: number;
+  label: string;
+  words: number;
+  pinned: boolean;
+};
+
+type Utterance = {
+  text: string;
+  durationMs: number | null;
+  model: string;
+  language: string | null;
+  processingMs: number;
+  backend: string;
+  confidence: number | null;
+};
+
+// Such as "1.8s · nemo-parakeet-tdt-0.6b-v3 · local".
+const describeUtterance = (utterance: Utterance) =>
+  [
+    utterance.durationMs != null ? `${(utterance.durationMs / 1000).toFixed(1)}s` : null,
+    utterance.model,
+    utterance.language,
+    utterance.backend === "onnx" ? "local" : utterance.backend,
+  ]
+    .filter(Boolean)
+    .join(" · ");
+
+type PendingResult = {
+  id: number;
+  text: string;
+  entryId: number | null;
+  autoConfirmMs: number | null;
+  warning: string | null;
+};
 
 function App() {
   const [status, setStatus] = useState<Status>("idle");
   const [visible, setVisible] = useState(false);
   const [, setError] = useState<string | null>(null);
+  const [pending, setPending] = useState<PendingResult | null>(null);
+  const [draft, setDraft] = useState("");
+  const [announcement, setAnnouncement] = useState("");
+  const [paused, setPaused] = useState(false);
+  const [picks, setPicks] = useState<QuickPick[]>([]);
+  const [picked, setPicked] = useState(0);
+  const [utteranceInfo, setUtteranceInfo] = useState("");
 
   const statusRef = useRef<Status>("idle");
-  const micStateRef = useRef<MicState>("unknown");
-  const streamRef = useRef<MediaStream | null>(null);
-  const audioContextRef = useRef<AudioContext | null>(null);
-  const workletNodeRef = useRef<AudioWorkletNode | null>(null);
-  const sourceNodeRef = useRef<MediaStreamAudioSourceNode | null>(null);
-  const pcmChunksRef = useRef<Float32Array[]>([]);
-  const workletUrlRef = useRef<string | null>(null);
-  const recordStartRef = useRef<number | null>(null);
-  const recordTimerRef = useRef<number | null>(null);
-  const warmStartedRef = useRef(false);
-  const lastHotkeyAtRef = useRef(0);
   const minStopAtRef = useRef(0);
-  const hasAudioRef = useRef(false);
+  const reviewTextRef = useRef<HTMLTextAreaElement>(null);
+  const pickerRef = useRef<HTMLDivElement>(null);
+  const captureRef = useRef<HTMLDivElement>(null);
 
   useEffect(() => {
     statusRef.current = status;
   }, [status]);
 
   useEffect(() => {
-    const unlistenPromise = listen("hotkey-pressed", () => {
-      const now = performance.now();
-      if (now - lastHotkeyAtRef.current < 250) {
-        return;
+    const unlistenPromise = listen<Announcement>("announcement", (event) => {
+      setAnnouncement(event.payload.text);
+    });
+
+    return () => {
+      void unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  // The backend hides the window once a dictation is over or never got going.
+  useEffect(() => {
+    const unlistenPromise = listen("overlay-auto-hidden", () => {
+      setVisible(false);
+      setStatus((current) => (current === "error" ? "idle" : current));
+    });
+
+    return () => {
+      void unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  useEffect(() => {
+    const pausedPromise = listen("recording-paused", () => setPaused(true));
+    const resumedPromise = listen("recording-resumed", () => setPaused(false));
+
+    return () => {
+      void pausedPromise.then((unlisten) => unlisten());
+      void resumedPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  // A pause ends with the recording.
+  useEffect(() => {
+    if (status !== "recording") {
+      setPaused(false);
+    }
+  }, [status]);
+
+  const keepOpen = () => {
+    void emit("overlay-keep-open");
+  };
+
+  // Voice commands ask before running shell commands unless shellActions says otherwise.
+  useEffect(() => {
+    const unlistenPromise = listen<ShellConfirmation>("shell-confirmation", (event) => {
+      const { id, commands } = event.payload;
+      const allow = window.confirm(`Run this voice command?\n\n${commands.join("\n")}`);
+      void invoke("answer_shell_confirmation", { id, allow });
+    });
+
+    return () => {
+      void unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  // Offered once per launch when updates.autoCheck finds a newer release.
+  useEffect(() => {
+    const unlistenPromise = listen<{ version: string }>("update-available", (event) => {
+      const message = `VType ${event.payload.version} is available. Install it and restart?`;
+      if (window.confirm(message)) {
+        void invoke("install_update").catch((err) => {
+          void invoke("log_message", { message: `Update failed: ${String(err)}` });
+        });
+      }
+    });
+
+    return () => {
+      void unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  useEffect(() => {
+    void invoke<string | null>("take_crash_report").then((path) => {
+      const message = "VType closed unexpectedly last time. Open the crash report?";
+      if (path && window.confirm(message)) {
+        void invoke("open_crash_report", { path });
       }
-      lastHotkeyAtRef.current = now;
+    });
+  }, []);
+
+  useEffect(() => {
+    // The backend drops bounces and holds presses made while a transcript is pending.
+    const unlistenPromise = listen("hotkey-pressed", () => {
       setVisible(true);
-      if (!warmStartedRef.current) {
-        warmStartedRef.current = true;
-        void invoke("warm_asr");
+      if (statusRef.current === "reviewing") {
+        // Dictating again replaces the transcript under review.
+        void invoke("discard_result");
       }
       void toggleRecording();
     });
@@ -54,22 +180,58 @@ function App() {
   }, []);
 
   useEffect(() => {
+    const unlistenPromise = listen<QuickPick[]>("quick-pick-requested", (event) => {
+      setPicks(event.payload);
+      setPicked(0);
+      setVisible(true);
+      setStatus("picking");
+    });
+
     return () => {
-      streamRef.current?.getTracks().forEach((track) => track.stop());
-      if (audioContextRef.current) {
-        void audioContextRef.current.close();
-      }
-      if (workletUrlRef.current) {
-        URL.revokeObjectURL(workletUrlRef.current);
-      }
-      if (recordTimerRef.current) {
-        window.clearInterval(recordTimerRef.current);
-      }
+      void unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  // The list takes the keys, so it can be used without the pointer.
+  useEffect(() => {
+    if (status === "picking") {
+      pickerRef.current?.focus();
+    } else if (status === "capturing") {
+      captureRef.current?.focus();
+    }
+  }, [status]);
+
+  useEffect(() => {
+    const requestedPromise = listen("hotkey-capture-requested", () => {
+      setVisible(true);
+      setStatus("capturing");
+    });
+    const endedPromise = listen("hotkey-capture-ended", () => {
+      setStatus((current) => (current === "capturing" ? "idle" : current));
+    });
+
+    return () => {
+      void requestedPromise.then((unlisten) => unlisten());
+      void endedPromise.then((unlisten) => unlisten());
     };
   }, []);
 
   useEffect(() => {
-    void verifyMicAccess();
+    const pendingPromise = listen<PendingResult>("review-pending", (event) => {
+      setPending(event.payload);
+      setDraft(event.payload.text);
+      setVisible(true);
+      setStatus("reviewing");
+    });
+    const closedPromise = listen("review-closed", () => {
+      setPending(null);
+      setStatus((current) => (current === "reviewing" ? "idle" : current));
+    });
+
+    return () => {
+      void pendingPromise.then((unlisten) => unlisten());
+      void closedPromise.then((unlisten) => unlisten());
+    };
   }, []);
 
   useEffect(() => {
@@ -83,89 +245,12 @@ function App() {
     return () => window.clearTimeout(timer);
   }, [status, visible]);
 
-  const ensureStream = async () => {
-    // Always acquire a fresh stream to avoid stale tracks after stop.
-    if (streamRef.current) {
-      streamRef.current.getTracks().forEach((track) => track.stop());
-      streamRef.current = null;
-    }
-    const stream = await navigator.mediaDevices.getUserMedia({ audio: true });
-    streamRef.current = stream;
-    return stream;
-  };
-
   const startRecording = async () => {
     setError(null);
-    try {
-      const devices = await navigator.mediaDevices.enumerateDevices();
-      const inputs = devices.filter((device) => device.kind === "audioinput");
-      console.log(
-        "Audio inputs:",
-        inputs.map((input) => `${input.label || "Unnamed"} (${input.deviceId})`)
-      );
-    } catch (err) {
-      console.warn("Failed to enumerate audio devices:", err);
-    }
-    const stream = await ensureStream();
-    stream.getAudioTracks().forEach((track) => {
-      track.enabled = true;
-    });
-    pcmChunksRef.current = [];
-    hasAudioRef.current = false;
-
-    const audioContext = new AudioContext({ sampleRate: TARGET_SAMPLE_RATE });
-    audioContextRef.current = audioContext;
-    if (audioContext.sampleRate !== TARGET_SAMPLE_RATE) {
-      throw new Error(
-        `AudioContext sample rate is ${audioContext.sampleRate}Hz. This app requires ${TARGET_SAMPLE_RATE}Hz.`
-      );
-    }
-    if (audioContext.state !== "running") {
-      await audioContext.resume();
-    }
-
-    if (!workletUrlRef.current) {
-      workletUrlRef.current = createWorkletUrl();
-    }
-
-    await audioContext.audioWorklet.addModule(workletUrlRef.current);
-
-    const sourceNode = audioContext.createMediaStreamSource(stream);
-    const workletNode = new AudioWorkletNode(audioContext, "pcm-capture", {
-      numberOfInputs: 1,
-      numberOfOutputs: 1,
-      channelCount: 1,
-    });
-    const gainNode = audioContext.createGain();
-    gainNode.gain.value = 0;
-
-    workletNode.port.onmessage = (event) => {
-      const data = event.data;
-      if (data instanceof Float32Array) {
-        pcmChunksRef.current.push(data);
-        hasAudioRef.current = true;
-      } else if (data instanceof ArrayBuffer) {
-        const chunk = new Float32Array(data);
-        pcmChunksRef.current.push(chunk);
-        hasAudioRef.current = true;
-      }
-    };
-
-    sourceNode.connect(workletNode);
-    workletNode.connect(gainNode);
-    gainNode.connect(audioContext.destination);
-
-    sourceNodeRef.current = sourceNode;
-    workletNodeRef.current = workletNode;
-
-    recordStartRef.current = audioContext.currentTime;
+    await invoke("start_recording");
     minStopAtRef.current = performance.now() + 350;
-    if (recordTimerRef.current) {
-      window.clearInterval(recordTimerRef.current);
-    }
-    recordTimerRef.current = window.setInterval(() => {}, 150);
-
     setStatus("recording");
+    setUtteranceInfo("");
   };
 
   const stopRecording = () => {
@@ -175,18 +260,8 @@ function App() {
     if (performance.now() < minStopAtRef.current) {
       return;
     }
-    if (!hasAudioRef.current) {
-      return;
-    }
     setStatus("processing");
-    if (recordTimerRef.current) {
-      window.clearInterval(recordTimerRef.current);
-      recordTimerRef.current = null;
-    }
-    // Small delay to allow the last worklet buffers to flush.
-    window.setTimeout(() => {
-      void handleStop();
-    }, 120);
+    void handleStop();
   };
 
   const toggleRecording = async () => {
@@ -195,113 +270,236 @@ function App() {
       stopRecording();
       return;
     }
-    if (current === "processing") {
-      return;
+    // Pressed while processing only with busyHotkey set to restart: the transcript under
+    // way is dropped by the backend.
+    if (current === "reviewing") {
+      setPending(null);
     }
     try {
       await startRecording();
     } catch (err) {
-      const errorDetail = formatMicError(err);
-      void invoke("log_message", { message: `Mic init error: ${errorDetail}` });
-      micStateRef.current = "denied";
+      void invoke("log_message", { message: `Mic init error: ${String(err)}` });
       setStatus("error");
-      setError(`Microphone error: ${errorDetail}`);
-    }
-  };
-
-  const verifyMicAccess = async () => {
-    try {
-      if ("permissions" in navigator && "query" in navigator.permissions) {
-        const status = await navigator.permissions.query({
-          name: "microphone" as PermissionName,
-        });
-        if (status.state === "granted") {
-          micStateRef.current = "granted";
-          return;
-        }
-        if (status.state === "denied") {
-          micStateRef.current = "denied";
-          return;
-        }
-      }
-
-      const stream = await navigator.mediaDevices.getUserMedia({ audio: true });
-      stream.getTracks().forEach((track) => track.stop());
-      micStateRef.current = "granted";
-      console.log("Mic check: getUserMedia succeeded");
-    } catch (err) {
-      const errorDetail = formatMicError(err);
-      micStateRef.current = "denied";
-      console.error("Mic check error:", errorDetail);
-      void invoke("log_message", { message: `Mic verify error: ${errorDetail}` });
+      setError(`Microphone error: ${String(err)}`);
     }
   };
 
   const handleStop = async () => {
+    let cancelled = false;
     try {
-      const audioContext = audioContextRef.current;
-      const sampleRate = audioContext?.sampleRate ?? 44100;
-      const outputSampleRate = TARGET_SAMPLE_RATE;
-
-      sourceNodeRef.current?.disconnect();
-      workletNodeRef.current?.disconnect();
-      sourceNodeRef.current = null;
-      workletNodeRef.current = null;
-      if (streamRef.current) {
-        streamRef.current.getTracks().forEach((track) => track.stop());
-        streamRef.current = null;
-      }
-
-      if (audioContext) {
-        await audioContext.close();
-        audioContextRef.current = null;
-      }
-
-      const samples = concatFloat32(pcmChunksRef.current);
-      if (!samples.length) {
-        console.warn("No audio captured.");
-        setStatus("error");
-        setError("No audio captured. Check microphone input.");
-        return;
-      }
-      console.log(
-        "Audio rates:",
-        `input=${sampleRate}Hz`,
-        `output=${outputSampleRate}Hz`,
-        `samples=${samples.length}`
-      );
-      const stats = getAudioStats(samples);
-      console.log(
-        "Audio stats:",
-        `seconds=${(samples.length / sampleRate).toFixed(2)}`,
-        `rms=${stats.rms.toFixed(4)}`,
-        `peak=${stats.peak.toFixed(4)}`
-      );
-      if (samples.length / sampleRate < 0.3) {
-        console.warn("Audio too short, transcription may be empty.");
-      }
-      if (stats.rms < 0.002) {
-        console.warn("Audio very quiet, transcription may be empty.");
-      }
-      const wavBytes = await convertPcmToWav(samples, sampleRate, outputSampleRate);
-      const wavBase64 = uint8ToBase64(wavBytes);
-      const result = await invoke<string>("transcribe_wav", { wavBase64 });
-      if (result) {
-        await invoke("paste_transcription", { text: result });
-        console.log("Transcription success:", result);
+      const result = await invoke<Utterance>("stop_recording", { withMetadata: true });
+      if (result.text) {
+        setUtteranceInfo(describeUtterance(result));
+        await invoke("paste_transcription", { text: result.text });
+        console.log("Transcription success:", result.text);
       } else {
         console.warn("Transcription returned empty result");
       }
-      setStatus("idle");
+      // With review enabled the transcript is now waiting in the overlay, and after a
+      // restart the next recording is.
+      setStatus((current) => (current === "processing" ? "idle" : current));
     } catch (err) {
-      await invoke("log_message", { message: String(err) });
+      const message = String(err);
+      if (message.startsWith("DICTATION_CANCELLED")) {
+        cancelled = true;
+        return;
+      }
+      await invoke("log_message", { message });
       setStatus("error");
-      setError("Transcription failed. Check the backend logs.");
+      setError(
+        message.startsWith("ELEVATED_TARGET")
+          ? "The focused app runs as administrator. The transcript is on the clipboard."
+          : "Transcription failed. Check the backend logs.",
+      );
+    } finally {
+      if (!cancelled) {
+        void invoke("finish_dictation");
+      }
+    }
+  };
+
+  const editDraft = (text: string) => {
+    if (pending?.autoConfirmMs != null && text !== draft) {
+      setPending({ ...pending, autoConfirmMs: null });
+      void invoke("keep_reviewing");
     }
+    setDraft(text);
+  };
+
+  // With part of the text selected, only that part is pasted; the history entry keeps the
+  // whole transcript.
+  const confirmPaste = () => {
+    const editor = reviewTextRef.current;
+    const selected = editor
+      ? draft.slice(editor.selectionStart, editor.selectionEnd).trim()
+      : "";
+    const request =
+      selected && pending?.entryId != null
+        ? invoke("paste_transcription", {
+            text: selected,
+            source: { entryId: pending.entryId },
+          })
+        : invoke("confirm_paste", { text: selected || draft });
+    void request.catch((err) =>
+      invoke("log_message", { message: `Paste failed: ${String(err)}` }),
+    );
+  };
+
+  const discardResult = () => {
+    void invoke("discard_result");
   };
 
+  const closeQuickPick = () => {
+    setPicks([]);
+    setStatus("idle");
+  };
+
+  const pasteQuickPick = (id: number) => {
+    closeQuickPick();
+    void invoke("paste_history_entry", { id }).catch((err) =>
+      invoke("log_message", { message: `Paste failed: ${String(err)}` }),
+    );
+  };
+
+  if (status === "picking" && picks.length > 0) {
+    return (
+      <main className="pill-shell reviewing visible">
+        <div
+          className="pill review"
+          ref={pickerRef}
+          tabIndex={-1}
+          onKeyDown={(event) => {
+            if (event.key === "ArrowDown" || event.key === "ArrowUp") {
+              event.preventDefault();
+              const step = event.key === "ArrowDown" ? 1 : picks.length - 1;
+              setPicked((current) => (current + step) % picks.length);
+            } else if (event.key === "Enter") {
+              event.preventDefault();
+              pasteQuickPick(picks[picked].id);
+            } else if (/^[1-9]$/.test(event.key) && Number(event.key) <= picks.length) {
+              event.preventDefault();
+              pasteQuickPick(picks[Number(event.key) - 1].id);
+            } else if (event.key === "Escape") {
+              event.preventDefault();
+              closeQuickPick();
+              void invoke("close_quick_pick");
+            }
+          }}
+        >
+          <ul className="quick-pick" role="listbox" aria-label="Recent transcripts">
+            {picks.map((pick, index) => (
+              <li
+                key={pick.id}
+                role="option"
+                aria-selected={index === picked}
+                className={index === picked ? "picked" : ""}
+                onMouseEnter={() => setPicked(index)}
+                onClick={() => pasteQuickPick(pick.id)}
+              >
+                <span className="quick-pick-key">{index < 9 ? index + 1 : ""}</span>
+                <span className="quick-pick-label">{pick.label}</span>
+                {pick.pinned && <span className="quick-pick-pin">Pinned</span>}
+              </li>
+            ))}
+          </ul>
+        </div>
+        <p className="announcer" role="status" aria-live="polite">
+          {announcement}
+        </p>
+      </main>
+    );
+  }
+
+  if (status === "capturing") {
+    return (
+      <main className="pill-shell reviewing visible">
+        <div
+          className="pill review"
+          ref={captureRef}
+          tabIndex={-1}
+          onKeyDown={(event) => {
+            event.preventDefault();
+            // Wait for the key the modifiers are held with.
+            if (["Control", "Alt", "Shift", "Meta"].includes(event.key)) {
+              return;
+            }
+            const modified = event.ctrlKey || event.altKey || event.shiftKey || event.metaKey;
+            const chord =
+              event.key === "Escape" && !modified
+                ? null
+                : {
+                    code: event.code,
+                    ctrl: event.ctrlKey,
+                    alt: event.altKey,
+                    shift: event.shiftKey,
+                    meta: event.metaKey,
+                  };
+            void invoke("hotkey_captured", { chord });
+          }}
+        >
+          <p className="review-hint">Press the new hotkey, or Escape to cancel</p>
+        </div>
+        <p className="announcer" role="status" aria-live="polite">
+          {announcement}
+        </p>
+      </main>
+    );
+  }
+
+  if (status === "reviewing" && pending) {
+    return (
+      <main className="pill-shell reviewing visible">
+        <div className="pill review">
+          <textarea
+            ref={reviewTextRef}
+            className="review-text"
+            value={draft}
+            autoFocus
+            spellCheck
+            onChange={(event) => editDraft(event.target.value)}
+            onKeyDown={(event) => {
+              if (event.key === "Enter" && !event.shiftKey) {
+                event.preventDefault();
+                confirmPaste();
+              } else if (event.key === "Escape") {
+                event.preventDefault();
+                discardResult();
+              }
+            }}
+          />
+          {pending.warning && (
+            <p className="review-warning" role="alert">
+              {pending.warning}
+            </p>
+          )}
+          <div className="confirm-actions">
+            {pending.autoConfirmMs != null && (
+              <span className="review-hint">
+                Pasting in {Math.round(pending.autoConfirmMs / 1000)}s
+              </span>
+            )}
+            <button className="confirm no" onClick={discardResult}>
+              Discard
+            </button>
+            <button className="confirm yes" onClick={confirmPaste}>
+              Paste
+            </button>
+          </div>
+        </div>
+        <p className="announcer" role="status" aria-live="polite">
+          {announcement}
+        </p>
+      </main>
+    );
+  }
+
   return (
-    <main className={`pill-shell ${status} ${visible ? "visible" : ""}`}>
+    <main
+      className={`pill-shell ${status} ${visible ? "visible" : ""} ${
+        paused ? "paused" : ""
+      }`}
+      onMouseEnter={keepOpen}
+    >
       <div className="pill">
         <span
           className={`wave ${status === "recording" ? "active" : ""} ${
@@ -314,120 +512,16 @@ function App() {
           <span className="bar" />
         </span>
       </div>
+      {status === "idle" && utteranceInfo && (
+        <p className="utterance-info" title={utteranceInfo}>
+          {utteranceInfo}
+        </p>
+      )}
+      <p className="announcer" role="status" aria-live="polite">
+        {announcement}
+      </p>
     </main>
   );
 }
 
-function encodeWavFromFloat32(samples: Float32Array, sampleRate: number) {
-  const bufferLength = samples.length;
-  const wavBuffer = new ArrayBuffer(44 + bufferLength * 2);
-  const view = new DataView(wavBuffer);
-
-  writeString(view, 0, "RIFF");
-  view.setUint32(4, 36 + bufferLength * 2, true);
-  writeString(view, 8, "WAVE");
-  writeString(view, 12, "fmt ");
-  view.setUint32(16, 16, true);
-  view.setUint16(20, 1, true);
-  view.setUint16(22, 1, true);
-  view.setUint32(24, sampleRate, true);
-  view.setUint32(28, sampleRate * 2, true);
-  view.setUint16(32, 2, true);
-  view.setUint16(34, 16, true);
-  writeString(view, 36, "data");
-  view.setUint32(40, bufferLength * 2, true);
-
-  let offset = 44;
-  for (let i = 0; i < bufferLength; i += 1) {
-    const sample = Math.max(-1, Math.min(1, samples[i]));
-    view.setInt16(offset, sample < 0 ? sample * 0x8000 : sample * 0x7fff, true);
-    offset += 2;
-  }
-
-  return new Uint8Array(wavBuffer);
-}
-
-async function convertPcmToWav(
-  samples: Float32Array,
-  sampleRate: number,
-  targetSampleRate: number
-) {
-  if (sampleRate !== targetSampleRate) {
-    throw new Error(
-      `Sample rate mismatch: got ${sampleRate}Hz, expected ${targetSampleRate}Hz`
-    );
-  }
-  return encodeWavFromFloat32(samples, sampleRate);
-}
-
-function writeString(view: DataView, offset: number, value: string) {
-  for (let i = 0; i < value.length; i += 1) {
-    view.setUint8(offset + i, value.charCodeAt(i));
-  }
-}
-
-function uint8ToBase64(data: Uint8Array) {
-  let binary = "";
-  const chunkSize = 0x8000;
-  for (let i = 0; i < data.length; i += chunkSize) {
-    const slice = data.subarray(i, i + chunkSize);
-    binary += String.fromCharCode(...slice);
-  }
-  return btoa(binary);
-}
-
-function concatFloat32(chunks: Float32Array[]) {
-  const totalLength = chunks.reduce((sum, chunk) => sum + chunk.length, 0);
-  const result = new Float32Array(totalLength);
-  let offset = 0;
-  for (const chunk of chunks) {
-    result.set(chunk, offset);
-    offset += chunk.length;
-  }
-  return result;
-}
-
-function createWorkletUrl() {
-  const processorCode = `
-class PCMCaptureProcessor extends AudioWorkletProcessor {
-  process(inputs) {
-    const input = inputs[0];
-    if (input && input[0] && input[0].length) {
-      const chunk = input[0].slice(0);
-      this.port.postMessage(chunk, [chunk.buffer]);
-    }
-    return true;
-  }
-}
-registerProcessor('pcm-capture', PCMCaptureProcessor);
-`;
-  const blob = new Blob([processorCode], { type: "application/javascript" });
-  return URL.createObjectURL(blob);
-}
-
-function formatMicError(err: unknown) {
-  if (err && typeof err === "object") {
-    const name = "name" in err ? String((err as { name?: unknown }).name) : "UnknownError";
-    const message =
-      "message" in err ? String((err as { message?: unknown }).message) : String(err);
-    return `${name}: ${message}`;
-  }
-  return String(err);
-}
-
-function getAudioStats(samples: Float32Array) {
-  let peak = 0;
-  let sumSquares = 0;
-  for (let i = 0; i < samples.length; i += 1) {
-    const value = samples[i];
-    const abs = Math.abs(value);
-    if (abs > peak) {
-      peak = abs;
-    }
-    sumSquares += value * value;
-  }
-  const rms = samples.length ? Math.sqrt(sumSquares / samples.length) : 0;
-  return { rms, peak };
-}
-
 export default App;